@@ -0,0 +1,38 @@
+use tauri::State;
+
+use crate::state::{ConnectionProfile, ConnectionsState, ProcessManager};
+
+/// Saved connection profiles, sorted by name
+#[tauri::command]
+pub async fn list_connection_profiles(
+    connections: State<'_, ConnectionsState>,
+) -> Result<Vec<ConnectionProfile>, String> {
+    Ok(connections.list())
+}
+
+/// Add or update a connection profile. Its file and content indexes are kept
+/// unless the server, user or workspace changed.
+#[tauri::command]
+pub async fn save_connection_profile(
+    profile: ConnectionProfile,
+    connections: State<'_, ConnectionsState>,
+) -> Result<Vec<ConnectionProfile>, String> {
+    connections
+        .save(profile)
+        .map_err(|e| format!("Failed to save connection profile: {}", e))?;
+    Ok(connections.list())
+}
+
+/// Delete a connection profile, stopping any of its processes still running
+#[tauri::command]
+pub async fn delete_connection_profile(
+    id: String,
+    connections: State<'_, ConnectionsState>,
+    state: State<'_, ProcessManager>,
+) -> Result<Vec<ConnectionProfile>, String> {
+    state.kill_connection(&Some(id.clone())).await;
+    connections
+        .remove(&id)
+        .map_err(|e| format!("Failed to delete connection profile: {}", e))?;
+    Ok(connections.list())
+}
@@ -1,7 +1,9 @@
+mod connections;
 mod p4;
 mod process;
 mod search;
 
+pub use connections::*;
 pub use p4::*;
 pub use process::*;
 pub use search::*;
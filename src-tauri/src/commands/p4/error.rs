@@ -0,0 +1,222 @@
+use serde::Serialize;
+
+use crate::state::P4Output;
+
+/// Category of a failed p4 invocation.
+/// Serialized as a stable camelCase `kind` tag so the frontend can branch on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum P4ErrorKind {
+    /// Server unreachable (bad P4PORT, server down, network issue)
+    ConnectionRefused,
+    /// No valid ticket: password unset, session expired, or logged out
+    LoginRequired,
+    /// SSL server whose fingerprint is not trusted yet, or has changed; see p4 trust
+    TrustRequired,
+    /// Client charset doesn't suit the server: no charset for a unicode server or the reverse
+    CharsetMismatch,
+    /// Requested files, revisions or shelves don't exist
+    NoSuchFile,
+    /// File is exclusively opened or locked by another user
+    FileLocked,
+    /// Opened files must be resolved before the operation can continue
+    NeedsResolve,
+    /// Workspace files are out of date and must be synced first
+    OutOfDate,
+    /// Protections table denies the operation
+    PermissionDenied,
+    /// A spec form (stream, client, ...) was rejected; see `field_errors`
+    InvalidSpec,
+    /// Anything else (including local failures such as spawn or I/O errors)
+    Unknown,
+}
+
+/// Typed error returned by every p4 command.
+/// Carries the raw stderr and exit code alongside a human-readable message.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4Error {
+    pub kind: P4ErrorKind,
+    pub message: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    /// Per-field problems when a spec form was rejected
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub field_errors: Vec<SpecFieldError>,
+}
+
+/// A problem with one field of a spec form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecFieldError {
+    /// Form field name as p4 spells it (Paths, Options, ...); None when p4 didn't say
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl P4Error {
+    /// Build an error from p4's stderr and exit code, classifying it by message.
+    pub fn from_stderr(stderr: &str, exit_code: Option<i32>) -> Self {
+        let trimmed = stderr.trim();
+        let message = if trimmed.is_empty() {
+            format!("p4 exited with code: {:?}", exit_code)
+        } else {
+            trimmed.to_string()
+        };
+
+        Self {
+            kind: classify_stderr(stderr),
+            message,
+            stderr: stderr.to_string(),
+            exit_code,
+            field_errors: Vec::new(),
+        }
+    }
+
+    /// Build an error from a finished process's captured output.
+    pub fn from_output(output: &P4Output) -> Self {
+        Self::from_stderr(&output.stderr_text(), output.exit_code)
+    }
+
+    /// Error that did not come from p4 itself (spawn failure, temp file I/O, bad input).
+    pub fn other(message: impl Into<String>) -> Self {
+        Self {
+            kind: P4ErrorKind::Unknown,
+            message: message.into(),
+            stderr: String::new(),
+            exit_code: None,
+            field_errors: Vec::new(),
+        }
+    }
+
+    /// Spec form rejected before or by p4, with the offending fields.
+    pub fn invalid_spec(message: impl Into<String>, field_errors: Vec<SpecFieldError>) -> Self {
+        Self {
+            kind: P4ErrorKind::InvalidSpec,
+            field_errors,
+            ..Self::other(message)
+        }
+    }
+
+    /// Files held by another workspace, found before asking p4 to open them.
+    pub fn file_locked(message: impl Into<String>) -> Self {
+        Self {
+            kind: P4ErrorKind::FileLocked,
+            ..Self::other(message)
+        }
+    }
+
+    /// Prefix the message with context, keeping kind, stderr and exit code intact.
+    pub fn context(mut self, context: &str) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn is(&self, kind: P4ErrorKind) -> bool {
+        self.kind == kind
+    }
+
+    /// The connection itself can't be used (unreachable, not logged in, untrusted,
+    /// wrong charset), so every further command would fail the same way
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self.kind,
+            P4ErrorKind::ConnectionRefused
+                | P4ErrorKind::LoginRequired
+                | P4ErrorKind::TrustRequired
+                | P4ErrorKind::CharsetMismatch
+        )
+    }
+}
+
+impl std::fmt::Display for P4Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for P4Error {}
+
+impl From<String> for P4Error {
+    fn from(message: String) -> Self {
+        Self::other(message)
+    }
+}
+
+impl From<&str> for P4Error {
+    fn from(message: &str) -> Self {
+        Self::other(message)
+    }
+}
+
+/// Map p4 stderr text to an error kind.
+/// Patterns are matched case-insensitively against the whole stderr; the first
+/// matching category wins, so more specific checks come first.
+pub fn classify_stderr(stderr: &str) -> P4ErrorKind {
+    let lower = stderr.to_lowercase();
+    let has = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+
+    if has(&[
+        "the authenticity of",
+        "p4port identification has changed",
+        "use the 'p4 trust' command",
+    ]) {
+        P4ErrorKind::TrustRequired
+    } else if has(&[
+        "unicode server permits only unicode enabled clients",
+        "unicode clients require a unicode enabled server",
+    ]) {
+        P4ErrorKind::CharsetMismatch
+    } else if has(&[
+        "connect to server failed",
+        "tcp connect to",
+        "connection refused",
+        "check $p4port",
+        "ssl connect to",
+    ]) {
+        P4ErrorKind::ConnectionRefused
+    } else if has(&[
+        "password (p4passwd) invalid or unset",
+        "your session has expired",
+        "please login again",
+        "ticket has expired",
+        "ticket expired",
+    ]) {
+        P4ErrorKind::LoginRequired
+    } else if has(&[
+        "you don't have permission",
+        "no permission for operation",
+        "protections table is empty",
+    ]) {
+        P4ErrorKind::PermissionDenied
+    } else if lower.contains("error in ") && lower.contains(" specification") {
+        // "Error in stream specification." and the like, from `p4 <spec> -i`
+        P4ErrorKind::InvalidSpec
+    } else if has(&[
+        "exclusive file already opened",
+        "can't edit exclusive file",
+        "locked by",
+        "file(s) locked",
+    ]) {
+        P4ErrorKind::FileLocked
+    } else if has(&[
+        "must resolve",
+        "resolve before submitting",
+        "out of date files must be resolved",
+        "needs resolve",
+    ]) {
+        P4ErrorKind::NeedsResolve
+    } else if has(&["must sync", "out of date", "not at head revision"]) {
+        P4ErrorKind::OutOfDate
+    } else if has(&[
+        "no such file(s)",
+        "file(s) not on client",
+        "no shelved files",
+        "not shelved",
+        "no shelf",
+    ]) {
+        P4ErrorKind::NoSuchFile
+    } else {
+        P4ErrorKind::Unknown
+    }
+}
@@ -0,0 +1,2487 @@
+use std::sync::Arc;
+
+use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+use tauri::{App, Manager};
+
+use crate::diff::DiffOptions;
+use crate::state::{
+    create_connections_state, ConnectionProfile, ConnectionsState, P4ExecutorState, ProcessManager,
+    ScriptedExecutor,
+};
+
+use super::marshal::MarshalValue;
+use super::tests::{marshal_dict, str_value};
+use super::*;
+
+/// Build a mock app with the scripted executor injected as P4ExecutorState
+fn mock_app(executor: &Arc<ScriptedExecutor>) -> App<MockRuntime> {
+    let state: P4ExecutorState = executor.clone();
+    mock_builder()
+        .manage(state)
+        .manage(ProcessManager::new())
+        .manage(create_connections_state())
+        .manage(create_opened_cache_state())
+        .build(mock_context(noop_assets()))
+        .expect("failed to build mock app")
+}
+
+/// Save a profile with the given settings and return its ID
+fn connection(
+    app: &App<MockRuntime>,
+    server: Option<String>,
+    user: Option<String>,
+    client: Option<String>,
+) -> Option<String> {
+    let profile = ConnectionProfile {
+        id: "test".to_string(),
+        name: "Test".to_string(),
+        server,
+        user,
+        client,
+        ..Default::default()
+    };
+    app.state::<ConnectionsState>().save(profile).unwrap();
+    Some("test".to_string())
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+/// Channel that forwards every message, as JSON, to the returned receiver
+fn json_channel<T: serde::Serialize>() -> (tauri::ipc::Channel<T>, std::sync::mpsc::Receiver<serde_json::Value>) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let channel = tauri::ipc::Channel::new(move |body| {
+        if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+            let _ = tx.send(serde_json::from_str::<serde_json::Value>(&json).unwrap());
+        }
+        Ok(())
+    });
+    (channel, rx)
+}
+
+/// Messages received until (and including) the first "complete", and anything sent after it
+fn drain_until_complete(
+    rx: &std::sync::mpsc::Receiver<serde_json::Value>,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+    let timeout = std::time::Duration::from_secs(5);
+    let mut messages = Vec::new();
+    loop {
+        let message = rx.recv_timeout(timeout).expect("stream never completed");
+        let done = message["type"] == "complete";
+        messages.push(message);
+        if done {
+            break;
+        }
+    }
+    let late = rx.try_iter().collect();
+    (messages, late)
+}
+
+#[test]
+fn test_p4_info_parses_ztag_output() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(
+        "... userName alice\n... clientName alice-ws\n... clientRoot /home/alice/ws\n... clientStream //stream/main\n... serverAddress ssl:perforce:1666\n",
+    ));
+    let app = mock_app(&executor);
+
+    let info = block_on(p4_info(
+        connection(&app, Some("ssl:perforce:1666".to_string()), None, None),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(info.user_name, "alice");
+    assert_eq!(info.client_name, "alice-ws");
+    assert_eq!(info.client_stream, Some("//stream/main".to_string()));
+
+    let calls = executor.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].args, ["-p", "ssl:perforce:1666", "-ztag", "info"]);
+    assert!(calls[0]
+        .envs
+        .contains(&("P4PORT".to_string(), "ssl:perforce:1666".to_string())));
+}
+
+#[test]
+fn test_commands_run_against_the_selected_profile() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("... userName bob\n... clientRoot /home/bob/ws\n")
+            .ok("... userName alice\n... clientRoot /home/alice/ws\n"),
+    );
+    let app = mock_app(&executor);
+    let connections = app.state::<ConnectionsState>();
+    connections
+        .save(ConnectionProfile {
+            id: "edge".to_string(),
+            name: "Edge".to_string(),
+            server: Some("ssl:edge:1666".to_string()),
+            user: Some("bob".to_string()),
+            charset: Some("utf8".to_string()),
+            tickets_file: Some("/home/bob/.p4tickets-edge".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let edge = block_on(p4_info(
+        Some("edge".to_string()),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(edge.user_name, "bob");
+    let default = block_on(p4_info(None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>()))
+        .unwrap();
+    assert_eq!(default.user_name, "alice");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-p", "ssl:edge:1666", "-u", "bob", "-C", "utf8", "-ztag", "info"]);
+    assert!(calls[0]
+        .envs
+        .contains(&("P4TICKETS".to_string(), "/home/bob/.p4tickets-edge".to_string())));
+    assert_eq!(calls[1].args, ["-ztag", "info"]);
+    assert!(calls[1].envs.is_empty());
+
+    let err = block_on(p4_info(
+        Some("gone".to_string()),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+    assert!(err.message.contains("Unknown connection profile 'gone'"));
+    assert_eq!(executor.calls().len(), 2);
+}
+
+#[test]
+fn test_p4_info_login_required() {
+    let executor = Arc::new(ScriptedExecutor::new().fail(
+        "Perforce password (P4PASSWD) invalid or unset.\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_info(None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::LoginRequired);
+    assert_eq!(err.exit_code, Some(1));
+}
+
+#[test]
+fn test_p4_info_detects_unicode_server_charset() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("... userName alice\n... clientRoot /home/alice/ws\n... unicode enabled\n")
+            .ok("P4CHARSET=shiftjis\n")
+            // "日本語" in Shift_JIS
+            .respond_bytes(b"... name \x93\xfa\x96\x7b\x8c\xea\n... type local\n", b"", 0),
+    );
+    let app = mock_app(&executor);
+
+    let info = block_on(p4_info(None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert!(info.server_unicode);
+    assert_eq!(info.charset.as_deref(), Some("shiftjis"));
+
+    // Later commands on the connection pass the charset and decode their output with it
+    let depots = block_on(p4_depots(None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(depots[0].name, "日本語");
+
+    let calls = executor.calls();
+    assert_eq!(calls[1].args, ["set", "-q", "P4CHARSET"]);
+    assert_eq!(calls[2].args, ["-C", "shiftjis", "-ztag", "depots"]);
+}
+
+#[test]
+fn test_p4_info_falls_back_to_utf8_for_unicode_server() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("... userName alice\n... clientRoot /home/alice/ws\n... unicode enabled\n")
+            .ok("P4CHARSET=auto\n"),
+    );
+    let app = mock_app(&executor);
+
+    let info = block_on(p4_info(
+        connection(&app, Some("ssl:perforce:1666".to_string()), None, None),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(info.charset.as_deref(), Some("utf8"));
+    let profile = &app.state::<ConnectionsState>().get(&Some("test".to_string())).unwrap().profile;
+    assert_eq!(profile.charset.as_deref(), Some("utf8"));
+}
+
+#[test]
+fn test_p4_create_change_rewrites_form() {
+    let template = "Change:\tnew\n\nClient:\talice-ws\n\nDescription:\n\t<enter description here>\n\nFiles:\n\t//depot/a.txt\t# edit\n";
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(template)
+            .ok("Change 1234 created.\n"),
+    );
+    let app = mock_app(&executor);
+
+    let id = block_on(p4_create_change(
+        "Fix the build".to_string(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(id, 1234);
+
+    let calls = executor.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].args, ["change", "-o"]);
+    assert_eq!(calls[1].args, ["change", "-i"]);
+    let form = calls[1].input.as_deref().unwrap();
+    assert!(form.contains("Description:\n\tFix the build\n"));
+    assert!(!form.contains("<enter description here>"));
+    assert!(!form.contains("Files:"));
+    assert!(!form.contains("//depot/a.txt"));
+}
+
+#[test]
+fn test_p4_edit_change_description_keeps_files_and_multiline_text() {
+    let form = "Change:\t1234\n\nStatus:\tpending\n\nDescription:\n\tOld text\n\tFiles: in the description\n\n\
+                Files:\n\t//depot/a.txt\t# edit\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(form).ok("Change 1234 updated.\n"));
+    let app = mock_app(&executor);
+
+    block_on(p4_edit_change_description(
+        1234,
+        "Fix the build\n\nFiles: listed below".to_string(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["change", "-o", "1234"]);
+    assert_eq!(calls[1].args, ["change", "-i"]);
+    let form = calls[1].input.as_deref().unwrap();
+    assert!(form.contains("Description:\n\tFix the build\n\t\n\tFiles: listed below\n\nFiles:\n\t//depot/a.txt\t# edit\n"));
+    assert!(!form.contains("Old text"));
+}
+
+#[test]
+fn test_p4_submit_returns_submitted_changelist() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(
+        "Submitting change 100.\nLocking 1 files ...\nedit //depot/a.txt#3\nChange 105 submitted.\n",
+    ));
+    let app = mock_app(&executor);
+
+    let submitted = block_on(p4_submit(
+        100,
+        None,
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(submitted, 105);
+    assert_eq!(executor.calls()[0].args, ["submit", "-c", "100"]);
+}
+
+#[test]
+fn test_p4_submit_sets_status_of_fixed_jobs() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("... Job job000012\n... Change 100\n... Status open\n\n... Job job000015\n... Change 100\n... Status open\n")
+            .ok("job000012 fixed by change 100.\njob000015 fixed by change 100.\n")
+            .ok("Submitting change 100.\nChange 100 submitted.\n"),
+    );
+    let app = mock_app(&executor);
+
+    let submitted = block_on(p4_submit(
+        100,
+        None,
+        Some("suspended".to_string()),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(submitted, 100);
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-ztag", "fixes", "-c", "100"]);
+    assert_eq!(calls[1].args, ["fix", "-s", "suspended", "-c", "100", "job000012", "job000015"]);
+    assert_eq!(calls[2].args, ["submit", "-c", "100"]);
+}
+
+#[test]
+fn test_p4_submit_default_changelist_rejects_job_status() {
+    let executor = Arc::new(ScriptedExecutor::new());
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_submit(
+        0,
+        Some("Fix crash".to_string()),
+        Some("closed".to_string()),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert!(err.message.contains("default changelist"));
+    assert!(executor.calls().is_empty());
+}
+
+#[test]
+fn test_p4_submit_needs_resolve() {
+    let executor = Arc::new(ScriptedExecutor::new().fail(
+        "Merges still pending -- use 'resolve' to merge files.\n//depot/a.txt - must resolve #4 before submitting\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_submit(
+        0,
+        Some("Desc".to_string()),
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::NeedsResolve);
+    assert_eq!(executor.calls()[0].args, ["submit", "-d", "Desc"]);
+}
+
+#[test]
+fn test_p4_reconcile_apply_uses_file_list() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("//depot/a.txt#1 - opened for edit\n"));
+    let app = mock_app(&executor);
+
+    block_on(p4_reconcile_apply(
+        vec!["//depot/a.txt#1".to_string(), "//depot/b.txt".to_string()],
+        Some(42),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let args = &executor.calls()[0].args;
+    assert_eq!(args[0], "-x");
+    assert!(args[1].contains("p4_reconcile_"));
+    assert_eq!(&args[2..], ["reconcile", "-c", "42"]);
+}
+
+#[test]
+fn test_p4_files_no_such_file_is_empty() {
+    let executor = Arc::new(
+        ScriptedExecutor::new().fail("//depot/missing/... - no such file(s).\n", 1),
+    );
+    let app = mock_app(&executor);
+
+    let files = block_on(p4_files(
+        "//depot/missing/...".to_string(),
+        100,
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(files.is_empty());
+}
+
+#[test]
+fn test_p4_files_connection_refused() {
+    let executor = Arc::new(ScriptedExecutor::new().fail(
+        "Perforce client error:\n\tConnect to server failed; check $P4PORT.\n\tTCP connect to perforce:1666 failed.\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_files(
+        "//depot/...".to_string(),
+        100,
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn test_p4_grep_streams_matches_and_completes() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "//depot/a.cpp#3-9-// setup\n//depot/a.cpp#3:10:Engine::Run();\n--\n//depot/b.cpp#1:2:engine::run()\n",
+        "//depot/missing/... - no such file(s).\n",
+        0,
+    ));
+    let app = mock_app(&executor);
+
+    let (channel, rx) = json_channel();
+
+    let options = GrepOptions {
+        pattern: "engine::run".to_string(),
+        context: Some(1),
+        revision_range: Some("#head".to_string()),
+        ..Default::default()
+    };
+    block_on(p4_grep(
+        options,
+        vec![],
+        Some("//depot/...".to_string()),
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let recv = || rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+    let data = recv();
+    assert_eq!(data["type"], "data");
+    assert_eq!(data["matches"].as_array().unwrap().len(), 3);
+    assert_eq!(data["matches"][1]["lineNumber"], 10);
+    assert_eq!(data["matches"][0]["isContext"], true);
+
+    let complete = recv();
+    assert_eq!(complete["type"], "complete");
+    assert_eq!(complete["totalMatches"], 2);
+    assert_eq!(complete["success"], true);
+
+    assert_eq!(
+        executor.calls()[0].args,
+        ["grep", "-n", "-i", "-F", "-C", "1", "-e", "engine::run", "//depot/...#head"]
+    );
+}
+
+#[test]
+fn test_p4_fstat_stream_sends_one_failed_completion() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "... depotFile //depot/a.cpp\n... clientFile /ws/a.cpp\n... headRev 2\n... haveRev 2\n\n",
+        "//depot/gone/... - no such file(s).\nPerforce password (P4PASSWD) invalid or unset.\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_fstat_stream(
+        vec![],
+        Some("//depot/...".to_string()),
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, late) = drain_until_complete(&rx);
+    assert!(late.is_empty(), "messages after completion: {:?}", late);
+    assert_eq!(messages[0]["type"], "data");
+    assert_eq!(messages[0]["totalReceived"], 1);
+
+    let complete = messages.last().unwrap();
+    assert_eq!(complete["totalFiles"], 1);
+    assert_eq!(complete["success"], false);
+    assert_eq!(complete["cancelled"], false);
+    assert_eq!(complete["exitCode"], 1);
+    assert_eq!(complete["errorKind"], "loginRequired");
+    assert_eq!(complete["error"], "Perforce password (P4PASSWD) invalid or unset.");
+}
+
+#[test]
+fn test_p4_sync_reports_conflicts_then_completes() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "//depot/a.cpp#3 - updating /ws/a.cpp\n",
+        "can't clobber writable file /ws/b.cpp\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_sync(
+        vec![],
+        Some("//depot/...".to_string()),
+        None,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, late) = drain_until_complete(&rx);
+    assert!(late.is_empty());
+    let progress: Vec<_> = messages.iter().filter(|m| m["type"] == "progress").collect();
+    assert_eq!(progress.len(), 2);
+    assert!(progress.iter().any(|p| p["is_conflict"] == true));
+    assert!(progress.iter().any(|p| p["depot_path"] == "//depot/a.cpp"));
+
+    let complete = messages.last().unwrap();
+    assert_eq!(complete["success"], false);
+    assert_eq!(complete["error"], "can't clobber writable file /ws/b.cpp");
+}
+
+#[test]
+fn test_p4_sync_up_to_date_is_success() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "",
+        "//depot/... - file(s) up-to-date.\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_sync(
+        vec![],
+        None,
+        None,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, _) = drain_until_complete(&rx);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["success"], true);
+    assert!(messages[0]["error"].is_null());
+}
+
+/// `p4 -G` output as the text ScriptedExecutor replays
+fn marshal_output(records: &[&[(&str, MarshalValue)]]) -> String {
+    let bytes: Vec<u8> = records.iter().flat_map(|r| marshal_dict(r)).collect();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_p4_add_uses_type_and_force_for_wildcard_names() {
+    let added = marshal_output(&[
+        &[
+            ("code", str_value("stat")),
+            ("depotFile", str_value("//depot/img/logo%402x.png")),
+            ("clientFile", str_value("/ws/img/logo@2x.png")),
+            ("workRev", str_value("1")),
+            ("action", str_value("add")),
+            ("type", str_value("binary+l")),
+        ],
+        &[
+            ("code", str_value("error")),
+            ("data", str_value("/ws/img/old.png - can't add existing file\n")),
+            ("severity", MarshalValue::Int(2)),
+        ],
+    ]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&added).ok(""));
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_add(
+        vec!["/ws/img/logo@2x.png".to_string(), "/ws/img/old.png".to_string()],
+        Some(7),
+        Some("binary+l".to_string()),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].success);
+    assert_eq!(results[0].file_type.as_deref(), Some("binary+l"));
+    assert!(!results[1].success);
+    assert_eq!(results[1].message.as_deref(), Some("can't add existing file"));
+
+    let calls = executor.calls();
+    assert_eq!(
+        calls[0].args,
+        ["-G", "add", "-c", "7", "-t", "binary+l", "-f", "/ws/img/logo@2x.png", "/ws/img/old.png"]
+    );
+    // Status refresh for the file that was opened
+    assert_eq!(calls[1].args, ["-G", "fstat", "//depot/img/logo%402x.png"]);
+}
+
+#[test]
+fn test_p4_edit_reports_exclusive_open_holder() {
+    let fstat = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/art/hero.psd")),
+        ("clientFile", str_value("/ws/art/hero.psd")),
+        ("headType", str_value("binary+l")),
+        ("headRev", str_value("3")),
+        ("haveRev", str_value("3")),
+        ("otherOpen", str_value("1")),
+        ("otherOpen0", str_value("bob@bob-ws")),
+        ("otherAction0", str_value("edit")),
+    ]]);
+    let exclusive = "//depot/art/hero.psd - can't edit exclusive file already opened\n";
+    let executor = Arc::new(ScriptedExecutor::new().fail(exclusive, 1).ok(&fstat));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_edit(
+        vec!["/ws/art/hero.psd".to_string()],
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::FileLocked);
+    assert_eq!(err.message, "//depot/art/hero.psd is exclusively opened by bob@bob-ws");
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["edit", "/ws/art/hero.psd"]);
+    assert_eq!(calls[1].args, ["-G", "fstat", "/ws/art/hero.psd"]);
+}
+
+#[test]
+fn test_p4_edit_keeps_p4_error_when_holder_lookup_fails() {
+    let exclusive = "//depot/art/hero.psd - can't edit exclusive file already opened\n";
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .fail(exclusive, 1)
+            .fail("Connect to server failed; check $P4PORT.\n", 1),
+    );
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_edit(
+        vec!["/ws/art/hero.psd".to_string()],
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::FileLocked);
+    assert!(err.message.contains("can't edit exclusive file"));
+}
+
+#[test]
+fn test_p4_edit_runs_without_extra_fstat() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("//depot/src/main.cpp#4 - opened for edit\n")
+            .ok(""),
+    );
+    let app = mock_app(&executor);
+
+    block_on(p4_edit(
+        vec!["/ws/src/main.cpp".to_string()],
+        Some(5),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let calls = executor.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].args, ["edit", "-c", "5", "/ws/src/main.cpp"]);
+    // Only the status refresh for the opened file
+    assert_eq!(calls[1].args, ["-G", "fstat", "//depot/src/main.cpp"]);
+}
+
+#[test]
+fn test_p4_unlock_force_releases_other_users_lock() {
+    let unlocked = marshal_output(&[&[
+        ("code", str_value("info")),
+        ("data", str_value("//depot/art/hero.psd - unlocking")),
+        ("level", MarshalValue::Int(0)),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&unlocked).ok(""));
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_unlock(
+        vec!["//depot/art/hero.psd".to_string()],
+        None,
+        true,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert!(results[0].success);
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-G", "unlock", "-f", "//depot/art/hero.psd"]);
+    assert_eq!(calls[1].args, ["-G", "fstat", "//depot/art/hero.psd"]);
+}
+
+#[test]
+fn test_p4_lock_changelist() {
+    let locked = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/art/hero.psd")),
+        ("clientFile", str_value("/ws/art/hero.psd")),
+        ("action", str_value("edit")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&locked).ok(""));
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_lock(
+        Vec::new(),
+        Some(12),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(results[0].success);
+    assert_eq!(executor.calls()[0].args, ["-G", "lock", "-c", "12"]);
+}
+
+#[test]
+fn test_p4_add_login_error_fails() {
+    let error = marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("Perforce password (P4PASSWD) invalid or unset.\n")),
+        ("severity", MarshalValue::Int(3)),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&error));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_add(
+        vec!["/ws/a.txt".to_string()],
+        None,
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::LoginRequired);
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_move_opens_sources_then_moves_each_request() {
+    let edited = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/old/a.cpp")),
+        ("action", str_value("edit")),
+    ]]);
+    let moved_dir = marshal_output(&[
+        &[
+            ("code", str_value("stat")),
+            ("depotFile", str_value("//depot/new/a.cpp")),
+            ("action", str_value("move/add")),
+            ("fromFile", str_value("//depot/old/a.cpp")),
+        ],
+        &[
+            ("code", str_value("stat")),
+            ("depotFile", str_value("//depot/new/b.cpp")),
+            ("action", str_value("move/add")),
+            ("fromFile", str_value("//depot/old/b.cpp")),
+        ],
+    ]);
+    let failed = marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("//depot/c.txt - can't move to an existing file\n")),
+        ("severity", MarshalValue::Int(3)),
+    ]]);
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&edited)
+            .ok(&moved_dir)
+            .ok(&failed)
+            .ok(""),
+    );
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_move(
+        vec![
+            P4MoveRequest {
+                from: "//depot/old/...".to_string(),
+                to: "//depot/new/...".to_string(),
+            },
+            P4MoveRequest {
+                from: "//depot/c.txt".to_string(),
+                to: "//depot/d.txt".to_string(),
+            },
+        ],
+        Some(12),
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(results.iter().filter(|r| r.success).count(), 2);
+    assert_eq!(results[1].moved_from.as_deref(), Some("//depot/old/b.cpp"));
+    assert!(!results[2].success);
+    assert_eq!(results[2].path, "//depot/c.txt");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-G", "edit", "-c", "12", "//depot/old/...", "//depot/c.txt"]);
+    assert_eq!(calls[1].args, ["-G", "move", "-c", "12", "//depot/old/...", "//depot/new/..."]);
+    assert_eq!(calls[2].args, ["-G", "move", "-c", "12", "//depot/c.txt", "//depot/d.txt"]);
+    assert_eq!(
+        calls[3].args,
+        ["-G", "fstat", "//depot/new/a.cpp", "//depot/old/a.cpp", "//depot/new/b.cpp", "//depot/old/b.cpp"]
+    );
+}
+
+#[test]
+fn test_p4_move_reports_first_move_failure_per_file() {
+    let edited = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/a.txt")),
+        ("action", str_value("edit")),
+    ]]);
+    let out_of_date = marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("//depot/a.txt - must sync/resolve #4 before submitting\n")),
+        ("severity", MarshalValue::Int(3)),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&edited).ok(&out_of_date).ok(""));
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_move(
+        vec![P4MoveRequest {
+            from: "//depot/a.txt".to_string(),
+            to: "//depot/b.txt".to_string(),
+        }],
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert!(!results[0].success);
+    // The source was left open for edit, so its status is refreshed
+    assert_eq!(executor.calls()[2].args, ["-G", "fstat", "//depot/a.txt"]);
+}
+
+#[test]
+fn test_p4_move_connection_error_still_refreshes_opened_sources() {
+    let edited = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/a.txt")),
+        ("action", str_value("edit")),
+    ]]);
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&edited)
+            .fail("Perforce password (P4PASSWD) invalid or unset.\n", 1)
+            .ok(""),
+    );
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_move(
+        vec![
+            P4MoveRequest {
+                from: "//depot/a.txt".to_string(),
+                to: "//depot/b.txt".to_string(),
+            },
+            P4MoveRequest {
+                from: "//depot/c.txt".to_string(),
+                to: "//depot/d.txt".to_string(),
+            },
+        ],
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::LoginRequired);
+    let calls = executor.calls();
+    // No second move once the session is gone
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[2].args, ["-G", "fstat", "//depot/a.txt"]);
+}
+
+#[test]
+fn test_p4_move_escapes_wildcards_in_file_names() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("").ok("").ok(""));
+    let app = mock_app(&executor);
+
+    block_on(p4_move(
+        vec![P4MoveRequest {
+            from: "/ws/art/100%.png".to_string(),
+            to: "/ws/art/a*b@2x.png".to_string(),
+        }],
+        None,
+        None,
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-G", "edit", "/ws/art/100%25.png"]);
+    assert_eq!(
+        calls[1].args,
+        ["-G", "move", "/ws/art/100%25.png", "/ws/art/a%2Ab%402x.png"]
+    );
+}
+
+#[test]
+fn test_p4_integrate_preview_already_integrated_is_empty() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "",
+        "//stream/dev/... - all revision(s) already integrated.\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+
+    let options: IntegrateOptions =
+        serde_json::from_value(serde_json::json!({ "kind": "merge", "stream": "//stream/dev" })).unwrap();
+    let preview = block_on(p4_integrate_preview(
+        options,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(preview.files.is_empty());
+    assert!(preview.messages.is_empty());
+    assert_eq!(executor.calls()[0].args, ["-ztag", "merge", "-n", "-S", "//stream/dev"]);
+}
+
+#[test]
+fn test_p4_integrate_preview_reports_actions_and_per_file_messages() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "... depotFile //depot/rel/a.cpp\n... clientFile /ws/rel/a.cpp\n... workRev 4\n... action integrate\n\
+         ... fromFile //depot/main/a.cpp\n... startFromRev #2\n... endFromRev #5\n\n\
+         ... depotFile //depot/rel/new.cpp\n... clientFile /ws/rel/new.cpp\n... action branch\n\
+         ... fromFile //depot/main/new.cpp\n... startFromRev none\n... endFromRev #1\n\n",
+        "//depot/rel/old.cpp - can't integrate from //depot/main/old.cpp#3 (deleted) without -d or -Ds flag\n",
+        0,
+    ));
+    let app = mock_app(&executor);
+
+    let options: IntegrateOptions = serde_json::from_value(serde_json::json!({
+        "kind": "integrate",
+        "branch": "release-1.2",
+        "changelist": 77,
+    }))
+    .unwrap();
+    let preview = block_on(p4_integrate_preview(
+        options,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(preview.files.len(), 2);
+    assert_eq!(preview.files[0].action, "integrate");
+    assert_eq!(preview.files[0].work_rev, Some(4));
+    assert_eq!(preview.files[0].start_from_rev.as_deref(), Some("#2"));
+    assert_eq!(preview.files[1].action, "branch");
+    assert_eq!(preview.files[1].from_file.as_deref(), Some("//depot/main/new.cpp"));
+    assert_eq!(preview.messages.len(), 1);
+    assert!(preview.messages[0].starts_with("//depot/rel/old.cpp - can't integrate"));
+    assert_eq!(
+        executor.calls()[0].args,
+        ["-ztag", "integrate", "-n", "-c", "77", "-b", "release-1.2"]
+    );
+}
+
+#[test]
+fn test_p4_integrate_preview_unknown_stream_fails() {
+    let executor = Arc::new(ScriptedExecutor::new().fail("Stream '//stream/nope' doesn't exist.\n", 1));
+    let app = mock_app(&executor);
+
+    let options: IntegrateOptions =
+        serde_json::from_value(serde_json::json!({ "kind": "copy", "stream": "//stream/nope" })).unwrap();
+    let err = block_on(p4_integrate_preview(
+        options,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert!(err.message.contains("doesn't exist"));
+}
+
+#[test]
+fn test_p4_integrate_streams_opened_files_and_completes() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "... depotFile //stream/dev/a.cpp\n... clientFile /ws/a.cpp\n... workRev 3\n... action integrate\n\
+         ... fromFile //stream/main/a.cpp\n... startFromRev #4\n... endFromRev #6\n",
+        "",
+        0,
+    ));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    let options: IntegrateOptions = serde_json::from_value(serde_json::json!({
+        "kind": "merge",
+        "stream": "//stream/dev",
+        "changelist": 12,
+    }))
+    .unwrap();
+    block_on(p4_integrate(
+        options,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, late) = drain_until_complete(&rx);
+    assert!(late.is_empty(), "messages after completion: {:?}", late);
+    // The last record has no trailing blank line and must still be sent
+    assert_eq!(messages[0]["type"], "data");
+    assert_eq!(messages[0]["files"][0]["depotPath"], "//stream/dev/a.cpp");
+    assert_eq!(messages[0]["files"][0]["endFromRev"], "#6");
+
+    let complete = messages.last().unwrap();
+    assert_eq!(complete["totalFiles"], 1);
+    assert_eq!(complete["success"], true);
+    assert_eq!(executor.calls()[0].args, ["-ztag", "merge", "-c", "12", "-S", "//stream/dev"]);
+}
+
+const DEV_ISTAT: &str = "... stream //depot/dev\n... parent //depot/main\n... type development\n\
+    ... changeFlowsToParent true\n... changeFlowsFromParent true\n\
+    ... integToParent false\n... integToParentHow copy\n\
+    ... integFromParent true\n... integFromParentHow merge\n";
+
+#[test]
+fn test_p4_stream_integration_status_lists_changes_to_merge_down() {
+    let interchanges = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("change", str_value("1201")),
+        ("time", str_value("1700000000")),
+        ("user", str_value("bob")),
+        ("client", str_value("bob-main")),
+        ("status", str_value("submitted")),
+        ("desc", str_value("Fix crash on startup\n\nDetails on a second line\n")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(DEV_ISTAT).ok(&interchanges));
+    let app = mock_app(&executor);
+
+    let status = block_on(p4_stream_integration_status(
+        "//depot/dev".to_string(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(status.needs_merge_down);
+    assert!(!status.needs_copy_up);
+    assert_eq!(status.pending_merge_down.len(), 1);
+    assert_eq!(status.pending_merge_down[0].id, 1201);
+    assert!(status.pending_merge_down[0].description.contains("second line"));
+    assert!(status.pending_copy_up.is_empty());
+
+    // Nothing to copy up, so interchanges only runs from the parent
+    let calls = executor.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].args, ["-ztag", "istat", "-a", "//depot/dev"]);
+    assert_eq!(calls[1].args, ["-G", "interchanges", "-l", "-S", "//depot/dev", "-r"]);
+}
+
+#[test]
+fn test_p4_list_streams_attaches_integration_status() {
+    let streams = "... Stream //depot/main\n... Name main\n... Parent none\n... Type mainline\n\n\
+                   ... Stream //depot/dev\n... Name dev\n... Parent //depot/main\n... Type development\n\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(streams).ok(DEV_ISTAT));
+    let app = mock_app(&executor);
+
+    let streams = block_on(p4_list_streams(
+        Some(true),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    // The mainline has no parent to integrate with
+    assert!(streams[0].integration.is_none());
+    let dev = streams[1].integration.as_ref().unwrap();
+    assert!(dev.needs_merge_down);
+    assert!(dev.pending_merge_down.is_empty());
+    assert_eq!(executor.calls().len(), 2);
+}
+
+#[test]
+fn test_p4_list_streams_records_istat_failure() {
+    let streams = "... Stream //depot/dev\n... Name dev\n... Parent //depot/main\n... Type development\n\n";
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(streams)
+            .fail("//depot/dev - no such stream.\n", 1),
+    );
+    let app = mock_app(&executor);
+
+    let streams = block_on(p4_list_streams(
+        Some(true),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let dev = streams[0].integration.as_ref().unwrap();
+    assert_eq!(dev.parent.as_deref(), Some("//depot/main"));
+    assert!(!dev.needs_merge_down);
+    assert!(dev.error.as_deref().unwrap().contains("no such stream"));
+}
+
+#[test]
+fn test_p4_save_stream_spec_reports_field_from_error_line() {
+    let form = "Stream:\t//depot/dev\n\nOwner:\talice\n\nName:\tdev\n\nParent:\t//depot/main\n\nType:\tdevelopment\n\n\
+                Description:\n\tdev work\n\nOptions:\tallsubmit unlocked toparent fromparent mergedown\n\nPaths:\n\tshare ...\n";
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(form)
+            .fail("Error in stream specification.\nError detected at line 19.\nPath 'import lib/...' needs a depot path.\n", 1),
+    );
+    let app = mock_app(&executor);
+
+    let spec: P4StreamSpec = serde_json::from_value(serde_json::json!({
+        "stream": "//depot/dev",
+        "name": "dev",
+        "owner": "alice",
+        "parent": "//depot/main",
+        "streamType": "development",
+        "description": "dev work",
+        "options": "allsubmit unlocked toparent fromparent mergedown",
+        "parentView": null,
+        "paths": ["share ...", "import lib/... x"],
+        "remapped": [],
+        "ignored": [],
+    }))
+    .unwrap();
+    let err = block_on(p4_save_stream_spec(
+        spec,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::InvalidSpec);
+    assert_eq!(err.field_errors.len(), 1);
+    assert_eq!(err.field_errors[0].field.as_deref(), Some("Paths"));
+    assert_eq!(err.field_errors[0].message, "Path 'import lib/...' needs a depot path.");
+    let json = serde_json::to_value(&err).unwrap();
+    assert_eq!(json["fieldErrors"][0]["field"], "Paths");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["stream", "-o", "//depot/dev"]);
+    assert_eq!(calls[1].args, ["stream", "-i"]);
+    assert!(calls[1].input.as_ref().unwrap().contains("Paths:\n\tshare ...\n\timport lib/... x\n"));
+}
+
+#[test]
+fn test_p4_save_stream_spec_validates_before_sending() {
+    let executor = Arc::new(ScriptedExecutor::new());
+    let app = mock_app(&executor);
+
+    let spec: P4StreamSpec = serde_json::from_value(serde_json::json!({
+        "stream": "//depot/dev",
+        "name": "dev",
+        "owner": "",
+        "parent": "//depot/main",
+        "streamType": "development",
+        "description": "",
+        "options": "",
+        "parentView": null,
+        "paths": [],
+        "remapped": [],
+        "ignored": [],
+    }))
+    .unwrap();
+    let err = block_on(p4_save_stream_spec(
+        spec,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::InvalidSpec);
+    let fields: Vec<_> = err.field_errors.iter().map(|e| e.field.as_deref().unwrap()).collect();
+    assert_eq!(fields, ["Owner", "Paths"]);
+    assert!(executor.calls().is_empty());
+}
+
+#[test]
+fn test_p4_create_stream_from_parent() {
+    let template = "Stream:\t//depot/task-42\n\nOwner:\talice\n\nName:\ttask-42\n\nParent:\t//depot/main\n\n\
+                    Type:\ttask\n\nDescription:\n\tCreated by alice.\n\nPaths:\n\tshare ...\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(template).ok("Stream //depot/task-42 saved.\n"));
+    let app = mock_app(&executor);
+
+    let message = block_on(p4_create_stream(
+        "//depot/task-42".to_string(),
+        "task".to_string(),
+        "//depot/main".to_string(),
+        None,
+        Some("Fix login crash".to_string()),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(message, "Stream //depot/task-42 saved.");
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["stream", "-o", "-t", "task", "-P", "//depot/main", "//depot/task-42"]);
+    let form = calls[1].input.clone().unwrap();
+    assert!(form.contains("Name:\ttask-42\n"));
+    assert!(form.contains("Description:\n\tFix login crash\n\nPaths:"));
+}
+
+const CLIENT_FORM: &str = "Client:\tbob-ws\n\nOwner:\talice\n\nHost:\n\nDescription:\n\tCreated by alice.\n\n\
+                           Root:\t/home/alice/ws\n\nOptions:\tnoallwrite noclobber nocompress unlocked nomodtime normdir\n\n\
+                           SubmitOptions:\tsubmitunchanged\n\nLineEnd:\tlocal\n\nView:\n\t//depot/main/... //bob-ws/main/...\n";
+
+fn client_spec_json(view: &[&str]) -> P4ClientSpec {
+    serde_json::from_value(serde_json::json!({
+        "client": "bob-ws",
+        "root": "/home/alice/ws",
+        "stream": null,
+        "owner": "alice",
+        "description": "Build workspace\nfor nightly runs",
+        "options": "noallwrite noclobber nocompress unlocked nomodtime normdir",
+        "host": "",
+        "submit_options": "revertunchanged",
+        "view": view,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_p4_save_client_spec_sends_every_field() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(CLIENT_FORM).ok("Client bob-ws saved.\n"));
+    let app = mock_app(&executor);
+
+    let spec = client_spec_json(&["//depot/main/... //bob-ws/main/...", "-//depot/main/tmp/... //bob-ws/main/tmp/..."]);
+    let message = block_on(p4_save_client_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(message, "Client bob-ws saved.");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["client", "-o", "bob-ws"]);
+    assert_eq!(calls[1].args, ["client", "-i"]);
+    let input = calls[1].input.as_ref().unwrap();
+    assert!(input.contains("Description:\n\tBuild workspace\n\tfor nightly runs\n"));
+    assert!(input.contains("SubmitOptions:\trevertunchanged\n"));
+    assert!(input.contains("View:\n\t//depot/main/... //bob-ws/main/...\n\t-//depot/main/tmp/... //bob-ws/main/tmp/...\n"));
+}
+
+#[test]
+fn test_p4_create_client_from_template_keeps_template_view() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("")
+            .ok(CLIENT_FORM)
+            .ok("Client bob-ws saved.\n"),
+    );
+    let app = mock_app(&executor);
+
+    let message = block_on(p4_create_client(
+        client_spec_json(&[]),
+        Some("alice-ws".to_string()),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(message, "Client bob-ws saved.");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-ztag", "clients", "-e", "bob-ws"]);
+    assert_eq!(calls[1].args, ["client", "-o", "-t", "alice-ws", "bob-ws"]);
+    assert!(calls[2].input.as_ref().unwrap().contains("View:\n\t//depot/main/... //bob-ws/main/...\n"));
+}
+
+#[test]
+fn test_p4_create_client_empty_template_does_not_adopt_default_view() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("").ok(CLIENT_FORM));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_create_client(
+        client_spec_json(&[]),
+        Some(String::new()),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::InvalidSpec);
+    assert_eq!(err.field_errors[0].field.as_deref(), Some("View"));
+    let calls = executor.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1].args, ["client", "-o", "bob-ws"]);
+}
+
+#[test]
+fn test_p4_create_client_refuses_existing_workspace() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("... client bob-ws\n... Owner alice\n"));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_create_client(
+        client_spec_json(&["//depot/... //bob-ws/..."]),
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::InvalidSpec);
+    assert_eq!(err.field_errors[0].field.as_deref(), Some("Client"));
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_delete_client_force() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("Client bob-ws deleted.\n"));
+    let app = mock_app(&executor);
+
+    let message = block_on(p4_delete_client(
+        "bob-ws".to_string(),
+        true,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(message, "Client bob-ws deleted.");
+    assert_eq!(executor.calls()[0].args, ["client", "-d", "-f", "bob-ws"]);
+}
+
+#[test]
+fn test_p4_delete_client_with_opened_files_fails() {
+    let executor = Arc::new(
+        ScriptedExecutor::new().fail("Client 'bob-ws' has files opened; use -f to force delete.\n", 1),
+    );
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_delete_client(
+        "bob-ws".to_string(),
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert!(err.message.contains("has files opened"));
+    assert_eq!(executor.calls()[0].args, ["client", "-d", "bob-ws"]);
+}
+
+#[test]
+fn test_p4_sync_to_label() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("//depot/a.cpp#2 - updating /ws/a.cpp\n"));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_sync(
+        vec!["//depot/src/...".to_string(), "//depot/docs/...".to_string()],
+        None,
+        Some("release-1.2".to_string()),
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, _) = drain_until_complete(&rx);
+    assert_eq!(messages.last().unwrap()["success"], true);
+    assert_eq!(
+        executor.calls()[0].args,
+        ["sync", "//depot/src/...@release-1.2", "//depot/docs/...@release-1.2"]
+    );
+}
+
+#[test]
+fn test_p4_files_at_label() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("//depot/a.cpp#3 - edit change 120 (text)\n"));
+    let app = mock_app(&executor);
+
+    let files = block_on(p4_files(
+        "//depot/...".to_string(),
+        100,
+        Some("@release-1.2".to_string()),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(files[0].revision, 3);
+    assert_eq!(executor.calls()[0].args, ["files", "//depot/...@release-1.2"]);
+}
+
+#[test]
+fn test_p4_list_labels_filters_by_owner_and_name() {
+    let labels = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("label", str_value("release-1.2")),
+        ("Owner", str_value("build")),
+        ("Update", str_value("1700000000")),
+        ("Access", str_value("1700000500")),
+        ("Options", str_value("locked noautoreload")),
+        ("Revision", str_value("@1234")),
+        ("Description", str_value("Release 1.2\n")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&labels));
+    let app = mock_app(&executor);
+
+    let labels = block_on(p4_list_labels(
+        Some("release-*".to_string()),
+        Some("build".to_string()),
+        Some(50),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(labels.len(), 1);
+    assert_eq!(labels[0].label, "release-1.2");
+    assert_eq!(labels[0].description, "Release 1.2");
+    assert_eq!(labels[0].revision.as_deref(), Some("@1234"));
+    assert_eq!(labels[0].update, 1700000000);
+    assert_eq!(
+        executor.calls()[0].args,
+        ["-G", "labels", "-u", "build", "-E", "release-*", "-m", "50"]
+    );
+}
+
+#[test]
+fn test_p4_save_label_spec_writes_view_and_revision() {
+    let form = "Label:\trelease-1.3\n\nOwner:\tbuild\n\nDescription:\n\tCreated by build.\n\n\
+                Options:\tunlocked noautoreload\n\nView:\n\t//...\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(form).ok("Label release-1.3 saved.\n"));
+    let app = mock_app(&executor);
+
+    let spec: P4LabelSpec = serde_json::from_value(serde_json::json!({
+        "label": "release-1.3",
+        "owner": "build",
+        "description": "Release 1.3",
+        "options": "locked noautoreload",
+        "revision": "@2000",
+        "view": ["//depot/main/...", "-//depot/main/tests/..."],
+    }))
+    .unwrap();
+    let message = block_on(p4_save_label_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(message, "Label release-1.3 saved.");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["label", "-o", "release-1.3"]);
+    let input = calls[1].input.as_deref().unwrap();
+    assert!(input.contains("Options:\tlocked noautoreload\n"));
+    assert!(input.contains("Revision:\t@2000\n"));
+    assert!(input.contains("View:\n\t//depot/main/...\n\t-//depot/main/tests/...\n"));
+}
+
+#[test]
+fn test_p4_user_spec_round_trip() {
+    let form = "# A Perforce User Specification.\n\nUser:\talice\n\nEmail:\talice@example.com\n\n\
+                Update:\t2024/05/01 10:00:00\n\nAccess:\t2024/05/02 09:00:00\n\nFullName:\tAlice\n\n\
+                Reviews:\n\t//depot/engine/...\n";
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(form)
+            .ok(form)
+            .ok("User alice saved.\n"),
+    );
+    let app = mock_app(&executor);
+
+    let mut spec = block_on(p4_get_user_spec(
+        String::new(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(spec.user, "alice");
+    assert_eq!(spec.reviews, ["//depot/engine/..."]);
+    assert_eq!(spec.access.as_deref(), Some("2024/05/02 09:00:00"));
+
+    spec.full_name = "Alice Smith".to_string();
+    spec.reviews.push("//depot/tools/...".to_string());
+    let message = block_on(p4_save_user_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(message, "User alice saved.");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["user", "-o"]);
+    assert_eq!(calls[1].args, ["user", "-o", "alice"]);
+    assert_eq!(calls[2].args, ["user", "-i"]);
+    let input = calls[2].input.as_deref().unwrap();
+    assert!(input.contains("FullName:\tAlice Smith\n"));
+    assert!(input.contains("Reviews:\n\t//depot/engine/...\n\t//depot/tools/...\n"));
+    assert!(input.contains("Update:\t2024/05/01 10:00:00\n"));
+}
+
+#[test]
+fn test_p4_save_user_spec_requires_email() {
+    let executor = Arc::new(ScriptedExecutor::new());
+    let app = mock_app(&executor);
+    let spec: P4UserSpec = serde_json::from_value(serde_json::json!({
+        "user": "alice",
+        "email": "",
+        "fullName": "Alice",
+        "reviews": ["depot/engine/..."],
+    }))
+    .unwrap();
+
+    let err = block_on(p4_save_user_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap_err();
+
+    let fields: Vec<_> = err.field_errors.iter().filter_map(|e| e.field.as_deref()).collect();
+    assert_eq!(fields, ["Email", "Reviews"]);
+    assert!(executor.calls().is_empty());
+}
+
+#[test]
+fn test_p4_tag_changelist_revisions() {
+    let tagged = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/main/a.cpp")),
+        ("rev", str_value("4")),
+        ("action", str_value("added")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&tagged));
+    let app = mock_app(&executor);
+
+    let results = block_on(p4_tag(
+        "release-1.3".to_string(),
+        vec!["//depot/main/...".to_string()],
+        Some(2000),
+        false,
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(results[0].depot_path.as_deref(), Some("//depot/main/a.cpp"));
+    assert_eq!(results[0].revision, Some(4));
+    assert_eq!(results[0].action.as_deref(), Some("added"));
+    assert_eq!(
+        executor.calls()[0].args,
+        ["-G", "tag", "-l", "release-1.3", "//depot/main/...@=2000"]
+    );
+}
+
+#[test]
+fn test_p4_labelsync_preview_adds_only() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(""));
+    let app = mock_app(&executor);
+
+    block_on(p4_labelsync(
+        "nightly".to_string(),
+        vec![],
+        true,
+        false,
+        true,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(executor.calls()[0].args, ["-G", "labelsync", "-a", "-n", "-l", "nightly"]);
+}
+
+const JOBSPEC_FORM: &str = "# A Perforce Job Specification.\n\nFields:\n\t101 Job word 32 required\n\t102 Status select 10 required\n\
+                            \t103 User word 32 required\n\t104 Date date 20 always\n\t105 Description text 0 required\n\
+                            \t110 Severity select 10 optional\n\nValues:\n\tStatus open/suspended/closed\n\tSeverity A/B/C\n\n\
+                            Presets:\n\tStatus open\n\tUser $user\n\tDate $now\n";
+
+#[test]
+fn test_p4_get_job_spec_types_fields_from_jobspec() {
+    let job = "Job:\tjob000012\n\nStatus:\topen\n\nUser:\talice\n\nDate:\t2024/05/01 10:00:00\n\n\
+               Description:\n\tCrash on startup\n\n\twhen offline\n\nSeverity:\tA\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(JOBSPEC_FORM).ok(job));
+    let app = mock_app(&executor);
+
+    let spec = block_on(p4_get_job_spec(
+        "job000012".to_string(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(spec.job, "job000012");
+    let names: Vec<_> = spec.fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["Job", "Status", "User", "Date", "Description", "Severity"]);
+    assert_eq!(spec.fields[1].values, ["open", "suspended", "closed"]);
+    assert_eq!(spec.fields[3].field_type, "always");
+    assert_eq!(spec.fields[4].value, "Crash on startup\n\nwhen offline");
+    assert_eq!(spec.fields[5].value, "A");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["jobspec", "-o"]);
+    assert_eq!(calls[1].args, ["job", "-o", "job000012"]);
+}
+
+#[test]
+fn test_p4_save_job_spec_new_job() {
+    let template = "Job:\tnew\n\nStatus:\topen\n\nUser:\talice\n\nDate:\t2024/05/01 10:00:00\n\n\
+                    Description:\n\t<enter description here>\n\nSeverity:\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(template).ok("Job job000016 saved.\n"));
+    let app = mock_app(&executor);
+
+    let field = |name: &str, value: &str, data_type: &str, field_type: &str| P4JobField {
+        name: name.to_string(),
+        value: value.to_string(),
+        data_type: data_type.to_string(),
+        field_type: field_type.to_string(),
+        values: Vec::new(),
+    };
+    let mut status = field("Status", "open", "select", "required");
+    status.values = vec!["open".to_string(), "closed".to_string()];
+    let spec = P4JobSpec {
+        job: "new".to_string(),
+        fields: vec![
+            field("Job", "new", "word", "required"),
+            status,
+            field("User", "alice", "word", "required"),
+            field("Date", "1999/01/01", "date", "always"),
+            field("Description", "Crash on startup\nwhen offline", "text", "required"),
+        ],
+    };
+    let message = block_on(p4_save_job_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(message, "Job job000016 saved.");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["job", "-o"]);
+    assert_eq!(calls[1].args, ["job", "-i"]);
+    let input = calls[1].input.as_deref().unwrap();
+    assert!(input.contains("Description:\n\tCrash on startup\n\twhen offline\n"));
+    // Server-set fields are sent back as p4 gave them
+    assert!(input.contains("Date:\t2024/05/01 10:00:00\n"));
+}
+
+#[test]
+fn test_p4_save_job_spec_rejects_unknown_select_value() {
+    let executor = Arc::new(ScriptedExecutor::new());
+    let app = mock_app(&executor);
+
+    let spec = P4JobSpec {
+        job: "job000012".to_string(),
+        fields: vec![P4JobField {
+            name: "Status".to_string(),
+            value: "fixed".to_string(),
+            data_type: "select".to_string(),
+            field_type: "required".to_string(),
+            values: vec!["open".to_string(), "closed".to_string()],
+        }],
+    };
+    let err = block_on(p4_save_job_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap_err();
+
+    assert_eq!(err.kind, P4ErrorKind::InvalidSpec);
+    assert_eq!(err.field_errors[0].field.as_deref(), Some("Status"));
+    assert!(executor.calls().is_empty());
+}
+
+#[test]
+fn test_p4_jobs_query() {
+    let jobs = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("Job", str_value("job000012")),
+        ("Status", str_value("open")),
+        ("User", str_value("alice")),
+        ("Date", str_value("2024/05/01 10:00:00")),
+        ("Description", str_value("Crash on startup\n")),
+        ("Severity", str_value("A")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&jobs));
+    let app = mock_app(&executor);
+
+    let jobs = block_on(p4_jobs(
+        Some("status=open crash".to_string()),
+        Some(20),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].job, "job000012");
+    assert_eq!(jobs[0].description, "Crash on startup");
+    assert_eq!(jobs[0].fields.get("Severity").map(String::as_str), Some("A"));
+    assert!(!jobs[0].fields.contains_key("Status"));
+    assert_eq!(executor.calls()[0].args, ["-G", "jobs", "-l", "-e", "status=open crash", "-m", "20"]);
+}
+
+#[test]
+fn test_p4_fix_detach() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("job000012 fix from change 100 deleted.\n"));
+    let app = mock_app(&executor);
+
+    let message = block_on(p4_fix(
+        100,
+        vec!["job000012".to_string()],
+        None,
+        true,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(message, "job000012 fix from change 100 deleted.");
+    assert_eq!(executor.calls()[0].args, ["fix", "-d", "-c", "100", "job000012"]);
+}
+
+#[test]
+fn test_p4_get_branch_spec_parses_view_mappings() {
+    let spec = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("Branch", str_value("main-to-rel")),
+        ("Owner", str_value("build")),
+        ("Options", str_value("unlocked")),
+        ("Description", str_value("Release branch\n")),
+        ("View0", str_value("//depot/main/... //depot/rel/...")),
+        ("View1", str_value("-//depot/main/tests/... //depot/rel/tests/...")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&spec));
+    let app = mock_app(&executor);
+
+    let spec = block_on(p4_get_branch_spec(
+        "main-to-rel".to_string(),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(spec.description, "Release branch");
+    assert_eq!(spec.view.len(), 2);
+    assert_eq!(spec.view[1].kind, MappingKind::Exclude);
+    assert_eq!(spec.view[1].source, "//depot/main/tests/...");
+    assert_eq!(executor.calls()[0].args, ["-G", "branch", "-o", "main-to-rel"]);
+}
+
+#[test]
+fn test_p4_save_branch_spec_writes_view_lines() {
+    let form = "Branch:\tmain-to-rel\n\nOwner:\tbuild\n\nDescription:\n\tCreated by build.\n\n\
+                Options:\tunlocked\n\nView:\n";
+    let executor = Arc::new(ScriptedExecutor::new().ok(form).ok("Branch main-to-rel saved.\n"));
+    let app = mock_app(&executor);
+
+    let spec: P4BranchSpec = serde_json::from_value(serde_json::json!({
+        "branch": "main-to-rel",
+        "owner": "build",
+        "description": "Release branch",
+        "options": "locked",
+        "view": [
+            { "kind": "include", "source": "//depot/main/...", "target": "//depot/rel/..." },
+            { "kind": "exclude", "source": "//depot/main/My Docs/...", "target": "//depot/rel/My Docs/..." },
+        ],
+    }))
+    .unwrap();
+    let message = block_on(p4_save_branch_spec(spec, None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+    assert_eq!(message, "Branch main-to-rel saved.");
+
+    let input = executor.calls()[1].input.clone().unwrap();
+    assert!(input.contains(
+        "View:\n\t//depot/main/... //depot/rel/...\n\t\"-//depot/main/My Docs/...\" \"//depot/rel/My Docs/...\"\n"
+    ));
+    assert!(input.contains("Options:\tlocked\n"));
+}
+
+#[test]
+fn test_p4_diff2_branch_reports_each_side() {
+    let pairs = marshal_output(&[
+        &[
+            ("code", str_value("stat")),
+            ("status", str_value("content")),
+            ("depotFile", str_value("//depot/main/a.c")),
+            ("rev", str_value("5")),
+            ("type", str_value("text")),
+            ("depotFile2", str_value("//depot/rel/a.c")),
+            ("rev2", str_value("3")),
+            ("type2", str_value("text")),
+        ],
+        &[
+            ("code", str_value("stat")),
+            ("status", str_value("left only")),
+            ("depotFile", str_value("//depot/main/new.c")),
+            ("rev", str_value("1")),
+            ("type", str_value("text")),
+            ("depotFile2", str_value("<none>")),
+        ],
+    ]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&pairs));
+    let app = mock_app(&executor);
+
+    let files = block_on(p4_diff2_branch(
+        "main-to-rel".to_string(),
+        Some("//depot/rel/...".to_string()),
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].status, Diff2Status::Content);
+    assert_eq!(files[0].right_revision, Some(3));
+    assert_eq!(files[1].status, Diff2Status::LeftOnly);
+    assert_eq!(files[1].left_path.as_deref(), Some("//depot/main/new.c"));
+    assert_eq!(files[1].right_path, None);
+    let json = serde_json::to_value(&files[1]).unwrap();
+    assert_eq!(json["status"], "leftOnly");
+    assert_eq!(
+        executor.calls()[0].args,
+        ["-G", "diff2", "-q", "-b", "main-to-rel", "//depot/rel/..."]
+    );
+}
+
+#[test]
+fn test_p4_diff2_branch_no_differences_is_empty() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(&marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("No differing files.\n")),
+        ("severity", MarshalValue::Int(2)),
+    ]])));
+    let app = mock_app(&executor);
+
+    let files = block_on(p4_diff2_branch(
+        "main-to-rel".to_string(),
+        None,
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(files.is_empty());
+}
+
+/// p4 -G fstat -Ol output for one revision
+fn fstat_type_and_size(file_type: &str, size: u64) -> String {
+    marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/a.txt")),
+        ("headType", str_value(file_type)),
+        ("fileSize", str_value(&size.to_string())),
+    ]])
+}
+
+#[test]
+fn test_p4_diff_shelved_against_local_file() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&fstat_type_and_size("text", 14))
+            .ok("one\ntwo\nthree\n"),
+    );
+    let app = mock_app(&executor);
+    let dir = tempfile::tempdir().unwrap();
+    let local = dir.path().join("a.txt");
+    std::fs::write(&local, "one\n2\nthree\n").unwrap();
+
+    let diff = block_on(p4_diff(
+        DiffSource::Shelved {
+            depot_path: "//depot/a.txt".to_string(),
+            changelist: 42,
+        },
+        DiffSource::Local {
+            path: local.to_string_lossy().to_string(),
+        },
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!((diff.added, diff.removed), (1, 1));
+    assert_eq!(diff.hunks[0].lines.len(), 4);
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-G", "fstat", "-Ol", "//depot/a.txt@=42"]);
+    assert_eq!(calls[1].args, ["print", "-q", "//depot/a.txt@=42"]);
+}
+
+#[test]
+fn test_p4_diff_depot_revisions_with_options() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&fstat_type_and_size("text", 6))
+            .ok("a  b\r\n")
+            .ok(&fstat_type_and_size("text", 4))
+            .ok("a b\n"),
+    );
+    let app = mock_app(&executor);
+    let depot = |revision: i32, revision_spec: Option<&str>| DiffSource::Depot {
+        depot_path: "//depot/a.txt".to_string(),
+        revision,
+        revision_spec: revision_spec.map(str::to_string),
+    };
+    let options: DiffOptions =
+        serde_json::from_value(serde_json::json!({ "whitespace": "ignoreChanges", "ignoreLineEndings": true }))
+            .unwrap();
+
+    let diff = block_on(p4_diff(
+        depot(3, None),
+        depot(0, Some("release-1")),
+        Some(options),
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(diff.hunks.is_empty());
+    let calls = executor.calls();
+    assert_eq!(calls[1].args, ["print", "-q", "//depot/a.txt#3"]);
+    assert_eq!(calls[3].args, ["print", "-q", "//depot/a.txt@release-1"]);
+}
+
+#[test]
+fn test_p4_diff_checks_size_and_type_before_printing() {
+    let depot = |revision: i32| DiffSource::Depot {
+        depot_path: "//depot/a.txt".to_string(),
+        revision,
+        revision_spec: None,
+    };
+
+    let executor = Arc::new(ScriptedExecutor::new().ok(&fstat_type_and_size("text", 64 * 1024 * 1024)));
+    let app = mock_app(&executor);
+    let err = block_on(p4_diff(
+        depot(2),
+        depot(1),
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+    assert!(err.message.starts_with("File too large to diff"));
+    assert_eq!(executor.calls().len(), 1);
+
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&fstat_type_and_size("binary+l", 100))
+            .ok(&fstat_type_and_size("binary+l", 100)),
+    );
+    let app = mock_app(&executor);
+    let diff = block_on(p4_diff(
+        depot(2),
+        depot(1),
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert!(diff.binary);
+    assert!(executor.calls().iter().all(|call| !call.args.contains(&"print".to_string())));
+}
+
+#[test]
+fn test_p4_diff_treats_every_non_text_type_as_binary() {
+    for file_type in ["apple", "resource", "symlink", "binary+F", "ubinary"] {
+        let fstat = fstat_type_and_size(file_type, 100);
+        let executor = Arc::new(ScriptedExecutor::new().ok(&fstat).ok(&fstat));
+        let app = mock_app(&executor);
+        let diff = block_on(p4_diff(
+            DiffSource::Depot {
+                depot_path: "//depot/a.txt".to_string(),
+                revision: 2,
+                revision_spec: None,
+            },
+            DiffSource::Depot {
+                depot_path: "//depot/a.txt".to_string(),
+                revision: 1,
+                revision_spec: None,
+            },
+            None,
+            None,
+            app.state::<ConnectionsState>(),
+            app.state::<P4ExecutorState>(),
+        ))
+        .unwrap();
+        assert!(diff.binary, "{} should not be printed", file_type);
+    }
+}
+
+#[test]
+fn test_p4_diff_shelved_uses_shelved_file_type() {
+    // Shelved as binary over a text head revision
+    let fstat = marshal_output(&[&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/a.txt")),
+        ("headType", str_value("text")),
+        ("type", str_value("binary+F")),
+        ("fileSize", str_value("100")),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&fstat).ok(&fstat));
+    let app = mock_app(&executor);
+
+    let diff = block_on(p4_diff(
+        DiffSource::Shelved {
+            depot_path: "//depot/a.txt".to_string(),
+            changelist: 42,
+        },
+        DiffSource::Shelved {
+            depot_path: "//depot/a.txt".to_string(),
+            changelist: 42,
+        },
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(diff.binary);
+    assert!(executor.calls().iter().all(|call| !call.args.contains(&"print".to_string())));
+}
+
+#[test]
+fn test_p4_diff_missing_revision_is_an_error() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok(&marshal_output(&[&[
+                ("code", str_value("error")),
+                ("data", str_value("//depot/a.txt#9 - no such file(s).\n")),
+                ("severity", MarshalValue::Int(2)),
+            ]]))
+            .ok(&fstat_type_and_size("text", 2))
+            .ok("a\n"),
+    );
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_diff(
+        DiffSource::Depot {
+            depot_path: "//depot/a.txt".to_string(),
+            revision: 9,
+            revision_spec: None,
+        },
+        DiffSource::Depot {
+            depot_path: "//depot/a.txt".to_string(),
+            revision: 1,
+            revision_spec: None,
+        },
+        None,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert!(err.is(P4ErrorKind::NoSuchFile));
+}
+
+#[test]
+fn test_p4_changelist_diff_pending_streams_opened_files() {
+    let opened = marshal_output(&[
+        &[("depotFile", str_value("//depot/b.txt")), ("action", str_value("edit"))],
+        &[("depotFile", str_value("//depot/a.txt")), ("action", str_value("add"))],
+    ]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&opened).respond(
+        "==== //depot/b.txt#2 - /ws/b.txt ====\n@@ -1 +1 @@\n-x\n+y\n",
+        "//depot/a.txt - file(s) not on client.\n",
+        0,
+    ));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_changelist_diff(
+        42,
+        ChangeDiffSource::Pending,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, late) = drain_until_complete(&rx);
+    assert!(late.is_empty());
+    assert_eq!(messages.len(), 2);
+    let file = &messages[0]["files"][0];
+    assert_eq!(file["depotPath"], "//depot/b.txt");
+    assert_eq!(file["action"], "edit");
+    assert_eq!(file["localPath"], "/ws/b.txt");
+    assert_eq!(file["hunks"][0]["lines"][1]["kind"], "added");
+    assert_eq!(messages[1]["totalFiles"], 1);
+    assert_eq!(messages[1]["success"], true);
+
+    let calls = executor.calls();
+    assert_eq!(
+        calls[0].args,
+        ["-G", "fstat", "-Ro", "-e", "42", "-T", "depotFile,action", "//..."]
+    );
+    assert_eq!(calls[1].args[0], "-x");
+    assert_eq!(calls[1].args[2..], ["diff", "-du"]);
+    assert_eq!(calls[1].arg_file.as_deref(), Some(&b"//depot/a.txt\n//depot/b.txt\n"[..]));
+}
+
+#[test]
+fn test_p4_changelist_diff_pending_writes_paths_in_connection_charset() {
+    let (depot_path, _, _) = encoding_rs::SHIFT_JIS.encode("//depot/設定.txt");
+    // p4 -G output in the command charset
+    let mut opened = vec![b'{'];
+    for value in [&b"depotFile"[..], &depot_path, b"action", b"edit"] {
+        opened.push(b's');
+        opened.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        opened.extend_from_slice(value);
+    }
+    opened.push(b'0');
+    let executor = Arc::new(ScriptedExecutor::new().respond_bytes(&opened, b"", 0).ok(""));
+    let app = mock_app(&executor);
+    app.state::<ConnectionsState>()
+        .save(ConnectionProfile {
+            id: "sjis".to_string(),
+            name: "Shift JIS".to_string(),
+            charset: Some("shiftjis".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    let (channel, rx) = json_channel();
+
+    block_on(p4_changelist_diff(
+        42,
+        ChangeDiffSource::Pending,
+        Some("sjis".to_string()),
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    drain_until_complete(&rx);
+
+    let mut expected = depot_path.into_owned();
+    expected.push(b'\n');
+    assert_eq!(executor.calls()[1].arg_file, Some(expected));
+}
+
+#[test]
+fn test_p4_changelist_diff_shelved_uses_describe() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(""));
+    let app = mock_app(&executor);
+    let (channel, rx) = json_channel();
+
+    block_on(p4_changelist_diff(
+        7,
+        ChangeDiffSource::Shelved,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let (messages, _) = drain_until_complete(&rx);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["totalFiles"], 0);
+    assert_eq!(executor.calls()[0].args, ["describe", "-du", "-S", "7"]);
+}
+
+#[test]
+fn test_p4_changelist_diff_pending_without_files_fails() {
+    let executor = Arc::new(ScriptedExecutor::new().ok(&marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("//... - no such file(s).\n")),
+        ("severity", MarshalValue::Int(2)),
+    ]])));
+    let app = mock_app(&executor);
+    let (channel, _rx) = json_channel();
+
+    let err = block_on(p4_changelist_diff(
+        0,
+        ChangeDiffSource::Pending,
+        None,
+        channel,
+        app.state::<ProcessManager>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.message, "No files opened in changelist default");
+    assert_eq!(executor.calls()[0].args[4], "default");
+}
+
+#[test]
+fn test_p4_login_pipes_password_on_stdin() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("User alice logged in.\n")
+            .ok("... User alice\n... TicketExpiration 43200\n"),
+    );
+    let app = mock_app(&executor);
+
+    let status = block_on(p4_login(
+        "s3cret pass".to_string(),
+        true,
+        connection(&app, Some("ssl:perforce:1666".to_string()), Some("alice".to_string()), None),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(status.logged_in);
+    assert_eq!(status.expires_in, Some(43200));
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-p", "ssl:perforce:1666", "-u", "alice", "login", "-a"]);
+    assert_eq!(calls[0].input.as_deref(), Some("s3cret pass\n"));
+    assert!(calls.iter().all(|c| !c.args.iter().any(|a| a.contains("s3cret"))));
+    assert_eq!(calls[1].args[4..], ["-ztag", "login", "-s"]);
+}
+
+#[test]
+fn test_p4_login_rejected_password() {
+    let executor = Arc::new(ScriptedExecutor::new().fail("Password invalid.\n", 1));
+    let app = mock_app(&executor);
+
+    let err = block_on(p4_login(
+        "wrong".to_string(),
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(err.message, "Login failed: Password invalid.");
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_login_status_expired_ticket() {
+    let executor = Arc::new(ScriptedExecutor::new().fail("Your session has expired, please login again.\n", 1));
+    let app = mock_app(&executor);
+
+    let status = block_on(p4_login_status(
+        connection(&app, None, Some("alice".to_string()), None),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert!(!status.logged_in);
+    assert_eq!(status.user.as_deref(), Some("alice"));
+}
+
+#[test]
+fn test_p4_trust_installs_confirmed_fingerprint() {
+    let executor = Arc::new(ScriptedExecutor::new().ok("Added trust for P4PORT 'ssl:perforce:1666'\n"));
+    let app = mock_app(&executor);
+
+    block_on(p4_trust(
+        " AB:CD:EF \n".to_string(),
+        true,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(executor.calls()[0].args, ["trust", "-f", "-i", "AB:CD:EF"]);
+
+    let err = block_on(p4_trust(
+        "-y".to_string(),
+        false,
+        None,
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap_err();
+    assert_eq!(err.message, "Invalid fingerprint '-y'");
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_trust_status_reports_unknown_key() {
+    let executor = Arc::new(ScriptedExecutor::new().respond(
+        "",
+        "The authenticity of 'ssl:perforce:1666' can't be established,\nthis may be your first attempt to connect to this P4PORT.\nThe fingerprint for the key sent to your client is\nAB:CD:EF:01\n",
+        1,
+    ));
+    let app = mock_app(&executor);
+
+    let status = block_on(p4_trust_status(None, app.state::<ConnectionsState>(), app.state::<P4ExecutorState>())).unwrap();
+
+    assert!(!status.trusted);
+    assert!(!status.changed);
+    assert_eq!(status.fingerprint.as_deref(), Some("AB:CD:EF:01"));
+    assert_eq!(executor.calls()[0].args, ["trust", "-n"]);
+}
+
+fn opened_record(depot_path: &'static str, user: &'static str, client: &'static str) -> Vec<(&'static str, MarshalValue)> {
+    vec![
+        ("code", str_value("stat")),
+        ("depotFile", str_value(depot_path)),
+        ("rev", str_value("3")),
+        ("action", str_value("edit")),
+        ("change", str_value("default")),
+        ("type", str_value("binary+l")),
+        ("user", str_value(user)),
+        ("client", str_value(client)),
+    ]
+}
+
+#[test]
+fn test_p4_opened_all_scopes_to_stream() {
+    let alice = opened_record("//game/main/art/hero.psd", "alice", "alice-ws");
+    let bob = opened_record("//game/main/art/hero.psd", "bob", "bob-ws");
+    let listing = marshal_output(&[&alice[..], &bob[..]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&listing));
+    let app = mock_app(&executor);
+
+    let files = block_on(p4_opened_all(
+        None,
+        Some("//game/main/".to_string()),
+        None,
+        None,
+        app.state::<OpenedCacheState>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|f| f.exclusive && !f.locked));
+    assert_eq!(executor.calls()[0].args, ["-G", "opened", "-a", "//game/main/..."]);
+
+    // The complete listing answers the hover lookup without another query
+    let opens = block_on(p4_file_opened_by(
+        "//game/main/art/hero.psd".to_string(),
+        None,
+        app.state::<OpenedCacheState>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert_eq!(opens, files);
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_opened_all_with_limit_skips_cache() {
+    let alice = opened_record("//depot/a.psd", "alice", "alice-ws");
+    let listing = marshal_output(&[&alice[..]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&listing).ok(&listing));
+    let app = mock_app(&executor);
+
+    block_on(p4_opened_all(
+        Some("//depot/...".to_string()),
+        Some("//ignored/main".to_string()),
+        Some(50),
+        None,
+        app.state::<OpenedCacheState>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    block_on(p4_file_opened_by(
+        "//depot/a.psd".to_string(),
+        None,
+        app.state::<OpenedCacheState>(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-G", "opened", "-a", "-m", "50", "//depot/..."]);
+    assert_eq!(calls[1].args, ["-G", "opened", "-a", "//depot/a.psd"]);
+}
+
+#[test]
+fn test_p4_file_opened_by_caches_unopened_file() {
+    let not_opened = marshal_output(&[&[
+        ("code", str_value("error")),
+        ("data", str_value("//depot/a.txt - file(s) not opened anywhere.\n")),
+        ("severity", MarshalValue::Int(2)),
+    ]]);
+    let executor = Arc::new(ScriptedExecutor::new().ok(&not_opened));
+    let app = mock_app(&executor);
+
+    for _ in 0..2 {
+        let opens = block_on(p4_file_opened_by(
+            "//depot/a.txt".to_string(),
+            None,
+            app.state::<OpenedCacheState>(),
+            app.state::<ConnectionsState>(),
+            app.state::<P4ExecutorState>(),
+        ))
+        .unwrap();
+        assert!(opens.is_empty());
+    }
+
+    assert_eq!(executor.calls().len(), 1);
+}
+
+#[test]
+fn test_p4_open_file_index_keys_on_p4_info_when_profile_has_no_client() {
+    let executor = Arc::new(
+        ScriptedExecutor::new()
+            .ok("... userName alice\n... clientName alice-ws\n... clientRoot /home/alice/ws\n... serverAddress ssl:perforce:1666\n")
+            .ok(&marshal_output(&[&[("change", str_value("1201"))]])),
+    );
+    let app = mock_app(&executor);
+    let connection = connection(&app, Some("ssl:edge:1666".to_string()), None, None);
+
+    let status = block_on(p4_open_file_index(
+        "//stream/main/...".to_string(),
+        connection.clone(),
+        app.handle().clone(),
+        app.state::<ConnectionsState>(),
+        app.state::<P4ExecutorState>(),
+    ))
+    .unwrap();
+    assert!(status.rebuild_required);
+    assert_eq!(status.changelist, 1201);
+
+    // The server the profile names wins over the address the server reports
+    let conn = app.state::<ConnectionsState>().get(&connection).unwrap();
+    let key = block_on(conn.file_index.lock()).key().cloned().unwrap();
+    assert_eq!(key.server, "ssl:edge:1666");
+    assert_eq!(key.client, "alice-ws");
+
+    let calls = executor.calls();
+    assert_eq!(calls[0].args, ["-p", "ssl:edge:1666", "-ztag", "info"]);
+}
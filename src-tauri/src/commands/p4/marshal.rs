@@ -0,0 +1,177 @@
+use encoding_rs::Encoding;
+use std::collections::HashMap;
+
+use crate::state::decode_text;
+
+/// A value inside a `p4 -G` record.
+/// p4 emits strings for almost everything; a few fields (e.g. `severity`, `generic`
+/// on error records) come through as integers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarshalValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    None,
+}
+
+impl MarshalValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MarshalValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Render as the text p4 -ztag would have printed for this field
+    pub fn to_field_string(&self) -> String {
+        match self {
+            MarshalValue::Str(s) => s.clone(),
+            MarshalValue::Int(n) => n.to_string(),
+            MarshalValue::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+            MarshalValue::None => String::new(),
+        }
+    }
+}
+
+/// One dictionary from `p4 -G` output
+pub type MarshalRecord = HashMap<String, MarshalValue>;
+
+/// Decode `p4 -G` output: a concatenation of Python marshal (version 0) dictionaries.
+/// Strings are in the command charset, `encoding` (None for UTF-8).
+pub fn decode_marshal_records(
+    data: &[u8],
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<MarshalRecord>, String> {
+    let mut reader = MarshalReader { data, pos: 0, encoding };
+    let mut records = Vec::new();
+
+    while !reader.at_end() {
+        let code = reader.byte()?;
+        if code != b'{' {
+            return Err(format!(
+                "Expected dictionary at offset {}, found type code {:?}",
+                reader.pos - 1,
+                code as char
+            ));
+        }
+        records.push(reader.dict()?);
+    }
+
+    Ok(records)
+}
+
+/// Decode `p4 -G` output into the same string records parse_ztag_records produces.
+pub fn parse_marshal_records(
+    data: &[u8],
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<HashMap<String, String>>, String> {
+    Ok(decode_marshal_records(data, encoding)?
+        .into_iter()
+        .map(|record| {
+            record
+                .into_iter()
+                .map(|(k, v)| (k, v.to_field_string()))
+                .collect()
+        })
+        .collect())
+}
+
+struct MarshalReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    encoding: Option<&'static Encoding>,
+}
+
+impl MarshalReader<'_> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| format!("Truncated marshal data at offset {}", self.pos))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        let bytes = self.take(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(bytes);
+        Ok(i64::from_le_bytes(buf))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Err(format!("Negative string length at offset {}", self.pos - 4));
+        }
+        let encoding = self.encoding;
+        Ok(decode_text(self.take(len as usize)?, encoding))
+    }
+
+    /// Python long: signed digit count followed by 15-bit digits, least significant first
+    fn long(&mut self) -> Result<i64, String> {
+        let count = self.i32()?;
+        if count.unsigned_abs() > 4 {
+            return Err("Marshal long out of range".to_string());
+        }
+        let mut value: i64 = 0;
+        for i in 0..count.unsigned_abs() {
+            let bytes = self.take(2)?;
+            let digit = u16::from_le_bytes([bytes[0], bytes[1]]) as i64;
+            value |= digit << (15 * i);
+        }
+        Ok(if count < 0 { -value } else { value })
+    }
+
+    /// Read a dictionary body (after the '{' type code) up to its '0' terminator
+    fn dict(&mut self) -> Result<MarshalRecord, String> {
+        let mut record = HashMap::new();
+        loop {
+            let key = match self.byte()? {
+                b'0' => return Ok(record),
+                b's' | b'u' | b't' => self.string()?,
+                code => {
+                    return Err(format!(
+                        "Unsupported dictionary key type {:?} at offset {}",
+                        code as char,
+                        self.pos - 1
+                    ))
+                }
+            };
+            let value = self.value()?;
+            record.insert(key, value);
+        }
+    }
+
+    fn value(&mut self) -> Result<MarshalValue, String> {
+        match self.byte()? {
+            b's' | b'u' | b't' => Ok(MarshalValue::Str(self.string()?)),
+            b'i' => Ok(MarshalValue::Int(self.i32()? as i64)),
+            b'I' => Ok(MarshalValue::Int(self.i64()?)),
+            b'l' => Ok(MarshalValue::Int(self.long()?)),
+            b'T' => Ok(MarshalValue::Bool(true)),
+            b'F' => Ok(MarshalValue::Bool(false)),
+            b'N' => Ok(MarshalValue::None),
+            code => Err(format!(
+                "Unsupported marshal type {:?} at offset {}",
+                code as char,
+                self.pos - 1
+            )),
+        }
+    }
+}
@@ -1,10 +1,21 @@
+mod error;
+mod marshal;
+mod opened_cache;
 mod parsing;
 mod p4handlers;
+mod spec;
+mod stream;
 mod types;
 
+pub use error::{P4Error, P4ErrorKind, SpecFieldError};
+pub use opened_cache::{create_opened_cache_state, OpenedCacheState};
 pub use p4handlers::*;
+pub use stream::{collect_stderr, finish_stream, StreamCompletion, TextLines};
 #[allow(unused_imports)]
 pub use types::*;
 
 #[cfg(test)]
 mod tests;
+
+#[cfg(test)]
+mod handler_tests;
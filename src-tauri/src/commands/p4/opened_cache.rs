@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::types::P4OpenedFile;
+
+/// How long a file's opens are reused before asking the server again
+pub const OPENED_CACHE_TTL: Duration = Duration::from_secs(15);
+
+/// Connection ID ("" for the default connection) and depot path
+type CacheKey = (String, String);
+
+struct CachedOpens {
+    fetched: Instant,
+    opens: Vec<P4OpenedFile>,
+}
+
+/// Short-lived cache of `p4 opened -a` results per depot path, so hovering over
+/// files doesn't send a query to the server for every file the pointer crosses
+pub struct OpenedCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, CachedOpens>>,
+}
+
+impl OpenedCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Opens of `depot_path` fetched within the TTL. An empty list means the file
+    /// was not open anywhere; None means it has to be queried.
+    pub fn get(&self, connection: &Option<String>, depot_path: &str) -> Option<Vec<P4OpenedFile>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&cache_key(connection, depot_path))
+            .filter(|cached| cached.fetched.elapsed() < self.ttl)
+            .map(|cached| cached.opens.clone())
+    }
+
+    /// Record the opens of one file, dropping entries that have expired
+    pub fn insert(&self, connection: &Option<String>, depot_path: &str, opens: Vec<P4OpenedFile>) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.retain(|_, cached| cached.fetched.elapsed() < ttl);
+        entries.insert(
+            cache_key(connection, depot_path),
+            CachedOpens {
+                fetched: Instant::now(),
+                opens,
+            },
+        );
+    }
+
+    /// Record every file in a complete listing. Files missing from the listing
+    /// are left alone, since the listing's scope may not have covered them.
+    pub fn insert_listing(&self, connection: &Option<String>, opens: &[P4OpenedFile]) {
+        let mut by_path: HashMap<&str, Vec<P4OpenedFile>> = HashMap::new();
+        for open in opens {
+            by_path.entry(&open.depot_path).or_default().push(open.clone());
+        }
+        for (depot_path, opens) in by_path {
+            self.insert(connection, depot_path, opens);
+        }
+    }
+}
+
+fn cache_key(connection: &Option<String>, depot_path: &str) -> CacheKey {
+    (connection.clone().unwrap_or_default(), depot_path.to_string())
+}
+
+pub type OpenedCacheState = Arc<OpenedCache>;
+
+pub fn create_opened_cache_state() -> OpenedCacheState {
+    Arc::new(OpenedCache::new(OPENED_CACHE_TTL))
+}
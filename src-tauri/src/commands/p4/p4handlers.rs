@@ -1,55 +1,61 @@
 use tokio::process::Command;
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tauri::{ipc::Channel, AppHandle, Emitter, State};
+use tauri::{ipc::Channel, AppHandle, Emitter, Runtime, State};
 use tempfile::Builder;
-
-use crate::state::ProcessManager;
+use std::sync::Arc;
+
+use crate::content_index::ContentIndex;
+use crate::diff::{diff_text, DiffOptions, TextDiff};
+use crate::file_index::{persist, IndexKey};
+use crate::state::{
+    encode_text, ConnectionProfile, ConnectionsState, P4Command, P4Executor, P4ExecutorState,
+    ProcessManager, RegisteredProcess, DEFAULT_UNICODE_CHARSET,
+};
+use super::error::{classify_stderr, P4Error, P4ErrorKind, SpecFieldError};
+use super::marshal::parse_marshal_records;
+use super::opened_cache::OpenedCacheState;
 use super::parsing::*;
+use super::spec::{fetch_spec_form, submit_spec_form};
+use super::stream::{collect_stderr, finish_stream, is_query_error, TextLines};
 use super::types::*;
 
-// Windows-specific import for hiding console windows
-#[cfg(target_os = "windows")]
-#[allow(unused_imports)] // Trait is used via creation_flags() method
-use std::os::windows::process::CommandExt;
-
-/// Helper function to create a Command with Windows console window hidden
-fn create_p4_command() -> Command {
-    let mut cmd = Command::new("p4");
-
-    // On Windows, hide the console window (CREATE_NO_WINDOW flag)
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(0x08000000);
-
-    cmd
-}
-
 /// Maximum file size for in-app content viewing (10MB)
 const MAX_CONTENT_SIZE: u64 = 10 * 1024 * 1024;
 
+/// p4 istat processes p4_list_streams runs at once
+const MAX_CONCURRENT_ISTAT: usize = 4;
+
 /// Get P4 client info (client root, user, server)
 #[tauri::command]
 pub async fn p4_info(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<P4ClientInfo, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4ClientInfo, P4Error> {
+    let mut conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["-ztag", "info"]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 info: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 info: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("p4 info failed: {}", stderr));
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("p4 info failed"));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_info(&stdout)
+    let stdout = output.stdout_text();
+    let mut info = parse_ztag_info(&stdout).map_err(P4Error::from)?;
+
+    // A unicode server rejects most commands from clients without a charset,
+    // so settle on one for the rest of the session
+    if info.server_unicode && conn.profile.charset.as_deref().unwrap_or("").is_empty() {
+        let charset = detect_charset(executor.as_ref(), &conn.profile).await;
+        conn = connections.set_detected_charset(&connection, &charset)?;
+    }
+    info.charset = conn.profile.charset.clone().filter(|c| !c.is_empty());
+    Ok(info)
 }
 
 /// Get file status information for given paths
@@ -60,15 +66,17 @@ pub async fn p4_info(
 pub async fn p4_fstat(
     paths: Vec<String>,
     depot_path: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4FileInfo>, String> {
-    // Build command: p4 -ztag fstat <paths>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileInfo>, P4Error> {
+    let conn = connections.get(&connection)?;
+    // Build command: p4 -G fstat <paths>
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    cmd.arg(format.flag());
     cmd.arg("fstat");
 
     if paths.is_empty() {
@@ -80,37 +88,39 @@ pub async fn p4_fstat(
     }
 
     // Execute command
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
 
     // Parse output even if exit code is non-zero (p4 fstat can return errors for individual files)
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    // Parse -ztag format
-    let files = parse_ztag_fstat(&stdout)?;
+    let records = match format.parse_output(&output) {
+        Ok(records) => records,
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) => Vec::new(),
+        Err(err) => return Err(err),
+    };
 
-    Ok(files)
+    Ok(file_infos_from_records(records))
 }
 
 /// Streaming fstat - sends batches of files via Channel for progressive loading
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn p4_fstat_stream(
     paths: Vec<String>,
     depot_path: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
+    connection: Option<String>,
     on_batch: Channel<FstatStreamBatch>,
     state: State<'_, ProcessManager>,
-) -> Result<String, String> {
-    use std::collections::HashMap;
-
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    // -ztag rather than -G: records are parsed line by line as they stream in
+    cmd.arg(RecordFormat::Ztag.flag());
     cmd.arg("fstat");
 
     if paths.is_empty() {
@@ -120,150 +130,107 @@ pub async fn p4_fstat_stream(
         cmd.args(&paths);
     }
 
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 fstat: {}", e)))?;
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 fstat: {}", e))?;
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
 
     // Register process for cancellation
-    let process_id = state.register(child).await;
-    let process_id_clone = process_id.clone();
-
-    // Stream stdout in background task
-    let on_batch_clone = on_batch.clone();
-    if let Some(stdout) = stdout {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stdout).lines();
-            let mut current_record: HashMap<String, String> = HashMap::new();
-            let mut batch: Vec<P4FileInfo> = Vec::new();
-            let mut total_received: u32 = 0;
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                let line = line.trim();
-
-                // Blank line = end of record
-                if line.is_empty() {
-                    if !current_record.is_empty() {
-                        // Build file info from record, filtering out deleted-at-head files
-                        if let Some(file_info) = build_file_info(&current_record) {
-                            if file_info.head_action.as_deref() != Some("delete") {
-                                batch.push(file_info);
-
-                                // Send batch when it reaches 100 files
-                                if batch.len() >= 100 {
-                                    total_received += batch.len() as u32;
-                                    let _ = on_batch_clone.send(FstatStreamBatch::Data {
-                                        files: std::mem::take(&mut batch),
-                                        total_received,
-                                    });
-                                }
-                            }
-                        }
-                        current_record.clear();
-                    }
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    // Missing paths are reported on stderr but don't fail the listing
+    let stderr_task = collect_stderr(stderr, encoding, |line| {
+        let is_error = is_query_error(line);
+        if is_error {
+            eprintln!("p4 fstat stderr: {}", line);
+        }
+        is_error
+    });
+
+    // Stream stdout in background task, then report how the command ended
+    tokio::spawn(async move {
+        let mut reader = ZtagRecordReader::default();
+        let mut batch: Vec<P4FileInfo> = Vec::new();
+        let mut total_received: u32 = 0;
+
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
+                let Some(file_info) = reader.push_line(&line).as_ref().and_then(listed_file_info) else {
                     continue;
-                }
-
-                // Parse ztag line: "... key value"
-                if let Some(stripped) = line.strip_prefix("... ") {
-                    if let Some((key, value)) = stripped.split_once(' ') {
-                        current_record.insert(key.to_string(), value.to_string());
-                    } else {
-                        // Key with no value (e.g., "... isMapped")
-                        current_record.insert(stripped.to_string(), String::new());
-                    }
+                };
+                batch.push(file_info);
+
+                // Send batch when it reaches 100 files
+                if batch.len() >= 100 {
+                    total_received += batch.len() as u32;
+                    let _ = on_batch.send(FstatStreamBatch::Data {
+                        files: std::mem::take(&mut batch),
+                        total_received,
+                    });
                 }
             }
-
-            // Send final batch
-            if !batch.is_empty() {
-                total_received += batch.len() as u32;
-                let _ = on_batch_clone.send(FstatStreamBatch::Data {
-                    files: batch,
-                    total_received,
-                });
-            }
-
-            // Send completion signal
-            let _ = on_batch_clone.send(FstatStreamBatch::Complete {
-                total_files: total_received,
-                success: true,
-                error: None,
+        }
+        batch.extend(reader.finish().as_ref().and_then(listed_file_info));
+
+        // Send final batch
+        if !batch.is_empty() {
+            total_received += batch.len() as u32;
+            let _ = on_batch.send(FstatStreamBatch::Data {
+                files: batch,
+                total_received,
             });
-        });
-    }
-
-    // Stream stderr in background task (for errors/warnings)
-    if let Some(stderr) = stderr {
-        let on_batch_stderr = on_batch.clone();
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stderr).lines();
-            let mut had_error = false;
-            let mut error_msg = String::new();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                // Skip informational messages
-                if line.contains("no such file(s)") {
-                    continue;
-                }
-                // Log actual errors
-                eprintln!("p4 fstat stderr: {}", line);
-                if !had_error {
-                    had_error = true;
-                    error_msg = line;
-                }
-            }
+        }
 
-            // If there were errors and no success completion was sent, send error completion
-            if had_error {
-                let _ = on_batch_stderr.send(FstatStreamBatch::Complete {
-                    total_files: 0,
-                    success: false,
-                    error: Some(error_msg),
-                });
-            }
+        let completion = finish_stream(exited, stderr_task).await;
+        let _ = on_batch.send(FstatStreamBatch::Complete {
+            total_files: total_received,
+            completion,
         });
-    }
+    });
+
+    Ok(process_id)
+}
 
-    Ok(process_id_clone)
+/// File info for a streamed fstat record, filtering out deleted-at-head files
+fn listed_file_info(record: &std::collections::HashMap<String, String>) -> Option<P4FileInfo> {
+    build_file_info(record).filter(|info| info.head_action.as_deref() != Some("delete"))
 }
 
 /// Get all files opened by current user
 #[tauri::command]
 pub async fn p4_opened(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4FileInfo>, String> {
-    // Execute: p4 -ztag fstat -Ro //...
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileInfo>, P4Error> {
+    let conn = connections.get(&connection)?;
+    // Execute: p4 -G fstat -Ro //...
     // Uses fstat instead of opened to get the "path" field (local filesystem path).
     // p4 opened only returns "clientFile" which is the client-spec path (//client/...),
     // not usable for local file operations like Open or Reveal in Explorer.
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["-ztag", "fstat", "-Ro", "//..."]);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    let format = RecordFormat::Marshal;
+    cmd.args([format.flag(), "fstat", "-Ro", "//..."]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat -Ro: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat -Ro: {}", e)))?;
 
-    // Parse -ztag format (similar to fstat)
-    let files = parse_ztag_fstat(&stdout)?;
+    // No opened files is reported as "file(s) not opened", not as a failure
+    let records = match format.parse_output(&output) {
+        Ok(records) => records,
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) => Vec::new(),
+        Err(err) => return Err(err),
+    };
 
-    Ok(files)
+    Ok(file_infos_from_records(records))
 }
 
 /// Get fstat info for currently opened files only.
@@ -271,29 +238,74 @@ pub async fn p4_opened(
 /// Returns full P4FileInfo including head revision info for sync status detection.
 #[tauri::command]
 pub async fn p4_fstat_opened(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4FileInfo>, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileInfo>, P4Error> {
     // p4_opened already uses "p4 fstat -Ro" which returns full P4FileInfo
     // including head revision info needed for delta refresh
-    p4_opened(server, user, client).await
+    p4_opened(connection, connections, executor).await
+}
+
+/// Get files opened in every workspace (p4 opened -a), optionally limited to a
+/// depot path or a stream. A complete listing also refreshes the opened-by cache.
+#[tauri::command]
+pub async fn p4_opened_all(
+    path: Option<String>,
+    stream: Option<String>,
+    max_files: Option<u32>,
+    connection: Option<String>,
+    cache: State<'_, OpenedCacheState>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4OpenedFile>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let scope = path.or_else(|| stream.map(|s| format!("{}/...", s.trim_end_matches('/'))));
+    let files = query_opened_all(executor.as_ref(), &conn.profile, scope.as_deref(), max_files).await?;
+
+    if max_files.is_none() {
+        cache.insert_listing(&connection, &files);
+    }
+    Ok(files)
+}
+
+/// Get the workspaces that have a file open, for hover details.
+/// Answers from the opened-by cache while its entry is fresh.
+#[tauri::command]
+pub async fn p4_file_opened_by(
+    depot_path: String,
+    connection: Option<String>,
+    cache: State<'_, OpenedCacheState>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4OpenedFile>, P4Error> {
+    if let Some(opens) = cache.get(&connection, &depot_path) {
+        return Ok(opens);
+    }
+
+    let conn = connections.get(&connection)?;
+    let opens = query_opened_all(executor.as_ref(), &conn.profile, Some(&depot_path), None).await?;
+    cache.insert(&connection, &depot_path, opens.clone());
+    Ok(opens)
 }
 
 /// Get changelists for current user
 #[tauri::command]
 pub async fn p4_changes(
     status: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4Changelist>, String> {
-    // Build command: p4 -ztag changes -s <status> -u <user> -c <client>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Changelist>, P4Error> {
+    let conn = connections.get(&connection)?;
+    // Build command: p4 -G changes -l -s <status> -u <user> -c <client>
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    cmd.arg(format.flag());
     cmd.arg("changes");
+    cmd.arg("-l"); // Long output to get full descriptions
 
     // Add status filter (default to pending)
     let status_filter = status.unwrap_or_else(|| "pending".to_string());
@@ -301,49 +313,42 @@ pub async fn p4_changes(
     cmd.arg(status_filter);
 
     // Filter by current user and client (use actual values, not shell vars)
-    if let Some(u) = user.as_ref().filter(|s| !s.is_empty()) {
+    if let Some(u) = conn.profile.user.as_ref().filter(|s| !s.is_empty()) {
         cmd.args(["-u", u]);
     }
-    if let Some(c) = client.as_ref().filter(|s| !s.is_empty()) {
+    if let Some(c) = conn.profile.client.as_ref().filter(|s| !s.is_empty()) {
         cmd.args(["-c", c]);
     }
 
     // Execute command
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 changes: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 changes: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let records = format.parse_output(&output)?;
 
-    // Parse -ztag output
-    let changelists = parse_ztag_changes(&stdout)?;
-
-    Ok(changelists)
+    Ok(changelists_from_records(records))
 }
 
 /// Open files for edit (or move to different changelist if already opened)
 #[tauri::command]
-pub async fn p4_edit(
+pub async fn p4_edit<R: Runtime>(
     paths: Vec<String>,
     changelist: Option<i32>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-    app: AppHandle,
-) -> Result<Vec<P4FileInfo>, String> {
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileInfo>, P4Error> {
+    let conn = connections.get(&connection)?;
     if paths.is_empty() {
-        return Err("No paths provided".to_string());
+        return Err(P4Error::other("No paths provided"));
     }
 
     // Build command: p4 edit -c <changelist> <paths>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("edit");
 
@@ -355,14 +360,14 @@ pub async fn p4_edit(
     cmd.args(&paths);
 
     // Execute command
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 edit: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 edit: {}", e)))?;
 
     // Check for errors (but note p4 edit can have partial success)
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Parse output to determine which files were successfully opened
     let mut opened_files = Vec::new();
@@ -380,7 +385,14 @@ pub async fn p4_edit(
     // Get updated file info for opened files
     // Use None for client_root since we're querying specific depot paths
     let file_info = if !opened_files.is_empty() {
-        p4_fstat(opened_files.clone(), None, server, user, client).await?
+        p4_fstat(
+            opened_files.clone(),
+            None,
+            connection.clone(),
+            connections.clone(),
+            executor.clone(),
+        )
+        .await?
     } else {
         Vec::new()
     };
@@ -392,7 +404,18 @@ pub async fn p4_edit(
 
     // If there were errors, include them in result
     if !stderr.is_empty() && file_info.is_empty() {
-        return Err(stderr.to_string());
+        let err = P4Error::from_output(&output);
+        // p4 only says an exclusive (+l) file is "already opened"; name the workspace holding
+        // it when fstat can tell us, and otherwise pass p4's message on
+        if err.is(P4ErrorKind::FileLocked) {
+            if let Ok(current) = p4_fstat(paths, None, connection, connections, executor).await {
+                let conflicts = exclusive_open_conflicts(&current);
+                if !conflicts.is_empty() {
+                    return Err(P4Error::file_locked(conflicts.join("\n")));
+                }
+            }
+        }
+        return Err(err);
     }
 
     Ok(file_info)
@@ -400,31 +423,32 @@ pub async fn p4_edit(
 
 /// Revert files (discard local changes)
 #[tauri::command]
-pub async fn p4_revert(
+pub async fn p4_revert<R: Runtime>(
     paths: Vec<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-    app: AppHandle,
-) -> Result<Vec<String>, String> {
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<String>, P4Error> {
+    let conn = connections.get(&connection)?;
     if paths.is_empty() {
-        return Err("No paths provided".to_string());
+        return Err(P4Error::other("No paths provided"));
     }
 
     // Execute: p4 revert <paths>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("revert");
     cmd.args(&paths);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 revert: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 revert: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Parse output to get reverted files
     let mut reverted_files = Vec::new();
@@ -452,59 +476,348 @@ pub async fn p4_revert(
 
     // Check for errors
     if !stderr.is_empty() && reverted_files.is_empty() {
-        return Err(stderr.to_string());
+        return Err(P4Error::from_output(&output));
     }
 
     Ok(reverted_files)
 }
 
+/// Open new files for add.
+/// `file_type` overrides the type p4 would detect (e.g. "binary+l").
+/// Files whose names contain p4 wildcard characters (@ # % *) are added literally with -f.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_add<R: Runtime>(
+    paths: Vec<String>,
+    changelist: Option<i32>,
+    file_type: Option<String>,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if paths.is_empty() {
+        return Err(P4Error::other("No paths provided"));
+    }
+
+    // Build command: p4 -G add [-c <changelist>] [-t <type>] [-f] <paths>
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "add"]);
+
+    if let Some(cl) = changelist {
+        cmd.args(["-c", &cl.to_string()]);
+    }
+    if let Some(file_type) = file_type.filter(|t| !t.is_empty()) {
+        cmd.args(["-t", &file_type]);
+    }
+    if paths.iter().any(|p| has_p4_special_chars(p)) {
+        cmd.arg("-f");
+    }
+    cmd.args(&paths);
+
+    let results = run_file_op(executor.as_ref(), &cmd, "add").await?;
+    emit_file_op_status(&app, &results, connection, connections, executor).await;
+    Ok(results)
+}
+
+/// Open files for delete. The workspace files are removed by p4.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_delete<R: Runtime>(
+    paths: Vec<String>,
+    changelist: Option<i32>,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if paths.is_empty() {
+        return Err(P4Error::other("No paths provided"));
+    }
+
+    // Build command: p4 -G delete [-c <changelist>] <paths>
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "delete"]);
+
+    if let Some(cl) = changelist {
+        cmd.args(["-c", &cl.to_string()]);
+    }
+    cmd.args(paths.iter().map(|p| escape_special_chars(p)));
+
+    let results = run_file_op(executor.as_ref(), &cmd, "delete").await?;
+    emit_file_op_status(&app, &results, connection, connections, executor).await;
+    Ok(results)
+}
+
+/// Lock opened files so no other workspace can submit them first
+#[tauri::command]
+pub async fn p4_lock<R: Runtime>(
+    paths: Vec<String>,
+    changelist: Option<i32>,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if paths.is_empty() && changelist.is_none() {
+        return Err(P4Error::other("No paths provided"));
+    }
+
+    // Build command: p4 -G lock [-c <changelist>] [<paths>]
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "lock"]);
+
+    if let Some(cl) = changelist {
+        cmd.args(["-c", &cl.to_string()]);
+    }
+    cmd.args(paths.iter().map(|p| escape_special_chars(p)));
+
+    let results = run_file_op(executor.as_ref(), &cmd, "lock").await?;
+    emit_file_op_status(&app, &results, connection, connections, executor).await;
+    Ok(results)
+}
+
+/// Release locks on opened files. With `force` (-f, admin only) another user's locks
+/// are released too; their files must then be given as depot paths.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_unlock<R: Runtime>(
+    paths: Vec<String>,
+    changelist: Option<i32>,
+    force: bool,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if paths.is_empty() && changelist.is_none() {
+        return Err(P4Error::other("No paths provided"));
+    }
+
+    // Build command: p4 -G unlock [-c <changelist>] [-f] [<paths>]
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "unlock"]);
+
+    if let Some(cl) = changelist {
+        cmd.args(["-c", &cl.to_string()]);
+    }
+    if force {
+        cmd.arg("-f");
+    }
+    cmd.args(paths.iter().map(|p| escape_special_chars(p)));
+
+    let results = run_file_op(executor.as_ref(), &cmd, "unlock").await?;
+    emit_file_op_status(&app, &results, connection, connections, executor).await;
+    Ok(results)
+}
+
+/// Move/rename files, or whole directories when both sides end in "/...".
+/// Sources that aren't open yet are opened for edit first, as p4 move requires.
+/// Each request is moved separately, so one failing rename doesn't stop the rest.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_move<R: Runtime>(
+    moves: Vec<P4MoveRequest>,
+    changelist: Option<i32>,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if moves.is_empty() {
+        return Err(P4Error::other("No files to move"));
+    }
+
+    let changelist_args: Vec<String> = changelist
+        .map(|cl| vec!["-c".to_string(), cl.to_string()])
+        .unwrap_or_default();
+
+    // p4 edit leaves files already open for edit or add as they are, so open every source;
+    // sources that can't be opened are reported by their move below
+    let mut edit = P4Command::new();
+    conn.profile.apply(&mut edit);
+    edit.args([RecordFormat::Marshal.flag(), "edit"]);
+    edit.args(&changelist_args);
+    edit.args(moves.iter().map(|m| escape_special_chars(&m.from)));
+    let opened = match run_file_op(executor.as_ref(), &edit, "edit").await {
+        Ok(opened) => opened,
+        Err(err) if err.is_connection_error() => return Err(err),
+        Err(_) => Vec::new(),
+    };
+
+    let mut results = Vec::new();
+    let mut failure = None;
+    for request in &moves {
+        // Build command: p4 -G move [-c <changelist>] <from> <to>
+        let mut cmd = P4Command::new();
+        conn.profile.apply(&mut cmd);
+        cmd.args([RecordFormat::Marshal.flag(), "move"]);
+        cmd.args(&changelist_args);
+        cmd.arg(escape_special_chars(&request.from));
+        cmd.arg(escape_special_chars(&request.to));
+
+        match run_file_op(executor.as_ref(), &cmd, "move").await {
+            Ok(moved) => results.extend(moved),
+            Err(err) if err.is_connection_error() => {
+                failure = Some(err);
+                break;
+            }
+            Err(err) => results.push(P4FileOpResult {
+                path: request.from.clone(),
+                depot_path: request.from.starts_with("//").then(|| request.from.clone()),
+                local_path: None,
+                action: None,
+                file_type: None,
+                revision: None,
+                moved_from: None,
+                success: false,
+                message: Some(err.message),
+            }),
+        }
+    }
+
+    // Sources whose move failed stay open for edit, so refresh them along with the moves
+    let moved = |path: &Option<String>| {
+        results
+            .iter()
+            .any(|r| r.success && (r.depot_path == *path || r.moved_from == *path))
+    };
+    let still_open: Vec<P4FileOpResult> = opened
+        .into_iter()
+        .filter(|o| o.success && !moved(&o.depot_path))
+        .collect();
+    let touched: Vec<P4FileOpResult> = results.iter().cloned().chain(still_open).collect();
+    emit_file_op_status(&app, &touched, connection, connections, executor).await;
+
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(results),
+    }
+}
+
+/// Run a `p4 -G` file operation and collect per-file results.
+/// Fails only when p4 reported errors that don't name a file and opened nothing.
+async fn run_file_op(
+    executor: &dyn P4Executor,
+    cmd: &P4Command,
+    name: &str,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let output = executor
+        .output(cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 {}: {}", name, e)))?;
+
+    let (records, errors) = parse_marshal_with_errors(&output)?;
+    let (results, unattributed) = file_op_results(records, errors);
+
+    if results.is_empty() {
+        if !unattributed.is_empty() {
+            let exit_code = output.exit_code.filter(|c| *c != 0).or(Some(1));
+            return Err(P4Error::from_stderr(&unattributed.join("\n"), exit_code));
+        }
+        if !output.success() {
+            return Err(P4Error::from_output(&output));
+        }
+    }
+
+    Ok(results)
+}
+
+/// Emit file-status-changed with fresh fstat info for every file an operation touched,
+/// including the source of each move
+async fn emit_file_op_status<R: Runtime>(
+    app: &AppHandle<R>,
+    results: &[P4FileOpResult],
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) {
+    let depot_paths: Vec<String> = results
+        .iter()
+        .filter(|r| r.success)
+        .flat_map(|r| r.depot_path.iter().chain(r.moved_from.iter()))
+        .cloned()
+        .collect();
+    if depot_paths.is_empty() {
+        return;
+    }
+
+    // The operation itself succeeded; a failed refresh only delays the tree update
+    if let Ok(file_info) = p4_fstat(depot_paths, None, connection, connections, executor).await {
+        for file in &file_info {
+            let _ = app.emit("file-status-changed", file.clone());
+        }
+    }
+}
+
 /// Submit a changelist
 ///
 /// For the default changelist (id=0), uses `p4 submit -d "description"`.
 /// For numbered changelists, uses `p4 submit -c <changelist>`.
+/// `job_status` sets the status every job fixed by the changelist gets on submit;
+/// default changelists can't have jobs, so passing one with id=0 is an error.
 #[tauri::command]
-pub async fn p4_submit(
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_submit<R: Runtime>(
     changelist: i32,
     description: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-    app: AppHandle,
-) -> Result<i32, String> {
+    job_status: Option<String>,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<i32, P4Error> {
+    let conn = connections.get(&connection)?;
+    let job_status = job_status.filter(|s| !s.is_empty());
+    if changelist == 0 && job_status.is_some() {
+        return Err(P4Error::other(
+            "The default changelist can't fix jobs; move the files to a numbered changelist to set a job status",
+        ));
+    }
     let output = if changelist == 0 {
         // Default changelist: must use -d flag with description
         let desc = description.unwrap_or_else(|| "Submitted from Depot".to_string());
-        let mut cmd = create_p4_command();
-        apply_connection_args(&mut cmd, &server, &user, &client);
+        let mut cmd = P4Command::new();
+        conn.profile.apply(&mut cmd);
         cmd.args(["submit", "-d", &desc]);
-        cmd.output()
+        executor.output(&cmd)
             .await
-            .map_err(|e| format!("Failed to execute p4 submit: {}", e))?
+            .map_err(|e| P4Error::other(format!("Failed to execute p4 submit: {}", e)))?
     } else {
         // Named changelist: update description if provided, then submit with -c
         if let Some(ref desc) = description {
             update_changelist_description(
+                executor.as_ref(),
                 changelist,
                 desc,
-                server.clone(),
-                user.clone(),
-                client.clone(),
+                &conn.profile,
             ).await?;
         }
-        let mut cmd = create_p4_command();
-        apply_connection_args(&mut cmd, &server, &user, &client);
+        if let Some(status) = job_status.as_deref() {
+            set_fixed_job_status(executor.as_ref(), changelist, status, &conn.profile).await?;
+        }
+        let mut cmd = P4Command::new();
+        conn.profile.apply(&mut cmd);
         cmd.args(["submit", "-c", &changelist.to_string()]);
-        cmd.output()
+        executor.output(&cmd)
             .await
-            .map_err(|e| format!("Failed to execute p4 submit: {}", e))?
+            .map_err(|e| P4Error::other(format!("Failed to execute p4 submit: {}", e)))?
     };
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
 
     // Check for errors first
-    if !output.status.success() {
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     // Parse output to get submitted changelist number
@@ -530,95 +843,53 @@ pub async fn p4_submit(
     Ok(submitted_cl)
 }
 
-/// Create a new changelist
-#[tauri::command]
-pub async fn p4_create_change(
-    description: String,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<i32, String> {
-    // Get template: p4 change -o
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["change", "-o"]);
-
-    let output = cmd
-        .output()
-        .await
-        .map_err(|e| format!("Failed to get changelist template: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
-
-    let form = String::from_utf8_lossy(&output.stdout);
-
-    // Update description and remove Files section
-    let mut new_form = String::new();
-    let mut in_description = false;
-    let mut in_files = false;
-
-    for line in form.lines() {
-        if line.starts_with("Description:") {
-            new_form.push_str("Description:\n");
-            new_form.push_str(&format!("\t{}\n", description));
-            in_description = true;
-        } else if in_description && line.starts_with('\t') {
-            // Skip old description lines
-            continue;
-        } else if line.starts_with("Files:") {
-            // Remove Files section for new changelist
-            in_files = true;
-            continue;
-        } else if in_files && line.starts_with('\t') {
-            // Skip file lines
-            continue;
-        } else if in_files && !line.starts_with('\t') {
-            in_files = false;
-        } else if in_description && !line.starts_with('\t') {
-            in_description = false;
-            new_form.push_str(line);
-            new_form.push('\n');
-        } else if !in_files {
-            new_form.push_str(line);
-            new_form.push('\n');
-        }
-    }
-
-    // Submit form: p4 change -i
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["change", "-i"]);
-    cmd.stdin(Stdio::piped());
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
-
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 change: {}", e))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(new_form.as_bytes())
-            .await
-            .map_err(|e| format!("Failed to write changelist form: {}", e))?;
-    }
-
-    let result = child
-        .wait_with_output()
+/// Re-fix every job linked to a pending changelist with the status it should get on submit
+async fn set_fixed_job_status(
+    executor: &dyn P4Executor,
+    changelist: i32,
+    status: &str,
+    connection: &ConnectionProfile,
+) -> Result<(), P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args([RecordFormat::Ztag.flag(), "fixes", "-c", &changelist.to_string()]);
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to create changelist: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fixes: {}", e)))?;
 
-    if !result.status.success() {
-        let stderr = String::from_utf8_lossy(&result.stderr);
-        return Err(stderr.to_string());
+    let jobs: Vec<String> = RecordFormat::Ztag
+        .parse_output(&output)?
+        .into_iter()
+        .filter_map(|mut record| record.remove("Job"))
+        .collect();
+    if !jobs.is_empty() {
+        fix_jobs(executor, changelist, &jobs, Some(status), false, connection).await?;
     }
+    Ok(())
+}
 
-    // Parse output: "Change NNNNN created."
-    let stdout = String::from_utf8_lossy(&result.stdout);
-    let changelist_id = stdout
+/// Create a new changelist
+#[tauri::command]
+pub async fn p4_create_change(
+    description: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<i32, P4Error> {
+    let conn = connections.get(&connection)?;
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+
+    // New changelists start empty: drop the default change's files
+    let mut form = fetch_spec_form(executor, &["change", "-o"], connection).await?;
+    form.set_text("Description", &description);
+    form.remove("Files");
+
+    // "Change NNNNN created."
+    let message = submit_spec_form(executor, "change", &form, connection).await?;
+    let changelist_id = message
         .lines()
         .find(|line| line.contains("Change") && line.contains("created"))
         .and_then(|line| {
@@ -626,7 +897,7 @@ pub async fn p4_create_change(
                 .nth(1)
                 .and_then(|s| s.parse::<i32>().ok())
         })
-        .ok_or_else(|| format!("Failed to parse changelist ID from: {}", stdout))?;
+        .ok_or_else(|| P4Error::other(format!("Failed to parse changelist ID from: {}", message)))?;
 
     Ok(changelist_id)
 }
@@ -635,23 +906,23 @@ pub async fn p4_create_change(
 #[tauri::command]
 pub async fn p4_delete_change(
     changelist: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<(), String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<(), P4Error> {
+    let conn = connections.get(&connection)?;
     // Execute: p4 change -d <changelist>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["change", "-d", &changelist.to_string()]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 change -d: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 change -d: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     Ok(())
@@ -662,17 +933,18 @@ pub async fn p4_delete_change(
 pub async fn p4_reopen(
     paths: Vec<String>,
     changelist: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<String>, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<String>, P4Error> {
+    let conn = connections.get(&connection)?;
     if paths.is_empty() {
-        return Err("No paths provided".to_string());
+        return Err(P4Error::other("No paths provided"));
     }
 
     // Execute: p4 reopen -c <changelist> <paths>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     let cl_str = if changelist == 0 {
         "default".to_string()
     } else {
@@ -681,13 +953,13 @@ pub async fn p4_reopen(
     cmd.args(["reopen", "-c", &cl_str]);
     cmd.args(&paths);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 reopen: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 reopen: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Parse output: "//depot/path#rev - reopened; change NNN"
     let mut reopened_paths = Vec::new();
@@ -701,8 +973,8 @@ pub async fn p4_reopen(
     }
 
     // Check for errors
-    if !output.status.success() || (!stderr.is_empty() && reopened_paths.is_empty()) {
-        return Err(stderr.to_string());
+    if !output.success() || (!stderr.is_empty() && reopened_paths.is_empty()) {
+        return Err(P4Error::from_output(&output));
     }
 
     Ok(reopened_paths)
@@ -713,149 +985,334 @@ pub async fn p4_reopen(
 pub async fn p4_edit_change_description(
     changelist: i32,
     description: String,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<(), String> {
-    update_changelist_description(changelist, &description, server, user, client).await
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<(), P4Error> {
+    let conn = connections.get(&connection)?;
+    update_changelist_description(executor.as_ref(), changelist, &description, &conn.profile)
+        .await
 }
 
 /// Sync files from depot (get latest)
 ///
 /// When paths is empty, uses depot_path if provided (e.g., "//stream/main/...")
-/// to sync all files in the workspace.
+/// to sync all files in the workspace. revision_spec ("@label", "@1234", "#head")
+/// applies to every path.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn p4_sync(
     paths: Vec<String>,
     depot_path: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-    on_progress: Channel<SyncProgress>,
+    revision_spec: Option<String>,
+    connection: Option<String>,
+    on_progress: Channel<SyncStreamEvent>,
     state: State<'_, ProcessManager>,
-    _app: AppHandle,
-) -> Result<String, String> {
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
     // Build command: p4 sync <paths>
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("sync");
 
+    let revision_spec = revision_spec.as_deref();
     if paths.is_empty() {
         // Use depot_path if provided (e.g., "//stream/main/..."), otherwise fall back to "//..."
         let query_path = depot_path.unwrap_or_else(|| "//...".to_string());
-        cmd.arg(query_path);
+        cmd.arg(with_revision_spec(&query_path, revision_spec)?);
     } else {
-        cmd.args(&paths);
+        for path in &paths {
+            cmd.arg(with_revision_spec(path, revision_spec)?);
+        }
     }
 
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
-
     // Spawn process
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 sync: {}", e))?;
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 sync: {}", e)))?;
 
     // Take stdout/stderr
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
 
     // Register process
-    let process_id = state.register(child).await;
-    let process_id_clone = process_id.clone();
-
-    // Stream stdout in background task
-    let on_progress_clone = on_progress.clone();
-    if let Some(stdout) = stdout {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stdout).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    // Stream stderr in background task (for errors/conflicts)
+    let on_progress_stderr = on_progress.clone();
+    let stderr_task = collect_stderr(stderr, encoding, move |line| {
+        // Skip informational messages that P4 sends to stderr
+        if line.contains("file(s) up-to-date") {
+            // This is not an error - workspace is already synced
+            return false;
+        }
+
+        // Emit actual errors/conflicts as they happen; they also fail the sync
+        let is_conflict = line.contains("can't clobber") || line.contains("can't overwrite");
+        let _ = on_progress_stderr.send(SyncStreamEvent::Progress(SyncProgress {
+            depot_path: line.to_string(),
+            action: if is_conflict { "conflict" } else { "error" }.to_string(),
+            revision: 0,
+            is_conflict,
+        }));
+        true
+    });
+
+    // Stream stdout in background task, then report how the sync ended
+    tokio::spawn(async move {
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
                 if let Some(progress) = parse_sync_line(&line) {
-                    let _ = on_progress_clone.send(progress);
+                    let _ = on_progress.send(SyncStreamEvent::Progress(progress));
                 }
             }
-        });
+        }
+
+        let completion = finish_stream(exited, stderr_task).await;
+        let _ = on_progress.send(SyncStreamEvent::Complete(completion));
+    });
+
+    Ok(process_id)
+}
+
+/// List available workspaces for the user of a connection profile, which need not be
+/// saved yet. The profile's own workspace is ignored.
+#[tauri::command]
+pub async fn p4_list_workspaces(
+    profile: ConnectionProfile,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Workspace>, P4Error> {
+    let user = profile
+        .user
+        .clone()
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| P4Error::other("No user provided"))?;
+    let mut cmd = P4Command::new();
+    profile.without_client().apply(&mut cmd);
+    cmd.args(["-ztag", "clients", "-u", &user]);
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 clients: {}", e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("p4 clients failed"));
     }
 
-    // Stream stderr in background task (for errors/conflicts)
-    if let Some(stderr) = stderr {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                // Skip informational messages that P4 sends to stderr
-                if line.contains("file(s) up-to-date") {
-                    // This is not an error - workspace is already synced
-                    continue;
-                }
+    let stdout = output.stdout_text();
+    parse_ztag_clients(&stdout).map_err(P4Error::from)
+}
 
-                // Only emit actual errors/conflicts
-                let is_conflict =
-                    line.contains("can't clobber") || line.contains("can't overwrite");
-                let _ = on_progress.send(SyncProgress {
-                    depot_path: line.clone(),
-                    action: if is_conflict { "conflict" } else { "error" }.to_string(),
-                    revision: 0,
-                    is_conflict,
-                });
-            }
-        });
+/// Test a connection profile before it is saved
+#[tauri::command]
+pub async fn p4_test_connection(
+    profile: ConnectionProfile,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4ClientInfo, P4Error> {
+    let mut cmd = P4Command::new();
+    profile.apply(&mut cmd);
+    cmd.args(["-ztag", "info"]);
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 info: {}", e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("Connection test failed"));
     }
 
-    Ok(process_id_clone)
+    let stdout = output.stdout_text();
+    let mut info = parse_ztag_info(&stdout).map_err(P4Error::from)?;
+    info.charset = match profile.charset.clone().filter(|c| !c.is_empty()) {
+        Some(charset) => Some(charset),
+        None if info.server_unicode => Some(detect_charset(executor.as_ref(), &profile).await),
+        None => None,
+    };
+    Ok(info)
+}
+
+/// Charset to use for a unicode server when the profile names none: whatever p4's own
+/// settings (P4CHARSET in the environment, P4CONFIG or `p4 set`) say, else utf8.
+/// "auto" is resolved too, since output can only be decoded in a known charset.
+async fn detect_charset(executor: &dyn P4Executor, connection: &ConnectionProfile) -> String {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args(["set", "-q", "P4CHARSET"]);
+
+    let configured = match executor.output(&cmd).await {
+        Ok(output) if output.success() => parse_p4_set_value(&output.stdout_text(), "P4CHARSET"),
+        _ => None,
+    };
+    configured
+        .filter(|c| !c.eq_ignore_ascii_case("none") && !c.eq_ignore_ascii_case("auto"))
+        .unwrap_or_else(|| DEFAULT_UNICODE_CHARSET.to_string())
 }
 
-/// List available workspaces for a given server and user
+/// Log in with `password`, which is piped to p4 on stdin and never appears on the
+/// command line. `all_hosts` gets a ticket valid from any machine (-a).
 #[tauri::command]
-pub async fn p4_list_workspaces(server: String, user: String) -> Result<Vec<P4Workspace>, String> {
-    let mut cmd = create_p4_command();
-    // Override all P4 env vars to ensure complete isolation from DVCS/local config
-    cmd.env("P4CONFIG", "");
-    cmd.env("P4ROOT", "");
-    cmd.env("P4PORT", &server);
-    cmd.env("P4USER", &user);
-    cmd.args(["-p", &server, "-u", &user, "-ztag", "clients", "-u", &user]);
-    let output = cmd
-        .output()
+pub async fn p4_login(
+    password: String,
+    all_hosts: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4LoginStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.arg("login");
+    if all_hosts {
+        cmd.arg("-a");
+    }
+
+    let output = executor
+        .output_with_input(&cmd, &format!("{}\n", password))
         .await
-        .map_err(|e| format!("Failed to execute p4 clients: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 login: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("p4 clients failed: {}", stderr));
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("Login failed"));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_clients(&stdout)
+    login_status(executor.as_ref(), &conn.profile).await
 }
 
-/// Test connection to P4 server with given credentials
+/// Invalidate the current ticket; `all_hosts` logs out every machine (-a)
 #[tauri::command]
-pub async fn p4_test_connection(
-    server: String,
-    user: String,
-    client: String,
-) -> Result<P4ClientInfo, String> {
-    let mut cmd = create_p4_command();
-    // Override all P4 env vars to ensure complete isolation from DVCS/local config
-    cmd.env("P4CONFIG", "");
-    cmd.env("P4ROOT", "");
-    cmd.env("P4PORT", &server);
-    cmd.env("P4USER", &user);
-    cmd.env("P4CLIENT", &client);
-    cmd.args(["-p", &server, "-u", &user, "-c", &client, "-ztag", "info"]);
-    let output = cmd
-        .output()
+pub async fn p4_logout(
+    all_hosts: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<(), P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.arg("logout");
+    if all_hosts {
+        cmd.arg("-a");
+    }
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 logout: {}", e)))?;
+
+    // Logging out without a ticket is already the desired state
+    if !output.success() && !P4Error::from_output(&output).is(P4ErrorKind::LoginRequired) {
+        return Err(P4Error::from_output(&output));
+    }
+
+    Ok(())
+}
+
+/// Whether the user has a valid ticket, and when it expires
+#[tauri::command]
+pub async fn p4_login_status(
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4LoginStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    login_status(executor.as_ref(), &conn.profile).await
+}
+
+async fn login_status(executor: &dyn P4Executor, connection: &ConnectionProfile) -> Result<P4LoginStatus, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args(["-ztag", "login", "-s"]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 login -s: {}", e)))?;
+
+    if !output.success() {
+        let err = P4Error::from_output(&output);
+        if err.is(P4ErrorKind::LoginRequired) {
+            return Ok(P4LoginStatus {
+                logged_in: false,
+                user: connection.user.clone(),
+                expires_in: None,
+            });
+        }
+        return Err(err);
+    }
+
+    Ok(parse_login_status(&output.stdout_text()))
+}
+
+/// Check whether the SSL server's fingerprint is trusted, without changing anything
+#[tauri::command]
+pub async fn p4_trust_status(
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4TrustStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    // -n: answer "no" instead of prompting to trust an unknown key
+    cmd.args(["trust", "-n"]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 trust: {}", e)))?;
+
+    let text = format!(
+        "{}{}",
+        output.stdout_text(),
+        output.stderr_text()
+    );
+    let status = parse_trust_status(&text);
+    if !output.success() && status.fingerprint.is_none() {
+        return Err(P4Error::from_output(&output));
+    }
+    Ok(status)
+}
+
+/// Trust the server's SSL key. Installs `fingerprint`, the one the user confirmed, rather than
+/// whatever the server presents now. `replace` overwrites a previously trusted fingerprint.
+#[tauri::command]
+pub async fn p4_trust(
+    fingerprint: String,
+    replace: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<(), P4Error> {
+    let conn = connections.get(&connection)?;
+    let fingerprint = fingerprint.trim();
+    if !is_fingerprint(fingerprint) {
+        return Err(P4Error::other(format!("Invalid fingerprint '{}'", fingerprint)));
+    }
+
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.arg("trust");
+    if replace {
+        cmd.arg("-f");
+    }
+    cmd.args(["-i", fingerprint]);
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 info: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 trust: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Connection test failed: {}", stderr));
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("Failed to trust server"));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_info(&stdout)
+    Ok(())
 }
 
 /// Get file history (revisions) for a depot path
@@ -863,14 +1320,16 @@ pub async fn p4_test_connection(
 pub async fn p4_filelog(
     depot_path: String,
     max_revisions: Option<i32>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4Revision>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Revision>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    let format = RecordFormat::Marshal;
+    cmd.arg(format.flag());
     cmd.arg("filelog");
 
     if let Some(max) = max_revisions {
@@ -880,18 +1339,13 @@ pub async fn p4_filelog(
 
     cmd.arg(depot_path);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 filelog: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 filelog: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_filelog(&stdout)
+    let records = format.parse_output(&output)?;
+    Ok(revisions_from_records(records))
 }
 
 /// Print a specific revision of a file to a temp file
@@ -899,10 +1353,11 @@ pub async fn p4_filelog(
 pub async fn p4_print_to_file(
     depot_path: String,
     revision: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
     // Extract file extension from depot path
     let extension = depot_path
         .rsplit('.')
@@ -915,65 +1370,70 @@ pub async fn p4_print_to_file(
     let temp_file = Builder::new()
         .suffix(&extension)
         .tempfile()
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to create temp file: {}", e)))?;
 
     let temp_path = temp_file.path().to_string_lossy().to_string();
 
     // Print file to temp location
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.args(["print", "-q", "-o", &temp_path]);
     cmd.arg(format!("{}#{}", depot_path, revision));
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 print: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 print: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     // Persist the temp file so it doesn't get deleted
     let (_, persistent_path) = temp_file
         .keep()
-        .map_err(|e| format!("Failed to persist temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to persist temp file: {}", e)))?;
 
     Ok(persistent_path.to_string_lossy().to_string())
 }
 
-/// Print a specific revision of a file and return its content as a string
+/// Print a specific revision of a file and return its content as a string.
+/// revision_spec ("@label", "@1234") takes the place of `revision` when given.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn p4_print_content(
     depot_path: String,
     revision: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
-    // First, check file size using p4 fstat to prevent memory exhaustion
-    let mut fstat_cmd = create_p4_command();
-    apply_connection_args(&mut fstat_cmd, &server, &user, &client);
-    fstat_cmd.args(["-ztag", "fstat"]);
-    fstat_cmd.arg(format!("{}#{}", depot_path, revision));
+    revision_spec: Option<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let file_spec = match revision_spec.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(spec) => with_revision_spec(&depot_path, Some(spec))?,
+        None => format!("{}#{}", depot_path, revision),
+    };
 
-    let fstat_output = fstat_cmd
-        .output()
+    // First, check file size using p4 fstat to prevent memory exhaustion
+    let format = RecordFormat::Marshal;
+    let mut fstat_cmd = P4Command::new();
+    conn.profile.apply(&mut fstat_cmd);
+    fstat_cmd.args([format.flag(), "fstat"]);
+    fstat_cmd.arg(&file_spec);
+
+    let fstat_output = executor
+        .output(&fstat_cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat: {}", e))?;
-
-    if !fstat_output.status.success() {
-        let stderr = String::from_utf8_lossy(&fstat_output.stderr);
-        return Err(format!("Failed to get file info: {}", stderr));
-    }
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
 
-    let fstat_stdout = String::from_utf8_lossy(&fstat_output.stdout);
-    let records = parse_ztag_records(&fstat_stdout);
+    let records = format
+        .parse_output(&fstat_output)
+        .map_err(|e| e.context("Failed to get file info"))?;
 
     if records.is_empty() {
-        return Err("File not found".to_string());
+        return Err(P4Error::other("File not found"));
     }
 
     let file_info = &records[0];
@@ -981,7 +1441,7 @@ pub async fn p4_print_content(
     // Check if file is binary
     if let Some(file_type) = file_info.get("headType") {
         if file_type.contains("binary") {
-            return Err(format!("Cannot view binary file (type: {})", file_type));
+            return Err(P4Error::other(format!("Cannot view binary file (type: {})", file_type)));
         }
     }
 
@@ -989,11 +1449,11 @@ pub async fn p4_print_content(
     if let Some(file_size_str) = file_info.get("fileSize") {
         if let Ok(file_size) = file_size_str.parse::<u64>() {
             if file_size > MAX_CONTENT_SIZE {
-                return Err(format!(
+                return Err(P4Error::other(format!(
                     "File too large to view: {:.1}MB (maximum: {}MB)",
                     file_size as f64 / 1024.0 / 1024.0,
                     MAX_CONTENT_SIZE / 1024 / 1024
-                ));
+                )));
             }
         }
     }
@@ -1009,31 +1469,30 @@ pub async fn p4_print_content(
     let temp_file = Builder::new()
         .suffix(&extension)
         .tempfile()
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to create temp file: {}", e)))?;
 
     let temp_path = temp_file.path().to_string_lossy().to_string();
 
     // Print file to temp location
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.args(["print", "-q", "-o", &temp_path]);
-    cmd.arg(format!("{}#{}", depot_path, revision));
+    cmd.arg(&file_spec);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 print: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 print: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     // Read the temp file content using tokio (async I/O)
     let content = tokio::fs::read_to_string(temp_file.path())
         .await
-        .map_err(|e| format!("Failed to read file content: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to read file content: {}", e)))?;
 
     // Note: temp_file is automatically cleaned up when it goes out of scope
 
@@ -1045,31 +1504,29 @@ pub async fn p4_print_content(
 pub async fn p4_annotate(
     depot_path: String,
     revision: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4AnnotationLine>, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4AnnotationLine>, P4Error> {
+    let conn = connections.get(&connection)?;
     // First, check file size using p4 fstat to prevent memory exhaustion
-    let mut fstat_cmd = create_p4_command();
-    apply_connection_args(&mut fstat_cmd, &server, &user, &client);
-    fstat_cmd.args(["-ztag", "fstat"]);
+    let format = RecordFormat::Marshal;
+    let mut fstat_cmd = P4Command::new();
+    conn.profile.apply(&mut fstat_cmd);
+    fstat_cmd.args([format.flag(), "fstat"]);
     fstat_cmd.arg(format!("{}#{}", depot_path, revision));
 
-    let fstat_output = fstat_cmd
-        .output()
+    let fstat_output = executor
+        .output(&fstat_cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat: {}", e))?;
-
-    if !fstat_output.status.success() {
-        let stderr = String::from_utf8_lossy(&fstat_output.stderr);
-        return Err(format!("Failed to get file info: {}", stderr));
-    }
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
 
-    let fstat_stdout = String::from_utf8_lossy(&fstat_output.stdout);
-    let records = parse_ztag_records(&fstat_stdout);
+    let records = format
+        .parse_output(&fstat_output)
+        .map_err(|e| e.context("Failed to get file info"))?;
 
     if records.is_empty() {
-        return Err("File not found".to_string());
+        return Err(P4Error::other("File not found"));
     }
 
     let file_info = &records[0];
@@ -1077,7 +1534,7 @@ pub async fn p4_annotate(
     // Check if file is binary
     if let Some(file_type) = file_info.get("headType") {
         if file_type.contains("binary") {
-            return Err(format!("Cannot annotate binary file (type: {})", file_type));
+            return Err(P4Error::other(format!("Cannot annotate binary file (type: {})", file_type)));
         }
     }
 
@@ -1085,33 +1542,32 @@ pub async fn p4_annotate(
     if let Some(file_size_str) = file_info.get("fileSize") {
         if let Ok(file_size) = file_size_str.parse::<u64>() {
             if file_size > MAX_CONTENT_SIZE {
-                return Err(format!(
+                return Err(P4Error::other(format!(
                     "File too large to annotate: {:.1}MB (maximum: {}MB)",
                     file_size as f64 / 1024.0 / 1024.0,
                     MAX_CONTENT_SIZE / 1024 / 1024
-                ));
+                )));
             }
         }
     }
 
     // Execute p4 annotate -u -c
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["annotate", "-u", "-c"]);
     cmd.arg(format!("{}#{}", depot_path, revision));
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 annotate: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 annotate: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_annotate_output(&stdout)
+    let stdout = output.stdout_text();
+    parse_annotate_output(&stdout).map_err(P4Error::from)
 }
 
 /// Launch external diff tool
@@ -1122,7 +1578,7 @@ pub async fn launch_diff_tool(
     diff_tool_path: String,
     diff_tool_args: Option<String>,
     _app: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), P4Error> {
     let mut cmd = Command::new(&diff_tool_path);
 
     // Parse and apply arguments
@@ -1159,7 +1615,7 @@ pub async fn launch_diff_tool(
 
     // Spawn without blocking
     cmd.spawn()
-        .map_err(|e| format!("Failed to launch diff tool: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to launch diff tool: {}", e)))?;
 
     Ok(())
 }
@@ -1168,14 +1624,16 @@ pub async fn launch_diff_tool(
 #[tauri::command]
 pub async fn p4_changes_submitted(
     max_changes: Option<i32>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4Changelist>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Changelist>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    let format = RecordFormat::Marshal;
+    cmd.arg(format.flag());
     cmd.arg("changes");
     cmd.arg("-l"); // Long output to get full descriptions
     cmd.arg("-s");
@@ -1183,18 +1641,13 @@ pub async fn p4_changes_submitted(
     cmd.arg("-m");
     cmd.arg(max_changes.unwrap_or(500).to_string());
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 changes: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 changes: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_changes(&stdout)
+    let records = format.parse_output(&output)?;
+    Ok(changelists_from_records(records))
 }
 
 /// Shelve files to a changelist
@@ -1202,12 +1655,13 @@ pub async fn p4_changes_submitted(
 pub async fn p4_shelve(
     changelist_id: i32,
     file_paths: Vec<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("shelve");
     cmd.arg("-c");
@@ -1217,16 +1671,15 @@ pub async fn p4_shelve(
         cmd.args(&file_paths);
     }
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 shelve: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 shelve: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
 
-    if !output.status.success() {
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     Ok(stdout.to_string())
@@ -1236,43 +1689,33 @@ pub async fn p4_shelve(
 #[tauri::command]
 pub async fn p4_describe_shelved(
     changelist_id: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4ShelvedFile>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-
-    cmd.arg("-ztag");
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4ShelvedFile>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    let format = RecordFormat::Marshal;
+    cmd.arg(format.flag());
     cmd.arg("describe");
     cmd.arg("-s");  // suppress diffs
     cmd.arg("-S");  // show shelved files
     cmd.arg(changelist_id.to_string());
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 describe: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 describe: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-
-
-    // CLs without shelved files may return non-zero exit with "no shelved files" message
+    // CLs without shelved files may fail with a "no shelved files" message
     // Treat this as empty result, not error
-    if !output.status.success() {
-        let stderr_lower = stderr.to_lowercase();
-        if stderr_lower.contains("no shelved files")
-            || stderr_lower.contains("not shelved")
-            || stderr_lower.contains("no shelf")
-            || (stdout.trim().is_empty() && stderr.trim().is_empty())
-        {
-            return Ok(vec![]);
-        }
-        return Err(stderr.to_string());
+    match format.parse_output(&output) {
+        Ok(records) => Ok(shelved_files_from_records(records)),
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) || err.stderr.trim().is_empty() => Ok(vec![]),
+        Err(err) => Err(err),
     }
-
-    parse_ztag_describe_shelved(&stdout)
 }
 
 /// Batch describe shelved files for multiple changelists
@@ -1280,21 +1723,22 @@ pub async fn p4_describe_shelved(
 #[tauri::command]
 pub async fn p4_describe_shelved_batch(
     changelist_ids: Vec<i32>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
+    connection: Option<String>,
     on_progress: Channel<ShelvedBatchProgress>,
     state: State<'_, ProcessManager>,
-) -> Result<String, String> {
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
     if changelist_ids.is_empty() {
         return Ok("".to_string());
     }
 
     let total = changelist_ids.len() as u32;
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
-    cmd.arg("-ztag");
+    cmd.arg(RecordFormat::Marshal.flag());
     cmd.arg("describe");
     cmd.arg("-s");  // suppress diffs
     cmd.arg("-S");  // show shelved files
@@ -1303,97 +1747,88 @@ pub async fn p4_describe_shelved_batch(
         cmd.arg(cl_id.to_string());
     }
 
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 describe: {}", e)))?;
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 describe: {}", e))?;
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
 
     // Register process for cancellation
-    let process_id = state.register(child).await;
-    let process_id_clone = process_id.clone();
-
-    // Parse stdout in background
-    let changelist_ids_clone = changelist_ids.clone();
-    if let Some(stdout) = stdout {
-        let on_progress_clone = on_progress.clone();
-        tokio::spawn(async move {
-            use tokio::io::AsyncReadExt;
-
-            let mut output = String::new();
-            let mut reader = stdout;
-            let _ = reader.read_to_string(&mut output).await;
-
-            let results = parse_describe_shelved_batch(&output);
-
-            let mut success_count = 0;
-            let mut error_count = 0;
-
-            for (index, cl_id) in changelist_ids_clone.iter().enumerate() {
-                let files = results.get(cl_id).cloned();
-
-                let result = if let Some(files) = files {
-                    success_count += 1;
-                    ShelvedBatchResult {
-                        changelist_id: *cl_id,
-                        files: Some(files),
-                        error: None,
-                    }
-                } else {
-                    error_count += 1;
-                    ShelvedBatchResult {
-                        changelist_id: *cl_id,
-                        files: None,
-                        error: Some(format!("No data returned for changelist {}", cl_id)),
-                    }
-                };
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    // Log errors but don't fail the batch for CLs without shelved files
+    let stderr_task = collect_stderr(stderr, encoding, |line| {
+        let is_error = is_query_error(line);
+        if is_error {
+            eprintln!("p4 describe shelved batch stderr: {}", line);
+        }
+        is_error
+    });
 
-                // Send result for this CL
-                let _ = on_progress_clone.send(ShelvedBatchProgress::Result { result });
+    // Parse stdout in background, then report how the command ended
+    tokio::spawn(async move {
+        use tokio::io::AsyncReadExt;
 
-                // Send progress update every 5 CLs
-                if (index + 1) % 5 == 0 || index + 1 == changelist_ids_clone.len() {
-                    let _ = on_progress_clone.send(ShelvedBatchProgress::Progress {
-                        loaded: (index + 1) as u32,
-                        total,
-                        message: format!("Loading shelved files... ({}/{})", index + 1, total),
-                    });
-                }
-            }
+        let mut output = Vec::new();
+        if let Some(mut stdout) = stdout {
+            let _ = stdout.read_to_end(&mut output).await;
+        }
 
-            // Send completion
-            let _ = on_progress_clone.send(ShelvedBatchProgress::Complete {
-                success_count,
-                error_count,
-                cancelled: false,
-            });
-        });
-    }
+        // Error records (e.g. CLs with no shelved files) carry no "change" field and
+        // are skipped; those CLs are reported as missing below
+        let records = parse_marshal_records(&output, encoding).unwrap_or_default();
+        let results = shelved_batch_from_records(records);
 
-    // Handle stderr in background - log errors but don't fail batch
-    if let Some(stderr) = stderr {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                let line_lower = line.to_lowercase();
-                // Skip known empty-result messages
-                if line_lower.contains("no shelved files")
-                    || line_lower.contains("not shelved")
-                    || line_lower.contains("no shelf")
-                {
-                    continue;
+        let mut success_count = 0;
+        let mut error_count = 0;
+
+        // A cancelled describe has partial output; report no per-CL results for it
+        let completion = finish_stream(exited, stderr_task).await;
+        let changelist_ids = if completion.cancelled { Vec::new() } else { changelist_ids };
+
+        for (index, cl_id) in changelist_ids.iter().enumerate() {
+            let files = results.get(cl_id).cloned();
+
+            let result = if let Some(files) = files {
+                success_count += 1;
+                ShelvedBatchResult {
+                    changelist_id: *cl_id,
+                    files: Some(files),
+                    error: None,
                 }
-                // Log actual errors
-                eprintln!("p4 describe shelved batch stderr: {}", line);
+            } else {
+                error_count += 1;
+                ShelvedBatchResult {
+                    changelist_id: *cl_id,
+                    files: None,
+                    error: Some(format!("No data returned for changelist {}", cl_id)),
+                }
+            };
+
+            // Send result for this CL
+            let _ = on_progress.send(ShelvedBatchProgress::Result { result });
+
+            // Send progress update every 5 CLs
+            if (index + 1) % 5 == 0 || index + 1 == changelist_ids.len() {
+                let _ = on_progress.send(ShelvedBatchProgress::Progress {
+                    loaded: (index + 1) as u32,
+                    total,
+                    message: format!("Loading shelved files... ({}/{})", index + 1, total),
+                });
             }
+        }
+
+        let _ = on_progress.send(ShelvedBatchProgress::Complete {
+            success_count,
+            error_count,
+            completion,
         });
-    }
+    });
 
-    Ok(process_id_clone)
+    Ok(process_id)
 }
 
 /// Describe a submitted changelist (metadata and file list, no diffs)
@@ -1401,27 +1836,24 @@ pub async fn p4_describe_shelved_batch(
 #[tauri::command]
 pub async fn p4_describe(
     changelist_id: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<P4ChangelistDescription, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    // -ztag for structured output, -s to suppress diffs (critical for large CLs)
-    cmd.args(["-ztag", "describe", "-s", &changelist_id.to_string()]);
-
-    let output = cmd
-        .output()
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4ChangelistDescription, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    // -G for structured output, -s to suppress diffs (critical for large CLs)
+    let format = RecordFormat::Marshal;
+    cmd.args([format.flag(), "describe", "-s", &changelist_id.to_string()]);
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 describe: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 describe: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_describe_output(&stdout, changelist_id)
+    let records = format.parse_output(&output)?;
+    describe_from_records(records, changelist_id).map_err(P4Error::from)
 }
 
 /// Unshelve files from a changelist
@@ -1430,12 +1862,13 @@ pub async fn p4_unshelve(
     source_changelist_id: i32,
     target_changelist_id: i32,
     file_paths: Option<Vec<String>>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("unshelve");
     cmd.arg("-s");
@@ -1450,13 +1883,13 @@ pub async fn p4_unshelve(
         }
     }
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 unshelve: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 unshelve: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Include stderr in result as it may contain conflict info
     let result = if stderr.is_empty() {
@@ -1465,8 +1898,10 @@ pub async fn p4_unshelve(
         format!("{}\n{}", stdout, stderr)
     };
 
-    if !output.status.success() {
-        return Err(result);
+    if !output.success() {
+        let mut err = P4Error::from_output(&output);
+        err.message = result;
+        return Err(err);
     }
 
     Ok(result)
@@ -1476,28 +1911,28 @@ pub async fn p4_unshelve(
 #[tauri::command]
 pub async fn p4_delete_shelf(
     changelist_id: i32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("shelve");
     cmd.arg("-d");
     cmd.arg("-c");
     cmd.arg(changelist_id.to_string());
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 shelve -d: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 shelve -d: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
 
-    if !output.status.success() {
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
     Ok(stdout.to_string())
@@ -1510,12 +1945,13 @@ pub async fn p4_delete_shelf(
 #[tauri::command]
 pub async fn p4_reconcile_preview(
     depot_path: Option<String>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<ReconcilePreview>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<ReconcilePreview>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     cmd.arg("reconcile");
     cmd.arg("-n"); // Dry run
@@ -1524,13 +1960,13 @@ pub async fn p4_reconcile_preview(
     let path = depot_path.unwrap_or_else(|| "//...".to_string());
     cmd.arg(path);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 reconcile -n: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 reconcile -n: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Stderr may contain "no file(s) to reconcile" - treat as empty result, not error
     if stderr.contains("no file(s) to reconcile") {
@@ -1548,12 +1984,13 @@ pub async fn p4_reconcile_preview(
 pub async fn p4_reconcile_apply(
     file_paths: Vec<String>,
     changelist_id: Option<i32>,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
     if file_paths.is_empty() {
-        return Err("No file paths provided".to_string());
+        return Err(P4Error::other("No file paths provided"));
     }
 
     // Clean paths: strip revision specifiers (#rev) that p4 reconcile -n may include
@@ -1574,27 +2011,27 @@ pub async fn p4_reconcile_apply(
         .prefix("p4_reconcile_")
         .suffix(".txt")
         .tempfile()
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to create temp file: {}", e)))?;
 
     let temp_path = temp_file.path().to_string_lossy().to_string();
 
     // Write file paths to temp file (one per line)
     use std::io::Write;
     let mut file = std::fs::File::create(&temp_path)
-        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to open temp file: {}", e)))?;
 
     for path in &cleaned_paths {
         writeln!(file, "{}", path)
-            .map_err(|e| format!("Failed to write to temp file: {}", e))?;
+            .map_err(|e| P4Error::other(format!("Failed to write to temp file: {}", e)))?;
     }
 
     // Flush to ensure all data is written
     file.flush()
-        .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to flush temp file: {}", e)))?;
     drop(file); // Close file before p4 reads it
 
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
 
     // Use -x flag to read file list from temp file
     cmd.arg("-x");
@@ -1607,31 +2044,27 @@ pub async fn p4_reconcile_apply(
         cmd.arg(cl.to_string());
     }
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 reconcile: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 reconcile: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = output.stdout_text();
+    let stderr = output.stderr_text();
 
     // Temp file is automatically cleaned up when temp_file goes out of scope
 
-    if !output.status.success() {
-        let err = if stderr.is_empty() {
-            stdout.to_string()
-        } else {
-            stderr.to_string()
-        };
-        return Err(err);
+    if !output.success() {
+        let err = if stderr.is_empty() { &stdout } else { &stderr };
+        return Err(P4Error::from_stderr(err, output.exit_code));
     }
 
     // Check if p4 actually reconciled anything
     let trimmed = stdout.trim();
     if trimmed.is_empty() {
-        return Err(
-            "No files were reconciled. The files may already be open or unchanged.".to_string(),
-        );
+        return Err(P4Error::other(
+            "No files were reconciled. The files may already be open or unchanged.",
+        ));
     }
 
     Ok(stdout.to_string())
@@ -1640,20 +2073,21 @@ pub async fn p4_reconcile_apply(
 /// Preview files needing resolution (without actually resolving)
 #[tauri::command]
 pub async fn p4_resolve_preview(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<String>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<String>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["resolve", "-n"]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 resolve -n: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 resolve -n: {}", e)))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout_text();
 
     // Parse output: lines like "//depot/path - merging ..." or "//depot/path - must resolve ..."
     let files: Vec<String> = stdout
@@ -1668,34 +2102,38 @@ pub async fn p4_resolve_preview(
     Ok(files)
 }
 
-/// Search depot for files matching a pattern
+/// Search depot for files matching a pattern, optionally at a revision such as "@label"
 /// Example: p4 files //depot/.../*.cpp
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn p4_files(
     pattern: String,
     max_results: u32,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4FileResult>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    revision_spec: Option<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.arg("files");
-    cmd.arg(&pattern);
+    cmd.arg(with_revision_spec(&pattern, revision_spec.as_deref())?);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 files: {}", e))?;
-
-    let stderr = String::from_utf8_lossy(&output.stderr);
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 files: {}", e)))?;
 
     // Check for errors (but allow "no such file(s)" as valid empty result)
-    if !output.status.success() && !stderr.contains("no such file(s)") {
-        return Err(stderr.to_string());
+    if !output.success() {
+        let err = P4Error::from_output(&output);
+        if !err.is(P4ErrorKind::NoSuchFile) {
+            return Err(err);
+        }
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout_text();
 
     // Parse output lines: format is "//depot/path#rev - action change N (type)"
     // Example: "//depot/main/src/foo.cpp#5 - edit change 12345 (text)"
@@ -1751,54 +2189,357 @@ pub async fn p4_files(
     Ok(results)
 }
 
-/// List available streams for the depot
+/// List available streams for the depot.
+/// With `with_integration`, each stream that has a parent also carries its p4 istat status
+/// (without the changelist lists; use p4_stream_integration_status for those).
 #[tauri::command]
 pub async fn p4_list_streams(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4Stream>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    with_integration: Option<bool>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Stream>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["-ztag", "streams"]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 streams: {}", e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
+    }
+
+    let stdout = output.stdout_text();
+    let mut streams = parse_ztag_streams(&stdout).map_err(P4Error::from)?;
+    if !with_integration.unwrap_or(false) {
+        return Ok(streams);
+    }
+
+    // One istat per stream with a parent, a few at a time
+    let limit = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_ISTAT));
+    let tasks: Vec<_> = streams
+        .iter()
+        .map(|stream| {
+            let parent = stream.parent.clone();
+            let stream = stream.stream.clone();
+            let executor = executor.inner().clone();
+            let conn = conn.clone();
+            let limit = limit.clone();
+            tokio::spawn(async move {
+                parent.as_ref()?;
+                let _permit = limit.acquire_owned().await.ok()?;
+                let connection = &conn.profile;
+                let status = stream_integration_status(executor.as_ref(), &stream, false, connection)
+                    .await
+                    .unwrap_or_else(|err| StreamIntegrationStatus {
+                        stream,
+                        parent,
+                        error: Some(err.message),
+                        ..Default::default()
+                    });
+                Some(status)
+            })
+        })
+        .collect();
+    for (stream, task) in streams.iter_mut().zip(tasks) {
+        stream.integration = task.await.ok().flatten();
+    }
+
+    Ok(streams)
+}
+
+/// Whether `stream` needs a merge down from its parent or is ready to copy up,
+/// with the changelists waiting in each direction
+#[tauri::command]
+pub async fn p4_stream_integration_status(
+    stream: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<StreamIntegrationStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    stream_integration_status(executor.inner().as_ref(), &stream, true, &conn.profile).await
+}
+
+/// p4 istat -a for one stream; with `include_changes`, also p4 interchanges in both directions
+async fn stream_integration_status(
+    executor: &dyn P4Executor,
+    stream: &str,
+    include_changes: bool,
+    connection: &ConnectionProfile,
+) -> Result<StreamIntegrationStatus, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args([RecordFormat::Ztag.flag(), "istat", "-a", stream]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 istat: {}", e)))?;
+
+    let mut status = RecordFormat::Ztag
+        .parse_output(&output)?
+        .first()
+        .and_then(build_stream_integration_status)
+        .ok_or_else(|| P4Error::other(format!("p4 istat returned no status for {}", stream)))?;
+
+    if include_changes {
+        if status.needs_merge_down {
+            status.pending_merge_down = stream_interchanges(executor, stream, true, connection).await?;
+        }
+        if status.needs_copy_up {
+            status.pending_copy_up = stream_interchanges(executor, stream, false, connection).await?;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Changelists not yet integrated between a stream and its parent.
+/// p4 interchanges -S runs from the stream to its parent; `from_parent` reverses it (-r).
+async fn stream_interchanges(
+    executor: &dyn P4Executor,
+    stream: &str,
+    from_parent: bool,
+    connection: &ConnectionProfile,
+) -> Result<Vec<P4Changelist>, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "interchanges", "-l", "-S", stream]);
+    if from_parent {
+        cmd.arg("-r");
+    }
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 streams: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 interchanges: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    // "All revision(s) already integrated" comes back as an error message
+    let (records, errors) = parse_marshal_with_errors(&output)?;
+    let errors: Vec<String> = errors.into_iter().filter(|e| is_integrate_error(e)).collect();
+    if records.is_empty() && !errors.is_empty() {
+        return Err(P4Error::from_stderr(&errors.join("\n"), output.exit_code));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_streams(&stdout)
+    Ok(changelists_from_records(records))
+}
+
+/// Get the editable spec of a stream
+#[tauri::command]
+pub async fn p4_get_stream_spec(
+    stream: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4StreamSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -G keeps multi-line descriptions intact
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "stream", "-o", &stream]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 stream -o: {}", e)))?;
+
+    format
+        .parse_output(&output)?
+        .first()
+        .and_then(build_stream_spec)
+        .ok_or_else(|| P4Error::other(format!("p4 stream -o returned no spec for {}", stream)))
+}
+
+/// Save an edited stream spec (p4 stream -o, rewrite fields, p4 stream -i).
+/// Problems are reported as an invalidSpec error with per-field details.
+#[tauri::command]
+pub async fn p4_save_stream_spec(
+    spec: P4StreamSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_stream_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid stream spec", field_errors));
+    }
+
+    let connection = &conn.profile;
+    let mut form = fetch_spec_form(executor.inner().as_ref(), &["stream", "-o", &spec.stream], connection).await?;
+    apply_stream_spec(&mut form, &spec);
+    submit_spec_form(executor.inner().as_ref(), "stream", &form, connection).await
+}
+
+/// Create a development, task, virtual or release stream under `parent`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_create_stream(
+    stream: String,
+    stream_type: String,
+    parent: String,
+    name: Option<String>,
+    description: Option<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    if !matches!(stream_type.as_str(), "development" | "task" | "virtual" | "release") {
+        return Err(P4Error::invalid_spec(
+            format!("Can't create a {} stream from a parent", stream_type),
+            vec![SpecFieldError {
+                field: Some("Type".to_string()),
+                message: "Choose development, task, virtual or release".to_string(),
+            }],
+        ));
+    }
+
+    // p4 fills in the default spec for the type and parent
+    let connection = &conn.profile;
+    let args = ["stream", "-o", "-t", &stream_type, "-P", &parent, &stream];
+    let mut form = fetch_spec_form(executor.inner().as_ref(), &args, connection).await?;
+    if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+        form.set_line("Name", name);
+    }
+    if let Some(description) = description.filter(|d| !d.trim().is_empty()) {
+        form.set_text("Description", &description);
+    }
+    submit_spec_form(executor.inner().as_ref(), "stream", &form, connection).await
 }
 
 /// Get client spec for a specific workspace
 #[tauri::command]
 pub async fn p4_get_client_spec(
     workspace: String,
-    server: Option<String>,
-    user: Option<String>,
-) -> Result<P4ClientSpec, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &None);
-    cmd.args(["-ztag", "client", "-o", &workspace]);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4ClientSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -G keeps multi-line descriptions intact
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.without_client().apply(&mut cmd);
+    cmd.args([format.flag(), "client", "-o", &workspace]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 client -o: {}", e)))?;
+
+    let records = format.parse_output(&output)?;
+    let fields = records.into_iter().next().unwrap_or_default();
+    build_client_spec(&fields).map_err(P4Error::from)
+}
 
-    let output = cmd
-        .output()
+/// Create a workspace. With `template`, the new workspace starts as a copy of that
+/// one (p4 client -t); a spec without a view or stream then keeps the template's.
+#[tauri::command]
+pub async fn p4_create_client(
+    mut spec: P4ClientSpec,
+    template: Option<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile.without_client();
+
+    // p4 client -i would silently overwrite an existing workspace
+    let mut cmd = P4Command::new();
+    conn.profile.without_client().apply(&mut cmd);
+    cmd.args([RecordFormat::Ztag.flag(), "clients", "-e", &spec.client]);
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 client -o: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 clients: {}", e)))?;
+    if !RecordFormat::Ztag.parse_output(&output)?.is_empty() {
+        return Err(P4Error::invalid_spec(
+            format!("Workspace {} already exists", spec.client),
+            vec![SpecFieldError {
+                field: Some("Client".to_string()),
+                message: "A workspace with this name already exists".to_string(),
+            }],
+        ));
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    let template = template.as_deref().filter(|t| !t.is_empty());
+    let mut args = vec!["client", "-o"];
+    if let Some(template) = template {
+        args.extend(["-t", template]);
     }
+    args.push(&spec.client);
+    let mut form = fetch_spec_form(executor, &args, connection).await?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_client_spec(&stdout)
+    if template.is_some() && spec.view.is_empty() && spec.stream.is_none() {
+        spec.stream = form.line("Stream").map(str::to_string);
+        spec.view = form.lines("View");
+    }
+    let field_errors = validate_client_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid workspace spec", field_errors));
+    }
+
+    apply_client_spec(&mut form, &spec);
+    submit_spec_form(executor, "client", &form, connection).await
+}
+
+/// Save every editable field of an existing workspace
+#[tauri::command]
+pub async fn p4_save_client_spec(
+    spec: P4ClientSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_client_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid workspace spec", field_errors));
+    }
+
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile.without_client();
+    let mut form = fetch_spec_form(executor, &["client", "-o", &spec.client], connection).await?;
+    apply_client_spec(&mut form, &spec);
+    submit_spec_form(executor, "client", &form, connection).await
+}
+
+/// Delete a workspace. p4 refuses while it has opened files unless `force` (-f, admin only).
+#[tauri::command]
+pub async fn p4_delete_client(
+    workspace: String,
+    force: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.without_client().apply(&mut cmd);
+    cmd.args(["client", "-d"]);
+    if force {
+        cmd.arg("-f");
+    }
+    cmd.arg(&workspace);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 client -d: {}", e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
+    }
+    Ok(output.stdout_text().trim().to_string())
 }
 
 /// Update client spec's Stream field (for stream switching)
@@ -1806,94 +2547,555 @@ pub async fn p4_get_client_spec(
 pub async fn p4_update_client_stream(
     workspace: String,
     new_stream: String,
-    server: Option<String>,
-    user: Option<String>,
-) -> Result<String, String> {
-    // 1. Get current client spec (without -ztag - need raw form format)
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &Some(workspace.clone()));
-    cmd.args(["client", "-o", &workspace]);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let executor = executor.inner().as_ref();
+    let connection = &ConnectionProfile {
+        client: Some(workspace.clone()),
+        ..conn.profile.clone()
+    };
+
+    let mut form = fetch_spec_form(executor, &["client", "-o", &workspace], connection).await?;
+    form.set_line("Stream", new_stream);
+    submit_spec_form(executor, "client", &form, connection).await
+}
+
+/// List labels, optionally only those owned by `owner` or whose name matches
+/// `name_filter` (case-insensitive, p4 wildcards such as "release-*")
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_list_labels(
+    name_filter: Option<String>,
+    owner: Option<String>,
+    max_results: Option<u32>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Label>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "labels"]);
+
+    if let Some(owner) = owner.filter(|o| !o.is_empty()) {
+        cmd.args(["-u", &owner]);
+    }
+    if let Some(filter) = name_filter.filter(|f| !f.is_empty()) {
+        cmd.args(["-E", &filter]);
+    }
+    if let Some(max) = max_results {
+        cmd.args(["-m", &max.to_string()]);
+    }
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 labels: {}", e)))?;
+
+    let records = format.parse_output(&output)?;
+    Ok(records.iter().filter_map(build_label).collect())
+}
+
+/// Get a label's spec. For a label that doesn't exist yet, p4 returns the defaults.
+#[tauri::command]
+pub async fn p4_get_label_spec(
+    label: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4LabelSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -G keeps multi-line descriptions intact
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "label", "-o", &label]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 label -o: {}", e)))?;
+
+    format
+        .parse_output(&output)?
+        .first()
+        .and_then(build_label_spec)
+        .ok_or_else(|| P4Error::other(format!("p4 label -o returned no spec for {}", label)))
+}
+
+/// Create or update a label (p4 label -o, rewrite fields, p4 label -i)
+#[tauri::command]
+pub async fn p4_save_label_spec(
+    spec: P4LabelSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_label_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid label spec", field_errors));
+    }
+
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+    let mut form = fetch_spec_form(executor, &["label", "-o", &spec.label], connection).await?;
+    apply_label_spec(&mut form, &spec);
+    submit_spec_form(executor, "label", &form, connection).await
+}
 
-    let output = cmd
-        .output()
+/// Delete a label. `force` (-f) deletes a label owned by someone else or locked (admin only).
+#[tauri::command]
+pub async fn p4_delete_label(
+    label: String,
+    force: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args(["label", "-d"]);
+    if force {
+        cmd.arg("-f");
+    }
+    cmd.arg(&label);
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to get client spec: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 label -d: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
+    Ok(output.stdout_text().trim().to_string())
+}
 
-    let form = String::from_utf8_lossy(&output.stdout);
+/// Add files to a label (or remove them with `delete`), with p4 tag.
+/// With `changelist`, tags the revisions submitted in that change, under `paths` or
+/// the whole depot. `preview` (-n) reports what would change without changing it.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_tag(
+    label: String,
+    paths: Vec<String>,
+    changelist: Option<i32>,
+    delete: bool,
+    preview: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let paths = match changelist {
+        Some(cl) if paths.is_empty() => vec![format!("//...@={}", cl)],
+        Some(cl) => paths.iter().map(|p| format!("{}@={}", p, cl)).collect(),
+        None if paths.is_empty() => return Err(P4Error::other("No paths provided")),
+        None => paths,
+    };
 
-    // 2. Modify Stream field in form
-    let mut new_form = String::new();
-    for line in form.lines() {
-        if line.starts_with("Stream:") {
-            new_form.push_str(&format!("Stream:\t{}\n", new_stream));
-        } else {
-            new_form.push_str(line);
-            new_form.push('\n');
-        }
+    // Build command: p4 -G tag [-d] [-n] -l <label> <paths>
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "tag"]);
+    if delete {
+        cmd.arg("-d");
+    }
+    if preview {
+        cmd.arg("-n");
     }
+    cmd.args(["-l", &label]);
+    cmd.args(&paths);
 
-    // 3. Submit modified form via p4 client -i
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &Some(workspace));
-    cmd.args(["client", "-i"]);
-    cmd.stdin(Stdio::piped());
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    run_file_op(executor.as_ref(), &cmd, "tag").await
+}
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 client -i: {}", e))?;
+/// Make a label match the workspace's have revisions, with p4 labelsync.
+/// `add` (-a) only adds files, `delete` (-d) only removes `paths` from the label;
+/// without paths the whole workspace is synced into the label.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_labelsync(
+    label: String,
+    paths: Vec<String>,
+    add: bool,
+    delete: bool,
+    preview: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4FileOpResult>, P4Error> {
+    let conn = connections.get(&connection)?;
+    if add && delete {
+        return Err(P4Error::other("labelsync can add or delete files, not both"));
+    }
+
+    // Build command: p4 -G labelsync [-a|-d] [-n] -l <label> [paths]
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "labelsync"]);
+    if add {
+        cmd.arg("-a");
+    }
+    if delete {
+        cmd.arg("-d");
+    }
+    if preview {
+        cmd.arg("-n");
+    }
+    cmd.args(["-l", &label]);
+    cmd.args(&paths);
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(new_form.as_bytes())
-            .await
-            .map_err(|e| format!("Failed to write form: {}", e))?;
-        // Drop stdin to signal EOF
-        drop(stdin);
+    run_file_op(executor.as_ref(), &cmd, "labelsync").await
+}
+
+/// Search jobs with a jobview query, e.g. "status=open user=alice crash"
+#[tauri::command]
+pub async fn p4_jobs(
+    query: Option<String>,
+    max_results: Option<u32>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Job>, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -l keeps full descriptions
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "jobs", "-l"]);
+
+    if let Some(query) = query.filter(|q| !q.trim().is_empty()) {
+        cmd.args(["-e", &query]);
+    }
+    if let Some(max) = max_results {
+        cmd.args(["-m", &max.to_string()]);
+    }
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 jobs: {}", e)))?;
+
+    let records = format.parse_output(&output)?;
+    Ok(records.into_iter().filter_map(build_job).collect())
+}
+
+/// Get a job with its fields typed by the server's jobspec.
+/// `job` "new" (or empty) returns a blank job to fill in.
+#[tauri::command]
+pub async fn p4_get_job_spec(
+    job: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4JobSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+    let jobspec = fetch_spec_form(executor, &["jobspec", "-o"], connection).await?;
+    let form = fetch_spec_form(executor, &job_form_args(&job), connection).await?;
+
+    Ok(P4JobSpec {
+        job: form.line("Job").unwrap_or("new").to_string(),
+        fields: job_fields(&jobspec, &form),
+    })
+}
+
+/// Create or update a job, returning p4's confirmation ("Job job000123 saved.")
+#[tauri::command]
+pub async fn p4_save_job_spec(
+    spec: P4JobSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_job_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid job", field_errors));
+    }
+
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+    let mut form = fetch_spec_form(executor, &job_form_args(&spec.job), connection).await?;
+    apply_job_spec(&mut form, &spec);
+    submit_spec_form(executor, "job", &form, connection).await
+}
+
+/// Get a user's spec; an empty `user` is the connection's own user
+#[tauri::command]
+pub async fn p4_get_user_spec(
+    user: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4UserSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    let form = fetch_spec_form(executor.inner().as_ref(), &user_form_args(&user), &conn.profile).await?;
+    user_spec_from_form(&form).ok_or_else(|| P4Error::other("p4 user -o returned no spec"))
+}
+
+/// Update a user spec (p4 user -o, rewrite fields, p4 user -i).
+/// Only the connection's own user can be saved; p4 needs -f (super) for anyone else.
+#[tauri::command]
+pub async fn p4_save_user_spec(
+    spec: P4UserSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_user_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid user spec", field_errors));
+    }
+
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+    let mut form = fetch_spec_form(executor, &user_form_args(&spec.user), connection).await?;
+    apply_user_spec(&mut form, &spec);
+    submit_spec_form(executor, "user", &form, connection).await
+}
+
+/// p4 user -o for a named user, or for the current one
+fn user_form_args(user: &str) -> Vec<&str> {
+    match user.trim() {
+        "" => vec!["user", "-o"],
+        user => vec!["user", "-o", user],
+    }
+}
+
+/// p4 job -o for an existing job, or for a new one
+fn job_form_args(job: &str) -> Vec<&str> {
+    match job.trim() {
+        "" | "new" => vec!["job", "-o"],
+        job => vec!["job", "-o", job],
     }
+}
+
+/// Link jobs to a pending changelist (p4 fix), or unlink them with `delete` (p4 fix -d).
+/// `status` is the status the jobs get when the changelist is submitted.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_fix(
+    changelist: i32,
+    jobs: Vec<String>,
+    status: Option<String>,
+    delete: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    if jobs.is_empty() {
+        return Err(P4Error::other("No jobs provided"));
+    }
+    if changelist == 0 {
+        return Err(P4Error::other("Jobs can only be linked to a numbered changelist"));
+    }
+    fix_jobs(
+        executor.inner().as_ref(),
+        changelist,
+        &jobs,
+        status.as_deref(),
+        delete,
+        &conn.profile,
+    )
+    .await
+}
 
-    let result = child
-        .wait_with_output()
+/// p4 fix [-d] [-s status] -c <changelist> <jobs>
+async fn fix_jobs(
+    executor: &dyn P4Executor,
+    changelist: i32,
+    jobs: &[String],
+    status: Option<&str>,
+    delete: bool,
+    connection: &ConnectionProfile,
+) -> Result<String, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.arg("fix");
+    if delete {
+        cmd.arg("-d");
+    }
+    if let Some(status) = status.filter(|s| !s.is_empty()) {
+        cmd.args(["-s", status]);
+    }
+    cmd.args(["-c", &changelist.to_string()]);
+    cmd.args(jobs);
+
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to update client: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fix: {}", e)))?;
 
-    if !result.status.success() {
-        let stderr = String::from_utf8_lossy(&result.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
+    Ok(output.stdout_text().trim().to_string())
+}
 
-    let stdout = String::from_utf8_lossy(&result.stdout);
-    Ok(stdout.to_string())
+/// List branch specs, optionally only those owned by `owner` or whose name matches
+/// `name_filter` (case-insensitive, p4 wildcards such as "rel-*")
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_list_branches(
+    name_filter: Option<String>,
+    owner: Option<String>,
+    max_results: Option<u32>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Branch>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "branches"]);
+
+    if let Some(owner) = owner.filter(|o| !o.is_empty()) {
+        cmd.args(["-u", &owner]);
+    }
+    if let Some(filter) = name_filter.filter(|f| !f.is_empty()) {
+        cmd.args(["-E", &filter]);
+    }
+    if let Some(max) = max_results {
+        cmd.args(["-m", &max.to_string()]);
+    }
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 branches: {}", e)))?;
+
+    let records = format.parse_output(&output)?;
+    Ok(records.iter().filter_map(build_branch).collect())
+}
+
+/// Get a branch spec. For a branch that doesn't exist yet, p4 returns the defaults.
+#[tauri::command]
+pub async fn p4_get_branch_spec(
+    branch: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<P4BranchSpec, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -G keeps multi-line descriptions intact
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "branch", "-o", &branch]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 branch -o: {}", e)))?;
+
+    format
+        .parse_output(&output)?
+        .first()
+        .and_then(build_branch_spec)
+        .ok_or_else(|| P4Error::other(format!("p4 branch -o returned no spec for {}", branch)))
+}
+
+/// Create or update a branch spec (p4 branch -o, rewrite fields, p4 branch -i)
+#[tauri::command]
+pub async fn p4_save_branch_spec(
+    spec: P4BranchSpec,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let field_errors = validate_branch_spec(&spec);
+    if !field_errors.is_empty() {
+        return Err(P4Error::invalid_spec("Invalid branch spec", field_errors));
+    }
+
+    let executor = executor.inner().as_ref();
+    let connection = &conn.profile;
+    let mut form = fetch_spec_form(executor, &["branch", "-o", &spec.branch], connection).await?;
+    apply_branch_spec(&mut form, &spec);
+    submit_spec_form(executor, "branch", &form, connection).await
+}
+
+/// Compare the two sides of a branch view file by file (p4 diff2 -b), optionally only
+/// under the target path `path`. Identical files are left out unless `include_identical`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_diff2_branch(
+    branch: String,
+    path: Option<String>,
+    include_identical: bool,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Diff2File>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([RecordFormat::Marshal.flag(), "diff2"]);
+    if !include_identical {
+        // -q: only files that differ, and no diff text
+        cmd.arg("-q");
+    }
+    cmd.args(["-b", &branch]);
+    if let Some(path) = path.filter(|p| !p.is_empty()) {
+        cmd.arg(path);
+    }
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 diff2: {}", e)))?;
+
+    let (records, errors) = parse_marshal_with_errors(&output)?;
+    let files: Vec<P4Diff2File> = records.iter().filter_map(build_diff2_file).collect();
+
+    // "No differing files." and an empty branch view are empty results, not failures
+    if files.is_empty() && !errors.is_empty() {
+        let err = P4Error::from_stderr(&errors.join("\n"), output.exit_code.filter(|c| *c != 0).or(Some(1)));
+        let nothing_to_compare = err.is(P4ErrorKind::NoSuchFile)
+            || errors.iter().all(|e| e.to_lowercase().contains("no differing files"));
+        if !nothing_to_compare {
+            return Err(err);
+        }
+    }
+    Ok(files)
 }
 
 /// List all depot roots
 #[tauri::command]
 pub async fn p4_depots(
-    server: Option<String>,
-    user: Option<String>,
-) -> Result<Vec<P4Depot>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &None);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4Depot>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.without_client().apply(&mut cmd);
     cmd.args(["-ztag", "depots"]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 depots: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 depots: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_depots(&stdout)
+    let stdout = output.stdout_text();
+    parse_ztag_depots(&stdout).map_err(P4Error::from)
 }
 
 /// List immediate subdirectories of a depot path
@@ -1901,12 +3103,13 @@ pub async fn p4_depots(
 pub async fn p4_dirs(
     depot_path: String,
     include_deleted: bool,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<String>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<String>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.arg("-ztag");
 
     if include_deleted {
@@ -1917,52 +3120,174 @@ pub async fn p4_dirs(
         cmd.args(["dirs", &depot_path]);
     }
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 dirs: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 dirs: {}", e)))?;
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = output.stderr_text();
 
     // Handle "no such file(s)" as empty result, not error
-    if stderr.contains("no such file(s)") || stderr.contains("must refer to client") {
+    if classify_stderr(&stderr) == P4ErrorKind::NoSuchFile
+        || stderr.contains("must refer to client")
+    {
         return Ok(Vec::new());
     }
 
-    if !output.status.success() {
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_ztag_dirs(&stdout)
+    let stdout = output.stdout_text();
+    parse_ztag_dirs(&stdout).map_err(P4Error::from)
+}
+
+/// Preview p4 merge / copy / integrate (-n): the files it would open and with which action
+#[tauri::command]
+pub async fn p4_integrate_preview(
+    options: IntegrateOptions,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<IntegratePreview, P4Error> {
+    let conn = connections.get(&connection)?;
+    let name = options.kind.command();
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.arg(RecordFormat::Ztag.flag());
+    cmd.args(integrate_args(&options, true)?);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 {} -n: {}", name, e)))?;
+
+    let files: Vec<IntegrateFile> = parse_ztag_records(&output.stdout_text())
+        .iter()
+        .filter_map(build_integrate_file)
+        .collect();
+
+    // "All revision(s) already integrated" and friends mean an empty preview
+    let stderr = output.stderr_text();
+    let messages: Vec<String> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && is_integrate_error(line))
+        .map(str::to_string)
+        .collect();
+
+    // Per-file messages name a depot path; anything else (login, unknown stream, ...)
+    // fails the preview when nothing could be mapped
+    if files.is_empty() && messages.iter().any(|m| !m.starts_with("//")) {
+        return Err(P4Error::from_stderr(&messages.join("\n"), output.exit_code));
+    }
+
+    Ok(IntegratePreview { files, messages })
+}
+
+/// Run p4 merge / copy / integrate, streaming the opened files in batches.
+/// Files left needing resolve show up in p4_fstat_unresolved once Complete arrives.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_integrate(
+    options: IntegrateOptions,
+    connection: Option<String>,
+    on_batch: Channel<IntegrateStreamBatch>,
+    state: State<'_, ProcessManager>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let name = options.kind.command();
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    // -ztag rather than -G: records are parsed line by line as they stream in
+    cmd.arg(RecordFormat::Ztag.flag());
+    cmd.args(integrate_args(&options, false)?);
+
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 {}: {}", name, e)))?;
+
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
+
+    // Register process for cancellation
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    let stderr_task = collect_stderr(stderr, encoding, is_integrate_error);
+
+    tokio::spawn(async move {
+        let mut reader = ZtagRecordReader::default();
+        let mut batch: Vec<IntegrateFile> = Vec::new();
+        let mut total_received: u32 = 0;
+
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
+                let Some(file) = reader.push_line(&line).as_ref().and_then(build_integrate_file) else {
+                    continue;
+                };
+                batch.push(file);
+
+                if batch.len() >= 100 {
+                    total_received += batch.len() as u32;
+                    let _ = on_batch.send(IntegrateStreamBatch::Data {
+                        files: std::mem::take(&mut batch),
+                        total_received,
+                    });
+                }
+            }
+        }
+        batch.extend(reader.finish().as_ref().and_then(build_integrate_file));
+
+        if !batch.is_empty() {
+            total_received += batch.len() as u32;
+            let _ = on_batch.send(IntegrateStreamBatch::Data {
+                files: batch,
+                total_received,
+            });
+        }
+
+        let completion = finish_stream(exited, stderr_task).await;
+        let _ = on_batch.send(IntegrateStreamBatch::Complete {
+            total_files: total_received,
+            completion,
+        });
+    });
+
+    Ok(process_id)
 }
 
 /// Detect files needing resolution
 #[tauri::command]
 pub async fn p4_fstat_unresolved(
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<Vec<P4UnresolvedFile>, String> {
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<Vec<P4UnresolvedFile>, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["-ztag", "fstat", "-Ru", "-Or", "//..."]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat -Ru -Or: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat -Ru -Or: {}", e)))?;
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = output.stderr_text();
 
     // Handle "no such file(s)" as empty result, not error
-    if stderr.contains("no such file(s)") {
+    if classify_stderr(&stderr) == P4ErrorKind::NoSuchFile {
         return Ok(Vec::new());
     }
 
     // If stderr has error text but stdout has data, still parse stdout
     // (p4 fstat may emit warnings alongside results)
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout_text();
 
     // Parse -ztag format
     let files = parse_ztag_fstat_unresolved(&stdout)?;
@@ -1975,33 +3300,33 @@ pub async fn p4_fstat_unresolved(
 pub async fn p4_resolve_accept(
     file_path: String,
     mode: String,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<String, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
     // Map mode to p4 flag
     let flag = match mode.as_str() {
         "theirs" => "-at",
         "yours" => "-ay",
         "merge" => "-am",
-        _ => return Err(format!("Invalid mode: {}. Must be 'theirs', 'yours', or 'merge'", mode)),
+        _ => return Err(P4Error::other(format!("Invalid mode: {}. Must be 'theirs', 'yours', or 'merge'", mode))),
     };
 
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(["resolve", flag, &file_path]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 resolve: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 resolve: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout_text();
     Ok(stdout.to_string())
 }
 
@@ -2010,10 +3335,11 @@ pub async fn p4_resolve_accept(
 pub async fn launch_merge_tool(
     depot_path: String,
     local_path: String,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<i32, String> {
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<i32, P4Error> {
+    let conn = connections.get(&connection)?;
     // Step 1: Check P4MERGE env var, fallback to MERGE
     let merge_tool = std::env::var("P4MERGE")
         .or_else(|_| std::env::var("MERGE"))
@@ -2022,24 +3348,22 @@ pub async fn launch_merge_tool(
         })?;
 
     // Step 2: Get base and theirs file info via p4 fstat
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["-ztag", "fstat", &depot_path]);
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "fstat", &depot_path]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4 fstat: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to get file info: {}", stderr));
-    }
+    let records = format
+        .parse_output(&output)
+        .map_err(|e| e.context("Failed to get file info"))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    // Parse ztag output to extract resolve file info
-    let fields = parse_ztag_records(&stdout).into_iter().next().unwrap_or_default();
+    // Extract resolve file info from the fstat record
+    let fields = records.into_iter().next().unwrap_or_default();
 
     // Extract base file and revision
     let base_file = fields
@@ -2073,12 +3397,12 @@ pub async fn launch_merge_tool(
         .map(|ext| format!(".{}", ext))
         .unwrap_or_else(|| ".txt".to_string());
 
-    let base_temp_path = temp_dir.join(format!("p4merge_base_{}_{}", timestamp, base_file.replace('/', "_").replace('\\', "_") + &extension));
-    let theirs_temp_path = temp_dir.join(format!("p4merge_theirs_{}_{}", timestamp, theirs_file.replace('/', "_").replace('\\', "_") + &extension));
+    let base_temp_path = temp_dir.join(format!("p4merge_base_{}_{}", timestamp, base_file.replace(['/', '\\'], "_") + &extension));
+    let theirs_temp_path = temp_dir.join(format!("p4merge_theirs_{}_{}", timestamp, theirs_file.replace(['/', '\\'], "_") + &extension));
 
     // Print base file to temp
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args([
         "print",
         "-q",
@@ -2087,19 +3411,18 @@ pub async fn launch_merge_tool(
         &format!("{}#{}", base_file, base_rev),
     ]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to print base file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to print base file: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to print base file: {}", stderr));
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("Failed to print base file"));
     }
 
     // Print theirs file to temp
-    let mut cmd = create_p4_command();
-    apply_connection_args(&mut cmd, &server, &user, &client);
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args([
         "print",
         "-q",
@@ -2108,14 +3431,13 @@ pub async fn launch_merge_tool(
         &format!("{}#{}", theirs_file, theirs_rev),
     ]);
 
-    let output = cmd
-        .output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to print theirs file: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to print theirs file: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to print theirs file: {}", stderr));
+    if !output.success() {
+        return Err(P4Error::from_output(&output).context("Failed to print theirs file"));
     }
 
     // Step 4: Spawn merge tool with blocking wait
@@ -2132,7 +3454,7 @@ pub async fn launch_merge_tool(
         Ok::<i32, String>(status.code().unwrap_or(-1))
     })
     .await
-    .map_err(|e| format!("Task failed: {}", e))??;
+    .map_err(|e| P4Error::other(format!("Task failed: {}", e)))??;
 
     // Step 5: Clean up temp files (best effort, ignore cleanup errors)
     let _ = std::fs::remove_file(&base_temp_path);
@@ -2140,3 +3462,592 @@ pub async fn launch_merge_tool(
 
     Ok(exit_code)
 }
+
+/// Latest submitted changelist affecting `depot_path` (0 if there are none)
+async fn head_changelist(
+    executor: &dyn P4Executor,
+    depot_path: &str,
+    connection: &ConnectionProfile,
+) -> Result<i64, P4Error> {
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args([format.flag(), "changes", "-m1", "-s", "submitted", depot_path]);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 changes: {}", e)))?;
+
+    let records = format.parse_output(&output)?;
+    Ok(records
+        .first()
+        .and_then(|r| r.get("change"))
+        .and_then(|c| c.parse::<i64>().ok())
+        .unwrap_or(0))
+}
+
+/// Key of the persisted index for `depot_path`. A profile without a server or client
+/// relies on p4's own settings (P4CONFIG, `p4 set`), so ask the server which it is
+/// talking to; otherwise every such profile would share one index file.
+async fn file_index_key(
+    executor: &dyn P4Executor,
+    depot_path: &str,
+    connection: &ConnectionProfile,
+) -> Result<IndexKey, P4Error> {
+    let server = connection.server.clone().filter(|s| !s.is_empty());
+    let client = connection.client.clone().filter(|c| !c.is_empty());
+    let (server, client) = match (server, client) {
+        (Some(server), Some(client)) => (server, client),
+        (server, client) => {
+            let mut cmd = P4Command::new();
+            connection.apply(&mut cmd);
+            cmd.args(["-ztag", "info"]);
+
+            let output = executor
+                .output(&cmd)
+                .await
+                .map_err(|e| P4Error::other(format!("Failed to execute p4 info: {}", e)))?;
+            if !output.success() {
+                return Err(P4Error::from_output(&output).context("p4 info failed"));
+            }
+            let info = parse_ztag_info(&output.stdout_text()).map_err(P4Error::from)?;
+            (
+                server.unwrap_or(info.server_address),
+                client.unwrap_or(info.client_name),
+            )
+        }
+    };
+    Ok(IndexKey {
+        server,
+        client,
+        depot_path: depot_path.to_string(),
+    })
+}
+
+/// Open the persisted file index for a workspace and bring it up to date.
+/// Loads the on-disk index when the workspace changed, then reconciles only the files
+/// changed since its changelist high-water mark instead of rebuilding. When there is no
+/// usable index on disk, the in-memory index is reset and `rebuildRequired` is returned;
+/// the caller repopulates it from a full fstat and then calls save_file_index.
+#[tauri::command]
+pub async fn p4_open_file_index<R: Runtime>(
+    depot_path: String,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<FileIndexStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    let index_state = &conn.file_index;
+    let key = file_index_key(executor.as_ref(), &depot_path, &conn.profile).await?;
+    let index_path = persist::index_dir(&app)
+        .map(|dir| persist::index_path(&dir, &key))
+        .map_err(|e| P4Error::other(format!("Failed to locate file index directory: {}", e)))?;
+
+    // Load from disk unless this workspace's complete index is already in memory
+    let in_memory = {
+        let index = index_state.lock().await;
+        index.key() == Some(&key) && index.changelist().is_some()
+    };
+    if !in_memory {
+        let loaded = persist::load_index(index_path.clone(), key.clone()).await;
+        let mut index = index_state.lock().await;
+        match loaded {
+            Ok(Some(loaded)) => *index = loaded,
+            // Missing, from an older format version, or corrupt: rebuild from scratch
+            Ok(None) | Err(_) => index.reset(key.clone()),
+        }
+    }
+
+    let head = head_changelist(executor.as_ref(), &depot_path, &conn.profile).await?;
+    let since = index_state.lock().await.changelist();
+
+    let since = match since {
+        Some(since) if since == head => {
+            return Ok(FileIndexStatus {
+                rebuild_required: false,
+                changelist: head,
+                file_count: index_state.lock().await.len(),
+                changed_files: 0,
+            });
+        }
+        Some(since) if since < head => since,
+        // No complete index, or the server's history moved backwards
+        _ => {
+            index_state.lock().await.reset(key);
+            return Ok(FileIndexStatus {
+                rebuild_required: true,
+                changelist: head,
+                file_count: 0,
+                changed_files: 0,
+            });
+        }
+    };
+
+    // Files with revisions submitted after the high-water mark
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "fstat", "-T", "depotFile,headAction,headModTime"]);
+    cmd.arg(format!("{}@{},@{}", depot_path, since + 1, head));
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
+
+    let records = match format.parse_output(&output) {
+        Ok(records) => records,
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    let (updated, removed) = index_changes_from_records(records);
+    let changed_files = updated.len() + removed.len();
+
+    let file_count = {
+        let mut index = index_state.lock().await;
+        index.apply_changes(updated, &removed);
+        index.set_changelist(head);
+        index.len()
+    };
+
+    persist::save_index(index_state, index_path)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to save file index: {}", e)))?;
+
+    Ok(FileIndexStatus {
+        rebuild_required: false,
+        changelist: head,
+        file_count,
+        changed_files,
+    })
+}
+
+/// Search file contents on the server with `p4 grep`, streaming matching lines via Channel.
+/// Searches `paths`, or `depot_path` (default "//...") when none are given.
+/// Returns the process ID for cancellation.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_grep(
+    options: GrepOptions,
+    paths: Vec<String>,
+    depot_path: Option<String>,
+    connection: Option<String>,
+    on_batch: Channel<GrepStreamBatch>,
+    state: State<'_, ProcessManager>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    if options.pattern.is_empty() {
+        return Err(P4Error::other("No search pattern provided"));
+    }
+
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+
+    cmd.args(["grep", "-n"]);
+    if !options.case_sensitive {
+        cmd.arg("-i");
+    }
+    if !options.regex {
+        cmd.arg("-F");
+    }
+    if options.all_revisions {
+        cmd.arg("-a");
+    }
+    if let Some(context) = options.context.filter(|c| *c > 0) {
+        cmd.args(["-C", &context.to_string()]);
+    }
+    cmd.args(["-e", &options.pattern]);
+
+    let paths = if paths.is_empty() {
+        vec![depot_path.unwrap_or_else(|| "//...".to_string())]
+    } else {
+        paths
+    };
+    let revision_range = options.revision_range.unwrap_or_default();
+    for path in &paths {
+        cmd.arg(format!("{}{}", path, revision_range));
+    }
+
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 grep: {}", e)))?;
+
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
+
+    // Register process for cancellation
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    // Paths with no (text) files are not an error for a search
+    let stderr_task = collect_stderr(stderr, encoding, is_query_error);
+
+    tokio::spawn(async move {
+        let mut batch: Vec<GrepMatch> = Vec::new();
+        let mut total_received: u32 = 0;
+        let mut total_matches: u32 = 0;
+
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
+                let Some(grep_match) = parse_grep_line(&line) else {
+                    continue;
+                };
+                if !grep_match.is_context {
+                    total_matches += 1;
+                }
+                batch.push(grep_match);
+
+                if batch.len() >= 100 {
+                    total_received += batch.len() as u32;
+                    let _ = on_batch.send(GrepStreamBatch::Data {
+                        matches: std::mem::take(&mut batch),
+                        total_received,
+                    });
+                }
+            }
+        }
+
+        if !batch.is_empty() {
+            total_received += batch.len() as u32;
+            let _ = on_batch.send(GrepStreamBatch::Data {
+                matches: batch,
+                total_received,
+            });
+        }
+
+        let completion = finish_stream(exited, stderr_task).await;
+        let _ = on_batch.send(GrepStreamBatch::Complete {
+            total_matches,
+            completion,
+        });
+    });
+
+    Ok(process_id)
+}
+
+/// Build the local content index from the have-revision text files under `depot_path`.
+/// Replaces any previous index; search it with search_content_index.
+#[tauri::command]
+pub async fn p4_build_content_index(
+    depot_path: String,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<ContentIndexStatus, P4Error> {
+    let conn = connections.get(&connection)?;
+    // -Rh: only files synced into this workspace
+    let format = RecordFormat::Marshal;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args([format.flag(), "fstat", "-Rh", "-T", "depotFile,path,clientFile,haveRev,headType"]);
+    cmd.arg(&depot_path);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
+
+    let records = match format.parse_output(&output) {
+        Ok(records) => records,
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    let docs = content_docs_from_records(records);
+
+    let (index, skipped_count) = tokio::task::spawn_blocking(move || ContentIndex::build(docs))
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to build content index: {}", e)))?;
+
+    let status = ContentIndexStatus {
+        file_count: index.len(),
+        skipped_count,
+        trigram_count: index.trigram_count(),
+    };
+    *conn.content_index.lock().await = Arc::new(index);
+
+    Ok(status)
+}
+
+/// Diff two file versions in-process and return structured hunks
+#[tauri::command]
+pub async fn p4_diff(
+    left: DiffSource,
+    right: DiffSource,
+    options: Option<DiffOptions>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<TextDiff, P4Error> {
+    let conn = connections.get(&connection)?;
+    let connection = &conn.profile;
+    let (old, new) = tokio::try_join!(
+        read_diff_source(executor.as_ref(), &left, connection),
+        read_diff_source(executor.as_ref(), &right, connection),
+    )?;
+    let (Some(old), Some(new)) = (old, new) else {
+        return Ok(TextDiff {
+            binary: true,
+            ..TextDiff::default()
+        });
+    };
+    let options = DiffOptions {
+        encoding: connection.file_encoding(),
+        ..options.unwrap_or_default()
+    };
+    tokio::task::spawn_blocking(move || diff_text(&old, &new, &options))
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to diff files: {}", e)))
+}
+
+/// Raw content of one side of a diff, limited to MAX_CONTENT_SIZE.
+/// None for a depot revision of a binary file type, which is not printed.
+async fn read_diff_source(
+    executor: &dyn P4Executor,
+    source: &DiffSource,
+    connection: &ConnectionProfile,
+) -> Result<Option<Vec<u8>>, P4Error> {
+    let too_large = |size: u64| {
+        P4Error::other(format!(
+            "File too large to diff: {:.1}MB (maximum: {}MB)",
+            size as f64 / 1024.0 / 1024.0,
+            MAX_CONTENT_SIZE / 1024 / 1024
+        ))
+    };
+
+    let file_spec = match source {
+        DiffSource::Local { path } => {
+            let metadata = tokio::fs::metadata(path)
+                .await
+                .map_err(|e| P4Error::other(format!("Failed to read {}: {}", path, e)))?;
+            if metadata.len() > MAX_CONTENT_SIZE {
+                return Err(too_large(metadata.len()));
+            }
+            return tokio::fs::read(path)
+                .await
+                .map(Some)
+                .map_err(|e| P4Error::other(format!("Failed to read {}: {}", path, e)));
+        }
+        DiffSource::Depot {
+            depot_path,
+            revision,
+            revision_spec,
+        } => match revision_spec.as_deref().filter(|s| !s.trim().is_empty()) {
+            Some(spec) => with_revision_spec(depot_path, Some(spec))?,
+            None => format!("{}#{}", depot_path, revision),
+        },
+        DiffSource::Shelved { depot_path, changelist } => format!("{}@={}", depot_path, changelist),
+    };
+
+    // Check type and size with fstat first so large or binary revisions are never printed
+    let format = RecordFormat::Marshal;
+    let mut fstat_cmd = P4Command::new();
+    connection.apply(&mut fstat_cmd);
+    fstat_cmd.args([format.flag(), "fstat", "-Ol"]);
+    fstat_cmd.arg(&file_spec);
+
+    let fstat_output = executor
+        .output(&fstat_cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
+    let records = format.parse_output(&fstat_output)?;
+    let Some(file_info) = records.first() else {
+        return Err(P4Error::other("File not found"));
+    };
+    // A shelved record's type is the shelved revision's; headType is the depot head's
+    let file_type = match source {
+        DiffSource::Shelved { .. } => file_info.get("type").or_else(|| file_info.get("headType")),
+        _ => file_info.get("headType"),
+    };
+    if file_type.is_some_and(|t| !is_text_file_type(t)) {
+        return Ok(None);
+    }
+    if let Some(file_size) = file_info.get("fileSize").and_then(|s| s.parse::<u64>().ok()) {
+        if file_size > MAX_CONTENT_SIZE {
+            return Err(too_large(file_size));
+        }
+    }
+
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args(["print", "-q"]);
+    cmd.arg(&file_spec);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 print: {}", e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
+    }
+    // print -q reports a missing revision on stderr but still exits 0
+    if output.stdout.is_empty() && !output.stderr.is_empty() {
+        return Err(P4Error::from_output(&output));
+    }
+    if output.stdout.len() as u64 > MAX_CONTENT_SIZE {
+        return Err(too_large(output.stdout.len() as u64));
+    }
+    Ok(Some(output.stdout))
+}
+
+/// Base file types (including the legacy names p4 still reports) whose content isn't text
+const NON_TEXT_FILE_TYPES: &[&str] = &[
+    "binary", "xbinary", "ubinary", "uxbinary", "tempobj", "ctempobj", "xtempobj",
+    "apple", "resource", "uresource", "symlink",
+];
+
+/// Whether a p4 file type (e.g. "text+k", "binary+F") holds text that can be diffed
+fn is_text_file_type(file_type: &str) -> bool {
+    let base = file_type.split('+').next().unwrap_or_default();
+    !NON_TEXT_FILE_TYPES.contains(&base)
+}
+
+/// Unified diffs of every file in a changelist, streamed via Channel as they are parsed.
+/// Submitted and shelved changes come from p4 describe -du; pending changes run p4 diff -du
+/// on the files opened in them. Each file's diff text is limited to MAX_CONTENT_SIZE.
+/// Returns the process ID for cancellation.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn p4_changelist_diff(
+    changelist: i32,
+    source: ChangeDiffSource,
+    connection: Option<String>,
+    on_batch: Channel<ChangeDiffBatch>,
+    state: State<'_, ProcessManager>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    let mut actions = std::collections::HashMap::new();
+    // File list for a pending diff; kept until the process has finished
+    let mut path_list = None;
+
+    match source {
+        ChangeDiffSource::Submitted => {
+            cmd.args(["describe", "-du", &changelist.to_string()]);
+        }
+        ChangeDiffSource::Shelved => {
+            cmd.args(["describe", "-du", "-S", &changelist.to_string()]);
+        }
+        ChangeDiffSource::Pending => {
+            let change = if changelist == 0 {
+                "default".to_string()
+            } else {
+                changelist.to_string()
+            };
+            let format = RecordFormat::Marshal;
+            let mut opened = P4Command::new();
+            conn.profile.apply(&mut opened);
+            opened.args([format.flag(), "fstat", "-Ro", "-e", &change, "-T", "depotFile,action", "//..."]);
+
+            let output = executor
+                .output(&opened)
+                .await
+                .map_err(|e| P4Error::other(format!("Failed to execute p4 fstat: {}", e)))?;
+            let records = match format.parse_output(&output) {
+                Ok(records) => records,
+                Err(err) if err.is(P4ErrorKind::NoSuchFile) => Vec::new(),
+                Err(err) => return Err(err),
+            };
+            for mut record in records {
+                if let (Some(depot_path), Some(action)) = (record.remove("depotFile"), record.remove("action")) {
+                    actions.insert(depot_path, action);
+                }
+            }
+            if actions.is_empty() {
+                return Err(P4Error::other(format!("No files opened in changelist {}", change)));
+            }
+
+            // Pass the paths with -x: a large changelist would exceed the command line limit
+            let mut paths: Vec<&String> = actions.keys().collect();
+            paths.sort();
+            let mut temp_file = Builder::new()
+                .prefix("p4_diff_")
+                .suffix(".txt")
+                .tempfile()
+                .map_err(|e| P4Error::other(format!("Failed to create temp file: {}", e)))?;
+            // p4 reads the file in the command's charset, like the paths it prints
+            let list: String = paths.iter().map(|path| format!("{}\n", path)).collect();
+            use std::io::Write;
+            temp_file
+                .write_all(&encode_text(&list, cmd.output_encoding()))
+                .map_err(|e| P4Error::other(format!("Failed to write to temp file: {}", e)))?;
+            temp_file
+                .flush()
+                .map_err(|e| P4Error::other(format!("Failed to flush temp file: {}", e)))?;
+
+            cmd.arg("-x");
+            cmd.arg(temp_file.path().to_string_lossy());
+            cmd.args(["diff", "-du"]);
+            path_list = Some(temp_file);
+        }
+    }
+
+    let command = match source {
+        ChangeDiffSource::Pending => "diff",
+        ChangeDiffSource::Submitted | ChangeDiffSource::Shelved => "describe",
+    };
+    let mut process = executor
+        .spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4 {}: {}", command, e)))?;
+
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
+
+    // Register process for cancellation
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection.clone(), None).await;
+
+    // Files that can't be diffed (e.g. opened for add) are reported but not a failure
+    let stderr_task = collect_stderr(stderr, encoding, is_query_error);
+
+    tokio::spawn(async move {
+        let mut parser = ChangeDiffParser::new(actions, MAX_CONTENT_SIZE);
+        let mut batch: Vec<P4FileDiff> = Vec::new();
+        let mut total_received: u32 = 0;
+
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
+                if let Some(file) = parser.push_line(&line) {
+                    batch.push(file);
+                }
+
+                if batch.len() >= 20 {
+                    total_received += batch.len() as u32;
+                    let _ = on_batch.send(ChangeDiffBatch::Data {
+                        files: std::mem::take(&mut batch),
+                        total_received,
+                    });
+                }
+            }
+        }
+        batch.extend(parser.finish());
+
+        if !batch.is_empty() {
+            total_received += batch.len() as u32;
+            let _ = on_batch.send(ChangeDiffBatch::Data {
+                files: batch,
+                total_received,
+            });
+        }
+
+        let completion = finish_stream(exited, stderr_task).await;
+        drop(path_list);
+        let _ = on_batch.send(ChangeDiffBatch::Complete {
+            total_files: total_received,
+            completion,
+        });
+    });
+
+    Ok(process_id)
+}
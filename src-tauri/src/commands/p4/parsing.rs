@@ -1,72 +1,139 @@
 use std::collections::HashMap;
 
+use crate::content_index::{is_text_type, ContentDoc};
+use crate::diff::{DiffHunk, DiffLine, DiffLineKind};
+use crate::file_index::FileEntry;
+use crate::state::{ConnectionProfile, P4Command, P4Executor, P4Output};
+use super::error::{classify_stderr, P4Error, P4ErrorKind, SpecFieldError};
+use super::marshal::decode_marshal_records;
+use super::spec::{fetch_spec_form, form_field_name, submit_spec_form, SpecForm};
 use super::types::*;
 
-// Windows-specific import for hiding console windows
-#[cfg(target_os = "windows")]
-#[allow(unused_imports)] // Trait is used via creation_flags() method
-use std::os::windows::process::CommandExt;
-
-/// Apply optional connection args to a p4 Command.
-/// When explicit args are provided, overrides P4 environment variables
-/// and clears P4CONFIG to ensure complete isolation from DVCS/local config.
-pub(super) fn apply_connection_args(
-    cmd: &mut tokio::process::Command,
-    server: &Option<String>,
-    user: &Option<String>,
-    client: &Option<String>,
-) {
-    let has_explicit = server.as_ref().is_some_and(|s| !s.is_empty())
-        || user.as_ref().is_some_and(|s| !s.is_empty())
-        || client.as_ref().is_some_and(|s| !s.is_empty());
+/// Tagged output format requested from p4, selectable per command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RecordFormat {
+    /// `-ztag` text: `... key value` lines. Streams line by line, but drops the
+    /// continuation lines of multi-line values such as descriptions.
+    Ztag,
+    /// `-G` Python marshal: binary-safe and keeps multi-line values intact.
+    Marshal,
+}
 
-    if has_explicit {
-        // Clear P4CONFIG and P4ROOT to prevent DVCS/local config from interfering
-        cmd.env("P4CONFIG", "");
-        cmd.env("P4ROOT", "");
+impl RecordFormat {
+    /// Global p4 flag selecting this format
+    pub(super) fn flag(self) -> &'static str {
+        match self {
+            RecordFormat::Ztag => "-ztag",
+            RecordFormat::Marshal => "-G",
+        }
     }
 
-    if let Some(s) = server.as_ref().filter(|s| !s.is_empty()) {
-        cmd.args(["-p", s]);
-        // Also set env var to ensure override even if P4PORT is set in environment
-        cmd.env("P4PORT", s);
+    /// Parse a finished command's output into records.
+    /// Fails only when p4 reported an error and returned no records, so commands that
+    /// report per-file errors alongside results (like fstat) keep their partial output.
+    pub(super) fn parse_output(self, output: &P4Output) -> Result<Vec<HashMap<String, String>>, P4Error> {
+        match self {
+            RecordFormat::Ztag => {
+                let records = parse_ztag_records(&output.stdout_text());
+                if records.is_empty() && !output.success() {
+                    return Err(P4Error::from_output(output));
+                }
+                Ok(records)
+            }
+            RecordFormat::Marshal => parse_marshal_output(output),
+        }
     }
-    if let Some(u) = user.as_ref().filter(|s| !s.is_empty()) {
-        cmd.args(["-u", u]);
-        cmd.env("P4USER", u);
+}
+
+/// Data records and error messages from `p4 -G` output
+type MarshalOutput = (Vec<HashMap<String, String>>, Vec<String>);
+
+/// Parse `p4 -G` output, failing when p4 reported an error and returned no records.
+fn parse_marshal_output(output: &P4Output) -> Result<Vec<HashMap<String, String>>, P4Error> {
+    let (records, errors) = parse_marshal_with_errors(output)?;
+
+    if records.is_empty() {
+        if !errors.is_empty() {
+            let exit_code = output.exit_code.filter(|c| *c != 0).or(Some(1));
+            return Err(P4Error::from_stderr(&errors.join("\n"), exit_code));
+        }
+        if !output.success() {
+            return Err(P4Error::from_output(output));
+        }
     }
-    if let Some(c) = client.as_ref().filter(|s| !s.is_empty()) {
-        cmd.args(["-c", c]);
-        cmd.env("P4CLIENT", c);
+
+    Ok(records)
+}
+
+/// Split `p4 -G` output into data records and error messages.
+/// With -G, p4 reports errors on stdout as `code: error` dictionaries rather than on stderr.
+/// Only undecodable output fails, so per-file errors can be reported next to successes.
+pub(super) fn parse_marshal_with_errors(output: &P4Output) -> Result<MarshalOutput, P4Error> {
+    let decoded = match decode_marshal_records(&output.stdout, output.encoding) {
+        Ok(decoded) => decoded,
+        Err(_) if !output.success() => return Err(P4Error::from_output(output)),
+        Err(e) => return Err(P4Error::other(format!("Failed to parse p4 -G output: {}", e))),
+    };
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    for mut record in decoded {
+        let code = record.remove("code");
+        if code.as_ref().and_then(|c| c.as_str()) == Some("error") {
+            if let Some(data) = record.get("data") {
+                errors.push(data.to_field_string().trim_end().to_string());
+            }
+            continue;
+        }
+        records.push(
+            record
+                .into_iter()
+                .map(|(k, v)| (k, v.to_field_string()))
+                .collect(),
+        );
     }
+
+    Ok((records, errors))
 }
 
 /// Parse p4 -ztag output into a vector of records (key-value HashMaps).
 /// Each record is separated by a blank line. Fields are in "... key value" format.
 pub(super) fn parse_ztag_records(output: &str) -> Vec<HashMap<String, String>> {
-    let mut records = Vec::new();
-    let mut current: HashMap<String, String> = HashMap::new();
+    let mut reader = ZtagRecordReader::default();
+    let mut records: Vec<_> = output.lines().filter_map(|line| reader.push_line(line)).collect();
+    records.extend(reader.finish());
+    records
+}
 
-    for line in output.lines() {
+/// Incremental -ztag parser for output read line by line from a running process
+#[derive(Default)]
+pub(super) struct ZtagRecordReader {
+    current: HashMap<String, String>,
+}
+
+impl ZtagRecordReader {
+    /// Feed one line; returns the record it completes, if any
+    pub(super) fn push_line(&mut self, line: &str) -> Option<HashMap<String, String>> {
         let line = line.trim();
+        // Blank line = end of record
         if line.is_empty() {
-            if !current.is_empty() {
-                records.push(std::mem::take(&mut current));
-            }
-            continue;
+            return (!self.current.is_empty()).then(|| std::mem::take(&mut self.current));
         }
         if let Some(stripped) = line.strip_prefix("... ") {
             if let Some((key, value)) = stripped.split_once(' ') {
-                current.insert(key.to_string(), value.to_string());
+                self.current.insert(key.to_string(), value.to_string());
             } else {
-                current.insert(stripped.to_string(), String::new());
+                // Key with no value (e.g., "... isMapped")
+                self.current.insert(stripped.to_string(), String::new());
             }
         }
+        None
     }
-    if !current.is_empty() {
-        records.push(current);
+
+    /// The last record, when output didn't end with a blank line
+    pub(super) fn finish(self) -> Option<HashMap<String, String>> {
+        (!self.current.is_empty()).then_some(self.current)
     }
-    records
 }
 
 /// Parse p4 -ztag info output into P4ClientInfo
@@ -85,16 +152,54 @@ pub(super) fn parse_ztag_info(output: &str) -> Result<P4ClientInfo, String> {
         client_stream: fields.get("clientStream").cloned(),
         user_name: fields.get("userName").cloned().unwrap_or_default(),
         server_address: fields.get("serverAddress").cloned().unwrap_or_default(),
+        server_unicode: fields.get("unicode").is_some_and(|v| v == "enabled"),
+        charset: None,
     })
 }
 
-/// Parse p4 -ztag fstat output into P4FileInfo structs
-pub(super) fn parse_ztag_fstat(output: &str) -> Result<Vec<P4FileInfo>, String> {
-    let files = parse_ztag_records(output)
+/// Value of `name` in `p4 set -q` output ("P4CHARSET=utf8"); None when unset
+pub(super) fn parse_p4_set_value(output: &str, name: &str) -> Option<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Parse p4 -ztag login -s. Servers without tickets print no record; the user is logged in.
+pub(super) fn parse_login_status(output: &str) -> P4LoginStatus {
+    let fields = parse_ztag_records(output).into_iter().next().unwrap_or_default();
+    P4LoginStatus {
+        logged_in: true,
+        user: fields.get("User").cloned(),
+        expires_in: fields.get("TicketExpiration").and_then(|t| t.parse().ok()),
+    }
+}
+
+/// Parse the output of p4 trust -n, which reports the server's fingerprint
+/// instead of prompting when it isn't trusted
+pub(super) fn parse_trust_status(output: &str) -> P4TrustStatus {
+    let fingerprint = output.lines().map(str::trim).find(|l| is_fingerprint(l)).map(str::to_string);
+    P4TrustStatus {
+        trusted: fingerprint.is_none() || output.to_lowercase().contains("trust already established"),
+        changed: output.to_lowercase().contains("identification has changed"),
+        fingerprint,
+    }
+}
+
+/// SSL fingerprint: colon-separated hex byte pairs ("AB:CD:...")
+pub(super) fn is_fingerprint(value: &str) -> bool {
+    let pairs: Vec<&str> = value.split(':').collect();
+    pairs.len() >= 2 && pairs.iter().all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Build P4FileInfo structs from fstat records
+pub(super) fn file_infos_from_records(records: Vec<HashMap<String, String>>) -> Vec<P4FileInfo> {
+    records
         .into_iter()
         .filter_map(|record| build_file_info(&record))
-        .collect();
-    Ok(files)
+        .collect()
 }
 
 /// Build P4FileInfo from parsed ztag fields
@@ -139,6 +244,15 @@ pub(super) fn build_file_info(fields: &HashMap<String, String>) -> Option<P4File
     // Derive status from fields
     let status = derive_file_status(&action, revision, head_revision);
 
+    let other_open = other_opens(fields);
+    let other_lock = fields.get("otherLock0").map(|holder| {
+        other_open
+            .iter()
+            .find(|open| open_holder(open) == *holder)
+            .cloned()
+            .unwrap_or_else(|| other_open_from(holder, None, None))
+    });
+
     Some(P4FileInfo {
         depot_path,
         local_path,
@@ -150,9 +264,90 @@ pub(super) fn build_file_info(fields: &HashMap<String, String>) -> Option<P4File
         file_type,
         head_action,
         head_mod_time,
+        other_open,
+        other_lock,
+        our_lock: fields.contains_key("ourLock"),
     })
 }
 
+/// Opens in other workspaces: fstat reports otherOpen<N> as "user@workspace"
+/// with matching otherAction<N> and otherChange<N>
+fn other_opens(fields: &HashMap<String, String>) -> Vec<P4OtherOpen> {
+    (0..)
+        .map_while(|i| {
+            let holder = fields.get(&format!("otherOpen{}", i))?;
+            Some(other_open_from(
+                holder,
+                fields.get(&format!("otherAction{}", i)).cloned(),
+                fields.get(&format!("otherChange{}", i)).cloned(),
+            ))
+        })
+        .collect()
+}
+
+fn other_open_from(holder: &str, action: Option<String>, changelist: Option<String>) -> P4OtherOpen {
+    let (user, client) = holder.split_once('@').unwrap_or((holder, ""));
+    P4OtherOpen {
+        user: user.to_string(),
+        client: client.to_string(),
+        action,
+        changelist,
+    }
+}
+
+/// "user@workspace", as p4 writes it
+pub(super) fn open_holder(open: &P4OtherOpen) -> String {
+    format!("{}@{}", open.user, open.client)
+}
+
+/// Whether a file type has the exclusive-open (+l) modifier, e.g. "binary+l" or "text+lw"
+pub(super) fn is_exclusive_type(file_type: &str) -> bool {
+    file_type
+        .split_once('+')
+        .is_some_and(|(_, modifiers)| modifiers.contains('l'))
+}
+
+/// Files that can't be opened because they are exclusive (+l) and already open in
+/// another workspace, each described with the workspace that has it
+pub(super) fn exclusive_open_conflicts(files: &[P4FileInfo]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| file.action.is_none() && is_exclusive_type(&file.file_type))
+        .filter_map(|file| {
+            let holder = file.other_lock.as_ref().or(file.other_open.first())?;
+            Some(format!(
+                "{} is exclusively opened by {}",
+                file.depot_path,
+                open_holder(holder)
+            ))
+        })
+        .collect()
+}
+
+/// Files opened in any workspace from p4 -G opened -a records
+pub(super) fn opened_files_from_records(records: &[HashMap<String, String>]) -> Vec<P4OpenedFile> {
+    records
+        .iter()
+        .filter_map(|record| {
+            let file_type = record.get("type").cloned();
+            Some(P4OpenedFile {
+                depot_path: record.get("depotFile")?.clone(),
+                user: record.get("user").cloned().unwrap_or_default(),
+                client: record.get("client").cloned().unwrap_or_default(),
+                changelist: record
+                    .get("change")
+                    .cloned()
+                    .unwrap_or_else(|| "default".to_string()),
+                action: record.get("action").cloned().unwrap_or_default(),
+                revision: record.get("rev").and_then(|rev| rev.parse().ok()),
+                locked: record.contains_key("ourLock") || record.contains_key("otherLock"),
+                exclusive: file_type.as_deref().is_some_and(is_exclusive_type),
+                file_type,
+            })
+        })
+        .collect()
+}
+
 /// Derive file status from p4 fields
 pub(super) fn derive_file_status(action: &Option<String>, have_rev: i32, head_rev: i32) -> String {
     if let Some(act) = action {
@@ -175,13 +370,12 @@ pub(super) fn derive_file_status(action: &Option<String>, have_rev: i32, head_re
     }
 }
 
-/// Parse p4 -ztag changes output into P4Changelist structs
-pub(super) fn parse_ztag_changes(output: &str) -> Result<Vec<P4Changelist>, String> {
-    let changelists = parse_ztag_records(output)
+/// Build P4Changelist structs from changes records
+pub(super) fn changelists_from_records(records: Vec<HashMap<String, String>>) -> Vec<P4Changelist> {
+    records
         .into_iter()
         .filter_map(|record| build_changelist(&record))
-        .collect();
-    Ok(changelists)
+        .collect()
 }
 
 /// Build P4Changelist from parsed ztag fields
@@ -257,6 +451,30 @@ pub(super) fn parse_sync_line(line: &str) -> Option<SyncProgress> {
     None
 }
 
+/// Parse a line of `p4 grep -n` output.
+/// Matches are "//depot/path#rev:line:text" and context lines "//depot/path#rev-line-text";
+/// "--" separators between context groups yield None.
+pub(super) fn parse_grep_line(line: &str) -> Option<GrepMatch> {
+    let (depot_path, rest) = line.split_once('#')?;
+    let rev_end = rest.find(|c: char| !c.is_ascii_digit())?;
+    let revision = rest[..rev_end].parse::<i32>().ok()?;
+
+    let rest = &rest[rev_end..];
+    let separator = rest.chars().next()?;
+    if separator != ':' && separator != '-' {
+        return None;
+    }
+    let (line_number, text) = rest[1..].split_once(separator)?;
+
+    Some(GrepMatch {
+        depot_path: depot_path.to_string(),
+        revision,
+        line_number: line_number.parse().ok()?,
+        line: text.to_string(),
+        is_context: separator == '-',
+    })
+}
+
 /// Parse p4 -ztag clients output into P4Workspace structs
 pub(super) fn parse_ztag_clients(output: &str) -> Result<Vec<P4Workspace>, String> {
     let workspaces = parse_ztag_records(output)
@@ -284,11 +502,10 @@ fn build_workspace(fields: &HashMap<String, String>) -> Option<P4Workspace> {
     })
 }
 
-/// Parse p4 -ztag filelog output into P4Revision structs
-/// Note: p4 filelog -ztag produces indexed fields like rev0, change0, action0, etc.
+/// Build P4Revision structs from filelog records
+/// Note: p4 filelog produces indexed fields like rev0, change0, action0, etc.
 /// all in a single record
-pub(super) fn parse_ztag_filelog(output: &str) -> Result<Vec<P4Revision>, String> {
-    let records = parse_ztag_records(output);
+pub(super) fn revisions_from_records(records: Vec<HashMap<String, String>>) -> Vec<P4Revision> {
     let fields = records.into_iter().next().unwrap_or_default();
 
     // Extract revisions by index
@@ -342,7 +559,7 @@ pub(super) fn parse_ztag_filelog(output: &str) -> Result<Vec<P4Revision>, String
         }
     }
 
-    Ok(revisions)
+    revisions
 }
 
 /// Parse p4 annotate -u -c output into P4AnnotationLine structs
@@ -385,60 +602,25 @@ pub(super) fn parse_annotate_output(output: &str) -> Result<Vec<P4AnnotationLine
     Ok(annotations)
 }
 
-/// Parse p4 -ztag describe -S output into P4ShelvedFile structs
+/// Build P4ShelvedFile structs from describe -S records for a single changelist
 /// Similar to filelog parsing - uses indexed fields (depotFile0, action0, type0, rev0, etc.)
-pub(super) fn parse_ztag_describe_shelved(output: &str) -> Result<Vec<P4ShelvedFile>, String> {
-    let records = parse_ztag_records(output);
-    // p4 describe output can split into multiple records due to blank lines in description fields.
-    // Merge all records into one since they all describe the same changelist.
+pub(super) fn shelved_files_from_records(records: Vec<HashMap<String, String>>) -> Vec<P4ShelvedFile> {
+    // p4 describe -ztag output can split into multiple records due to blank lines in description
+    // fields. Merge all records into one since they all describe the same changelist.
     let mut fields = HashMap::new();
     for record in records {
         fields.extend(record);
     }
 
-    // Extract shelved files by index
-    let mut files = Vec::new();
-    let mut index = 0;
-
-    loop {
-        let depot_file_key = format!("depotFile{}", index);
-        let action_key = format!("action{}", index);
-        let type_key = format!("type{}", index);
-        let rev_key = format!("rev{}", index);
-
-        // Check if this index exists
-        if let Some(depot_path) = fields.get(&depot_file_key) {
-            let action = fields.get(&action_key).cloned().unwrap_or_default();
-            let file_type = fields
-                .get(&type_key)
-                .cloned()
-                .unwrap_or_else(|| "text".to_string());
-            let revision = fields
-                .get(&rev_key)
-                .and_then(|s| s.parse::<i32>().ok())
-                .unwrap_or(0);
-
-            files.push(P4ShelvedFile {
-                depot_path: depot_path.clone(),
-                action,
-                file_type,
-                revision,
-            });
-
-            index += 1;
-        } else {
-            break;
-        }
-    }
-
-    Ok(files)
+    extract_shelved_files_from_fields(&fields)
 }
 
-/// Parse p4 -ztag describe -S output for multiple changelists into HashMap
+/// Group describe -S records for multiple changelists into shelved files per CL
 /// Tracks changelist context via "change" field, extracts shelved files per CL
 /// Field indices (depotFile0, depotFile1, etc.) reset per changelist
-pub(super) fn parse_describe_shelved_batch(output: &str) -> HashMap<i32, Vec<P4ShelvedFile>> {
-    let records = parse_ztag_records(output);
+pub(super) fn shelved_batch_from_records(
+    records: Vec<HashMap<String, String>>,
+) -> HashMap<i32, Vec<P4ShelvedFile>> {
     let mut results: HashMap<i32, Vec<P4ShelvedFile>> = HashMap::new();
     let mut current_cl_id: Option<i32> = None;
     let mut current_fields: HashMap<String, String> = HashMap::new();
@@ -507,12 +689,13 @@ fn extract_shelved_files_from_fields(fields: &HashMap<String, String>) -> Vec<P4
     files
 }
 
-/// Parse p4 describe -ztag output into P4ChangelistDescription
-/// The output contains numbered fields for each file:
+/// Build P4ChangelistDescription from describe records
+/// Records contain numbered fields for each file:
 /// depotFile0, rev0, action0, type0, depotFile1, rev1, action1, type1, ...
-pub(super) fn parse_describe_output(output: &str, changelist_id: i32) -> Result<P4ChangelistDescription, String> {
-    let records = parse_ztag_records(output);
-
+pub(super) fn describe_from_records(
+    records: Vec<HashMap<String, String>>,
+    changelist_id: i32,
+) -> Result<P4ChangelistDescription, String> {
     if records.is_empty() {
         return Err(format!("No data returned for changelist {}", changelist_id));
     }
@@ -562,6 +745,17 @@ pub(super) fn parse_describe_output(output: &str, changelist_id: i32) -> Result<
         }
     }
 
+    // Jobs fixed by the change: job0/jobstat0, job1/jobstat1, ...
+    let jobs = (0..)
+        .map_while(|i| {
+            let job = record.get(&format!("job{}", i))?;
+            Some(P4FixedJob {
+                job: job.clone(),
+                status: record.get(&format!("jobstat{}", i)).cloned().unwrap_or_default(),
+            })
+        })
+        .collect();
+
     Ok(P4ChangelistDescription {
         id: changelist_id,
         user,
@@ -570,6 +764,7 @@ pub(super) fn parse_describe_output(output: &str, changelist_id: i32) -> Result<
         description,
         status,
         files,
+        jobs,
     })
 }
 
@@ -608,6 +803,92 @@ pub(super) fn parse_reconcile_output(output: &str) -> Result<Vec<ReconcilePrevie
     Ok(previews)
 }
 
+/// Whether a local path contains characters p4 reads as wildcards or revision specifiers.
+/// `p4 add` only accepts such files with -f.
+pub(super) fn has_p4_special_chars(path: &str) -> bool {
+    path.contains(['@', '#', '%', '*'])
+}
+
+/// Escape a file path so `%`, `@`, `#` and `*` in its name are taken literally rather than
+/// as escapes, revision specifiers or wildcards. Depot paths arrive from p4 already escaped,
+/// so their `%` sequences are kept; p4 never leaves the other three unescaped in them.
+pub(super) fn escape_special_chars(path: &str) -> String {
+    let path = if path.starts_with("//") {
+        path.to_string()
+    } else {
+        path.replace('%', "%25")
+    };
+    path.replace('@', "%40").replace('#', "%23").replace('*', "%2A")
+}
+
+/// Per-file results of `p4 -G add`, `delete` or `move` from its records and error messages.
+/// Also returns the errors that don't name a file (connection, login, ...).
+/// Untagged successes (`lock`, `unlock` on some servers) arrive as info messages
+/// such as "//depot/a.psd#3 - locking" and count for the file they name.
+pub(super) fn file_op_results(
+    records: Vec<HashMap<String, String>>,
+    errors: Vec<String>,
+) -> (Vec<P4FileOpResult>, Vec<String>) {
+    let mut results: Vec<P4FileOpResult> = records
+        .into_iter()
+        .filter_map(|mut record| {
+            let depot_path = match record.remove("depotFile") {
+                Some(depot_path) => depot_path,
+                None => {
+                    let (path, _) = record.get("data")?.split_once(" - ")?;
+                    without_revision(path.trim()).to_string()
+                }
+            };
+            Some(P4FileOpResult {
+                path: depot_path.clone(),
+                depot_path: Some(depot_path),
+                local_path: record.remove("clientFile"),
+                action: record.remove("action"),
+                file_type: record.remove("type"),
+                // Opened files report workRev; tag and labelsync report rev
+                revision: record
+                    .get("workRev")
+                    .or_else(|| record.get("rev"))
+                    .and_then(|r| r.parse().ok()),
+                moved_from: record.remove("fromFile"),
+                success: true,
+                message: None,
+            })
+        })
+        .collect();
+
+    let mut unattributed = Vec::new();
+    for error in errors {
+        // Per-file messages read "<path> - <message>"
+        let Some((path, message)) = error.split_once(" - ") else {
+            unattributed.push(error);
+            continue;
+        };
+        let path = without_revision(path.trim());
+        results.push(P4FileOpResult {
+            path: path.to_string(),
+            depot_path: path.starts_with("//").then(|| path.to_string()),
+            local_path: None,
+            action: None,
+            file_type: None,
+            revision: None,
+            moved_from: None,
+            success: false,
+            message: Some(message.trim().to_string()),
+        });
+    }
+
+    (results, unattributed)
+}
+
+/// Strip a "#rev" suffix from a path in a p4 message
+fn without_revision(path: &str) -> &str {
+    match path.rfind('#') {
+        Some(hash_pos) if path[hash_pos + 1..].parse::<i32>().is_ok() => &path[..hash_pos],
+        _ => path,
+    }
+}
+
 /// Parse p4 -ztag streams output into P4Stream structs
 pub(super) fn parse_ztag_streams(output: &str) -> Result<Vec<P4Stream>, String> {
     let streams = parse_ztag_records(output)
@@ -621,7 +902,8 @@ pub(super) fn parse_ztag_streams(output: &str) -> Result<Vec<P4Stream>, String>
 fn build_stream(fields: &HashMap<String, String>) -> Option<P4Stream> {
     let stream = fields.get("Stream")?.clone();
     let name = fields.get("Name").cloned().unwrap_or_else(|| stream.clone());
-    let parent = fields.get("Parent").cloned();
+    // Mainlines report "Parent none"
+    let parent = fields.get("Parent").filter(|p| *p != "none").cloned();
     let stream_type = fields.get("Type")?.clone();
     let description = fields
         .get("desc")
@@ -634,14 +916,801 @@ fn build_stream(fields: &HashMap<String, String>) -> Option<P4Stream> {
         parent,
         stream_type,
         description,
+        integration: None,
     })
 }
 
-/// Parse p4 -ztag client -o output into P4ClientSpec
-pub(super) fn parse_ztag_client_spec(output: &str) -> Result<P4ClientSpec, String> {
-    let records = parse_ztag_records(output);
-    let fields = records.into_iter().next().unwrap_or_default();
+/// Build StreamIntegrationStatus from a p4 -ztag istat -a record.
+/// `integFromParent` / `integToParent` are "true" when changes are waiting in that direction.
+pub(super) fn build_stream_integration_status(
+    fields: &HashMap<String, String>,
+) -> Option<StreamIntegrationStatus> {
+    let flag = |key: &str| fields.get(key).is_some_and(|v| v == "true");
+    Some(StreamIntegrationStatus {
+        stream: fields.get("stream")?.clone(),
+        parent: fields.get("parent").filter(|p| *p != "none").cloned(),
+        change_flows_from_parent: flag("changeFlowsFromParent"),
+        change_flows_to_parent: flag("changeFlowsToParent"),
+        needs_merge_down: flag("integFromParent"),
+        merge_down_how: fields.get("integFromParentHow").cloned(),
+        needs_copy_up: flag("integToParent"),
+        copy_up_how: fields.get("integToParentHow").cloned(),
+        pending_merge_down: Vec::new(),
+        pending_copy_up: Vec::new(),
+        error: None,
+    })
+}
+
+/// Values of an indexed field (Paths0, Paths1, ...) in a tagged spec record
+pub(super) fn indexed_field_values(fields: &HashMap<String, String>, name: &str) -> Vec<String> {
+    (0..)
+        .map_while(|i| fields.get(&format!("{}{}", name, i)).cloned())
+        .collect()
+}
+
+/// Build P4StreamSpec from a p4 -G stream -o record
+pub(super) fn build_stream_spec(fields: &HashMap<String, String>) -> Option<P4StreamSpec> {
+    Some(P4StreamSpec {
+        stream: fields.get("Stream")?.clone(),
+        name: fields.get("Name").cloned().unwrap_or_default(),
+        owner: fields.get("Owner").cloned().unwrap_or_default(),
+        parent: fields.get("Parent").filter(|p| *p != "none").cloned(),
+        stream_type: fields.get("Type")?.clone(),
+        description: fields
+            .get("Description")
+            .map(|d| d.trim_end().to_string())
+            .unwrap_or_default(),
+        options: fields.get("Options").cloned().unwrap_or_default(),
+        parent_view: fields.get("ParentView").cloned(),
+        paths: indexed_field_values(fields, "Paths"),
+        remapped: indexed_field_values(fields, "Remapped"),
+        ignored: indexed_field_values(fields, "Ignored"),
+        update: fields.get("Update").cloned(),
+        access: fields.get("Access").cloned(),
+    })
+}
+
+/// Per-field errors from p4 rejecting `form`.
+/// p4 names either the field ("Missing required field 'Paths'.") or the form line
+/// ("Error detected at line 12."), which is mapped back to its field.
+pub(super) fn spec_field_errors(stderr: &str, form: &str) -> Vec<SpecFieldError> {
+    let mut field = None;
+    let mut messages = Vec::new();
+    for line in stderr.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let lower = line.to_lowercase();
+        if lower.starts_with("error in ") && lower.contains(" specification") {
+            continue;
+        }
+        if let Some(rest) = lower.strip_prefix("error detected at line ") {
+            let line_number: usize = rest.trim_end_matches('.').trim().parse().unwrap_or(0);
+            field = form
+                .lines()
+                .take(line_number)
+                .filter_map(form_field_name)
+                .last()
+                .map(str::to_string);
+            continue;
+        }
+        if let Some((_, rest)) = line.split_once("field '") {
+            if let Some((name, _)) = rest.split_once('\'') {
+                field = Some(name.to_string());
+            }
+        }
+        messages.push(line.to_string());
+    }
+
+    if messages.is_empty() {
+        return Vec::new();
+    }
+    vec![SpecFieldError {
+        field,
+        message: messages.join("\n"),
+    }]
+}
 
+const STREAM_PATH_TYPES: &[&str] = &["share", "isolate", "import", "import+", "exclude"];
+const STREAM_OPTIONS: &[&[&str]] = &[
+    &["allsubmit", "ownersubmit"],
+    &["unlocked", "locked"],
+    &["toparent", "notoparent"],
+    &["fromparent", "nofromparent"],
+    &["mergedown", "mergeany"],
+];
+
+/// Problems p4 would reject a stream spec for, found before sending it
+pub(super) fn validate_stream_spec(spec: &P4StreamSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(SpecFieldError {
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    // //depot/name, or //depot/parent/name for multi-level stream depots
+    if !spec.stream.starts_with("//") || spec.stream[2..].split('/').filter(|p| !p.is_empty()).count() < 2 {
+        error("Stream", format!("'{}' is not a stream path like //depot/name", spec.stream));
+    }
+    if spec.owner.trim().is_empty() {
+        error("Owner", "Owner is required".to_string());
+    }
+    match spec.stream_type.as_str() {
+        "mainline" => {}
+        "development" | "release" | "virtual" | "task" | "sparsedev" | "sparserel" => {
+            if spec.parent.as_deref().is_none_or(|p| p.trim().is_empty()) {
+                error("Parent", format!("A {} stream needs a parent", spec.stream_type));
+            }
+        }
+        other => error("Type", format!("Unknown stream type '{}'", other)),
+    }
+    if spec.paths.is_empty() {
+        error("Paths", "At least one path is required, e.g. \"share ...\"".to_string());
+    }
+    for path in &spec.paths {
+        let mut words = path.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(kind), Some(_)) if STREAM_PATH_TYPES.contains(&kind) => {}
+            (Some(kind), Some(_)) => error("Paths", format!("Unknown path type '{}' in '{}'", kind, path)),
+            _ => error("Paths", format!("'{}' needs a path type and a path", path)),
+        }
+    }
+    for line in &spec.remapped {
+        if line.split_whitespace().count() != 2 {
+            error("Remapped", format!("'{}' should map one path to another", line));
+        }
+    }
+    for option in spec.options.split_whitespace() {
+        if !STREAM_OPTIONS.iter().any(|choices| choices.contains(&option)) {
+            error("Options", format!("Unknown option '{}'", option));
+        }
+    }
+    if let Some(parent_view) = spec.parent_view.as_deref() {
+        if !matches!(parent_view, "inherit" | "noinherit") {
+            error("ParentView", format!("'{}' should be inherit or noinherit", parent_view));
+        }
+    }
+    errors
+}
+
+/// Write the editable fields of `spec` into a `p4 stream -o` form
+pub(super) fn apply_stream_spec(form: &mut SpecForm, spec: &P4StreamSpec) {
+    form.set_line("Name", spec.name.as_str());
+    form.set_line("Owner", spec.owner.as_str());
+    form.set_line("Parent", spec.parent.as_deref().unwrap_or("none"));
+    form.set_line("Type", spec.stream_type.as_str());
+    form.set_text("Description", &spec.description);
+    form.set_line("Options", spec.options.as_str());
+    form.set_lines("Paths", spec.paths.clone());
+    form.set_lines("Remapped", spec.remapped.clone());
+    form.set_lines("Ignored", spec.ignored.clone());
+    if let Some(parent_view) = &spec.parent_view {
+        form.set_line("ParentView", parent_view.as_str());
+    }
+}
+
+/// Build P4Label from a p4 -G labels record
+pub(super) fn build_label(fields: &HashMap<String, String>) -> Option<P4Label> {
+    Some(P4Label {
+        label: fields.get("label")?.clone(),
+        owner: fields.get("Owner").cloned().unwrap_or_default(),
+        description: fields
+            .get("Description")
+            .map(|d| d.trim_end().to_string())
+            .unwrap_or_default(),
+        options: fields.get("Options").cloned().unwrap_or_default(),
+        revision: fields.get("Revision").filter(|r| !r.is_empty()).cloned(),
+        update: fields.get("Update").and_then(|t| t.parse().ok()).unwrap_or(0),
+        access: fields.get("Access").and_then(|t| t.parse().ok()).unwrap_or(0),
+    })
+}
+
+/// Build P4LabelSpec from a p4 -G label -o record
+pub(super) fn build_label_spec(fields: &HashMap<String, String>) -> Option<P4LabelSpec> {
+    Some(P4LabelSpec {
+        label: fields.get("Label")?.clone(),
+        owner: fields.get("Owner").cloned().unwrap_or_default(),
+        description: fields
+            .get("Description")
+            .map(|d| d.trim_end().to_string())
+            .unwrap_or_default(),
+        options: fields.get("Options").cloned().unwrap_or_default(),
+        revision: fields.get("Revision").filter(|r| !r.is_empty()).cloned(),
+        view: indexed_field_values(fields, "View"),
+        update: fields.get("Update").cloned(),
+        access: fields.get("Access").cloned(),
+    })
+}
+
+const LABEL_OPTIONS: &[&[&str]] = &[&["locked", "unlocked"], &["autoreload", "noautoreload"]];
+
+/// Problems p4 would reject a label spec for, found before sending it
+pub(super) fn validate_label_spec(spec: &P4LabelSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(SpecFieldError {
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    if let Err(message) = check_spec_name(&spec.label) {
+        error("Label", message);
+    }
+    if spec.owner.trim().is_empty() {
+        error("Owner", "Owner is required".to_string());
+    }
+    for option in spec.options.split_whitespace() {
+        if !LABEL_OPTIONS.iter().any(|choices| choices.contains(&option)) {
+            error("Options", format!("Unknown option '{}'", option));
+        }
+    }
+    if let Some(revision) = spec.revision.as_deref().filter(|r| !r.is_empty()) {
+        if !revision.starts_with('@') && !revision.starts_with('#') {
+            error("Revision", format!("'{}' should be a revision like @1234 or @label", revision));
+        }
+    }
+    if spec.view.is_empty() {
+        error("View", "At least one depot path is required, e.g. //depot/...".to_string());
+    }
+    for path in &spec.view {
+        let depot = path.trim_start_matches(['-', '+', '"']);
+        if !depot.starts_with("//") {
+            error("View", format!("'{}' is not a depot path", path));
+        }
+    }
+    errors
+}
+
+/// Labels, workspaces and other specs share p4's naming rules
+fn check_spec_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        Err("Name is required".to_string())
+    } else if name.contains(char::is_whitespace) {
+        Err("Name can't contain spaces".to_string())
+    } else if name.chars().all(|c| c.is_ascii_digit()) {
+        Err("Name can't be all digits".to_string())
+    } else if name.contains(['@', '#', '%', '*', ',']) || name.contains("...") {
+        Err("Name can't contain @, #, %, *, commas or ...".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Write the editable fields of `spec` into a `p4 label -o` form
+pub(super) fn apply_label_spec(form: &mut SpecForm, spec: &P4LabelSpec) {
+    form.set_line("Label", spec.label.as_str());
+    form.set_line("Owner", spec.owner.as_str());
+    form.set_text("Description", &spec.description);
+    form.set_line("Options", spec.options.as_str());
+    match spec.revision.as_deref().filter(|r| !r.is_empty()) {
+        Some(revision) => form.set_line("Revision", revision),
+        None => form.remove("Revision"),
+    }
+    form.set_lines("View", spec.view.clone());
+}
+
+/// User spec from a p4 user -o form
+pub(super) fn user_spec_from_form(form: &SpecForm) -> Option<P4UserSpec> {
+    let line = |name: &str| form.line(name).map(str::to_string);
+    Some(P4UserSpec {
+        user: line("User")?,
+        email: line("Email").unwrap_or_default(),
+        full_name: line("FullName").unwrap_or_default(),
+        job_view: line("JobView"),
+        reviews: form.lines("Reviews"),
+        update: line("Update"),
+        access: line("Access"),
+    })
+}
+
+pub(super) fn validate_user_spec(spec: &P4UserSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(SpecFieldError {
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    if let Err(message) = check_spec_name(&spec.user) {
+        error("User", message);
+    }
+    if spec.email.trim().is_empty() {
+        error("Email", "Email is required".to_string());
+    }
+    if spec.full_name.trim().is_empty() {
+        error("FullName", "Full name is required".to_string());
+    }
+    for path in &spec.reviews {
+        let depot = path.trim_start_matches(['-', '+', '"']);
+        if !depot.starts_with("//") {
+            error("Reviews", format!("'{}' is not a depot path", path));
+        }
+    }
+    errors
+}
+
+/// Write the editable fields of `spec` into a p4 user -o form
+pub(super) fn apply_user_spec(form: &mut SpecForm, spec: &P4UserSpec) {
+    form.set_line("User", spec.user.as_str());
+    form.set_line("Email", spec.email.as_str());
+    form.set_line("FullName", spec.full_name.as_str());
+    match spec.job_view.as_deref().filter(|v| !v.is_empty()) {
+        Some(job_view) => form.set_line("JobView", job_view),
+        None => form.remove("JobView"),
+    }
+    if spec.reviews.is_empty() {
+        form.remove("Reviews");
+    } else {
+        form.set_lines("Reviews", spec.reviews.clone());
+    }
+}
+
+/// `path` at `revision_spec`: "@1234", "#head", "@label" or a bare label name
+pub(super) fn with_revision_spec(path: &str, revision_spec: Option<&str>) -> Result<String, P4Error> {
+    let Some(spec) = revision_spec.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(path.to_string());
+    };
+    if spec.contains(char::is_whitespace) {
+        return Err(P4Error::other(format!("Invalid revision '{}'", spec)));
+    }
+    if spec.starts_with('@') || spec.starts_with('#') {
+        Ok(format!("{}{}", path, spec))
+    } else {
+        Ok(format!("{}@{}", path, spec))
+    }
+}
+
+/// Build P4Job from a p4 -G jobs record
+pub(super) fn build_job(mut fields: HashMap<String, String>) -> Option<P4Job> {
+    let job = fields.remove("Job")?;
+    let mut take = |name: &str| fields.remove(name).unwrap_or_default();
+    let status = take("Status");
+    let user = take("User");
+    let date = take("Date");
+    let description = take("Description").trim_end().to_string();
+    Some(P4Job {
+        job,
+        status,
+        user,
+        date,
+        description,
+        fields,
+    })
+}
+
+/// Fields of a `p4 job -o` form, typed by the `p4 jobspec -o` form.
+/// Jobspec lines read "101 Job word 32 required" and "Status open/suspended/closed".
+pub(super) fn job_fields(jobspec: &SpecForm, job: &SpecForm) -> Vec<P4JobField> {
+    let values: HashMap<String, Vec<String>> = jobspec
+        .lines("Values")
+        .iter()
+        .filter_map(|line| {
+            let (name, choices) = line.split_once(char::is_whitespace)?;
+            Some((name.to_string(), choices.trim().split('/').map(str::to_string).collect()))
+        })
+        .collect();
+
+    jobspec
+        .lines("Fields")
+        .iter()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [_code, name, data_type, _length, field_type] = words[..] else {
+                return None;
+            };
+            Some(P4JobField {
+                name: name.to_string(),
+                value: job.lines(name).join("\n"),
+                data_type: data_type.to_string(),
+                field_type: field_type.to_string(),
+                values: values.get(name).cloned().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Fields the server fills in itself; they're never sent back
+fn is_server_job_field(field: &P4JobField) -> bool {
+    matches!(field.field_type.as_str(), "once" | "always")
+}
+
+/// Problems p4 would reject a job for, found before sending it
+pub(super) fn validate_job_spec(spec: &P4JobSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    for field in spec.fields.iter().filter(|f| !is_server_job_field(f)) {
+        let value = field.value.trim();
+        let message = if value.is_empty() {
+            (field.field_type == "required").then(|| format!("{} is required", field.name))
+        } else if field.data_type == "select" && !field.values.iter().any(|v| v == value) {
+            Some(format!("'{}' should be one of {}", value, field.values.join(", ")))
+        } else if field.data_type == "word" && value.contains(char::is_whitespace) {
+            Some(format!("{} must be a single word", field.name))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            errors.push(SpecFieldError {
+                field: Some(field.name.clone()),
+                message,
+            });
+        }
+    }
+    errors
+}
+
+/// Write the editable fields of `spec` into a `p4 job -o` form
+pub(super) fn apply_job_spec(form: &mut SpecForm, spec: &P4JobSpec) {
+    for field in spec.fields.iter().filter(|f| !is_server_job_field(f)) {
+        match field.data_type.as_str() {
+            "text" | "bulk" => form.set_text(&field.name, &field.value),
+            _ => form.set_line(&field.name, field.value.trim()),
+        }
+    }
+}
+
+/// Build P4Branch from a p4 -G branches record
+pub(super) fn build_branch(fields: &HashMap<String, String>) -> Option<P4Branch> {
+    Some(P4Branch {
+        branch: fields.get("branch")?.clone(),
+        owner: fields.get("Owner").cloned().unwrap_or_default(),
+        description: fields
+            .get("Description")
+            .map(|d| d.trim_end().to_string())
+            .unwrap_or_default(),
+        options: fields.get("Options").cloned().unwrap_or_default(),
+        update: fields.get("Update").and_then(|t| t.parse().ok()).unwrap_or(0),
+        access: fields.get("Access").and_then(|t| t.parse().ok()).unwrap_or(0),
+    })
+}
+
+/// Build P4BranchSpec from a p4 -G branch -o record
+pub(super) fn build_branch_spec(fields: &HashMap<String, String>) -> Option<P4BranchSpec> {
+    Some(P4BranchSpec {
+        branch: fields.get("Branch")?.clone(),
+        owner: fields.get("Owner").cloned().unwrap_or_default(),
+        description: fields
+            .get("Description")
+            .map(|d| d.trim_end().to_string())
+            .unwrap_or_default(),
+        options: fields.get("Options").cloned().unwrap_or_default(),
+        view: indexed_field_values(fields, "View")
+            .iter()
+            .filter_map(|line| parse_view_mapping(line))
+            .collect(),
+        update: fields.get("Update").cloned(),
+        access: fields.get("Access").cloned(),
+    })
+}
+
+/// Parse a view line such as `-//depot/a/... //depot/b/...` or
+/// `"//depot/My Docs/..." "//depot/Their Docs/..."`
+pub(super) fn parse_view_mapping(line: &str) -> Option<P4ViewMapping> {
+    let (source, target) = split_view_mapping(line)?;
+    let (kind, source) = match source.as_bytes().first() {
+        Some(b'-') => (MappingKind::Exclude, &source[1..]),
+        Some(b'+') => (MappingKind::Overlay, &source[1..]),
+        _ => (MappingKind::Include, &source[..]),
+    };
+    Some(P4ViewMapping {
+        kind,
+        source: source.to_string(),
+        target,
+    })
+}
+
+/// View line for a mapping, quoting paths that contain spaces
+pub(super) fn format_view_mapping(mapping: &P4ViewMapping) -> String {
+    let quote = |path: &str| {
+        if path.contains(char::is_whitespace) {
+            format!("\"{}\"", path)
+        } else {
+            path.to_string()
+        }
+    };
+    let prefix = match mapping.kind {
+        MappingKind::Include => "",
+        MappingKind::Exclude => "-",
+        MappingKind::Overlay => "+",
+    };
+    format!("{} {}", quote(&format!("{}{}", prefix, mapping.source)), quote(&mapping.target))
+}
+
+/// Problems p4 would reject a branch spec for, found before sending it
+pub(super) fn validate_branch_spec(spec: &P4BranchSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(SpecFieldError {
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    if let Err(message) = check_spec_name(&spec.branch) {
+        error("Branch", message);
+    }
+    if spec.owner.trim().is_empty() {
+        error("Owner", "Owner is required".to_string());
+    }
+    for option in spec.options.split_whitespace() {
+        if !matches!(option, "locked" | "unlocked") {
+            error("Options", format!("'{}' should be locked or unlocked", option));
+        }
+    }
+    if spec.view.is_empty() {
+        error("View", "At least one mapping is required".to_string());
+    }
+    for mapping in &spec.view {
+        if !mapping.source.starts_with("//") || !mapping.target.starts_with("//") {
+            error(
+                "View",
+                format!("'{}' should map a depot path to a depot path", format_view_mapping(mapping)),
+            );
+        }
+    }
+    errors
+}
+
+/// Write the editable fields of `spec` into a `p4 branch -o` form
+pub(super) fn apply_branch_spec(form: &mut SpecForm, spec: &P4BranchSpec) {
+    form.set_line("Branch", spec.branch.as_str());
+    form.set_line("Owner", spec.owner.as_str());
+    form.set_text("Description", &spec.description);
+    form.set_line("Options", spec.options.as_str());
+    form.set_lines("View", spec.view.iter().map(format_view_mapping).collect());
+}
+
+/// Build P4Diff2File from a p4 -G diff2 record. p4 writes "<none>" for a missing side.
+pub(super) fn build_diff2_file(fields: &HashMap<String, String>) -> Option<P4Diff2File> {
+    let status = match fields.get("status")?.as_str() {
+        "identical" => Diff2Status::Identical,
+        "types" => Diff2Status::Types,
+        "left only" => Diff2Status::LeftOnly,
+        "right only" => Diff2Status::RightOnly,
+        _ => Diff2Status::Content,
+    };
+    let side = |name: &str| fields.get(name).filter(|v| *v != "<none>").cloned();
+    Some(P4Diff2File {
+        status,
+        left_path: side("depotFile"),
+        left_revision: side("rev").and_then(|r| r.parse().ok()),
+        left_type: side("type"),
+        right_path: side("depotFile2"),
+        right_revision: side("rev2").and_then(|r| r.parse().ok()),
+        right_type: side("type2"),
+    })
+}
+
+/// Incremental parser for the unified diffs printed by `p4 describe -du` and `p4 diff -du`.
+/// Feed stdout line by line; a file is returned once the next one starts, the last from finish.
+pub(super) struct ChangeDiffParser {
+    /// Open actions by depot path, from describe's file list or from the caller
+    actions: HashMap<String, String>,
+    /// Diff text kept per file before it is marked truncated
+    max_size: u64,
+    file: Option<P4FileDiff>,
+    /// Diff text seen for the current file
+    size: u64,
+    /// The current file already has its "--- " header
+    has_unified_header: bool,
+    hunk: Option<OpenHunk>,
+}
+
+/// Hunk being filled: lines still expected on each side and the next line numbers
+struct OpenHunk {
+    hunk: DiffHunk,
+    old_remaining: u32,
+    new_remaining: u32,
+    old_line: u32,
+    new_line: u32,
+}
+
+impl ChangeDiffParser {
+    pub(super) fn new(actions: HashMap<String, String>, max_size: u64) -> Self {
+        Self {
+            actions,
+            max_size,
+            file: None,
+            size: 0,
+            has_unified_header: false,
+            hunk: None,
+        }
+    }
+
+    pub(super) fn push_line(&mut self, line: &str) -> Option<P4FileDiff> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // "\ No newline at end of file"
+        if line.starts_with("\\ ") || self.push_hunk_line(line) {
+            return None;
+        }
+        self.close_hunk();
+
+        if let Some(header) = line.strip_prefix("==== ") {
+            let file = parse_diff_file_header(header)?;
+            return self.start_file(file);
+        }
+        if let Some(path) = line.strip_prefix("--- ") {
+            if self.file.as_ref().is_some_and(|f| f.hunks.is_empty()) && !self.has_unified_header {
+                self.has_unified_header = true;
+                return None;
+            }
+            let (depot_path, revision) = split_file_revision(path.split('\t').next().unwrap_or(path));
+            let finished = self.start_file(P4FileDiff {
+                depot_path,
+                revision,
+                ..Default::default()
+            });
+            self.has_unified_header = true;
+            return finished;
+        }
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.split('\t').next().unwrap_or(path);
+            if let Some(file) = self.file.as_mut().filter(|_| !path.starts_with("//")) {
+                file.local_path = Some(path.to_string());
+            }
+            return None;
+        }
+        if line.starts_with("@@ ") {
+            if let (Some((old_start, old_lines, new_start, new_lines)), true) = (parse_hunk_header(line), self.file.is_some()) {
+                self.hunk = Some(OpenHunk {
+                    hunk: DiffHunk {
+                        old_start,
+                        old_lines,
+                        new_start,
+                        new_lines,
+                        lines: Vec::new(),
+                    },
+                    old_remaining: old_lines,
+                    new_remaining: new_lines,
+                    old_line: old_start.max(1),
+                    new_line: new_start.max(1),
+                });
+            }
+            return None;
+        }
+        // "... //depot/a.txt#3 edit" in describe's list of affected or shelved files
+        if let Some((spec, action)) = line.strip_prefix("... ").and_then(|l| l.rsplit_once(' ')) {
+            let (depot_path, _) = split_file_revision(spec);
+            self.actions.insert(depot_path, action.to_string());
+        }
+        None
+    }
+
+    pub(super) fn finish(mut self) -> Option<P4FileDiff> {
+        self.close_hunk();
+        self.file.take()
+    }
+
+    fn start_file(&mut self, mut file: P4FileDiff) -> Option<P4FileDiff> {
+        let finished = self.finish_file();
+        file.action = self.actions.get(&file.depot_path).cloned();
+        self.file = Some(file);
+        finished
+    }
+
+    fn finish_file(&mut self) -> Option<P4FileDiff> {
+        self.close_hunk();
+        self.size = 0;
+        self.has_unified_header = false;
+        self.file.take()
+    }
+
+    /// Take `line` as the next line of the open hunk; false once the hunk is complete
+    fn push_hunk_line(&mut self, line: &str) -> bool {
+        let (Some(open), Some(file)) = (self.hunk.as_mut(), self.file.as_mut()) else {
+            return false;
+        };
+        if open.old_remaining == 0 && open.new_remaining == 0 {
+            return false;
+        }
+        // p4 may strip the leading space of an empty context line
+        let (kind, text) = match line.chars().next() {
+            Some(' ') | None if open.old_remaining > 0 && open.new_remaining > 0 => {
+                (DiffLineKind::Context, line.get(1..).unwrap_or(""))
+            }
+            Some('-') if open.old_remaining > 0 => (DiffLineKind::Removed, &line[1..]),
+            Some('+') if open.new_remaining > 0 => (DiffLineKind::Added, &line[1..]),
+            _ => return false,
+        };
+
+        let (old_line, new_line) = match kind {
+            DiffLineKind::Context => {
+                open.old_remaining -= 1;
+                open.new_remaining -= 1;
+                (Some(open.old_line), Some(open.new_line))
+            }
+            DiffLineKind::Removed => {
+                open.old_remaining -= 1;
+                file.removed += 1;
+                (Some(open.old_line), None)
+            }
+            DiffLineKind::Added => {
+                open.new_remaining -= 1;
+                file.added += 1;
+                (None, Some(open.new_line))
+            }
+        };
+        if old_line.is_some() {
+            open.old_line += 1;
+        }
+        if new_line.is_some() {
+            open.new_line += 1;
+        }
+
+        self.size += line.len() as u64 + 1;
+        if self.size > self.max_size {
+            file.truncated = true;
+        }
+        if !file.truncated {
+            open.hunk.lines.push(DiffLine {
+                kind,
+                old_line,
+                new_line,
+                text: text.to_string(),
+                segments: None,
+            });
+        }
+        true
+    }
+
+    /// Move the open hunk into the current file, unless the file went over the limit in it
+    fn close_hunk(&mut self) {
+        if let (Some(open), Some(file)) = (self.hunk.take(), self.file.as_mut()) {
+            if !file.truncated {
+                file.hunks.push(open.hunk);
+            }
+        }
+    }
+}
+
+/// File from a "==== ... ====" header: describe's "//depot/a.txt#3 (text)" or
+/// diff's "//depot/a.txt#3 - /ws/a.txt"
+fn parse_diff_file_header(header: &str) -> Option<P4FileDiff> {
+    let inner = &header[..header.rfind(" ====")?];
+    let (spec, file_type, local_path) = match inner.rfind(" (").filter(|_| inner.ends_with(')')) {
+        Some(open) => (&inner[..open], Some(inner[open + 2..inner.len() - 1].to_string()), None),
+        None => match inner.split_once(" - ") {
+            Some((spec, local)) => (spec, None, Some(local.to_string())),
+            None => (inner, None, None),
+        },
+    };
+    let (depot_path, revision) = split_file_revision(spec);
+    let binary = file_type.as_deref().is_some_and(|t| t.contains("binary")) || header.ends_with("(binary)");
+    Some(P4FileDiff {
+        depot_path,
+        revision,
+        local_path,
+        file_type,
+        binary,
+        ..Default::default()
+    })
+}
+
+/// "//depot/a.txt#3" into the path and revision; "#none" has no revision
+fn split_file_revision(spec: &str) -> (String, Option<i32>) {
+    match spec.rsplit_once('#') {
+        Some((path, revision)) => (path.to_string(), revision.parse().ok()),
+        None => (spec.to_string(), None),
+    }
+}
+
+/// "@@ -12,5 +12,6 @@" into (old start, old lines, new start, new lines); counts default to 1
+fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32, u32)> {
+    let ranges = line.strip_prefix("@@ -")?.split_once(" @@")?.0;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |r: &str| -> Option<(u32, u32)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+    Some((old_start, old_lines, new_start, new_lines))
+}
+
+/// Build P4ClientSpec from a tagged p4 client -o record (-G or -ztag)
+pub(super) fn build_client_spec(fields: &HashMap<String, String>) -> Result<P4ClientSpec, String> {
     // Case-insensitive field lookup helper
     let get_field = |name: &str| -> Option<String> {
         fields.get(name).cloned().or_else(|| {
@@ -652,28 +1721,18 @@ pub(super) fn parse_ztag_client_spec(output: &str) -> Result<P4ClientSpec, Strin
         })
     };
 
-    // Extract View mapping lines (View0, View1, View2, ...) from the flat record
-    let mut view_lines: Vec<String> = Vec::new();
-    let mut view_idx = 0;
-    loop {
-        let key = format!("View{}", view_idx);
-        if let Some(value) = get_field(&key) {
-            view_lines.push(value);
-            view_idx += 1;
-        } else {
-            break;
-        }
-    }
-
     let client = get_field("Client").ok_or("Missing Client field")?;
     let root = get_field("Root").unwrap_or_default();  // Optional for virtual-root workspaces
     let stream = get_field("Stream");
     let owner = get_field("Owner").ok_or("Missing Owner field")?;
-    let description = get_field("Description").unwrap_or_default();
+    let description = get_field("Description")
+        .map(|d| d.trim_end().to_string())
+        .unwrap_or_default();
     let options = get_field("Options").unwrap_or_default();
     let host = get_field("Host").unwrap_or_default();
     let submit_options = get_field("SubmitOptions")
         .unwrap_or_else(|| "submitunchanged".to_string());
+    let line_end = get_field("LineEnd").unwrap_or_else(|| "local".to_string());
 
     Ok(P4ClientSpec {
         client,
@@ -681,13 +1740,136 @@ pub(super) fn parse_ztag_client_spec(output: &str) -> Result<P4ClientSpec, Strin
         stream,
         owner,
         description,
-        view: view_lines,
+        // View mapping lines (View0, View1, View2, ...) from the flat record
+        view: indexed_field_values(fields, "View"),
         options,
         host,
         submit_options,
+        line_end,
     })
 }
 
+const CLIENT_OPTIONS: &[&[&str]] = &[
+    &["allwrite", "noallwrite"],
+    &["clobber", "noclobber"],
+    &["compress", "nocompress"],
+    &["locked", "unlocked"],
+    &["modtime", "nomodtime"],
+    &["rmdir", "normdir"],
+    &["altsync", "noaltsync"],
+];
+const CLIENT_SUBMIT_OPTIONS: &[&str] = &[
+    "submitunchanged",
+    "submitunchanged+reopen",
+    "revertunchanged",
+    "revertunchanged+reopen",
+    "leaveunchanged",
+    "leaveunchanged+reopen",
+];
+const CLIENT_LINE_ENDS: &[&str] = &["local", "unix", "mac", "win", "share"];
+
+/// Problems p4 would reject a client spec for, found before sending it
+pub(super) fn validate_client_spec(spec: &P4ClientSpec) -> Vec<SpecFieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(SpecFieldError {
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    if let Err(message) = check_spec_name(&spec.client) {
+        error("Client", message);
+    }
+    if spec.owner.trim().is_empty() {
+        error("Owner", "Owner is required".to_string());
+    }
+    if spec.root.trim().is_empty() {
+        error("Root", "Root is required".to_string());
+    }
+    for option in spec.options.split_whitespace() {
+        if !CLIENT_OPTIONS.iter().any(|choices| choices.contains(&option)) {
+            error("Options", format!("Unknown option '{}'", option));
+        }
+    }
+    if !CLIENT_SUBMIT_OPTIONS.contains(&spec.submit_options.as_str()) {
+        error("SubmitOptions", format!("Unknown submit option '{}'", spec.submit_options));
+    }
+    if !CLIENT_LINE_ENDS.contains(&spec.line_end.as_str()) {
+        error("LineEnd", format!("'{}' should be one of {}", spec.line_end, CLIENT_LINE_ENDS.join(", ")));
+    }
+
+    if spec.stream.as_deref().is_some_and(|s| !s.trim().is_empty()) {
+        return errors;
+    }
+    if spec.view.is_empty() {
+        error("View", "A workspace without a stream needs at least one view mapping".to_string());
+    }
+    let client_prefix = format!("//{}/", spec.client);
+    for mapping in &spec.view {
+        let (depot, client) = match split_view_mapping(mapping) {
+            Some(sides) => sides,
+            None => {
+                error("View", format!("'{}' should map a depot path to a workspace path", mapping));
+                continue;
+            }
+        };
+        if !depot.trim_start_matches(['-', '+']).starts_with("//") {
+            error("View", format!("'{}' is not a depot path", depot));
+        }
+        if !client.starts_with(&client_prefix) {
+            error("View", format!("'{}' should start with {}", client, client_prefix));
+        }
+    }
+    errors
+}
+
+/// Depot and client sides of a view mapping; paths containing spaces are "quoted"
+fn split_view_mapping(mapping: &str) -> Option<(String, String)> {
+    let mut sides = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in mapping.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    sides.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        sides.push(current);
+    }
+    let [depot, client] = <[String; 2]>::try_from(sides).ok()?;
+    Some((depot, client))
+}
+
+/// Write a client spec's editable fields into its form
+pub(super) fn apply_client_spec(form: &mut SpecForm, spec: &P4ClientSpec) {
+    form.set_line("Client", spec.client.as_str());
+    form.set_line("Owner", spec.owner.as_str());
+    form.set_line("Host", spec.host.as_str());
+    form.set_text("Description", &spec.description);
+    form.set_line("Root", spec.root.as_str());
+    form.set_line("Options", spec.options.as_str());
+    form.set_line("SubmitOptions", spec.submit_options.as_str());
+    form.set_line("LineEnd", spec.line_end.as_str());
+    match spec.stream.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(stream) => {
+            // The server regenerates the view from the stream
+            form.set_line("Stream", stream);
+            form.remove("View");
+        }
+        None => {
+            form.remove("Stream");
+            form.set_lines("View", spec.view.clone());
+        }
+    }
+}
+
 /// Parse p4 -ztag depots output into P4Depot structs
 pub(super) fn parse_ztag_depots(output: &str) -> Result<Vec<P4Depot>, String> {
     let depots = parse_ztag_records(output)
@@ -756,92 +1938,187 @@ fn build_unresolved_file_info(fields: &HashMap<String, String>) -> Option<P4Unre
     })
 }
 
-/// Update changelist description (used by submit and edit_change_description)
-pub(super) async fn update_changelist_description(
-    changelist: i32,
-    description: &str,
-    server: Option<String>,
-    user: Option<String>,
-    client: Option<String>,
-) -> Result<(), String> {
-    use tokio::io::AsyncWriteExt;
-    use std::process::Stdio;
-
-    // Get current changelist form
-    let mut cmd = tokio::process::Command::new("p4");
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["change", "-o", &changelist.to_string()]);
-
-    // On Windows, hide the console window (CREATE_NO_WINDOW flag)
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(0x08000000);
-
-    let output = cmd
-        .output()
-        .await
-        .map_err(|e| format!("Failed to get changelist: {}", e))?;
+/// Arguments for p4 merge / copy / integrate (after the global flags).
+/// `preview` adds -n, so nothing is opened.
+pub(super) fn integrate_args(options: &IntegrateOptions, preview: bool) -> Result<Vec<String>, P4Error> {
+    let mut args = vec![options.kind.command().to_string()];
+    if preview {
+        args.push("-n".to_string());
+    }
+    if let Some(changelist) = options.changelist.filter(|c| *c > 0) {
+        args.push("-c".to_string());
+        args.push(changelist.to_string());
+    }
+
+    let range = options.revision_range.as_deref().unwrap_or("");
+    let with_range = |path: &str| format!("{}{}", path, range);
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(stderr.to_string());
+    match (&options.stream, &options.branch) {
+        (Some(_), Some(_)) => {
+            return Err(P4Error::other("Choose either a stream or a branch spec, not both"));
+        }
+        (Some(stream), None) => {
+            args.push("-S".to_string());
+            args.push(stream.clone());
+            if let Some(parent) = &options.parent_stream {
+                args.push("-P".to_string());
+                args.push(parent.clone());
+            }
+            if options.reverse {
+                args.push("-r".to_string());
+            }
+            match &options.to_path {
+                Some(to_path) => args.push(with_range(to_path)),
+                // Source revisions need a target path to attach to
+                None if !range.is_empty() => args.push(with_range("//...")),
+                None => {}
+            }
+        }
+        (None, Some(branch)) => {
+            args.push("-b".to_string());
+            args.push(branch.clone());
+            if options.reverse {
+                args.push("-r".to_string());
+            }
+            match (&options.from_path, &options.to_path) {
+                (Some(from_path), to_path) => {
+                    args.push("-s".to_string());
+                    args.push(with_range(from_path));
+                    args.extend(to_path.clone());
+                }
+                (None, Some(to_path)) => args.push(with_range(to_path)),
+                (None, None) if !range.is_empty() => args.push(with_range("//...")),
+                (None, None) => {}
+            }
+        }
+        (None, None) => {
+            let (Some(from_path), Some(to_path)) = (&options.from_path, &options.to_path) else {
+                return Err(P4Error::other(
+                    "Choose a stream, a branch spec, or both a source and a target path",
+                ));
+            };
+            if options.reverse {
+                return Err(P4Error::other("Reverse needs a stream or a branch spec"));
+            }
+            args.push(with_range(from_path));
+            args.push(to_path.clone());
+        }
     }
 
-    let form = String::from_utf8_lossy(&output.stdout);
+    Ok(args)
+}
+
+/// Build IntegrateFile from a -ztag merge / copy / integrate record
+pub(super) fn build_integrate_file(fields: &HashMap<String, String>) -> Option<IntegrateFile> {
+    Some(IntegrateFile {
+        depot_path: fields.get("depotFile")?.clone(),
+        local_path: fields.get("clientFile").cloned(),
+        action: fields.get("action").cloned().unwrap_or_default(),
+        from_file: fields.get("fromFile").cloned(),
+        start_from_rev: fields.get("startFromRev").cloned(),
+        end_from_rev: fields.get("endFromRev").cloned(),
+        work_rev: fields.get("workRev").and_then(|r| r.parse().ok()),
+    })
+}
+
+/// Whether a merge / copy / integrate stderr line is a real failure.
+/// "Nothing to do" messages are not: everything already integrated, or no files in the range.
+pub(super) fn is_integrate_error(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    if lower.contains("already integrated") || lower.contains("no target file(s)") {
+        return false;
+    }
+    classify_stderr(line) != P4ErrorKind::NoSuchFile
+}
 
-    // Update description in form
-    let mut new_form = String::new();
-    let mut in_description = false;
+/// Split fstat records for files changed in a changelist range into index updates
+/// and removals. Files whose head revision is a delete are dropped from the index.
+pub(super) fn index_changes_from_records(
+    records: Vec<HashMap<String, String>>,
+) -> (Vec<FileEntry>, Vec<String>) {
+    let mut updated = Vec::new();
+    let mut removed = Vec::new();
 
-    for line in form.lines() {
-        if line.starts_with("Description:") {
-            new_form.push_str("Description:\n");
-            new_form.push_str(&format!("\t{}\n", description));
-            in_description = true;
-        } else if in_description && line.starts_with('\t') {
-            // Skip old description lines
+    for record in records {
+        let Some(depot_path) = record.get("depotFile").cloned() else {
             continue;
-        } else if in_description && !line.starts_with('\t') {
-            in_description = false;
-            new_form.push_str(line);
-            new_form.push('\n');
+        };
+        let action = record.get("headAction").map(String::as_str).unwrap_or("");
+        if matches!(action, "delete" | "move/delete" | "purge" | "archive") {
+            removed.push(depot_path);
         } else {
-            new_form.push_str(line);
-            new_form.push('\n');
+            let mod_time = record
+                .get("headModTime")
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(0);
+            updated.push(FileEntry { depot_path, mod_time });
         }
     }
 
-    // Submit updated form
-    let mut cmd = tokio::process::Command::new("p4");
-    apply_connection_args(&mut cmd, &server, &user, &client);
-    cmd.args(["change", "-i"]);
-    cmd.stdin(Stdio::piped());
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+    (updated, removed)
+}
 
-    // On Windows, hide the console window (CREATE_NO_WINDOW flag)
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(0x08000000);
+/// Build content index documents from `fstat -Rh` records: text files synced
+/// into the workspace, with their local paths and have revisions.
+pub(super) fn content_docs_from_records(records: Vec<HashMap<String, String>>) -> Vec<ContentDoc> {
+    records
+        .into_iter()
+        .filter_map(|record| {
+            let file_type = record.get("headType").or_else(|| record.get("type"))?;
+            if !is_text_type(file_type) {
+                return None;
+            }
+            Some(ContentDoc {
+                depot_path: record.get("depotFile")?.clone(),
+                // "path" is the local path; "clientFile" may be in //client/... syntax
+                local_path: record.get("path").or_else(|| record.get("clientFile"))?.into(),
+                revision: record.get("haveRev")?.parse().ok()?,
+            })
+        })
+        .collect()
+}
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn p4 change: {}", e))?;
+/// Update changelist description (used by submit and edit_change_description)
+pub(super) async fn update_changelist_description(
+    executor: &dyn P4Executor,
+    changelist: i32,
+    description: &str,
+    connection: &ConnectionProfile,
+) -> Result<(), P4Error> {
+    let mut form = fetch_spec_form(executor, &["change", "-o", &changelist.to_string()], connection).await?;
+    form.set_text("Description", description);
+    submit_spec_form(executor, "change", &form, connection).await?;
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(new_form.as_bytes())
-            .await
-            .map_err(|e| format!("Failed to write changelist form: {}", e))?;
+    Ok(())
+}
+
+/// Run p4 opened -a over `scope` (everything when None), listing every workspace's opens
+pub(super) async fn query_opened_all(
+    executor: &dyn P4Executor,
+    connection: &ConnectionProfile,
+    scope: Option<&str>,
+    max_files: Option<u32>,
+) -> Result<Vec<P4OpenedFile>, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    let format = RecordFormat::Marshal;
+    cmd.args([format.flag(), "opened", "-a"]);
+    if let Some(max) = max_files {
+        cmd.arg("-m").arg(max.to_string());
+    }
+    if let Some(scope) = scope {
+        cmd.arg(scope);
     }
 
-    let result = child
-        .wait_with_output()
+    let output = executor
+        .output(&cmd)
         .await
-        .map_err(|e| format!("Failed to update changelist: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 opened -a: {}", e)))?;
 
-    if !result.status.success() {
-        let stderr = String::from_utf8_lossy(&result.stderr);
-        return Err(stderr.to_string());
+    // "file(s) not opened anywhere" is an empty result
+    match format.parse_output(&output) {
+        Ok(records) => Ok(opened_files_from_records(&records)),
+        Err(err) if err.is(P4ErrorKind::NoSuchFile) || err.stderr.contains("not opened") => Ok(Vec::new()),
+        Err(err) => Err(err),
     }
-
-    Ok(())
 }
@@ -0,0 +1,201 @@
+use crate::state::{ConnectionProfile, P4Command, P4Executor};
+use super::error::{P4Error, P4ErrorKind};
+use super::parsing::spec_field_errors;
+
+/// Value of one field of a spec form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum SpecValue {
+    /// Written on the field line: "Owner:\talice"
+    Line(String),
+    /// Written as tab-indented lines below the field: views, paths and free text
+    Lines(Vec<String>),
+}
+
+/// A Perforce spec form, as read from `p4 <spec> -o` and sent back with `p4 <spec> -i`.
+/// Fields keep their order; comments are dropped (p4 ignores them on input).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct SpecForm {
+    fields: Vec<(String, SpecValue)>,
+}
+
+impl SpecForm {
+    /// Parse form text. A field starts with "Name:" at column 0; its value is the rest of
+    /// that line, or the tab-indented lines that follow (one leading tab removed).
+    /// Blank lines inside an indented block are kept as empty lines.
+    pub(super) fn parse(text: &str) -> Self {
+        let mut fields: Vec<(String, SpecValue)> = Vec::new();
+        let mut blank_lines = 0;
+
+        for line in text.lines() {
+            if let Some(value) = line.strip_prefix('\t') {
+                match fields.last_mut() {
+                    Some((_, SpecValue::Lines(lines))) => {
+                        lines.extend(std::iter::repeat_n(String::new(), blank_lines));
+                        lines.push(value.to_string());
+                    }
+                    // Continuation of a value that started on the field line
+                    Some((_, SpecValue::Line(first))) => {
+                        let first = std::mem::take(first);
+                        let mut lines = vec![first];
+                        lines.extend(std::iter::repeat_n(String::new(), blank_lines));
+                        lines.push(value.to_string());
+                        fields.last_mut().unwrap().1 = SpecValue::Lines(lines);
+                    }
+                    None => {}
+                }
+                blank_lines = 0;
+                continue;
+            }
+
+            if line.trim().is_empty() {
+                blank_lines += 1;
+                continue;
+            }
+            blank_lines = 0;
+            if line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = form_field_name(line) {
+                let rest = line[name.len() + 1..].trim();
+                let value = if rest.is_empty() {
+                    SpecValue::Lines(Vec::new())
+                } else {
+                    SpecValue::Line(rest.to_string())
+                };
+                fields.push((name.to_string(), value));
+            }
+        }
+
+        Self { fields }
+    }
+
+    /// Field value, if the form has the field
+    pub(super) fn get(&self, name: &str) -> Option<&SpecValue> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Single-line value; a multi-line value yields its first line
+    pub(super) fn line(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            SpecValue::Line(value) => Some(value),
+            SpecValue::Lines(lines) => lines.first().map(String::as_str),
+        }
+    }
+
+    /// Multi-line value as lines; a single-line value is one line
+    pub(super) fn lines(&self, name: &str) -> Vec<String> {
+        match self.get(name) {
+            Some(SpecValue::Line(value)) => vec![value.clone()],
+            Some(SpecValue::Lines(lines)) => lines.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Set a field, replacing it in place or appending it at the end.
+    /// An empty line is stored as an empty block, which is how it parses back.
+    pub(super) fn set(&mut self, name: &str, value: SpecValue) {
+        let value = match value {
+            SpecValue::Line(line) if line.is_empty() => SpecValue::Lines(Vec::new()),
+            value => value,
+        };
+        match self.fields.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    pub(super) fn set_line(&mut self, name: &str, value: impl Into<String>) {
+        self.set(name, SpecValue::Line(value.into()));
+    }
+
+    pub(super) fn set_lines(&mut self, name: &str, lines: Vec<String>) {
+        self.set(name, SpecValue::Lines(lines));
+    }
+
+    /// Set free text, one form line per text line
+    pub(super) fn set_text(&mut self, name: &str, text: &str) {
+        self.set_lines(name, text.lines().map(str::to_string).collect());
+    }
+
+    pub(super) fn remove(&mut self, name: &str) {
+        self.fields.retain(|(n, _)| n != name);
+    }
+
+    /// Form text for `p4 <spec> -i`. Parsing the result gives back the same form.
+    pub(super) fn to_form(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.fields {
+            match value {
+                SpecValue::Line(value) => out.push_str(&format!("{}:\t{}\n", name, value)),
+                SpecValue::Lines(lines) => {
+                    out.push_str(&format!("{}:\n", name));
+                    for line in lines {
+                        out.push('\t');
+                        out.push_str(line);
+                        out.push('\n');
+                    }
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Field name if `line` starts a field ("Name:" at column 0), not a value or comment.
+/// Jobspecs can define names with `_` and `-`, e.g. "Fix_Version" or "Due-Date".
+pub(super) fn form_field_name(line: &str) -> Option<&str> {
+    let (name, _) = line.split_once(':')?;
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    (!name.is_empty() && name.chars().all(is_name_char)).then_some(name)
+}
+
+/// Spec form from `p4 <spec> -o ...`, for editing and sending back with [`submit_spec_form`]
+pub(super) async fn fetch_spec_form(
+    executor: &dyn P4Executor,
+    args: &[&str],
+    connection: &ConnectionProfile,
+) -> Result<SpecForm, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args(args);
+
+    let output = executor
+        .output(&cmd)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 {}: {}", args.join(" "), e)))?;
+
+    if !output.success() {
+        return Err(P4Error::from_output(&output));
+    }
+    Ok(SpecForm::parse(&output.stdout_text()))
+}
+
+/// Send a spec form with `p4 <spec> -i`, returning p4's confirmation ("Stream //x saved.").
+/// Rejections carry the offending field.
+pub(super) async fn submit_spec_form(
+    executor: &dyn P4Executor,
+    spec: &str,
+    form: &SpecForm,
+    connection: &ConnectionProfile,
+) -> Result<String, P4Error> {
+    let mut cmd = P4Command::new();
+    connection.apply(&mut cmd);
+    cmd.args([spec, "-i"]);
+
+    let form = form.to_form();
+    let output = executor
+        .output_with_input(&cmd, &form)
+        .await
+        .map_err(|e| P4Error::other(format!("Failed to execute p4 {} -i: {}", spec, e)))?;
+
+    if !output.success() {
+        let mut err = P4Error::from_output(&output);
+        if err.is(P4ErrorKind::InvalidSpec) {
+            err.field_errors = spec_field_errors(&err.stderr, &form);
+        }
+        return Err(err);
+    }
+    Ok(output.stdout_text().trim().to_string())
+}
@@ -0,0 +1,160 @@
+use encoding_rs::Encoding;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::state::{decode_text, ExitReason, P4OutputStream, ProcessExit};
+use super::error::{classify_stderr, P4ErrorKind};
+
+/// How a streamed command ended.
+/// Every streaming command sends exactly one of these, as its last message,
+/// once stdout and stderr are drained and the process has been reaped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamCompletion {
+    pub success: bool,
+    /// Stopped through kill_process / kill_all_processes
+    pub cancelled: bool,
+    /// None when killed or the exit status could not be read
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Classification of `error`, when it came from p4
+    pub error_kind: Option<P4ErrorKind>,
+}
+
+/// Stderr lines of a streamed command, split by the command's own rules
+#[derive(Debug, Default)]
+pub struct StderrSummary {
+    /// Lines that make the command fail
+    pub errors: Vec<String>,
+    /// Informational lines (e.g. "no such file(s)", "file(s) up-to-date").
+    /// p4 still exits non-zero for some of these.
+    pub ignored: usize,
+}
+
+impl StreamCompletion {
+    /// Derive the completion from the process exit and its stderr.
+    /// `exit` is None if the supervisor went away without reporting.
+    pub fn new(exit: Option<ProcessExit>, stderr: StderrSummary) -> Self {
+        let Some(exit) = exit else {
+            return Self::failed(None, "Lost track of the p4 process".to_string(), None);
+        };
+
+        match exit.reason {
+            ExitReason::Killed => Self {
+                success: false,
+                cancelled: true,
+                exit_code: None,
+                error: None,
+                error_kind: None,
+            },
+            ExitReason::TimedOut | ExitReason::Failed => Self::failed(
+                exit.exit_code,
+                exit.error.unwrap_or_else(|| "p4 process failed".to_string()),
+                None,
+            ),
+            ExitReason::Exited if !stderr.errors.is_empty() => {
+                let error = stderr.errors.join("\n");
+                let kind = classify_stderr(&error);
+                Self::failed(exit.exit_code, error, Some(kind))
+            }
+            // A non-zero exit is expected when p4 only reported informational messages
+            ExitReason::Exited if exit.exit_code == Some(0) || stderr.ignored > 0 => Self {
+                success: true,
+                cancelled: false,
+                exit_code: exit.exit_code,
+                error: None,
+                error_kind: None,
+            },
+            ExitReason::Exited => Self::failed(
+                exit.exit_code,
+                format!("p4 exited with code: {:?}", exit.exit_code),
+                None,
+            ),
+        }
+    }
+
+    fn failed(exit_code: Option<i32>, error: String, error_kind: Option<P4ErrorKind>) -> Self {
+        Self {
+            success: false,
+            cancelled: false,
+            exit_code,
+            error: Some(error),
+            error_kind,
+        }
+    }
+}
+
+/// Lines of a p4 output stream, decoded from the command's charset.
+/// Unlike `AsyncBufReadExt::lines`, bytes that don't decode don't end the stream.
+pub struct TextLines {
+    reader: BufReader<P4OutputStream>,
+    encoding: Option<&'static Encoding>,
+    buf: Vec<u8>,
+}
+
+impl TextLines {
+    pub fn new(stream: P4OutputStream, encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+            encoding,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Next line without its line ending; None at the end of the stream or on a read error
+    pub async fn next_line(&mut self) -> Option<String> {
+        self.buf.clear();
+        match self.reader.read_until(b'\n', &mut self.buf).await {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                let line = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                Some(decode_text(line, self.encoding))
+            }
+        }
+    }
+}
+
+/// Drain stderr in its own task. `is_error` sees every line and decides whether
+/// it fails the command; it may also forward the line to the frontend.
+pub fn collect_stderr<F>(
+    stderr: Option<P4OutputStream>,
+    encoding: Option<&'static Encoding>,
+    mut is_error: F,
+) -> JoinHandle<StderrSummary>
+where
+    F: FnMut(&str) -> bool + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut summary = StderrSummary::default();
+        let Some(stderr) = stderr else {
+            return summary;
+        };
+        let mut lines = TextLines::new(stderr, encoding);
+        while let Some(line) = lines.next_line().await {
+            if is_error(&line) {
+                summary.errors.push(line);
+            } else {
+                summary.ignored += 1;
+            }
+        }
+        summary
+    })
+}
+
+/// Stderr rule shared by read-only queries: missing files are not a failure
+pub fn is_query_error(line: &str) -> bool {
+    classify_stderr(line) != P4ErrorKind::NoSuchFile
+}
+
+/// Wait for the stderr task and the process exit, then derive the completion.
+/// Call after stdout has been drained.
+pub async fn finish_stream(
+    exited: oneshot::Receiver<ProcessExit>,
+    stderr: JoinHandle<StderrSummary>,
+) -> StreamCompletion {
+    let stderr = stderr.await.unwrap_or_default();
+    StreamCompletion::new(exited.await.ok(), stderr)
+}
@@ -1,6 +1,32 @@
 use std::collections::HashMap;
 
+use super::error::*;
+use super::marshal::*;
+use super::opened_cache::OpenedCache;
 use super::parsing::*;
+use super::spec::{SpecForm, SpecValue};
+use super::stream::*;
+use super::types::*;
+use crate::diff::DiffLineKind;
+use crate::state::{ExitReason, P4Output, ProcessExit};
+
+// -ztag text entry points for the record builders, so fixtures can stay readable
+
+fn parse_ztag_fstat(output: &str) -> Result<Vec<P4FileInfo>, String> {
+    Ok(file_infos_from_records(parse_ztag_records(output)))
+}
+
+fn parse_ztag_changes(output: &str) -> Result<Vec<P4Changelist>, String> {
+    Ok(changelists_from_records(parse_ztag_records(output)))
+}
+
+fn parse_ztag_filelog(output: &str) -> Result<Vec<P4Revision>, String> {
+    Ok(revisions_from_records(parse_ztag_records(output)))
+}
+
+fn parse_ztag_describe_shelved(output: &str) -> Result<Vec<P4ShelvedFile>, String> {
+    Ok(shelved_files_from_records(parse_ztag_records(output)))
+}
 
 #[test]
 fn test_parse_ztag_records_single_record() {
@@ -79,6 +105,17 @@ fn test_parse_ztag_info() {
     assert_eq!(info.client_stream, Some("//depot/main".to_string()));
     assert_eq!(info.user_name, "john_doe");
     assert_eq!(info.server_address, "perforce:1666");
+    assert!(!info.server_unicode);
+
+    let unicode = parse_ztag_info("... clientRoot /ws\n... unicode enabled\n").unwrap();
+    assert!(unicode.server_unicode);
+}
+
+#[test]
+fn test_parse_p4_set_value() {
+    assert_eq!(parse_p4_set_value("P4CHARSET=shiftjis\n", "P4CHARSET"), Some("shiftjis".to_string()));
+    assert_eq!(parse_p4_set_value("P4PORT=main:1666\n", "P4CHARSET"), None);
+    assert_eq!(parse_p4_set_value("", "P4CHARSET"), None);
 }
 
 #[test]
@@ -374,6 +411,143 @@ fn test_build_file_info_missing_required_fields() {
     assert!(result.is_none());
 }
 
+#[test]
+fn test_build_file_info_other_opens_and_locks() {
+    let fields: HashMap<String, String> = [
+        ("depotFile", "//depot/art/hero.psd"),
+        ("clientFile", "/ws/art/hero.psd"),
+        ("headType", "binary+l"),
+        ("otherOpen", "2"),
+        ("otherOpen0", "bob@bob-ws"),
+        ("otherAction0", "edit"),
+        ("otherChange0", "default"),
+        ("otherOpen1", "carol@carol-laptop"),
+        ("otherAction1", "edit"),
+        ("otherChange1", "42"),
+        ("otherLock", ""),
+        ("otherLock0", "carol@carol-laptop"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    let info = build_file_info(&fields).unwrap();
+    assert_eq!(info.other_open.len(), 2);
+    assert_eq!(info.other_open[0].user, "bob");
+    assert_eq!(info.other_open[0].client, "bob-ws");
+    assert_eq!(info.other_open[0].changelist.as_deref(), Some("default"));
+    let lock = info.other_lock.as_ref().unwrap();
+    assert_eq!((lock.user.as_str(), lock.changelist.as_deref()), ("carol", Some("42")));
+    assert!(!info.our_lock);
+
+    // The lock holder is named ahead of other opens
+    assert_eq!(
+        exclusive_open_conflicts(std::slice::from_ref(&info)),
+        ["//depot/art/hero.psd is exclusively opened by carol@carol-laptop"]
+    );
+
+    // Files we have open ourselves, or that aren't +l, are no conflict
+    let ours = P4FileInfo { action: Some("edit".to_string()), our_lock: true, ..info.clone() };
+    let text = P4FileInfo { file_type: "text".to_string(), ..info };
+    assert!(exclusive_open_conflicts(&[ours, text]).is_empty());
+}
+
+#[test]
+fn test_is_exclusive_type() {
+    assert!(is_exclusive_type("binary+l"));
+    assert!(is_exclusive_type("text+lw"));
+    assert!(!is_exclusive_type("ltext"));
+    assert!(!is_exclusive_type("binary+S"));
+}
+
+#[test]
+fn test_parse_opened_all() {
+    let records = parse_ztag_records(
+        "... depotFile //depot/art/hero.psd\n\
+         ... rev 4\n\
+         ... action edit\n\
+         ... change default\n\
+         ... type binary+l\n\
+         ... user alice\n\
+         ... client alice-ws\n\
+         \n\
+         ... depotFile //depot/src/main.cpp\n\
+         ... rev 12\n\
+         ... action edit\n\
+         ... change 345\n\
+         ... type text\n\
+         ... user bob\n\
+         ... client bob-ws\n\
+         ... ourLock \n\
+         \n\
+         ... depotFile //depot/src/util.cpp\n\
+         ... action add\n\
+         ... change 346\n\
+         ... type text\n\
+         ... user carol\n\
+         ... client carol-ws\n",
+    );
+
+    let files = opened_files_from_records(&records);
+
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[0].user, "alice");
+    assert_eq!(files[0].client, "alice-ws");
+    assert_eq!(files[0].changelist, "default");
+    assert_eq!(files[0].revision, Some(4));
+    assert!(files[0].exclusive);
+    assert!(!files[0].locked, "an open +l file is not locked until p4 lock");
+    assert_eq!(files[1].changelist, "345");
+    assert!(files[1].locked);
+    assert!(!files[1].exclusive);
+    assert_eq!(files[2].action, "add");
+    assert_eq!(files[2].revision, None);
+    assert!(!files[2].locked);
+}
+
+fn opened_file(depot_path: &str, user: &str) -> P4OpenedFile {
+    P4OpenedFile {
+        depot_path: depot_path.to_string(),
+        user: user.to_string(),
+        client: format!("{}-ws", user),
+        changelist: "default".to_string(),
+        action: "edit".to_string(),
+        file_type: None,
+        revision: Some(1),
+        locked: false,
+        exclusive: false,
+    }
+}
+
+#[test]
+fn test_opened_cache_groups_listing_by_path() {
+    let cache = OpenedCache::new(std::time::Duration::from_secs(60));
+    let conn = Some("work".to_string());
+    cache.insert_listing(
+        &conn,
+        &[
+            opened_file("//depot/a.txt", "alice"),
+            opened_file("//depot/a.txt", "bob"),
+            opened_file("//depot/b.txt", "bob"),
+        ],
+    );
+
+    let opens = cache.get(&conn, "//depot/a.txt").unwrap();
+    assert_eq!(opens.iter().map(|o| o.user.as_str()).collect::<Vec<_>>(), ["alice", "bob"]);
+    assert_eq!(cache.get(&conn, "//depot/b.txt").unwrap().len(), 1);
+    // Not in the listing, and cached per connection
+    assert!(cache.get(&conn, "//depot/c.txt").is_none());
+    assert!(cache.get(&None, "//depot/a.txt").is_none());
+}
+
+#[test]
+fn test_opened_cache_expires_entries() {
+    let cache = OpenedCache::new(std::time::Duration::ZERO);
+    cache.insert(&None, "//depot/a.txt", Vec::new());
+
+    assert!(cache.get(&None, "//depot/a.txt").is_none());
+}
+
 #[test]
 fn test_build_changelist_missing_required_fields() {
     let mut fields = HashMap::new();
@@ -526,6 +700,29 @@ fn test_parse_ztag_describe_shelved_with_metadata_split() {
     assert_eq!(files[0].revision, 5);
 }
 
+#[test]
+fn test_describe_from_records_lists_fixed_jobs() {
+    let input = r#"... change 120
+... user alice
+... client alice-ws
+... time 1770178673
+... desc Fix crash
+... status submitted
+... depotFile0 //depot/main/app.cpp
+... action0 edit
+... type0 text
+... rev0 7
+... job0 job000012
+... jobstat0 closed
+... job1 job000015
+... jobstat1 open
+"#;
+    let description = describe_from_records(parse_ztag_records(input), 120).unwrap();
+    assert_eq!(description.files.len(), 1);
+    let jobs: Vec<_> = description.jobs.iter().map(|j| (j.job.as_str(), j.status.as_str())).collect();
+    assert_eq!(jobs, [("job000012", "closed"), ("job000015", "open")]);
+}
+
 #[test]
 fn test_parse_ztag_streams() {
     let input = r#"... Stream //depot/main
@@ -533,6 +730,8 @@ fn test_parse_ztag_streams() {
 ... Type mainline
 ... desc Main development stream
 
+... Parent none
+
 ... Stream //depot/release
 ... Name release
 ... Parent //depot/main
@@ -570,9 +769,8 @@ fn test_parse_ztag_client_spec() {
 ... View0 //depot/main/... //my_workspace/...
 ... View1 -//depot/main/exclude/... //my_workspace/exclude/...
 "#;
-    let result = parse_ztag_client_spec(input);
-    assert!(result.is_ok());
-    let spec = result.unwrap();
+    let records = parse_ztag_records(input);
+    let spec = build_client_spec(&records[0]).unwrap();
 
     assert_eq!(spec.client, "my_workspace");
     assert_eq!(spec.root, "C:\\workspace");
@@ -585,6 +783,258 @@ fn test_parse_ztag_client_spec() {
     assert_eq!(spec.view.len(), 2);
     assert_eq!(spec.view[0], "//depot/main/... //my_workspace/...");
     assert_eq!(spec.view[1], "-//depot/main/exclude/... //my_workspace/exclude/...");
+    assert_eq!(spec.line_end, "local");
+}
+
+fn client_spec(view: &[&str]) -> P4ClientSpec {
+    P4ClientSpec {
+        client: "alice-ws".to_string(),
+        root: "/home/alice/ws".to_string(),
+        stream: None,
+        owner: "alice".to_string(),
+        description: "".to_string(),
+        options: "noallwrite noclobber nocompress unlocked nomodtime normdir".to_string(),
+        host: "".to_string(),
+        submit_options: "submitunchanged".to_string(),
+        view: view.iter().map(|v| v.to_string()).collect(),
+        line_end: "local".to_string(),
+    }
+}
+
+#[test]
+fn test_validate_client_spec_accepts_quoted_and_excluded_mappings() {
+    let spec = client_spec(&[
+        "//depot/main/... //alice-ws/main/...",
+        "\"//depot/main/My Docs/...\" \"//alice-ws/main/My Docs/...\"",
+        "-//depot/main/tmp/... //alice-ws/main/tmp/...",
+    ]);
+    assert!(validate_client_spec(&spec).is_empty());
+}
+
+#[test]
+fn test_validate_client_spec_reports_each_field() {
+    let mut spec = client_spec(&["//depot/main/...", "//depot/main/... //other-ws/..."]);
+    spec.owner = "".to_string();
+    spec.line_end = "dos".to_string();
+    let fields: Vec<_> = validate_client_spec(&spec)
+        .into_iter()
+        .map(|e| e.field.unwrap())
+        .collect();
+    assert_eq!(fields, ["Owner", "LineEnd", "View", "View"]);
+}
+
+#[test]
+fn test_validate_client_spec_stream_workspace_needs_no_view() {
+    let mut spec = client_spec(&[]);
+    spec.stream = Some("//stream/main".to_string());
+    assert!(validate_client_spec(&spec).is_empty());
+
+    spec.stream = None;
+    let errors = validate_client_spec(&spec);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field.as_deref(), Some("View"));
+}
+
+#[test]
+fn test_apply_client_spec_replaces_view_and_keeps_other_fields() {
+    let form = "# A Perforce Client Specification.\n\nClient:\talice-ws\n\nUpdate:\t2024/01/01 00:00:00\n\n\
+                Owner:\talice\n\nDescription:\n\tCreated by alice.\n\nRoot:\t/tmp\n\n\
+                Options:\tallwrite clobber nocompress unlocked nomodtime normdir\n\n\
+                SubmitOptions:\tsubmitunchanged\n\nLineEnd:\tlocal\n\nView:\n\t//depot/... //alice-ws/...\n";
+    let mut form = SpecForm::parse(form);
+    let mut spec = client_spec(&["//depot/main/... //alice-ws/main/...", "//depot/lib/... //alice-ws/lib/..."]);
+    spec.description = "Line one\n\nFiles: not a field".to_string();
+    apply_client_spec(&mut form, &spec);
+
+    let text = form.to_form();
+    assert!(text.contains("Update:\t2024/01/01 00:00:00\n"));
+    assert!(text.contains("Description:\n\tLine one\n\t\n\tFiles: not a field\n"));
+    assert!(text.contains("View:\n\t//depot/main/... //alice-ws/main/...\n\t//depot/lib/... //alice-ws/lib/...\n"));
+    assert!(!text.contains("Stream:"));
+    assert_eq!(SpecForm::parse(&text), form);
+}
+
+#[test]
+fn test_spec_form_parse_multiline_and_round_trip() {
+    let text = "# comment\nChange:\tnew\n\nDescription:\n\tfirst\n\n\tthird: with colon\n\nFiles:\n\t//depot/a.txt\t# edit\n";
+    let form = SpecForm::parse(text);
+    assert_eq!(form.line("Change"), Some("new"));
+    assert_eq!(form.lines("Description"), ["first", "", "third: with colon"]);
+    assert_eq!(form.lines("Files"), ["//depot/a.txt\t# edit"]);
+    assert_eq!(form.get("Jobs"), None);
+    assert_eq!(SpecForm::parse(&form.to_form()), form);
+}
+
+#[test]
+fn test_spec_form_set_and_remove() {
+    let mut form = SpecForm::parse("Client:\tws\n\nStream:\t//s/main\n\nView:\n\t//a/... //ws/...\n");
+    form.set_line("Stream", "//s/dev");
+    form.remove("View");
+    form.set_line("Host", "");
+    assert_eq!(form.to_form(), "Client:\tws\n\nStream:\t//s/dev\n\nHost:\n\n");
+}
+
+const CHANGE_FORM: &str = "# A Perforce Change Specification.\n#\n#  Change:      The change number. 'new' on a new changelist.\n\n\
+                           Change:\t1234\n\nDate:\t2024/03/01 12:00:00\n\nClient:\talice-ws\n\nUser:\talice\n\nStatus:\tpending\n\n\
+                           Description:\n\tFix the build\n\tFiles: still part of the description\n\t\tindented with a tab\n\n\
+                           Files:\n\t//depot/main/a.c\t# edit\n\t//depot/main/b c.h\t# add\n";
+
+#[test]
+fn test_spec_form_parses_change_form() {
+    let form = SpecForm::parse(CHANGE_FORM);
+    assert_eq!(form.line("Change"), Some("1234"));
+    assert_eq!(form.line("Status"), Some("pending"));
+    assert_eq!(
+        form.lines("Description"),
+        ["Fix the build", "Files: still part of the description", "\tindented with a tab"]
+    );
+    assert_eq!(form.lines("Files"), ["//depot/main/a.c\t# edit", "//depot/main/b c.h\t# add"]);
+}
+
+#[test]
+fn test_spec_form_round_trip_is_lossless() {
+    let form = SpecForm::parse(CHANGE_FORM);
+    let text = form.to_form();
+    assert!(text.starts_with("Change:\t1234\n\nDate:\t2024/03/01 12:00:00\n"));
+    assert_eq!(SpecForm::parse(&text), form);
+    assert_eq!(SpecForm::parse(&text).to_form(), text);
+}
+
+#[test]
+fn test_spec_form_keeps_field_order_and_unknown_fields() {
+    let form = SpecForm::parse("Label:\tv1\n\nRevision:\t@1234\n\nExtraTag:\tcustom\n\nView:\n\t//depot/...\n");
+    let text = form.to_form();
+    assert_eq!(text, "Label:\tv1\n\nRevision:\t@1234\n\nExtraTag:\tcustom\n\nView:\n\t//depot/...\n\n");
+}
+
+#[test]
+fn test_spec_form_custom_job_fields_round_trip() {
+    let text = "Job:\tjob000123\n\nStatus:\topen\n\nFix_Version:\t2.1\n\nDue-Date:\t2024/06/01\n\n\
+                Release_Notes:\n\tFirst line\n\tSecond line\n\nDescription:\n\tCrash on load\n\n";
+    let form = SpecForm::parse(text);
+
+    assert_eq!(form.line("Status"), Some("open"));
+    assert_eq!(form.line("Fix_Version"), Some("2.1"));
+    assert_eq!(form.line("Due-Date"), Some("2024/06/01"));
+    assert_eq!(form.lines("Release_Notes"), ["First line", "Second line"]);
+    assert_eq!(form.lines("Description"), ["Crash on load"]);
+    assert_eq!(form.to_form(), text);
+}
+
+#[test]
+fn test_spec_form_description_edit_replaces_only_description() {
+    let mut form = SpecForm::parse(CHANGE_FORM);
+    form.set_text("Description", "New summary\n\nFiles:\tnot a field\n\tand a tab");
+    let reparsed = SpecForm::parse(&form.to_form());
+
+    assert_eq!(reparsed.lines("Description"), ["New summary", "", "Files:\tnot a field", "\tand a tab"]);
+    assert_eq!(reparsed.lines("Files").len(), 2);
+    assert_eq!(reparsed.line("User"), Some("alice"));
+}
+
+#[test]
+fn test_spec_form_single_line_value_continued_on_tab_lines() {
+    // p4 user -o writes some values on the field line and continues them when long
+    let form = SpecForm::parse("Reviews:\t//depot/a/...\n\t//depot/b/...\n\nEmail:\talice@example.com\n");
+    assert_eq!(form.lines("Reviews"), ["//depot/a/...", "//depot/b/..."]);
+    assert_eq!(form.line("Reviews"), Some("//depot/a/..."));
+    assert_eq!(form.lines("Email"), ["alice@example.com"]);
+}
+
+#[test]
+fn test_spec_form_empty_and_missing_fields() {
+    let mut form = SpecForm::parse("Jobs:\n\nFiles:\n");
+    assert_eq!(form.get("Jobs"), Some(&SpecValue::Lines(Vec::new())));
+    assert_eq!(form.line("Jobs"), None);
+    assert!(form.lines("Missing").is_empty());
+
+    form.set_lines("Jobs", vec!["job000123".to_string()]);
+    form.set_line("Type", "public");
+    assert_eq!(form.to_form(), "Jobs:\n\tjob000123\n\nFiles:\n\nType:\tpublic\n\n");
+}
+
+#[test]
+fn test_spec_form_ignores_comments_and_stray_lines() {
+    let form = SpecForm::parse("\t orphan value\n# Owner: not a field\nnot a field line\nOwner:\talice\n");
+    assert_eq!(form, SpecForm::parse("Owner:\talice\n"));
+}
+
+#[test]
+fn test_validate_label_spec() {
+    let spec = P4LabelSpec {
+        label: "release-1.3".to_string(),
+        owner: "build".to_string(),
+        description: String::new(),
+        options: "unlocked noautoreload".to_string(),
+        revision: Some("@2000".to_string()),
+        view: vec!["//depot/main/...".to_string(), "\"-//depot/main/My Docs/...\"".to_string()],
+        update: None,
+        access: None,
+    };
+    assert!(validate_label_spec(&spec).is_empty());
+
+    let spec = P4LabelSpec {
+        label: "1234".to_string(),
+        options: "unlocked sometimes".to_string(),
+        revision: Some("2000".to_string()),
+        view: vec!["depot/main/...".to_string()],
+        ..spec
+    };
+    let fields: Vec<_> = validate_label_spec(&spec).into_iter().map(|e| e.field.unwrap()).collect();
+    assert_eq!(fields, ["Label", "Options", "Revision", "View"]);
+}
+
+#[test]
+fn test_view_mapping_round_trip() {
+    let lines = [
+        "//depot/main/... //depot/rel/...",
+        "-//depot/main/tests/... //depot/rel/tests/...",
+        "+//depot/overlay/... //depot/rel/...",
+        "\"//depot/main/My Docs/...\" \"//depot/rel/My Docs/...\"",
+        "\"-//depot/main/Old Stuff/...\" \"//depot/rel/Old Stuff/...\"",
+    ];
+    for line in lines {
+        let mapping = parse_view_mapping(line).unwrap();
+        assert_eq!(format_view_mapping(&mapping), line);
+    }
+
+    let excluded = parse_view_mapping(lines[4]).unwrap();
+    assert_eq!(excluded.kind, MappingKind::Exclude);
+    assert_eq!(excluded.source, "//depot/main/Old Stuff/...");
+    assert_eq!(excluded.target, "//depot/rel/Old Stuff/...");
+    assert!(parse_view_mapping("//depot/main/...").is_none());
+}
+
+#[test]
+fn test_validate_branch_spec() {
+    let spec = P4BranchSpec {
+        branch: "main-to-rel".to_string(),
+        owner: "build".to_string(),
+        description: String::new(),
+        options: "unlocked".to_string(),
+        view: vec![parse_view_mapping("//depot/main/... //depot/rel/...").unwrap()],
+        update: None,
+        access: None,
+    };
+    assert!(validate_branch_spec(&spec).is_empty());
+
+    let spec = P4BranchSpec {
+        options: "open".to_string(),
+        view: vec![parse_view_mapping("//depot/main/... rel/...").unwrap()],
+        ..spec
+    };
+    let fields: Vec<_> = validate_branch_spec(&spec).into_iter().map(|e| e.field.unwrap()).collect();
+    assert_eq!(fields, ["Options", "View"]);
+}
+
+#[test]
+fn test_with_revision_spec() {
+    assert_eq!(with_revision_spec("//depot/...", None).unwrap(), "//depot/...");
+    assert_eq!(with_revision_spec("//depot/...", Some(" ")).unwrap(), "//depot/...");
+    assert_eq!(with_revision_spec("//depot/...", Some("@rel-1")).unwrap(), "//depot/...@rel-1");
+    assert_eq!(with_revision_spec("//depot/...", Some("rel-1")).unwrap(), "//depot/...@rel-1");
+    assert_eq!(with_revision_spec("//depot/a.c", Some("#head")).unwrap(), "//depot/a.c#head");
+    assert!(with_revision_spec("//depot/...", Some("@rel 1")).is_err());
 }
 
 #[test]
@@ -689,3 +1139,852 @@ C:\workspace\file3.cpp - opened for delete
     assert_eq!(previews[2].depot_path, "C:\\workspace\\file3.cpp");
     assert_eq!(previews[2].action, "delete");
 }
+
+#[test]
+fn test_classify_stderr_connection_refused() {
+    let stderr = "Perforce client error:\n\tConnect to server failed; check $P4PORT.\n\tTCP connect to perforce:1666 failed.\n";
+    assert_eq!(classify_stderr(stderr), P4ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn test_classify_stderr_login_required() {
+    assert_eq!(
+        classify_stderr("Perforce password (P4PASSWD) invalid or unset.\n"),
+        P4ErrorKind::LoginRequired
+    );
+    assert_eq!(
+        classify_stderr("Your session has expired, please login again.\n"),
+        P4ErrorKind::LoginRequired
+    );
+}
+
+#[test]
+fn test_classify_stderr_trust_required() {
+    let unknown = "The authenticity of '10.0.0.1:1666' can't be established,\nthis may be your first attempt to connect to this P4PORT.\nThe fingerprint for the key sent to your client is\nAB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:AB:CD:EF:01\nTo allow connection use the 'p4 trust' command.\n";
+    assert_eq!(classify_stderr(unknown), P4ErrorKind::TrustRequired);
+    assert_eq!(
+        classify_stderr("******* WARNING P4PORT IDENTIFICATION HAS CHANGED! *******\n"),
+        P4ErrorKind::TrustRequired
+    );
+}
+
+#[test]
+fn test_classify_stderr_charset_mismatch() {
+    assert_eq!(
+        classify_stderr("Unicode server permits only unicode enabled clients.\n"),
+        P4ErrorKind::CharsetMismatch
+    );
+    assert_eq!(
+        classify_stderr("Unicode clients require a unicode enabled server.\n"),
+        P4ErrorKind::CharsetMismatch
+    );
+}
+
+#[test]
+fn test_classify_stderr_file_states() {
+    assert_eq!(
+        classify_stderr("//depot/main/file.cpp - no such file(s).\n"),
+        P4ErrorKind::NoSuchFile
+    );
+    assert_eq!(
+        classify_stderr("Change 123 has no shelved files.\n"),
+        P4ErrorKind::NoSuchFile
+    );
+    assert_eq!(
+        classify_stderr("//depot/art/hero.psd - can't edit exclusive file already opened\n"),
+        P4ErrorKind::FileLocked
+    );
+    assert_eq!(
+        classify_stderr("Merges still pending -- use 'resolve' to merge files.\n//depot/main/file.cpp - must resolve before submitting\n"),
+        P4ErrorKind::NeedsResolve
+    );
+    assert_eq!(
+        classify_stderr("//depot/main/file.cpp - must sync/resolve #5 before submitting.\n"),
+        P4ErrorKind::OutOfDate
+    );
+    assert_eq!(
+        classify_stderr("Out of date files must be resolved or reverted.\n"),
+        P4ErrorKind::NeedsResolve
+    );
+    assert_eq!(
+        classify_stderr("You don't have permission for this operation.\n"),
+        P4ErrorKind::PermissionDenied
+    );
+    assert_eq!(classify_stderr("Something unexpected\n"), P4ErrorKind::Unknown);
+}
+
+#[test]
+fn test_p4_error_from_stderr() {
+    let err = P4Error::from_stderr("Perforce password (P4PASSWD) invalid or unset.\n", Some(1));
+    assert_eq!(err.kind, P4ErrorKind::LoginRequired);
+    assert_eq!(err.message, "Perforce password (P4PASSWD) invalid or unset.");
+    assert_eq!(err.exit_code, Some(1));
+    assert!(err.is(P4ErrorKind::LoginRequired));
+
+    // Empty stderr falls back to exit code message
+    let err = P4Error::from_stderr("", Some(2));
+    assert_eq!(err.kind, P4ErrorKind::Unknown);
+    assert_eq!(err.message, "p4 exited with code: Some(2)");
+}
+
+#[test]
+fn test_p4_error_serializes_with_kind_tag() {
+    let err = P4Error::from_stderr("//depot/x - no such file(s).", Some(1)).context("p4 files failed");
+    let json = serde_json::to_value(&err).unwrap();
+    assert_eq!(json["kind"], "noSuchFile");
+    assert_eq!(json["message"], "p4 files failed: //depot/x - no such file(s).");
+    assert_eq!(json["stderr"], "//depot/x - no such file(s).");
+    assert_eq!(json["exitCode"], 1);
+}
+
+/// Encode string/int fields as a Python marshal dictionary, as written by `p4 -G`
+pub(super) fn marshal_dict(fields: &[(&str, MarshalValue)]) -> Vec<u8> {
+    fn string(out: &mut Vec<u8>, s: &str) {
+        out.push(b's');
+        out.extend_from_slice(&(s.len() as i32).to_le_bytes());
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    let mut out = vec![b'{'];
+    for (key, value) in fields {
+        string(&mut out, key);
+        match value {
+            MarshalValue::Str(s) => string(&mut out, s),
+            MarshalValue::Int(n) => {
+                out.push(b'i');
+                out.extend_from_slice(&(*n as i32).to_le_bytes());
+            }
+            MarshalValue::Bool(b) => out.push(if *b { b'T' } else { b'F' }),
+            MarshalValue::None => out.push(b'N'),
+        }
+    }
+    out.push(b'0');
+    out
+}
+
+pub(super) fn str_value(value: &str) -> MarshalValue {
+    MarshalValue::Str(value.to_string())
+}
+
+#[test]
+fn test_decode_marshal_records_typed_values() {
+    let mut data = marshal_dict(&[
+        ("code", str_value("error")),
+        ("data", str_value("//depot/x - no such file(s).\n")),
+        ("severity", MarshalValue::Int(2)),
+        ("generic", MarshalValue::Int(17)),
+    ]);
+    data.extend(marshal_dict(&[("code", str_value("stat")), ("depotFile", str_value("//depot/y"))]));
+
+    let records = decode_marshal_records(&data, None).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].get("severity"), Some(&MarshalValue::Int(2)));
+    assert_eq!(records[0].get("code").and_then(|v| v.as_str()), Some("error"));
+    assert_eq!(records[1].get("depotFile"), Some(&str_value("//depot/y")));
+}
+
+#[test]
+fn test_decode_marshal_records_in_command_charset() {
+    // "depotFile" => "//depot/日本語.txt" with the value in EUC-JP
+    let mut data = b"{s\x09\x00\x00\x00depotFile".to_vec();
+    let value = b"//depot/\xc6\xfc\xcb\xdc\xb8\xec.txt";
+    data.push(b's');
+    data.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    data.extend_from_slice(value);
+    data.push(b'0');
+
+    let records = parse_marshal_records(&data, Some(encoding_rs::EUC_JP)).unwrap();
+    assert_eq!(records[0].get("depotFile"), Some(&"//depot/日本語.txt".to_string()));
+}
+
+#[test]
+fn test_decode_marshal_records_long_and_flags() {
+    // 'l' long 100000 = 0b11_000011010100000 -> digits [0x06A0, 0x0003]
+    let data = [
+        b'{', b's', 1, 0, 0, 0, b'n', b'l', 2, 0, 0, 0, 0xA0, 0x06, 0x03, 0x00, b's', 1, 0, 0, 0,
+        b't', b'T', b's', 1, 0, 0, 0, b'z', b'N', b'0',
+    ];
+    let records = decode_marshal_records(&data, None).unwrap();
+    assert_eq!(records[0].get("n"), Some(&MarshalValue::Int(100000)));
+    assert_eq!(records[0].get("t"), Some(&MarshalValue::Bool(true)));
+    assert_eq!(records[0].get("z"), Some(&MarshalValue::None));
+}
+
+#[test]
+fn test_decode_marshal_records_truncated() {
+    let mut data = marshal_dict(&[("change", str_value("12345"))]);
+    data.truncate(data.len() - 3);
+    assert!(decode_marshal_records(&data, None).is_err());
+    assert!(decode_marshal_records(b"... change 1\n", None).is_err());
+    assert!(decode_marshal_records(b"", None).unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_marshal_changes_keeps_multiline_description() {
+    let data = marshal_dict(&[
+        ("code", str_value("stat")),
+        ("change", str_value("12345")),
+        ("time", str_value("1704067200")),
+        ("user", str_value("john_doe")),
+        ("client", str_value("my_workspace")),
+        ("status", str_value("submitted")),
+        ("desc", str_value("Fix parser\n\n- handle blank lines\n- handle tabs\n")),
+    ]);
+
+    let changelists = changelists_from_records(parse_marshal_records(&data, None).unwrap());
+    assert_eq!(changelists.len(), 1);
+    assert_eq!(changelists[0].id, 12345);
+    assert_eq!(
+        changelists[0].description,
+        "Fix parser\n\n- handle blank lines\n- handle tabs\n"
+    );
+}
+
+#[test]
+fn test_parse_marshal_filelog_indexed_fields() {
+    let data = marshal_dict(&[
+        ("code", str_value("stat")),
+        ("depotFile", str_value("//depot/main/file.cpp")),
+        ("rev0", str_value("2")),
+        ("change0", str_value("200")),
+        ("action0", str_value("edit")),
+        ("desc0", str_value("Second line\nwith detail")),
+        ("rev1", str_value("1")),
+        ("change1", str_value("100")),
+        ("action1", str_value("add")),
+    ]);
+
+    let revisions = revisions_from_records(parse_marshal_records(&data, None).unwrap());
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0].rev, 2);
+    assert_eq!(revisions[0].desc, "Second line\nwith detail");
+    assert_eq!(revisions[1].action, "add");
+}
+
+#[test]
+fn test_record_format_marshal_error_records() {
+    // p4 -G reports errors as dictionaries on stdout
+    let output = P4Output {
+        stdout: marshal_dict(&[
+            ("code", str_value("error")),
+            ("data", str_value("//depot/missing/... - no such file(s).\n")),
+            ("severity", MarshalValue::Int(2)),
+        ]),
+        stderr: Vec::new(),
+        exit_code: Some(0),
+        encoding: None,
+    };
+    let err = RecordFormat::Marshal.parse_output(&output).unwrap_err();
+    assert_eq!(err.kind, P4ErrorKind::NoSuchFile);
+    assert_eq!(err.message, "//depot/missing/... - no such file(s).");
+    assert_eq!(err.exit_code, Some(1));
+
+    // Partial results are kept; the code field is stripped
+    let mut stdout = output.stdout.clone();
+    stdout.extend(marshal_dict(&[("code", str_value("stat")), ("depotFile", str_value("//depot/a"))]));
+    let records = RecordFormat::Marshal
+        .parse_output(&P4Output { stdout, ..output })
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].get("depotFile"), Some(&"//depot/a".to_string()));
+    assert!(!records[0].contains_key("code"));
+}
+
+#[test]
+fn test_record_format_ztag_failure() {
+    let output = P4Output {
+        stdout: Vec::new(),
+        stderr: b"Perforce password (P4PASSWD) invalid or unset.\n".to_vec(),
+        exit_code: Some(1),
+        encoding: None,
+    };
+    assert_eq!(RecordFormat::Ztag.flag(), "-ztag");
+    let err = RecordFormat::Ztag.parse_output(&output).unwrap_err();
+    assert_eq!(err.kind, P4ErrorKind::LoginRequired);
+}
+
+#[test]
+fn test_index_changes_from_records() {
+    let input = r#"... depotFile //stream/main/a.cpp
+... headAction edit
+... headModTime 1704067200
+
+... depotFile //stream/main/old.cpp
+... headAction delete
+... headModTime 1704067300
+
+... depotFile //stream/main/moved.cpp
+... headAction move/delete
+
+... depotFile //stream/main/new.cpp
+... headAction move/add
+"#;
+    let (updated, removed) = index_changes_from_records(parse_ztag_records(input));
+
+    assert_eq!(updated.len(), 2);
+    assert_eq!(updated[0].depot_path, "//stream/main/a.cpp");
+    assert_eq!(updated[0].mod_time, 1704067200);
+    assert_eq!(updated[1].depot_path, "//stream/main/new.cpp");
+    assert_eq!(updated[1].mod_time, 0);
+    assert_eq!(removed, ["//stream/main/old.cpp", "//stream/main/moved.cpp"]);
+}
+
+#[test]
+fn test_parse_grep_line_match_and_context() {
+    let m = parse_grep_line("//depot/main/engine.cpp#12:48:    Engine::Run(); // run-loop").unwrap();
+    assert_eq!(m.depot_path, "//depot/main/engine.cpp");
+    assert_eq!(m.revision, 12);
+    assert_eq!(m.line_number, 48);
+    assert_eq!(m.line, "    Engine::Run(); // run-loop");
+    assert!(!m.is_context);
+
+    let c = parse_grep_line("//depot/my-project/a.txt#3-7-context: with colon").unwrap();
+    assert_eq!(c.depot_path, "//depot/my-project/a.txt");
+    assert_eq!(c.line_number, 7);
+    assert_eq!(c.line, "context: with colon");
+    assert!(c.is_context);
+
+    assert!(parse_grep_line("--").is_none());
+    assert!(parse_grep_line("//depot/a.txt#head:1:x").is_none());
+}
+
+#[test]
+fn test_content_docs_from_records_keeps_synced_text_files() {
+    let input = r#"... depotFile //stream/main/a.cpp
+... clientFile //alice-ws/a.cpp
+... path /home/alice/ws/a.cpp
+... haveRev 4
+... headType text+k
+
+... depotFile //stream/main/logo.png
+... path /home/alice/ws/logo.png
+... haveRev 2
+... headType binary+F
+
+... depotFile //stream/main/notes.md
+... clientFile /home/alice/ws/notes.md
+... haveRev 1
+... headType utf8
+"#;
+    let docs = content_docs_from_records(parse_ztag_records(input));
+
+    assert_eq!(docs.len(), 2);
+    assert_eq!(docs[0].depot_path, "//stream/main/a.cpp");
+    assert_eq!(docs[0].local_path, std::path::Path::new("/home/alice/ws/a.cpp"));
+    assert_eq!(docs[0].revision, 4);
+    assert_eq!(docs[1].local_path, std::path::Path::new("/home/alice/ws/notes.md"));
+}
+
+fn process_exit(reason: ExitReason, exit_code: Option<i32>, error: Option<&str>) -> ProcessExit {
+    ProcessExit {
+        process_id: "p1".to_string(),
+        exit_code,
+        reason,
+        duration_ms: 5,
+        error: error.map(String::from),
+    }
+}
+
+fn stderr_summary(errors: &[&str], ignored: usize) -> StderrSummary {
+    StderrSummary {
+        errors: errors.iter().map(|e| e.to_string()).collect(),
+        ignored,
+    }
+}
+
+#[test]
+fn test_stream_completion_from_exit() {
+    let clean = StreamCompletion::new(
+        Some(process_exit(ExitReason::Exited, Some(0), None)),
+        stderr_summary(&[], 0),
+    );
+    assert!(clean.success && !clean.cancelled && clean.error.is_none());
+
+    // Only informational stderr: p4 exits 1 but nothing went wrong
+    let benign = StreamCompletion::new(
+        Some(process_exit(ExitReason::Exited, Some(1), None)),
+        stderr_summary(&[], 2),
+    );
+    assert!(benign.success);
+
+    let silent_failure = StreamCompletion::new(
+        Some(process_exit(ExitReason::Exited, Some(2), None)),
+        stderr_summary(&[], 0),
+    );
+    assert!(!silent_failure.success);
+    assert_eq!(silent_failure.error.as_deref(), Some("p4 exited with code: Some(2)"));
+
+    let errors = StreamCompletion::new(
+        Some(process_exit(ExitReason::Exited, Some(0), None)),
+        stderr_summary(&["Connect to server failed; check $P4PORT."], 0),
+    );
+    assert!(!errors.success);
+    assert_eq!(errors.error_kind, Some(P4ErrorKind::ConnectionRefused));
+
+    let killed = StreamCompletion::new(
+        Some(process_exit(ExitReason::Killed, None, None)),
+        stderr_summary(&["partial"], 0),
+    );
+    assert!(killed.cancelled && !killed.success);
+    assert!(killed.error.is_none());
+
+    let timed_out = StreamCompletion::new(
+        Some(process_exit(ExitReason::TimedOut, None, Some("Timed out after 5s"))),
+        stderr_summary(&[], 0),
+    );
+    assert!(!timed_out.success && !timed_out.cancelled);
+    assert_eq!(timed_out.error.as_deref(), Some("Timed out after 5s"));
+
+    let lost = StreamCompletion::new(None, stderr_summary(&[], 0));
+    assert!(!lost.success && lost.error.is_some());
+}
+
+#[test]
+fn test_file_op_results_from_records_and_errors() {
+    let mut moved = HashMap::new();
+    moved.insert("depotFile".to_string(), "//depot/new/b.cpp".to_string());
+    moved.insert("clientFile".to_string(), "/ws/new/b.cpp".to_string());
+    moved.insert("action".to_string(), "move/add".to_string());
+    moved.insert("workRev".to_string(), "4".to_string());
+    moved.insert("fromFile".to_string(), "//depot/old/b.cpp".to_string());
+    let mut info = HashMap::new();
+    info.insert("data".to_string(), "some informational message".to_string());
+
+    let (results, unattributed) = file_op_results(
+        vec![moved, info],
+        vec![
+            "//depot/old/c.cpp#2 - can't move (already opened for delete)".to_string(),
+            "/ws/notes@v2.txt - can't add existing file".to_string(),
+            "Perforce password (P4PASSWD) invalid or unset.".to_string(),
+        ],
+    );
+
+    assert_eq!(results.len(), 3);
+    assert!(results[0].success);
+    assert_eq!(results[0].depot_path.as_deref(), Some("//depot/new/b.cpp"));
+    assert_eq!(results[0].moved_from.as_deref(), Some("//depot/old/b.cpp"));
+    assert_eq!(results[0].revision, Some(4));
+
+    assert!(!results[1].success);
+    assert_eq!(results[1].path, "//depot/old/c.cpp");
+    assert_eq!(results[1].depot_path.as_deref(), Some("//depot/old/c.cpp"));
+    assert_eq!(results[1].message.as_deref(), Some("can't move (already opened for delete)"));
+
+    assert_eq!(results[2].path, "/ws/notes@v2.txt");
+    assert_eq!(results[2].depot_path, None);
+
+    assert_eq!(unattributed, ["Perforce password (P4PASSWD) invalid or unset."]);
+}
+
+#[test]
+fn test_file_op_results_from_untagged_messages() {
+    let mut locked = HashMap::new();
+    locked.insert("data".to_string(), "//depot/art/hero.psd#3 - locking".to_string());
+    locked.insert("level".to_string(), "0".to_string());
+
+    let (results, unattributed) = file_op_results(
+        vec![locked],
+        vec!["//depot/art/map.psd - file(s) not opened on this client.".to_string()],
+    );
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].success);
+    assert_eq!(results[0].depot_path.as_deref(), Some("//depot/art/hero.psd"));
+    assert!(!results[1].success);
+    assert!(unattributed.is_empty());
+}
+
+#[test]
+fn test_p4_special_chars_and_escaping() {
+    assert!(has_p4_special_chars("/ws/build@2x.png"));
+    assert!(has_p4_special_chars("/ws/100%.txt"));
+    assert!(!has_p4_special_chars("/ws/plain.txt"));
+    assert_eq!(escape_special_chars("/ws/a@b#c.txt"), "/ws/a%40b%23c.txt");
+    assert_eq!(escape_special_chars("//depot/a%40b.txt"), "//depot/a%40b.txt");
+    assert_eq!(escape_special_chars("/ws/100%40.txt"), "/ws/100%2540.txt");
+    assert_eq!(escape_special_chars("/ws/a*b.txt"), "/ws/a%2Ab.txt");
+    assert_eq!(escape_special_chars("//depot/dir/..."), "//depot/dir/...");
+}
+
+fn integrate_options(kind: IntegrateKind) -> IntegrateOptions {
+    IntegrateOptions {
+        kind,
+        stream: None,
+        parent_stream: None,
+        branch: None,
+        reverse: false,
+        from_path: None,
+        to_path: None,
+        changelist: None,
+        revision_range: None,
+    }
+}
+
+#[test]
+fn test_integrate_args_stream_branch_and_paths() {
+    let mut stream = integrate_options(IntegrateKind::Merge);
+    stream.stream = Some("//stream/dev".to_string());
+    stream.changelist = Some(1234);
+    stream.revision_range = Some("@500".to_string());
+    assert_eq!(
+        integrate_args(&stream, true).unwrap(),
+        ["merge", "-n", "-c", "1234", "-S", "//stream/dev", "//...@500"]
+    );
+
+    let mut copy = integrate_options(IntegrateKind::Copy);
+    copy.stream = Some("//stream/dev".to_string());
+    copy.parent_stream = Some("//stream/main".to_string());
+    copy.reverse = true;
+    assert_eq!(
+        integrate_args(&copy, false).unwrap(),
+        ["copy", "-S", "//stream/dev", "-P", "//stream/main", "-r"]
+    );
+
+    let mut branch = integrate_options(IntegrateKind::Integrate);
+    branch.branch = Some("release-1.2".to_string());
+    branch.from_path = Some("//depot/main/src/...".to_string());
+    branch.revision_range = Some("@1200,@1300".to_string());
+    assert_eq!(
+        integrate_args(&branch, false).unwrap(),
+        ["integrate", "-b", "release-1.2", "-s", "//depot/main/src/...@1200,@1300"]
+    );
+
+    let mut pair = integrate_options(IntegrateKind::Integrate);
+    pair.from_path = Some("//depot/main/...".to_string());
+    pair.to_path = Some("//depot/rel/...".to_string());
+    pair.revision_range = Some("#head".to_string());
+    assert_eq!(
+        integrate_args(&pair, false).unwrap(),
+        ["integrate", "//depot/main/...#head", "//depot/rel/..."]
+    );
+}
+
+#[test]
+fn test_integrate_args_rejects_ambiguous_mappings() {
+    let mut both = integrate_options(IntegrateKind::Merge);
+    both.stream = Some("//stream/dev".to_string());
+    both.branch = Some("release-1.2".to_string());
+    assert!(integrate_args(&both, false).is_err());
+
+    let mut half_pair = integrate_options(IntegrateKind::Integrate);
+    half_pair.from_path = Some("//depot/main/...".to_string());
+    assert!(integrate_args(&half_pair, false).is_err());
+
+    let mut reversed_pair = integrate_options(IntegrateKind::Integrate);
+    reversed_pair.from_path = Some("//depot/main/...".to_string());
+    reversed_pair.to_path = Some("//depot/rel/...".to_string());
+    reversed_pair.reverse = true;
+    assert!(integrate_args(&reversed_pair, false).is_err());
+}
+
+#[test]
+fn test_is_integrate_error() {
+    assert!(!is_integrate_error("All revision(s) already integrated."));
+    assert!(!is_integrate_error("//stream/dev/... - all revision(s) already integrated."));
+    assert!(!is_integrate_error("//depot/rel/... - no target file(s) in both client and branch view."));
+    assert!(!is_integrate_error("//depot/gone/... - no such file(s)."));
+    assert!(is_integrate_error(
+        "//depot/rel/a.cpp - can't integrate from //depot/main/a.cpp#3 (deleted) without -d or -Ds flag"
+    ));
+    assert!(is_integrate_error("Perforce password (P4PASSWD) invalid or unset."));
+}
+
+#[test]
+fn test_ztag_record_reader_incremental() {
+    let mut reader = ZtagRecordReader::default();
+    assert!(reader.push_line("... depotFile //depot/rel/a.cpp").is_none());
+    assert!(reader.push_line("... action integrate").is_none());
+    let first = reader.push_line("").unwrap();
+    assert_eq!(first["action"], "integrate");
+    // Repeated blank lines don't produce empty records
+    assert!(reader.push_line("").is_none());
+
+    assert!(reader.push_line("... depotFile //depot/rel/b.cpp").is_none());
+    assert!(reader.push_line("... isMapped").is_none());
+    let last = reader.finish().unwrap();
+    assert_eq!(last["depotFile"], "//depot/rel/b.cpp");
+    assert_eq!(last["isMapped"], "");
+
+    let integrated = build_integrate_file(&first).unwrap();
+    assert_eq!(integrated.depot_path, "//depot/rel/a.cpp");
+    assert_eq!(integrated.work_rev, None);
+}
+
+#[test]
+fn test_build_stream_integration_status() {
+    let record = parse_ztag_records(
+        "... stream //depot/dev\n... parent //depot/main\n... type development\n\
+         ... parentType mainline\n... changeFlowsToParent true\n... changeFlowsFromParent true\n\
+         ... integToParent false\n... integToParentHow copy\n... toResult cache\n\
+         ... integFromParent true\n... integFromParentHow merge\n... fromResult cache\n",
+    );
+    let status = build_stream_integration_status(&record[0]).unwrap();
+
+    assert_eq!(status.stream, "//depot/dev");
+    assert_eq!(status.parent.as_deref(), Some("//depot/main"));
+    assert!(status.change_flows_from_parent && status.change_flows_to_parent);
+    assert!(status.needs_merge_down);
+    assert_eq!(status.merge_down_how.as_deref(), Some("merge"));
+    assert!(!status.needs_copy_up);
+    assert_eq!(status.copy_up_how.as_deref(), Some("copy"));
+    assert!(status.pending_merge_down.is_empty());
+}
+
+const STREAM_FORM: &str = "# A Perforce Stream Specification.\n#  Paths:  Identify paths in the stream and how they are to be\n\nStream:\t//depot/dev\n\nUpdate:\t2024/01/02 10:00:00\n\nOwner:\talice\n\nName:\tdev\n\nParent:\t//depot/main\n\nType:\tdevelopment\n\nDescription:\n\tOld description\n\tsecond line\n\nOptions:\tallsubmit unlocked toparent fromparent mergedown\n\nParentView:\tinherit\n\nPaths:\n\tshare ...\n";
+
+fn stream_spec() -> P4StreamSpec {
+    P4StreamSpec {
+        stream: "//depot/dev".to_string(),
+        name: "dev".to_string(),
+        owner: "alice".to_string(),
+        parent: Some("//depot/main".to_string()),
+        stream_type: "development".to_string(),
+        description: "New description\n\nwith a gap".to_string(),
+        options: "ownersubmit locked toparent fromparent mergedown".to_string(),
+        parent_view: Some("inherit".to_string()),
+        paths: vec!["share ...".to_string(), "import lib/... //depot/lib/...".to_string()],
+        remapped: vec![],
+        ignored: vec![".git/...".to_string()],
+        update: None,
+        access: None,
+    }
+}
+
+#[test]
+fn test_apply_stream_spec_keeps_other_fields() {
+    let mut form = SpecForm::parse(STREAM_FORM);
+    apply_stream_spec(&mut form, &stream_spec());
+    let form = form.to_form();
+
+    assert!(form.starts_with("Stream:\t//depot/dev\n\nUpdate:"));
+    assert!(form.contains("Update:\t2024/01/02 10:00:00\n"));
+    assert!(form.contains("Description:\n\tNew description\n\t\n\twith a gap\n\nOptions:"));
+    assert!(!form.contains("Old description"));
+    assert!(form.contains("Options:\townersubmit locked toparent fromparent mergedown\n"));
+    assert!(form.contains("Paths:\n\tshare ...\n\timport lib/... //depot/lib/...\n"));
+    // Fields the form didn't have are appended
+    assert!(form.ends_with("\nRemapped:\n\nIgnored:\n\t.git/...\n\n"));
+}
+
+#[test]
+fn test_validate_stream_spec() {
+    assert!(validate_stream_spec(&stream_spec()).is_empty());
+
+    let mut spec = stream_spec();
+    spec.parent = None;
+    spec.paths = vec!["shared ...".to_string(), "isolate".to_string()];
+    spec.options = "allsubmit sometimes".to_string();
+    let fields: Vec<_> = validate_stream_spec(&spec)
+        .into_iter()
+        .map(|e| e.field.unwrap())
+        .collect();
+    assert_eq!(fields, ["Parent", "Paths", "Paths", "Options"]);
+
+    let mut mainline = stream_spec();
+    mainline.stream_type = "mainline".to_string();
+    mainline.parent = None;
+    assert!(validate_stream_spec(&mainline).is_empty());
+}
+
+#[test]
+fn test_spec_field_errors_from_line_and_name() {
+    let form = "Stream:\t//depot/dev\n\nOwner:\talice\n\nPaths:\n\tshare ...\n\tbogus x\n";
+    let errors = spec_field_errors(
+        "Error in stream specification.\nError detected at line 7.\nInvalid path type 'bogus'.\n",
+        form,
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field.as_deref(), Some("Paths"));
+    assert_eq!(errors[0].message, "Invalid path type 'bogus'.");
+
+    let errors = spec_field_errors(
+        "Error in client specification.\nMissing required field 'Root'.\n",
+        form,
+    );
+    assert_eq!(errors[0].field.as_deref(), Some("Root"));
+    assert_eq!(
+        classify_stderr("Error in client specification.\nMissing required field 'Root'."),
+        P4ErrorKind::InvalidSpec
+    );
+}
+
+#[test]
+fn test_build_stream_spec_from_record() {
+    let mut record = HashMap::new();
+    for (key, value) in [
+        ("Stream", "//depot/main"),
+        ("Owner", "alice"),
+        ("Name", "main"),
+        ("Parent", "none"),
+        ("Type", "mainline"),
+        ("Description", "Line one\nLine two\n"),
+        ("Options", "allsubmit unlocked notoparent nofromparent mergedown"),
+        ("Paths0", "share ..."),
+        ("Paths1", "exclude docs/..."),
+        ("Ignored0", "*.tmp"),
+    ] {
+        record.insert(key.to_string(), value.to_string());
+    }
+
+    let spec = build_stream_spec(&record).unwrap();
+    assert_eq!(spec.parent, None);
+    assert_eq!(spec.description, "Line one\nLine two");
+    assert_eq!(spec.paths, ["share ...", "exclude docs/..."]);
+    assert!(spec.remapped.is_empty());
+    assert_eq!(spec.ignored, ["*.tmp"]);
+    assert_eq!(spec.parent_view, None);
+}
+
+const DESCRIBE_DIFF: &str = "Change 1234 by alice@ws on 2024/05/01 10:00:00
+
+\tFix the --- separator
+\t... and the header
+
+Affected files ...
+
+... //depot/main/a.txt#3 edit
+... //depot/main/logo.png#2 edit
+... //depot/main/new.txt#1 add
+
+Differences ...
+
+==== //depot/main/a.txt#3 (text) ====
+
+@@ -1,4 +1,4 @@
+ one
+--- two
++++ two
+ three
+
+@@ -10,2 +10,0 @@
+-ten
+-eleven
+\\ No newline at end of file
+
+==== //depot/main/logo.png#2 (binary+F) ====
+
+==== //depot/main/new.txt#1 (text) ====
+
+";
+
+fn parse_change_diff(output: &str, max_size: u64) -> Vec<P4FileDiff> {
+    let mut parser = ChangeDiffParser::new(HashMap::new(), max_size);
+    let mut files: Vec<P4FileDiff> = output.lines().filter_map(|line| parser.push_line(line)).collect();
+    files.extend(parser.finish());
+    files
+}
+
+#[test]
+fn test_change_diff_parser_describe_output() {
+    let files = parse_change_diff(DESCRIBE_DIFF, u64::MAX);
+    assert_eq!(files.len(), 3);
+
+    let text = &files[0];
+    assert_eq!(text.depot_path, "//depot/main/a.txt");
+    assert_eq!(text.revision, Some(3));
+    assert_eq!(text.action.as_deref(), Some("edit"));
+    assert_eq!(text.file_type.as_deref(), Some("text"));
+    assert_eq!((text.added, text.removed), (1, 3));
+    assert_eq!(text.hunks.len(), 2);
+    let first = &text.hunks[0];
+    // Removed and added lines that look like unified headers stay in the hunk
+    assert_eq!(first.lines[1].kind, DiffLineKind::Removed);
+    assert_eq!(first.lines[1].text, "-- two");
+    assert_eq!(first.lines[2].text, "++ two");
+    assert_eq!((first.lines[2].old_line, first.lines[2].new_line), (None, Some(2)));
+    assert_eq!((first.lines[3].old_line, first.lines[3].new_line), (Some(3), Some(3)));
+    let second = &text.hunks[1];
+    assert_eq!((second.old_start, second.old_lines, second.new_lines), (10, 2, 0));
+    assert_eq!(second.lines[1].old_line, Some(11));
+
+    assert!(files[1].binary);
+    assert!(files[1].hunks.is_empty());
+    assert_eq!(files[2].action.as_deref(), Some("add"));
+    assert!(!files[2].truncated);
+}
+
+#[test]
+fn test_change_diff_parser_p4_diff_output() {
+    let output = "--- //depot/main/a.txt\t2024/05/01 10:00:00
++++ /home/alice/ws/a.txt\t2024/05/02 09:00:00
+@@ -2 +2 @@
+-old
++new
+==== //depot/main/b.txt#5 - /home/alice/ws/b.txt ====
+--- //depot/main/b.txt\t2024/05/01 10:00:00
++++ /home/alice/ws/b.txt\t2024/05/02 09:00:00
+@@ -1,2 +1,3 @@
+ keep
+
++added
+";
+    let mut actions = HashMap::new();
+    actions.insert("//depot/main/b.txt".to_string(), "integrate".to_string());
+    let mut parser = ChangeDiffParser::new(actions, u64::MAX);
+    let mut files: Vec<P4FileDiff> = output.lines().filter_map(|line| parser.push_line(line)).collect();
+    files.extend(parser.finish());
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].depot_path, "//depot/main/a.txt");
+    assert_eq!(files[0].local_path.as_deref(), Some("/home/alice/ws/a.txt"));
+    assert_eq!(files[0].hunks[0].lines.len(), 2);
+    assert_eq!(files[1].revision, Some(5));
+    assert_eq!(files[1].action.as_deref(), Some("integrate"));
+    // An empty context line may lose its leading space
+    assert_eq!(files[1].hunks[0].lines[1].kind, DiffLineKind::Context);
+    assert_eq!(files[1].added, 1);
+}
+
+#[test]
+fn test_change_diff_parser_truncates_large_files() {
+    let files = parse_change_diff(DESCRIBE_DIFF, 20);
+    let text = &files[0];
+    assert!(text.truncated);
+    // The first hunk crossed the limit, so nothing is kept, but lines are still counted
+    assert!(text.hunks.is_empty());
+    assert_eq!((text.added, text.removed), (1, 3));
+    // The limit is per file
+    assert!(!files[2].truncated);
+}
+
+#[test]
+fn test_parse_login_status() {
+    let status = parse_login_status("... User alice\n... TicketExpiration 43185\n");
+    assert!(status.logged_in);
+    assert_eq!(status.user.as_deref(), Some("alice"));
+    assert_eq!(status.expires_in, Some(43185));
+
+    // Servers without tickets
+    let status = parse_login_status("'login' not necessary, no password set for this user.\n");
+    assert!(status.logged_in);
+    assert_eq!(status.expires_in, None);
+}
+
+#[test]
+fn test_parse_trust_status() {
+    let status = parse_trust_status("Trust already established.\n");
+    assert_eq!(
+        status,
+        P4TrustStatus {
+            trusted: true,
+            fingerprint: None,
+            changed: false
+        }
+    );
+
+    let status = parse_trust_status(
+        "******* WARNING P4PORT IDENTIFICATION HAS CHANGED! *******\nIt is possible that someone is intercepting your connection\nto the Perforce P4PORT '10.0.0.1:1666'\nThe fingerprint for the mismatched key sent to your client is\n 01:23:45:67:89:AB \nTo allow connection use the 'p4 trust' command.\n",
+    );
+    assert!(!status.trusted);
+    assert!(status.changed);
+    assert_eq!(status.fingerprint.as_deref(), Some("01:23:45:67:89:AB"));
+}
+
+#[test]
+fn test_is_fingerprint() {
+    assert!(is_fingerprint("AB:cd:01"));
+    assert!(!is_fingerprint("-y"));
+    assert!(!is_fingerprint("AB"));
+    assert!(!is_fingerprint("AB:CDE:01"));
+    assert!(!is_fingerprint("AB:GG"));
+}
@@ -1,4 +1,9 @@
-use serde::Serialize;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::stream::StreamCompletion;
+use crate::diff::DiffHunk;
 
 /// File information from p4 fstat
 #[derive(Debug, Clone, Serialize)]
@@ -13,15 +18,53 @@ pub struct P4FileInfo {
     pub file_type: String,
     pub head_action: Option<String>,
     pub head_mod_time: Option<i64>, // Unix timestamp of head revision (for recency bias)
+    /// Opens of this file in other workspaces
+    pub other_open: Vec<P4OtherOpen>,
+    /// Another workspace's lock on the file (p4 lock, or an open of an exclusive +l file)
+    pub other_lock: Option<P4OtherOpen>,
+    /// This workspace holds the file's lock
+    pub our_lock: bool,
+}
+
+/// Another workspace's open of a file, from fstat otherOpen<N>/otherAction<N>/otherChange<N>
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct P4OtherOpen {
+    pub user: String,
+    pub client: String,
+    pub action: Option<String>,
+    /// Changelist number, or "default"
+    pub changelist: Option<String>,
+}
+
+/// A file opened in any workspace, from p4 opened -a
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4OpenedFile {
+    pub depot_path: String,
+    pub user: String,
+    pub client: String,
+    /// Changelist number, or "default"
+    pub changelist: String,
+    pub action: String,
+    pub file_type: Option<String>,
+    pub revision: Option<i32>,
+    /// Locked with p4 lock
+    pub locked: bool,
+    /// The file type is exclusive-open (+l), so no other workspace can open it
+    pub exclusive: bool,
 }
 
 /// Streaming batch for p4_fstat_stream
-/// Data variant contains file batches, Complete signals end of stream
+/// Data variant contains file batches, Complete is sent once at the end of the stream
 #[derive(Clone, Serialize)]
-#[serde(tag = "type", rename_all = "camelCase")]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
 pub enum FstatStreamBatch {
     Data { files: Vec<P4FileInfo>, total_received: u32 },
-    Complete { total_files: u32, success: bool, error: Option<String> },
+    Complete {
+        total_files: u32,
+        #[serde(flatten)]
+        completion: StreamCompletion,
+    },
 }
 
 /// Changelist information from p4 changes
@@ -44,6 +87,31 @@ pub struct P4ClientInfo {
     pub client_stream: Option<String>,
     pub user_name: String,
     pub server_address: String,
+    /// Server runs in unicode mode and requires clients to set a charset
+    pub server_unicode: bool,
+    /// Charset commands on this connection use, configured or detected
+    pub charset: Option<String>,
+}
+
+/// Ticket state from p4 login -s
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4LoginStatus {
+    pub logged_in: bool,
+    pub user: Option<String>,
+    /// Seconds until the ticket expires; None when logged out or the server doesn't use tickets
+    pub expires_in: Option<u64>,
+}
+
+/// SSL trust state of the server, from p4 trust
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4TrustStatus {
+    pub trusted: bool,
+    /// Fingerprint the server presented, when it isn't trusted
+    pub fingerprint: Option<String>,
+    /// The server's key no longer matches the fingerprint trusted before
+    pub changed: bool,
 }
 
 /// Workspace information from p4 clients
@@ -77,6 +145,70 @@ pub struct SyncProgress {
     pub is_conflict: bool,
 }
 
+/// Streaming sync response sent via Channel
+/// Progress is sent per file (and per error/conflict line), Complete once at the end
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SyncStreamEvent {
+    Progress(SyncProgress),
+    Complete(StreamCompletion),
+}
+
+/// Search options for p4_grep
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrepOptions {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression (p4 grep -e) rather than a fixed string (-F)
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Lines of context before and after each match (-C)
+    pub context: Option<u32>,
+    /// Revision specifier appended to every path, e.g. "@2024/01/01,@now" or "#head"
+    pub revision_range: Option<String>,
+    /// Search every revision in the range rather than only the latest (-a)
+    #[serde(default)]
+    pub all_revisions: bool,
+}
+
+/// A line from content search: a match or a context line around one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrepMatch {
+    pub depot_path: String,
+    pub revision: i32,
+    /// 1-based line number in the file
+    pub line_number: u32,
+    pub line: String,
+    /// True for lines shown only as context (-A/-B/-C)
+    pub is_context: bool,
+}
+
+/// Streaming grep response sent via Channel
+/// Data variant contains batches of matching lines, Complete is sent once at the end of the stream
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum GrepStreamBatch {
+    Data { matches: Vec<GrepMatch>, total_received: u32 },
+    Complete {
+        total_matches: u32,
+        #[serde(flatten)]
+        completion: StreamCompletion,
+    },
+}
+
+/// Result of building the local content index
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentIndexStatus {
+    pub file_count: usize,
+    /// Files left out because they were missing, binary or too large
+    pub skipped_count: usize,
+    pub trigram_count: usize,
+}
+
 /// Annotation line information from p4 annotate -u -c
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -109,11 +241,16 @@ pub struct ShelvedBatchResult {
 
 /// Progress updates for batch shelved file query via Channel
 #[derive(Clone, Serialize)]
-#[serde(tag = "type", rename_all = "camelCase")]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
 pub enum ShelvedBatchProgress {
     Progress { loaded: u32, total: u32, message: String },
     Result { result: ShelvedBatchResult },
-    Complete { success_count: u32, error_count: u32, cancelled: bool },
+    Complete {
+        success_count: u32,
+        error_count: u32,
+        #[serde(flatten)]
+        completion: StreamCompletion,
+    },
 }
 
 /// File info from p4 describe output
@@ -137,6 +274,17 @@ pub struct P4ChangelistDescription {
     pub description: String,
     pub status: String,
     pub files: Vec<P4DescribeFile>,
+    /// Jobs the changelist fixes (or will fix, when pending)
+    pub jobs: Vec<P4FixedJob>,
+}
+
+/// Job linked to a changelist by p4 fix
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4FixedJob {
+    pub job: String,
+    /// Job status; for a pending changelist, the status it gets on submit
+    pub status: String,
 }
 
 /// Reconcile preview information from p4 reconcile -n
@@ -148,6 +296,207 @@ pub struct ReconcilePreview {
     pub action: String,
 }
 
+/// Job from p4 jobs. Fields beyond the standard five depend on the server's jobspec.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4Job {
+    pub job: String,
+    pub status: String,
+    pub user: String,
+    pub date: String,
+    pub description: String,
+    /// Every other field of the job, by name
+    pub fields: HashMap<String, String>,
+}
+
+/// Editable job from p4 job -o, with each field described by the jobspec
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4JobSpec {
+    /// Job name; "new" until the server assigns one
+    pub job: String,
+    /// Fields in jobspec order
+    pub fields: Vec<P4JobField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4JobField {
+    pub name: String,
+    pub value: String,
+    /// word, date, select, line, text or bulk
+    pub data_type: String,
+    /// optional, default, required, once or always; once and always are set by the server
+    pub field_type: String,
+    /// Choices for select fields
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// Branch spec from p4 branches
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4Branch {
+    pub branch: String,
+    pub owner: String,
+    pub description: String,
+    /// locked or unlocked
+    pub options: String,
+    /// Unix seconds of the last spec change and of the last use
+    pub update: i64,
+    pub access: i64,
+}
+
+/// What a view line does with the files it matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MappingKind {
+    Include,
+    /// "-" line: leave the files out
+    Exclude,
+    /// "+" line: add to the files mapped by earlier lines
+    Overlay,
+}
+
+/// One line of a spec's view: source (left) mapped to target (right)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4ViewMapping {
+    pub kind: MappingKind,
+    pub source: String,
+    pub target: String,
+}
+
+/// Editable branch spec from p4 branch -o
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4BranchSpec {
+    pub branch: String,
+    pub owner: String,
+    pub description: String,
+    pub options: String,
+    pub view: Vec<P4ViewMapping>,
+    /// Read-only dates reported by the server
+    #[serde(default)]
+    pub update: Option<String>,
+    #[serde(default)]
+    pub access: Option<String>,
+}
+
+/// How the two sides of a p4 diff2 pair compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Diff2Status {
+    /// Contents differ
+    Content,
+    /// Same contents, different file types
+    Types,
+    Identical,
+    /// Only on the source side of the branch view
+    LeftOnly,
+    /// Only on the target side
+    RightOnly,
+}
+
+/// One file pair from p4 diff2; the side a file is missing from is None
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4Diff2File {
+    pub status: Diff2Status,
+    pub left_path: Option<String>,
+    pub left_revision: Option<i32>,
+    pub left_type: Option<String>,
+    pub right_path: Option<String>,
+    pub right_revision: Option<i32>,
+    pub right_type: Option<String>,
+}
+
+/// One side of an in-app diff
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DiffSource {
+    /// A depot revision: `#revision`, or `revision_spec` ("@1234", "@label") when given
+    Depot {
+        depot_path: String,
+        revision: i32,
+        revision_spec: Option<String>,
+    },
+    /// The version shelved in a pending changelist
+    Shelved { depot_path: String, changelist: i32 },
+    /// A file on disk, typically the workspace copy
+    Local { path: String },
+}
+
+/// Which diff of a changelist to produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeDiffSource {
+    /// Submitted change: each file against its previous revision
+    Submitted,
+    /// Shelved files against the revisions they were shelved from
+    Shelved,
+    /// Opened files in a pending change against their have revisions
+    Pending,
+}
+
+/// Unified diff of one file in a changelist
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4FileDiff {
+    pub depot_path: String,
+    pub revision: Option<i32>,
+    /// Workspace file, for pending changes
+    pub local_path: Option<String>,
+    pub action: Option<String>,
+    pub file_type: Option<String>,
+    /// p4 reports no diff text for binary files
+    pub binary: bool,
+    pub hunks: Vec<DiffHunk>,
+    pub added: u32,
+    pub removed: u32,
+    /// The diff was larger than the content limit; hunks past it were dropped
+    pub truncated: bool,
+}
+
+/// Data variant contains parsed files, Complete is sent once at the end of the stream
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ChangeDiffBatch {
+    Data { files: Vec<P4FileDiff>, total_received: u32 },
+    Complete {
+        total_files: u32,
+        #[serde(flatten)]
+        completion: StreamCompletion,
+    },
+}
+
+/// Outcome of add, delete, move, tag or labelsync for a single file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4FileOpResult {
+    /// Depot path, or the path p4 reported when it couldn't map the file
+    pub path: String,
+    pub depot_path: Option<String>,
+    pub local_path: Option<String>,
+    /// Open action: add, delete, move/add
+    pub action: Option<String>,
+    pub file_type: Option<String>,
+    pub revision: Option<i32>,
+    /// Source depot path of a move/add
+    pub moved_from: Option<String>,
+    pub success: bool,
+    /// p4's error or warning when the file wasn't opened
+    pub message: Option<String>,
+}
+
+/// A single rename for p4_move; either side may be a directory ending in "/..."
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4MoveRequest {
+    pub from: String,
+    pub to: String,
+}
+
 /// Stream information from p4 streams
 #[derive(Debug, Clone, Serialize)]
 pub struct P4Stream {
@@ -156,20 +505,134 @@ pub struct P4Stream {
     pub parent: Option<String>,     // Parent stream path
     pub stream_type: String,        // mainline, development, release
     pub description: String,
+    /// Merge-down / copy-up status, when requested from p4_list_streams
+    pub integration: Option<StreamIntegrationStatus>,
+}
+
+/// Integration status of a stream against its parent, from p4 istat and p4 interchanges
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamIntegrationStatus {
+    pub stream: String,
+    pub parent: Option<String>,
+    /// The stream type lets changes flow from the parent / to the parent at all
+    pub change_flows_from_parent: bool,
+    pub change_flows_to_parent: bool,
+    /// The parent has changes not yet merged down into the stream
+    pub needs_merge_down: bool,
+    /// How p4 would bring them down: "merge" or "copy"
+    pub merge_down_how: Option<String>,
+    /// The stream has changes not yet copied up to the parent
+    pub needs_copy_up: bool,
+    pub copy_up_how: Option<String>,
+    /// Changelists in the parent not yet in the stream (p4 interchanges -S -r)
+    pub pending_merge_down: Vec<P4Changelist>,
+    /// Changelists in the stream not yet in the parent (p4 interchanges -S)
+    pub pending_copy_up: Vec<P4Changelist>,
+    /// Why the status couldn't be read; the other fields are unset when this is
+    pub error: Option<String>,
+}
+
+/// Editable stream spec from p4 stream -o
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4StreamSpec {
+    pub stream: String,
+    pub name: String,
+    pub owner: String,
+    /// None for mainlines (written back as "none")
+    pub parent: Option<String>,
+    /// mainline, development, release, virtual or task
+    pub stream_type: String,
+    pub description: String,
+    /// e.g. "allsubmit unlocked toparent fromparent mergedown"
+    pub options: String,
+    /// inherit or noinherit; older servers don't report it
+    pub parent_view: Option<String>,
+    /// View lines such as "share ..." or "import lib/... //depot/lib/..."
+    pub paths: Vec<String>,
+    pub remapped: Vec<String>,
+    pub ignored: Vec<String>,
+    /// Read-only dates reported by the server
+    #[serde(default)]
+    pub update: Option<String>,
+    #[serde(default)]
+    pub access: Option<String>,
 }
 
 /// Client spec information from p4 client -o
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct P4ClientSpec {
     pub client: String,
     pub root: String,
     pub stream: Option<String>,
     pub owner: String,
     pub description: String,
+    /// Ignored for stream workspaces: the server derives the view from the stream
     pub view: Vec<String>,
-    pub options: String,
+    pub options: String, // e.g. "noallwrite noclobber nocompress unlocked nomodtime normdir"
     pub host: String,
     pub submit_options: String,
+    #[serde(default = "default_line_end")]
+    pub line_end: String, // local, unix, mac, win, share
+}
+
+fn default_line_end() -> String {
+    "local".to_string()
+}
+
+/// Label from p4 labels
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4Label {
+    pub label: String,
+    pub owner: String,
+    pub description: String,
+    /// e.g. "unlocked noautoreload"
+    pub options: String,
+    /// Revision the label's view is pinned to ("@1234", "@other-label"), if any
+    pub revision: Option<String>,
+    /// Unix seconds of the last spec change and of the last use
+    pub update: i64,
+    pub access: i64,
+}
+
+/// Editable label spec from p4 label -o
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4LabelSpec {
+    pub label: String,
+    pub owner: String,
+    pub description: String,
+    pub options: String,
+    pub revision: Option<String>,
+    /// Depot paths the label may contain
+    pub view: Vec<String>,
+    /// Read-only dates reported by the server
+    #[serde(default)]
+    pub update: Option<String>,
+    #[serde(default)]
+    pub access: Option<String>,
+}
+
+/// Editable user spec from p4 user -o
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct P4UserSpec {
+    pub user: String,
+    pub email: String,
+    pub full_name: String,
+    /// Jobs the user is notified about, e.g. "status=open"
+    #[serde(default)]
+    pub job_view: Option<String>,
+    /// Depot paths whose submitted changes the user is notified about
+    #[serde(default)]
+    pub reviews: Vec<String>,
+    /// Read-only dates reported by the server
+    #[serde(default)]
+    pub update: Option<String>,
+    #[serde(default)]
+    pub access: Option<String>,
 }
 
 /// File result from p4 files command
@@ -200,3 +663,103 @@ pub struct P4UnresolvedFile {
     pub have_rev: i32,
     pub resolve_action: String,
 }
+
+/// Which branching command to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrateKind {
+    /// p4 merge: bring changes down from a parent (or across a branch)
+    Merge,
+    /// p4 copy: make the target an exact copy of the source
+    Copy,
+    /// p4 integrate: the general form, for file pairs and branch specs
+    Integrate,
+}
+
+impl IntegrateKind {
+    pub fn command(self) -> &'static str {
+        match self {
+            IntegrateKind::Merge => "merge",
+            IntegrateKind::Copy => "copy",
+            IntegrateKind::Integrate => "integrate",
+        }
+    }
+}
+
+/// Source and target of p4 merge / copy / integrate.
+/// Exactly one of `stream`, `branch` or the `from_path`/`to_path` pair picks the mapping.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrateOptions {
+    pub kind: IntegrateKind,
+    /// Integrate between this stream and its parent (-S)
+    pub stream: Option<String>,
+    /// Use this parent instead of the stream's own (-P, with `stream` only)
+    pub parent_stream: Option<String>,
+    /// Branch spec mapping source to target (-b)
+    pub branch: Option<String>,
+    /// Run the stream or branch mapping from target to source (-r)
+    #[serde(default)]
+    pub reverse: bool,
+    /// Source files; with `branch`, limits the source side (-s)
+    pub from_path: Option<String>,
+    /// Target files; with `stream` or `branch`, limits the target side
+    pub to_path: Option<String>,
+    /// Open the target files in this pending changelist (-c)
+    pub changelist: Option<i32>,
+    /// Revision specifier limiting the source revisions, e.g. "@1200" or "@1200,@1300"
+    pub revision_range: Option<String>,
+}
+
+/// A target file opened (or, with -n, to be opened) by merge / copy / integrate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrateFile {
+    pub depot_path: String,
+    pub local_path: Option<String>,
+    /// integrate, branch, delete, import, ... as reported by p4
+    pub action: String,
+    pub from_file: Option<String>,
+    /// Source revisions being integrated, as "#rev" / "none"
+    pub start_from_rev: Option<String>,
+    pub end_from_rev: Option<String>,
+    /// Target revision the file will be opened at
+    pub work_rev: Option<i32>,
+}
+
+/// Result of p4 merge / copy / integrate -n
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegratePreview {
+    pub files: Vec<IntegrateFile>,
+    /// Per-file messages p4 reported instead of an action
+    /// (e.g. "can't integrate from ... without -d or -Ds flag")
+    pub messages: Vec<String>,
+}
+
+/// Streaming merge / copy / integrate response sent via Channel
+/// Data variant contains batches of opened files, Complete is sent once at the end of the stream
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum IntegrateStreamBatch {
+    Data { files: Vec<IntegrateFile>, total_received: u32 },
+    Complete {
+        total_files: u32,
+        #[serde(flatten)]
+        completion: StreamCompletion,
+    },
+}
+
+/// Result of opening the persisted file index for a workspace
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIndexStatus {
+    /// No usable index on disk: repopulate from a full fstat, then save with `changelist`
+    pub rebuild_required: bool,
+    /// Head submitted changelist the index is (or will be, after rebuild) current to
+    pub changelist: i64,
+    /// Files in the index after loading and reconciling
+    pub file_count: usize,
+    /// Files added, updated or removed while reconciling
+    pub changed_files: usize,
+}
@@ -1,15 +1,11 @@
-use tokio::process::Command;
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::time::Duration;
 use tauri::ipc::Channel;
 use tauri::State;
 
-use crate::state::ProcessManager;
-
-// Windows-specific import for hiding console windows
-#[cfg(target_os = "windows")]
-#[allow(unused_imports)] // Trait is used via creation_flags() method
-use std::os::windows::process::CommandExt;
+use crate::state::{
+    ConnectionsState, P4Command, P4ExecutorState, ProcessInfo, ProcessManager, RegisteredProcess,
+};
+use super::p4::{collect_stderr, finish_stream, P4Error, StreamCompletion, TextLines};
 
 /// Payload sent to frontend for each stdout/stderr line.
 #[derive(Clone, serde::Serialize)]
@@ -18,89 +14,98 @@ pub struct OutputLine {
     pub is_stderr: bool,
 }
 
-/// Spawn p4.exe with given arguments, streaming output via Channel.
+/// Message on the spawn_p4_command channel: output lines, then exactly one Complete
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum OutputEvent {
+    Output(OutputLine),
+    Complete(StreamCompletion),
+}
+
+/// Spawn p4.exe with given arguments against a connection, streaming output via Channel.
+/// The process is killed if it runs longer than `timeout_ms`.
 /// Returns process ID for cancellation.
 #[tauri::command]
 pub async fn spawn_p4_command(
     args: Vec<String>,
-    on_output: Channel<OutputLine>,
+    timeout_ms: Option<u64>,
+    connection: Option<String>,
+    on_output: Channel<OutputEvent>,
     state: State<'_, ProcessManager>,
-) -> Result<String, String> {
-    // Spawn p4.exe with piped stdout/stderr
-    let mut cmd = Command::new("p4");
-    cmd.args(&args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
 
-    // On Windows, hide the console window (CREATE_NO_WINDOW flag)
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(0x08000000);
+    // Spawn p4.exe with piped stdout/stderr
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
+    cmd.args(&args);
 
-    let mut child = cmd.spawn()
-        .map_err(|e| format!("Failed to spawn p4: {}", e))?;
+    let mut process = executor.spawn(&cmd)
+        .map_err(|e| P4Error::other(format!("Failed to spawn p4: {}", e)))?;
 
     // Take stdout/stderr before moving child
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let encoding = process.encoding;
 
     // Register process for tracking
-    let process_id = state.register(child).await;
-    let process_id_clone = process_id.clone();
-
-    // Stream stdout in background task
-    let on_output_clone = on_output.clone();
-    if let Some(stdout) = stdout {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stdout).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                let _ = on_output_clone.send(OutputLine {
+    let timeout = timeout_ms.map(Duration::from_millis);
+    let RegisteredProcess { id: process_id, exited } =
+        state.register(process.child, cmd.args.clone(), connection, timeout).await;
+
+    // Stream stderr in background task; any stderr output fails a raw command
+    let on_output_stderr = on_output.clone();
+    let stderr_task = collect_stderr(stderr, encoding, move |line| {
+        let _ = on_output_stderr.send(OutputEvent::Output(OutputLine {
+            line: line.to_string(),
+            is_stderr: true,
+        }));
+        true
+    });
+
+    // Stream stdout in background task, then report how the command ended
+    tokio::spawn(async move {
+        if let Some(stdout) = stdout {
+            let mut lines = TextLines::new(stdout, encoding);
+            while let Some(line) = lines.next_line().await {
+                let _ = on_output.send(OutputEvent::Output(OutputLine {
                     line,
                     is_stderr: false,
-                });
+                }));
             }
-        });
-    }
+        }
 
-    // Stream stderr in background task
-    if let Some(stderr) = stderr {
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                let _ = on_output.send(OutputLine {
-                    line,
-                    is_stderr: true,
-                });
-            }
-        });
-    }
+        let completion = finish_stream(exited, stderr_task).await;
+        let _ = on_output.send(OutputEvent::Complete(completion));
+    });
 
-    Ok(process_id_clone)
+    Ok(process_id)
 }
 
 /// Execute p4 command and wait for completion (for short commands like 'p4 info').
-/// Returns stdout on success, error message on failure.
+/// Returns stdout on success, typed P4Error on failure.
 #[tauri::command]
-pub async fn p4_command(args: Vec<String>) -> Result<String, String> {
-    let mut cmd = Command::new("p4");
+pub async fn p4_command(
+    args: Vec<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+    executor: State<'_, P4ExecutorState>,
+) -> Result<String, P4Error> {
+    let conn = connections.get(&connection)?;
+    let mut cmd = P4Command::new();
+    conn.profile.apply(&mut cmd);
     cmd.args(&args);
 
-    // On Windows, hide the console window (CREATE_NO_WINDOW flag)
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(0x08000000);
-
-    let output = cmd.output()
+    let output = executor.output(&cmd)
         .await
-        .map_err(|e| format!("Failed to execute p4: {}", e))?;
+        .map_err(|e| P4Error::other(format!("Failed to execute p4: {}", e)))?;
 
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    if output.success() {
+        Ok(output.stdout_text())
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        if stderr.is_empty() {
-            Err(format!("p4 exited with code: {:?}", output.status.code()))
-        } else {
-            Err(stderr)
-        }
+        Err(P4Error::from_output(&output))
     }
 }
 
@@ -113,9 +118,25 @@ pub async fn kill_process(
     state.kill(&process_id).await
 }
 
+/// List tracked processes that are still running.
+#[tauri::command]
+pub async fn list_processes(state: State<'_, ProcessManager>) -> Result<Vec<ProcessInfo>, String> {
+    Ok(state.list().await)
+}
+
 /// Kill all tracked processes. Called on app close.
 #[tauri::command]
 pub async fn kill_all_processes(state: State<'_, ProcessManager>) -> Result<(), String> {
     state.kill_all().await;
     Ok(())
 }
+
+/// Kill the processes of one connection, leaving other connections running
+#[tauri::command]
+pub async fn kill_connection_processes(
+    connection: Option<String>,
+    state: State<'_, ProcessManager>,
+) -> Result<(), String> {
+    state.kill_connection(&connection).await;
+    Ok(())
+}
@@ -1,31 +1,46 @@
-use crate::file_index::{FileEntry, FileIndexState, SearchMode, SearchResult};
-use tauri::State;
+use crate::content_index::{ContentMatch, ContentQuery};
+use crate::file_index::{persist, ChangeSource, FileEntry, SearchMode, SearchResult};
+use crate::state::ConnectionsState;
+use tauri::{AppHandle, Runtime, State};
 
-/// Search workspace files using the persistent index
+/// Search workspace files using the persistent index.
+/// Scoring runs on a snapshot off the async runtime, so index updates aren't
+/// blocked; a newer search cancels this one, which then fails with an error.
 #[tauri::command]
 pub async fn search_workspace_files(
     query: String,
     mode: String,
     max_results: usize,
-    state: State<'_, FileIndexState>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
 ) -> Result<Vec<SearchResult>, String> {
-    let index = state.lock().await;
+    let conn = connections.get(&connection)?;
+    let ticket = conn.searcher.begin();
+    let snapshot = conn.file_index.lock().await.snapshot();
 
     let search_mode = match mode.as_str() {
         "exact" => SearchMode::Exact,
         _ => SearchMode::Fuzzy,
     };
 
-    Ok(index.search(&query, search_mode, max_results))
+    let searcher = conn.searcher.clone();
+    tokio::task::spawn_blocking(move || {
+        searcher.search(&snapshot, ticket, &query, search_mode, max_results)
+    })
+    .await
+    .map_err(|e| format!("Search failed: {}", e))?
+    .ok_or_else(|| "Search superseded by a newer query".to_string())
 }
 
 /// Add files to the index (called from streaming fstat)
 #[tauri::command]
 pub async fn add_files_to_index(
     files: Vec<FileIndexEntry>,
-    state: State<'_, FileIndexState>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
 ) -> Result<usize, String> {
-    let mut index = state.lock().await;
+    let conn = connections.get(&connection)?;
+    let mut index = conn.file_index.lock().await;
 
     let entries: Vec<FileEntry> = files
         .into_iter()
@@ -40,18 +55,128 @@ pub async fn add_files_to_index(
     Ok(index.len())
 }
 
+/// Remove files from the index by depot path. Returns the new index size.
+#[tauri::command]
+pub async fn remove_files_from_index(
+    depot_paths: Vec<String>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<usize, String> {
+    let conn = connections.get(&connection)?;
+    let mut index = conn.file_index.lock().await;
+    for depot_path in &depot_paths {
+        index.remove(depot_path);
+    }
+    Ok(index.len())
+}
+
+/// Apply file changes reported by sync progress, a submit or a revert,
+/// so the index stays current without a full rebuild. Returns the new index size.
+#[tauri::command]
+pub async fn apply_file_index_changes(
+    source: ChangeSource,
+    changes: Vec<FileIndexChange>,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<usize, String> {
+    let conn = connections.get(&connection)?;
+    let mut index = conn.file_index.lock().await;
+    for change in &changes {
+        index.apply_action(source, &change.depot_path, &change.action, change.mod_time);
+    }
+    Ok(index.len())
+}
+
 /// Clear the file index (called when workspace changes)
 #[tauri::command]
-pub async fn clear_file_index(state: State<'_, FileIndexState>) -> Result<(), String> {
-    let mut index = state.lock().await;
+pub async fn clear_file_index(
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<(), String> {
+    let conn = connections.get(&connection)?;
+    let mut index = conn.file_index.lock().await;
     index.clear();
     Ok(())
 }
 
+/// Persist the index after a full rebuild, marking it current to `changelist`
+/// (the head changelist reported by p4_open_file_index before the rebuild started).
+#[tauri::command]
+pub async fn save_file_index<R: Runtime>(
+    changelist: i64,
+    connection: Option<String>,
+    app: AppHandle<R>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<usize, String> {
+    let conn = connections.get(&connection)?;
+    let (key, count) = {
+        let mut index = conn.file_index.lock().await;
+        let key = index
+            .key()
+            .cloned()
+            .ok_or("File index has no workspace; open it before saving")?;
+        index.set_changelist(changelist);
+        (key, index.len())
+    };
+
+    let dir = persist::index_dir(&app)
+        .map_err(|e| format!("Failed to locate file index directory: {}", e))?;
+    persist::save_index(&conn.file_index, persist::index_path(&dir, &key))
+        .await
+        .map_err(|e| format!("Failed to save file index: {}", e))?;
+
+    Ok(count)
+}
+
+/// Search file contents using the local content index (see p4_build_content_index).
+/// Returns matching lines plus `context` lines around each, like p4_grep.
+#[tauri::command]
+pub async fn search_content_index(
+    pattern: String,
+    regex: bool,
+    case_sensitive: bool,
+    context: u32,
+    max_results: usize,
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<Vec<ContentMatch>, String> {
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let conn = connections.get(&connection)?;
+    let index = conn.content_index.lock().await.clone();
+    let query = ContentQuery {
+        pattern,
+        regex,
+        case_sensitive,
+        context,
+        encoding: conn.profile.file_encoding(),
+    };
+    tokio::task::spawn_blocking(move || index.search(&query, max_results))
+        .await
+        .map_err(|e| format!("Content search failed: {}", e))?
+}
+
+/// Drop the local content index
+#[tauri::command]
+pub async fn clear_content_index(
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<(), String> {
+    let conn = connections.get(&connection)?;
+    *conn.content_index.lock().await = Default::default();
+    Ok(())
+}
+
 /// Get current index size
 #[tauri::command]
-pub async fn get_file_index_count(state: State<'_, FileIndexState>) -> Result<usize, String> {
-    let index = state.lock().await;
+pub async fn get_file_index_count(
+    connection: Option<String>,
+    connections: State<'_, ConnectionsState>,
+) -> Result<usize, String> {
+    let conn = connections.get(&connection)?;
+    let index = conn.file_index.lock().await;
     Ok(index.len())
 }
 
@@ -62,3 +187,13 @@ pub struct FileIndexEntry {
     pub depot_path: String,
     pub mod_time: u64,
 }
+
+/// Input type for apply_file_index_changes command
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIndexChange {
+    pub depot_path: String,
+    /// Action as reported by the operation named by the change source
+    pub action: String,
+    pub mod_time: Option<u64>,
+}
@@ -0,0 +1,393 @@
+use encoding_rs::Encoding;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::state::decode_text;
+
+/// Files larger than this are not indexed (generated sources, data dumps)
+const MAX_INDEXED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Leading bytes checked for NUL when deciding whether a file is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Candidate files read per parallel step of a search; results are checked between steps
+const SEARCH_BATCH_SIZE: usize = 256;
+
+type Trigram = [u8; 3];
+
+/// A have-revision text file in the client workspace
+#[derive(Clone, Debug)]
+pub struct ContentDoc {
+    pub depot_path: String,
+    pub local_path: PathBuf,
+    /// Have revision when the index was built
+    pub revision: i32,
+}
+
+/// A line from a local content search: a match or a context line around one.
+/// Same shape as the GrepMatch lines streamed by p4_grep.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMatch {
+    pub depot_path: String,
+    pub revision: i32,
+    /// 1-based line number in the file
+    pub line_number: u32,
+    pub line: String,
+    pub is_context: bool,
+}
+
+/// Options for ContentIndex::search
+pub struct ContentQuery {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression rather than a literal string
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Lines of context to include before and after each match
+    pub context: u32,
+    /// Workspace file encoding (the connection's charset); None is UTF-8
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// Trigram index over the contents of workspace text files.
+/// Only trigrams are kept in memory; candidate files are re-read from disk to
+/// find the matching lines, so results always reflect the files as they are now.
+/// Text added to a file after the index was built may be missed until it is rebuilt.
+#[derive(Default)]
+pub struct ContentIndex {
+    docs: Vec<ContentDoc>,
+    /// ASCII-lowercased trigram -> ids (positions in `docs`) of files containing it, ascending
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read and index `docs` in parallel.
+    /// Returns the index and the number of files skipped because they were
+    /// unreadable, too large or binary.
+    pub fn build(docs: Vec<ContentDoc>) -> (Self, usize) {
+        let trigram_sets: Vec<Option<Vec<Trigram>>> =
+            docs.par_iter().map(read_trigrams).collect();
+
+        let mut index = Self::new();
+        let mut skipped = 0;
+        for (doc, trigrams) in docs.into_iter().zip(trigram_sets) {
+            let Some(trigrams) = trigrams else {
+                skipped += 1;
+                continue;
+            };
+            let id = index.docs.len() as u32;
+            for trigram in trigrams {
+                index.postings.entry(trigram).or_default().push(id);
+            }
+            index.docs.push(doc);
+        }
+
+        (index, skipped)
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Number of distinct trigrams
+    pub fn trigram_count(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Find lines matching `query` in indexed files: the first `max_results` matching lines
+    /// (context lines not counted) in index order, so the same query always gives the same results.
+    pub fn search(&self, query: &ContentQuery, max_results: usize) -> Result<Vec<ContentMatch>, String> {
+        let matcher = build_matcher(query)?;
+        let candidates = self.candidates(query);
+
+        // Files are searched in parallel a batch at a time, in index order, so the
+        // search can stop after the batch that reaches max_results
+        let mut results: Vec<ContentMatch> = Vec::new();
+        let mut remaining = max_results;
+        for batch in candidates.chunks(SEARCH_BATCH_SIZE) {
+            if remaining == 0 {
+                break;
+            }
+            let per_doc: Vec<Vec<ContentMatch>> = batch
+                .par_iter()
+                .map(|&id| search_doc(&self.docs[id as usize], &matcher, query.context, query.encoding))
+                .collect();
+            for matches in per_doc {
+                // Line number of the last match counted in this file
+                let mut last_match = 0;
+                for m in matches {
+                    if remaining == 0 {
+                        // Keep the after-context of the last match, but not the next match's before-context
+                        if m.is_context && m.line_number <= last_match + query.context {
+                            results.push(m);
+                            continue;
+                        }
+                        return Ok(results);
+                    }
+                    if !m.is_context {
+                        remaining -= 1;
+                        last_match = m.line_number;
+                    }
+                    results.push(m);
+                }
+                if remaining == 0 {
+                    return Ok(results);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Ids of files containing every trigram the query requires.
+    /// Falls back to every file when the query has no usable trigrams.
+    fn candidates(&self, query: &ContentQuery) -> Vec<u32> {
+        let literals = if query.regex {
+            required_literals(&query.pattern)
+        } else {
+            vec![query.pattern.clone()]
+        };
+
+        let mut trigrams: HashSet<Trigram> = HashSet::new();
+        for literal in &literals {
+            // Index trigrams are ASCII-lowercased; non-ASCII case folding can't be filtered on
+            if !query.case_sensitive && !literal.is_ascii() {
+                continue;
+            }
+            // Files are indexed as raw bytes, so look for the literal as the workspace encodes it
+            let bytes = match query.encoding {
+                Some(encoding) if !literal.is_ascii() => {
+                    let (bytes, _, unmappable) = encoding.encode(literal);
+                    if unmappable {
+                        continue;
+                    }
+                    bytes.into_owned()
+                }
+                _ => literal.as_bytes().to_vec(),
+            };
+            trigrams.extend(literal_trigrams(&bytes));
+        }
+
+        if trigrams.is_empty() {
+            return (0..self.docs.len() as u32).collect();
+        }
+
+        // Intersect starting from the rarest trigram
+        let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(trigrams.len());
+        for trigram in &trigrams {
+            match self.postings.get(trigram) {
+                Some(ids) => lists.push(ids),
+                None => return Vec::new(),
+            }
+        }
+        lists.sort_by_key(|ids| ids.len());
+
+        let mut result = lists[0].clone();
+        for ids in &lists[1..] {
+            result = intersect(&result, ids);
+            if result.is_empty() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// Thread-safe state wrapper, one per connection (see state::Connection).
+/// The index is immutable once built; searches clone the Arc and release the lock.
+pub type ContentIndexState = Arc<Mutex<Arc<ContentIndex>>>;
+
+pub fn create_content_index_state() -> ContentIndexState {
+    Arc::new(Mutex::new(Arc::new(ContentIndex::new())))
+}
+
+/// Whether a p4 file type holds text that can be indexed.
+/// utf16 files are left out: they are stored as UTF-16 in the workspace.
+pub fn is_text_type(file_type: &str) -> bool {
+    let base = file_type.split('+').next().unwrap_or_default();
+    matches!(base, "unicode" | "utf8") || base.ends_with("text")
+}
+
+/// Sorted, deduplicated trigrams of a file, or None if it shouldn't be indexed
+fn read_trigrams(doc: &ContentDoc) -> Option<Vec<Trigram>> {
+    let metadata = fs::metadata(&doc.local_path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_INDEXED_FILE_SIZE {
+        return None;
+    }
+    let data = fs::read(&doc.local_path).ok()?;
+    if data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+        return None;
+    }
+
+    let mut trigrams: Vec<Trigram> = literal_trigrams(&data).collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    Some(trigrams)
+}
+
+fn literal_trigrams(bytes: &[u8]) -> impl Iterator<Item = Trigram> + '_ {
+    bytes.windows(3).map(|w| {
+        [
+            w[0].to_ascii_lowercase(),
+            w[1].to_ascii_lowercase(),
+            w[2].to_ascii_lowercase(),
+        ]
+    })
+}
+
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::with_capacity(a.len().min(b.len()));
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+fn build_matcher(query: &ContentQuery) -> Result<Regex, String> {
+    let pattern = if query.regex {
+        query.pattern.clone()
+    } else {
+        regex::escape(&query.pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Matching lines (with context) of one file as it is on disk now, decoded in `encoding`
+fn search_doc(
+    doc: &ContentDoc,
+    matcher: &Regex,
+    context: u32,
+    encoding: Option<&'static Encoding>,
+) -> Vec<ContentMatch> {
+    let Ok(data) = fs::read(&doc.local_path) else {
+        return Vec::new();
+    };
+    let text = decode_text(&data, encoding);
+    let lines: Vec<&str> = text.lines().collect();
+    let context = context as usize;
+
+    let mut results = Vec::new();
+    // Lines before this index have already been emitted
+    let mut emitted = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if !matcher.is_match(line) {
+            continue;
+        }
+        let start = i.saturating_sub(context).max(emitted);
+        let end = (i + context + 1).min(lines.len());
+        for (j, text) in lines.iter().enumerate().take(end).skip(start) {
+            // A later match inside this window emits its own line as a match
+            if j > i && matcher.is_match(text) {
+                break;
+            }
+            results.push(ContentMatch {
+                depot_path: doc.depot_path.clone(),
+                revision: doc.revision,
+                line_number: j as u32 + 1,
+                line: text.to_string(),
+                is_context: j != i,
+            });
+            emitted = j + 1;
+        }
+    }
+    results
+}
+
+/// Literal substrings that every match of the regex `pattern` must contain.
+/// Conservative: alternations yield nothing (every file is a candidate), and
+/// anything not understood as a plain literal breaks the current run.
+fn required_literals(pattern: &str) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut current = String::new();
+    // Literals inside groups may be optional or alternated, so only depth 0 counts
+    let mut depth = 0usize;
+    let mut chars = pattern.chars();
+
+    fn flush(current: &mut String, literals: &mut Vec<String>) {
+        if !current.is_empty() {
+            literals.push(std::mem::take(current));
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '|' => return Vec::new(),
+            '\\' => chars.next().filter(|e| e.is_ascii_punctuation() && depth == 0),
+            '[' => {
+                // Skip the class, honouring escapes and a leading ']'
+                let mut first = true;
+                while let Some(k) = chars.next() {
+                    match k {
+                        '\\' => {
+                            chars.next();
+                        }
+                        ']' if !first => break,
+                        _ => {}
+                    }
+                    first = false;
+                }
+                None
+            }
+            '(' => {
+                depth += 1;
+                None
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                None
+            }
+            '*' | '?' | '{' => {
+                // The preceding character may occur zero times
+                current.pop();
+                if c == '{' {
+                    for k in chars.by_ref() {
+                        if k == '}' {
+                            break;
+                        }
+                    }
+                }
+                None
+            }
+            '+' | '.' | '^' | '$' => None,
+            c if depth == 0 => Some(c),
+            _ => None,
+        };
+
+        match literal {
+            Some(l) => current.push(l),
+            None => flush(&mut current, &mut literals),
+        }
+    }
+    flush(&mut current, &mut literals);
+    literals
+}
+
+#[cfg(test)]
+mod tests;
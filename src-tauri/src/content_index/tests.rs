@@ -0,0 +1,199 @@
+use super::*;
+use std::path::Path;
+
+fn doc(dir: &Path, name: &str, contents: &[u8]) -> ContentDoc {
+    let local_path = dir.join(name);
+    fs::write(&local_path, contents).unwrap();
+    ContentDoc {
+        depot_path: format!("//stream/main/{}", name),
+        local_path,
+        revision: 3,
+    }
+}
+
+fn query(pattern: &str) -> ContentQuery {
+    ContentQuery {
+        pattern: pattern.to_string(),
+        regex: false,
+        case_sensitive: false,
+        context: 0,
+        encoding: None,
+    }
+}
+
+fn built(dir: &Path) -> ContentIndex {
+    let docs = vec![
+        doc(dir, "main.cpp", b"#include \"engine.h\"\nint main() {\n    Engine::Run();\n}\n"),
+        doc(dir, "engine.h", b"class Engine {\npublic:\n    static void Run();\n};\n"),
+        doc(dir, "logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+    ];
+    let (index, skipped) = ContentIndex::build(docs);
+    assert_eq!(skipped, 1);
+    index
+}
+
+fn lines(matches: &[ContentMatch]) -> Vec<(&str, u32, bool)> {
+    matches
+        .iter()
+        .map(|m| (m.depot_path.as_str(), m.line_number, m.is_context))
+        .collect()
+}
+
+#[test]
+fn test_build_skips_binary_and_missing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut docs = vec![doc(dir.path(), "a.txt", b"hello")];
+    docs.push(ContentDoc {
+        depot_path: "//stream/main/gone.txt".to_string(),
+        local_path: dir.path().join("gone.txt"),
+        revision: 1,
+    });
+
+    let (index, skipped) = ContentIndex::build(docs);
+    assert_eq!(index.len(), 1);
+    assert_eq!(skipped, 1);
+    assert_eq!(index.trigram_count(), 3);
+}
+
+#[test]
+fn test_search_literal_ignores_case() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = built(dir.path());
+
+    let matches = index.search(&query("engine::run"), 100).unwrap();
+    assert_eq!(lines(&matches), [("//stream/main/main.cpp", 3, false)]);
+    assert_eq!(matches[0].line, "    Engine::Run();");
+    assert_eq!(matches[0].revision, 3);
+
+    let mut sensitive = query("engine::run");
+    sensitive.case_sensitive = true;
+    assert!(index.search(&sensitive, 100).unwrap().is_empty());
+}
+
+#[test]
+fn test_search_regex_and_context() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = built(dir.path());
+
+    let mut q = query(r"static\s+void\s+\w+\(");
+    q.regex = true;
+    q.context = 1;
+    let matches = index.search(&q, 100).unwrap();
+    assert_eq!(
+        lines(&matches),
+        [
+            ("//stream/main/engine.h", 2, true),
+            ("//stream/main/engine.h", 3, false),
+            ("//stream/main/engine.h", 4, true),
+        ]
+    );
+
+    let mut invalid = query("(unclosed");
+    invalid.regex = true;
+    assert!(index.search(&invalid, 100).is_err());
+}
+
+#[test]
+fn test_search_stops_at_max_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = built(dir.path());
+
+    let matches = index.search(&query("Run"), 1).unwrap();
+    assert_eq!(matches.len(), 1);
+}
+
+#[test]
+fn test_search_with_limit_is_repeatable() {
+    let dir = tempfile::tempdir().unwrap();
+    let docs: Vec<ContentDoc> = (0..600)
+        .map(|i| doc(dir.path(), &format!("f{:03}.txt", i), b"needle\nhay\nneedle\n"))
+        .collect();
+    let (index, _) = ContentIndex::build(docs);
+
+    let first = index.search(&query("needle"), 25).unwrap();
+    let second = index.search(&query("needle"), 25).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first.len(), 25);
+    // The first matches in index order
+    assert_eq!(first[0].depot_path, "//stream/main/f000.txt");
+    assert_eq!(first[24].depot_path, "//stream/main/f012.txt");
+}
+
+#[test]
+fn test_search_limit_keeps_after_context_of_last_match() {
+    let dir = tempfile::tempdir().unwrap();
+    let (index, _) = ContentIndex::build(vec![
+        doc(dir.path(), "a.txt", b"x\nfoo 1\ny\nz\nw\nfoo 2\n"),
+        doc(dir.path(), "b.txt", b"foo 3\n"),
+    ]);
+
+    let mut q = query("foo");
+    q.context = 1;
+    let matches = index.search(&q, 1).unwrap();
+    assert_eq!(
+        lines(&matches),
+        [
+            ("//stream/main/a.txt", 1, true),
+            ("//stream/main/a.txt", 2, false),
+            ("//stream/main/a.txt", 3, true),
+        ]
+    );
+
+    // The next match's before-context isn't the last match's after-context
+    q.context = 2;
+    let matches = index.search(&q, 1).unwrap();
+    assert_eq!(lines(&matches).last(), Some(&("//stream/main/a.txt", 4, true)));
+}
+
+#[test]
+fn test_search_decodes_workspace_charset() {
+    let dir = tempfile::tempdir().unwrap();
+    let (text, _, _) = encoding_rs::SHIFT_JIS.encode("// 設定を読み込む\nload();\n");
+    let (index, _) = ContentIndex::build(vec![doc(dir.path(), "config.cpp", &text)]);
+
+    let mut q = query("設定");
+    q.case_sensitive = true;
+    q.encoding = Some(encoding_rs::SHIFT_JIS);
+    let matches = index.search(&q, 10).unwrap();
+    assert_eq!(lines(&matches), [("//stream/main/config.cpp", 1, false)]);
+    assert_eq!(matches[0].line, "// 設定を読み込む");
+}
+
+#[test]
+fn test_adjacent_matches_are_not_repeated_as_context() {
+    let dir = tempfile::tempdir().unwrap();
+    let (index, _) = ContentIndex::build(vec![doc(dir.path(), "a.txt", b"foo 1\nfoo 2\nbar\n")]);
+
+    let mut q = query("foo");
+    q.context = 1;
+    let matches = index.search(&q, 100).unwrap();
+    assert_eq!(
+        lines(&matches),
+        [
+            ("//stream/main/a.txt", 1, false),
+            ("//stream/main/a.txt", 2, false),
+            ("//stream/main/a.txt", 3, true),
+        ]
+    );
+}
+
+#[test]
+fn test_required_literals() {
+    assert_eq!(required_literals(r"Engine::Run\("), ["Engine::Run("]);
+    assert_eq!(required_literals(r"static\s+void"), ["static", "void"]);
+    assert_eq!(required_literals("colou?r"), ["colo", "r"]);
+    assert_eq!(required_literals("ab+c"), ["ab", "c"]);
+    assert_eq!(required_literals("foo[0-9]{2}bar"), ["foo", "bar"]);
+    assert_eq!(required_literals("(?i)needle(opt)?"), ["needle"]);
+    assert!(required_literals("foo|bar").is_empty());
+}
+
+#[test]
+fn test_is_text_type() {
+    assert!(is_text_type("text"));
+    assert!(is_text_type("text+kx"));
+    assert!(is_text_type("ktext"));
+    assert!(is_text_type("unicode"));
+    assert!(!is_text_type("binary+l"));
+    assert!(!is_text_type("utf16"));
+}
@@ -0,0 +1,495 @@
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::state::decode_text;
+
+/// Leading bytes checked for NUL when deciding whether content is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Changed lines longer than this are not split into words
+const MAX_WORD_DIFF_LINE_LEN: usize = 4096;
+
+/// How whitespace counts when comparing lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WhitespaceMode {
+    #[default]
+    Exact,
+    /// Runs of whitespace compare equal to a single space; trailing whitespace is ignored (diff -b)
+    IgnoreChanges,
+    /// Whitespace is ignored entirely (diff -w)
+    IgnoreAll,
+}
+
+/// Options for diff_text
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change
+    pub context: u32,
+    pub whitespace: WhitespaceMode,
+    /// Treat CRLF and LF line endings as the same
+    pub ignore_line_endings: bool,
+    /// Mark the changed words within paired removed/added lines
+    pub word_diff: bool,
+    /// Charset of content without a byte order mark; None for UTF-8.
+    /// Set from the connection, not by the frontend.
+    #[serde(skip)]
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            whitespace: WhitespaceMode::Exact,
+            ignore_line_endings: false,
+            word_diff: false,
+            encoding: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A run of text within a changed line; `changed` runs differ from the paired line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffSegment {
+    pub text: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line numbers; None on the side the line doesn't exist in
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    /// Line text without its line ending
+    pub text: String,
+    /// Word-level breakdown, for changed lines paired with a line on the other side
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<DiffSegment>>,
+}
+
+/// A block of changes with its surrounding context, as in a unified diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// First line of the hunk on each side (1-based; the line before it when the side is empty)
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Line diff of two texts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDiff {
+    /// Either side looked binary; no hunks are produced
+    pub binary: bool,
+    pub hunks: Vec<DiffHunk>,
+    pub added: u32,
+    pub removed: u32,
+}
+
+/// Diff two texts line by line. Content with a NUL byte near the start is treated as binary.
+pub fn diff_text(old: &[u8], new: &[u8], options: &DiffOptions) -> TextDiff {
+    if is_binary(old) || is_binary(new) {
+        return TextDiff {
+            binary: true,
+            ..TextDiff::default()
+        };
+    }
+
+    let old_text = decode_text(old, options.encoding);
+    let new_text = decode_text(new, options.encoding);
+    let old_lines = split_lines(&old_text);
+    let new_lines = split_lines(&new_text);
+
+    // Compare interned keys rather than strings
+    let mut interner = HashMap::new();
+    let mut key = |line: &str| {
+        let normalized = normalize_line(line, options);
+        let next = interner.len() as u32;
+        *interner.entry(normalized).or_insert(next)
+    };
+    let old_keys: Vec<u32> = old_lines.iter().map(|l| key(l)).collect();
+    let new_keys: Vec<u32> = new_lines.iter().map(|l| key(l)).collect();
+
+    let ops = edit_script(&old_keys, &new_keys);
+    let mut diff = TextDiff::default();
+    for op in &ops {
+        match op {
+            Op::Delete(_) => diff.removed += 1,
+            Op::Insert(_) => diff.added += 1,
+            Op::Equal(..) => {}
+        }
+    }
+    diff.hunks = build_hunks(&ops, &old_lines, &new_lines, options);
+    diff
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    // UTF-16 text is full of NULs; its byte order mark gives it away
+    if Encoding::for_bom(content).is_some() {
+        return false;
+    }
+    content[..content.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Lines without their "\n"; a trailing "\r" is kept so line endings can be compared
+fn split_lines(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    text.split('\n').collect()
+}
+
+/// Comparison key for a line under `options`
+fn normalize_line(line: &str, options: &DiffOptions) -> String {
+    let line = if options.ignore_line_endings {
+        line.strip_suffix('\r').unwrap_or(line)
+    } else {
+        line
+    };
+    match options.whitespace {
+        WhitespaceMode::Exact => line.to_string(),
+        WhitespaceMode::IgnoreChanges => line.split_whitespace().collect::<Vec<_>>().join(" "),
+        WhitespaceMode::IgnoreAll => line.chars().filter(|c| !c.is_whitespace()).collect(),
+    }
+}
+
+/// One step of an edit script, by line index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Shortest edit script from `old` to `new`. Between unchanged lines, deletions come first.
+fn edit_script<T: Eq>(old: &[T], new: &[T]) -> Vec<Op> {
+    let mut matches = Vec::new();
+    match_range(old, new, 0, 0, &mut matches);
+
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    for (x, y) in matches.into_iter().chain(std::iter::once((old.len(), new.len()))) {
+        ops.extend((i..x).map(Op::Delete));
+        ops.extend((j..y).map(Op::Insert));
+        if x < old.len() {
+            ops.push(Op::Equal(x, y));
+        }
+        (i, j) = (x + 1, y + 1);
+    }
+    ops
+}
+
+/// Append the (old, new) index pairs of a longest common subsequence of the two ranges.
+/// Linear-space Myers: trim the common ends, split at the middle snake, recurse.
+fn match_range<T: Eq>(old: &[T], new: &[T], old_base: usize, new_base: usize, matches: &mut Vec<(usize, usize)>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    matches.extend((0..prefix).map(|i| (old_base + i, new_base + i)));
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let (old_base, new_base) = (old_base + prefix, new_base + prefix);
+
+    let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    if !old.is_empty() && !new.is_empty() {
+        if let Some((x, y)) = middle_snake(old, new) {
+            match_range(&old[..x], &new[..y], old_base, new_base, matches);
+            match_range(&old[x..], &new[y..], old_base + x, new_base + y, matches);
+        }
+        // No split: nothing in common, the whole range is replaced
+    }
+
+    let (old_end, new_end) = (old_base + old.len(), new_base + new.len());
+    matches.extend((0..suffix).map(|i| (old_end + i, new_end + i)));
+}
+
+/// A point on a shortest edit path through the middle of the edit graph, found by
+/// searching forward from the start and backward from the end until the paths meet.
+/// Returns None when the ranges have nothing in common or the point is a corner.
+fn middle_snake<T: Eq>(old: &[T], new: &[T]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let len = 2 * max_d + 2;
+    // Furthest x reached on each diagonal k = x - y; the backward search runs on the
+    // reversed sequences, so its x counts from the end
+    let mut forward = vec![-1isize; len as usize];
+    let mut backward = vec![-1isize; len as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // With an odd delta the paths meet during a forward step, otherwise a backward one
+    let meet_forward = delta % 2 != 0;
+    // Diagonals that have run off the grid are skipped from then on
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+
+    let split = |x: isize, y: isize| {
+        let inside = (0..=n).contains(&x) && (0..=m).contains(&y);
+        let corner = (x, y) == (0, 0) || (x, y) == (n, m);
+        (inside && !corner).then_some((x as usize, y as usize))
+    };
+
+    for d in 0..max_d {
+        let mut k = -d + forward_start;
+        while k <= d - forward_end {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if meet_forward {
+                let j = offset + delta - k;
+                if j >= 0 && j < len && backward[j as usize] != -1 && x >= n - backward[j as usize] {
+                    return split(x, y);
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d + backward_start;
+        while k <= d - backward_end {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !meet_forward {
+                let j = offset + delta - k;
+                if j >= 0 && j < len && forward[j as usize] != -1 {
+                    let forward_x = forward[j as usize];
+                    let forward_y = offset + forward_x - j;
+                    if forward_x >= n - x {
+                        return split(forward_x, forward_y);
+                    }
+                }
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+/// Group an edit script into hunks with `options.context` lines around each change
+fn build_hunks(ops: &[Op], old_lines: &[&str], new_lines: &[&str], options: &DiffOptions) -> Vec<DiffHunk> {
+    let context = options.context as usize;
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], Op::Equal(..))).collect();
+    let Some(&first) = changes.first() else {
+        return Vec::new();
+    };
+
+    // Op ranges [start, end) of each hunk; changes closer than 2 * context share one
+    let mut ranges = Vec::new();
+    let mut start = first.saturating_sub(context);
+    let mut end = first + 1;
+    for &change in &changes[1..] {
+        if change - end > 2 * context {
+            ranges.push((start, (end + context).min(ops.len())));
+            start = change - context;
+        }
+        end = change + 1;
+    }
+    ranges.push((start, (end + context).min(ops.len())));
+
+    let consumed: Vec<(usize, usize)> = ops
+        .iter()
+        .scan((0, 0), |(old, new), op| {
+            let before = (*old, *new);
+            match op {
+                Op::Equal(..) => (*old, *new) = (*old + 1, *new + 1),
+                Op::Delete(_) => *old += 1,
+                Op::Insert(_) => *new += 1,
+            }
+            Some(before)
+        })
+        .collect();
+    let display = |line: &str| line.strip_suffix('\r').unwrap_or(line).to_string();
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let mut lines: Vec<DiffLine> = ops[start..end]
+                .iter()
+                .map(|op| match *op {
+                    Op::Equal(i, j) => DiffLine {
+                        kind: DiffLineKind::Context,
+                        old_line: Some(i as u32 + 1),
+                        new_line: Some(j as u32 + 1),
+                        text: display(new_lines[j]),
+                        segments: None,
+                    },
+                    Op::Delete(i) => DiffLine {
+                        kind: DiffLineKind::Removed,
+                        old_line: Some(i as u32 + 1),
+                        new_line: None,
+                        text: display(old_lines[i]),
+                        segments: None,
+                    },
+                    Op::Insert(j) => DiffLine {
+                        kind: DiffLineKind::Added,
+                        old_line: None,
+                        new_line: Some(j as u32 + 1),
+                        text: display(new_lines[j]),
+                        segments: None,
+                    },
+                })
+                .collect();
+            if options.word_diff {
+                add_word_segments(&mut lines);
+            }
+            hunk_from_lines(&consumed, start, lines)
+        })
+        .collect()
+}
+
+/// A hunk for ops[start..end]; `consumed[k]` is the (old, new) lines before ops[k]
+fn hunk_from_lines(consumed: &[(usize, usize)], start: usize, lines: Vec<DiffLine>) -> DiffHunk {
+    let old_lines = lines.iter().filter(|l| l.kind != DiffLineKind::Added).count() as u32;
+    let new_lines = lines.iter().filter(|l| l.kind != DiffLineKind::Removed).count() as u32;
+    let (old_before, new_before) = consumed[start];
+    // As in unified diffs, a side with no lines starts at the line before the hunk
+    let start_line = |before: usize, count: u32| if count == 0 { before as u32 } else { before as u32 + 1 };
+    DiffHunk {
+        old_start: start_line(old_before, old_lines),
+        old_lines,
+        new_start: start_line(new_before, new_lines),
+        new_lines,
+        lines,
+    }
+}
+
+/// Pair each run of removed lines with the added lines that follow it and mark the
+/// words that differ within each pair
+fn add_word_segments(lines: &mut [DiffLine]) {
+    let mut i = 0;
+    while i < lines.len() {
+        let removed_start = i;
+        while i < lines.len() && lines[i].kind == DiffLineKind::Removed {
+            i += 1;
+        }
+        let added_start = i;
+        while i < lines.len() && lines[i].kind == DiffLineKind::Added {
+            i += 1;
+        }
+        let pairs = (added_start - removed_start).min(i - added_start);
+        for p in 0..pairs {
+            let (old, new) = (&lines[removed_start + p].text, &lines[added_start + p].text);
+            if old.len() > MAX_WORD_DIFF_LINE_LEN || new.len() > MAX_WORD_DIFF_LINE_LEN {
+                continue;
+            }
+            let (old_segments, new_segments) = word_segments(old, new);
+            lines[removed_start + p].segments = Some(old_segments);
+            lines[added_start + p].segments = Some(new_segments);
+        }
+        if i == removed_start {
+            i += 1;
+        }
+    }
+}
+
+/// Segments of two lines, split into words, whitespace runs and single symbols
+fn word_segments(old: &str, new: &str) -> (Vec<DiffSegment>, Vec<DiffSegment>) {
+    let old_words = split_words(old);
+    let new_words = split_words(new);
+    let ops = edit_script(&old_words, &new_words);
+
+    let mut old_segments = Vec::new();
+    let mut new_segments = Vec::new();
+    for op in ops {
+        match op {
+            Op::Equal(i, j) => {
+                push_segment(&mut old_segments, old_words[i], false);
+                push_segment(&mut new_segments, new_words[j], false);
+            }
+            Op::Delete(i) => push_segment(&mut old_segments, old_words[i], true),
+            Op::Insert(j) => push_segment(&mut new_segments, new_words[j], true),
+        }
+    }
+    (old_segments, new_segments)
+}
+
+fn push_segment(segments: &mut Vec<DiffSegment>, text: &str, changed: bool) {
+    match segments.last_mut() {
+        Some(last) if last.changed == changed => last.text.push_str(text),
+        _ => segments.push(DiffSegment {
+            text: text.to_string(),
+            changed,
+        }),
+    }
+}
+
+fn split_words(line: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Symbol,
+    }
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Symbol
+        }
+    };
+
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<Class> = None;
+    for (i, c) in line.char_indices() {
+        let current = class(c);
+        // Symbols stand alone; words and whitespace extend while the class holds
+        let continues = previous.as_ref() == Some(&current) && current != Class::Symbol;
+        if !continues && i > start {
+            words.push(&line[start..i]);
+            start = i;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,213 @@
+use super::*;
+
+fn options() -> DiffOptions {
+    DiffOptions::default()
+}
+
+fn kinds(hunk: &DiffHunk) -> Vec<(DiffLineKind, &str)> {
+    hunk.lines.iter().map(|l| (l.kind, l.text.as_str())).collect()
+}
+
+fn lcs_len(a: &[u8], b: &[u8]) -> usize {
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    table[0][0]
+}
+
+#[test]
+fn test_identical_texts_have_no_hunks() {
+    let diff = diff_text(b"a\nb\nc\n", b"a\nb\nc\n", &options());
+    assert!(!diff.binary);
+    assert!(diff.hunks.is_empty());
+    assert_eq!((diff.added, diff.removed), (0, 0));
+}
+
+#[test]
+fn test_changed_line_with_context() {
+    let old = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    let new = b"1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    let diff = diff_text(old, new, &options());
+    assert_eq!((diff.added, diff.removed), (1, 1));
+    assert_eq!(diff.hunks.len(), 1);
+    let hunk = &diff.hunks[0];
+    assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (2, 7, 2, 7));
+    assert_eq!(
+        kinds(hunk),
+        vec![
+            (DiffLineKind::Context, "2"),
+            (DiffLineKind::Context, "3"),
+            (DiffLineKind::Context, "4"),
+            (DiffLineKind::Removed, "5"),
+            (DiffLineKind::Added, "five"),
+            (DiffLineKind::Context, "6"),
+            (DiffLineKind::Context, "7"),
+            (DiffLineKind::Context, "8"),
+        ]
+    );
+    assert_eq!(hunk.lines[3].old_line, Some(5));
+    assert_eq!(hunk.lines[3].new_line, None);
+    assert_eq!(hunk.lines[4].new_line, Some(5));
+    assert!(hunk.lines[3].segments.is_none());
+}
+
+#[test]
+fn test_distant_changes_split_into_hunks() {
+    let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+    let new: String = (1..=20)
+        .filter(|&i| i != 19)
+        .map(|i| if i == 2 { "two\n".to_string() } else { format!("{}\n", i) })
+        .collect();
+    let diff = diff_text(old.as_bytes(), new.as_bytes(), &DiffOptions { context: 1, ..options() });
+    assert_eq!(diff.hunks.len(), 2);
+    assert_eq!(diff.hunks[0].old_start, 1);
+    let last = &diff.hunks[1];
+    assert_eq!((last.old_start, last.old_lines, last.new_start, last.new_lines), (18, 3, 18, 2));
+
+    // Close enough to share context
+    let diff = diff_text(old.as_bytes(), new.as_bytes(), &DiffOptions { context: 8, ..options() });
+    assert_eq!(diff.hunks.len(), 1);
+}
+
+#[test]
+fn test_insert_and_delete_only_hunks() {
+    let diff = diff_text(b"", b"a\nb\n", &options());
+    let hunk = &diff.hunks[0];
+    assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (0, 0, 1, 2));
+
+    let diff = diff_text(b"a\nb\nc\n", b"a\nc\n", &DiffOptions { context: 0, ..options() });
+    let hunk = &diff.hunks[0];
+    assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (2, 1, 1, 0));
+}
+
+#[test]
+fn test_binary_content_is_not_diffed() {
+    let diff = diff_text(b"\x89PNG\r\n\x1a\n\0\0", b"text\n", &options());
+    assert!(diff.binary);
+    assert!(diff.hunks.is_empty());
+}
+
+#[test]
+fn test_content_is_decoded_in_connection_charset() {
+    // "日本" in Shift_JIS, then UTF-16 with a BOM whatever the connection charset
+    let options = DiffOptions { encoding: Some(encoding_rs::SHIFT_JIS), ..options() };
+    let diff = diff_text(b"a\n", b"\x93\xfa\x96\x7b\n", &options);
+    assert_eq!(diff.hunks[0].lines[1].text, "日本");
+
+    let diff = diff_text(b"a\n", b"\xff\xfeb\x00\n\x00", &options);
+    assert!(!diff.binary);
+    assert_eq!(diff.hunks[0].lines[1].text, "b");
+}
+
+#[test]
+fn test_line_ending_option() {
+    let crlf = b"a\r\nb\r\n";
+    let lf = b"a\nb\n";
+    assert_eq!(diff_text(crlf, lf, &options()).removed, 2);
+    let diff = diff_text(crlf, lf, &DiffOptions { ignore_line_endings: true, ..options() });
+    assert!(diff.hunks.is_empty());
+
+    // The carriage return is never part of the displayed text
+    let diff = diff_text(b"x\r\n", b"y\r\n", &options());
+    assert_eq!(diff.hunks[0].lines[0].text, "x");
+}
+
+#[test]
+fn test_whitespace_modes() {
+    let old = b"if (a)  {\n  return;\n";
+    let new = b"if (a) {\n    return;  \n";
+    assert_eq!(diff_text(old, new, &options()).removed, 2);
+    let changes = DiffOptions { whitespace: WhitespaceMode::IgnoreChanges, ..options() };
+    assert!(diff_text(old, new, &changes).hunks.is_empty());
+    assert_eq!(diff_text(b"a b\n", b"ab\n", &changes).removed, 1);
+    let all = DiffOptions { whitespace: WhitespaceMode::IgnoreAll, ..options() };
+    assert!(diff_text(b"a b\n", b"ab\n", &all).hunks.is_empty());
+}
+
+#[test]
+fn test_word_diff_marks_changed_words() {
+    let diff = diff_text(
+        b"let total = price * count;\n",
+        b"let total = price * quantity;\n",
+        &DiffOptions { word_diff: true, ..options() },
+    );
+    let lines = &diff.hunks[0].lines;
+    let segments = |line: &DiffLine| {
+        line.segments
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|s| (s.text.clone(), s.changed))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        segments(&lines[0]),
+        vec![
+            ("let total = price * ".to_string(), false),
+            ("count".to_string(), true),
+            (";".to_string(), false),
+        ]
+    );
+    assert_eq!(segments(&lines[1])[1], ("quantity".to_string(), true));
+}
+
+#[test]
+fn test_word_diff_only_pairs_lines() {
+    let diff = diff_text(b"a\nb\n", b"c\n", &DiffOptions { word_diff: true, ..options() });
+    let lines = &diff.hunks[0].lines;
+    assert!(lines[0].segments.is_some());
+    assert!(lines[1].segments.is_none());
+    assert!(lines[2].segments.is_some());
+}
+
+#[test]
+fn test_split_words() {
+    assert_eq!(split_words("foo_bar(x,  y)"), vec!["foo_bar", "(", "x", ",", "  ", "y", ")"]);
+    assert_eq!(split_words(""), Vec::<&str>::new());
+}
+
+#[test]
+fn test_edit_script_is_minimal() {
+    // Compare against a dynamic-programming LCS on pseudo-random inputs
+    let mut seed = 0x2545_f491_u32;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+    for _ in 0..500 {
+        let old: Vec<u8> = (0..next() % 30).map(|_| (next() % 4) as u8).collect();
+        let new: Vec<u8> = (0..next() % 30).map(|_| (next() % 4) as u8).collect();
+        let ops = edit_script(&old, &new);
+
+        let (mut i, mut j, mut equal) = (0, 0, 0);
+        for op in &ops {
+            match *op {
+                Op::Equal(x, y) => {
+                    assert_eq!((x, y), (i, j));
+                    assert_eq!(old[x], new[y]);
+                    (i, j) = (i + 1, j + 1);
+                    equal += 1;
+                }
+                Op::Delete(x) => {
+                    assert_eq!(x, i);
+                    i += 1;
+                }
+                Op::Insert(y) => {
+                    assert_eq!(y, j);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()));
+        assert_eq!(equal, lcs_len(&old, &new), "{:?} -> {:?}", old, new);
+    }
+}
@@ -1,8 +1,11 @@
+pub mod persist;
 pub mod search;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-pub use search::{SearchMode, SearchResult};
+pub use persist::IndexKey;
+pub use search::{create_search_state, IndexSnapshot, SearchMode, SearchResult, SearchState, Searcher};
 
 /// Entry in the file index: depot path + modification timestamp (unix seconds)
 #[derive(Clone)]
@@ -13,22 +16,143 @@ pub struct FileEntry {
 
 /// Persistent file index for workspace search
 pub struct FileIndex {
-    files: Vec<FileEntry>,
+    /// Shared with in-flight searches; mutations copy it only while a search holds a snapshot
+    files: Arc<Vec<FileEntry>>,
+    /// Depot path -> position in `files`, so each path appears at most once
+    slots: HashMap<String, usize>,
+    /// Workspace view the entries were built from
+    key: Option<IndexKey>,
+    /// Highest submitted changelist reflected in the entries (None while a rebuild is in progress)
+    changelist: Option<i64>,
+    /// Bumped on every change to `files`, so cached search hits can be invalidated
+    generation: u64,
 }
 
 impl FileIndex {
     pub fn new() -> Self {
-        Self { files: Vec::new() }
+        Self {
+            files: Arc::new(Vec::new()),
+            slots: HashMap::new(),
+            key: None,
+            changelist: None,
+            generation: 0,
+        }
     }
 
-    /// Add a batch of files to the index
+    /// Drop all entries and start over for a (possibly different) workspace view
+    pub fn reset(&mut self, key: IndexKey) {
+        self.clear_files();
+        self.key = Some(key);
+        self.changelist = None;
+    }
+
+    pub fn key(&self) -> Option<&IndexKey> {
+        self.key.as_ref()
+    }
+
+    pub fn changelist(&self) -> Option<i64> {
+        self.changelist
+    }
+
+    /// Record that the entries are complete up to and including `changelist`
+    pub fn set_changelist(&mut self, changelist: i64) {
+        self.changelist = Some(changelist);
+    }
+
+    /// Replace entries for changed files and drop removed ones
+    pub fn apply_changes(&mut self, updated: Vec<FileEntry>, removed: &[String]) {
+        for depot_path in removed {
+            self.remove(depot_path);
+        }
+        self.add_batch(updated);
+    }
+
+    /// Insert an entry, or replace the existing one for the same depot path.
+    /// Returns true if the path was not in the index before.
+    pub fn upsert(&mut self, entry: FileEntry) -> bool {
+        self.generation += 1;
+        let files = Arc::make_mut(&mut self.files);
+        match self.slots.get(&entry.depot_path) {
+            Some(&slot) => {
+                files[slot] = entry;
+                false
+            }
+            None => {
+                self.slots.insert(entry.depot_path.clone(), files.len());
+                files.push(entry);
+                true
+            }
+        }
+    }
+
+    /// Remove the entry for `depot_path`. Returns true if it was present.
+    pub fn remove(&mut self, depot_path: &str) -> bool {
+        let Some(slot) = self.slots.remove(depot_path) else {
+            return false;
+        };
+        self.generation += 1;
+        let files = Arc::make_mut(&mut self.files);
+        files.swap_remove(slot);
+        // The last entry moved into the freed slot
+        if let Some(moved) = files.get(slot) {
+            self.slots.insert(moved.depot_path.clone(), slot);
+        }
+        true
+    }
+
+    /// Look up the entry for `depot_path`
+    pub fn get(&self, depot_path: &str) -> Option<&FileEntry> {
+        self.slots.get(depot_path).map(|&slot| &self.files[slot])
+    }
+
+    /// Apply a single file change reported by a p4 operation.
+    /// `action` is the word p4 reports for that operation (see ChangeSource).
+    /// Entries keep their previous mod time when `mod_time` is None and the file
+    /// is already indexed; new entries fall back to the current time.
+    /// Returns true if the index changed.
+    pub fn apply_action(
+        &mut self,
+        source: ChangeSource,
+        depot_path: &str,
+        action: &str,
+        mod_time: Option<u64>,
+    ) -> bool {
+        match source.effect(action) {
+            ChangeEffect::Remove => self.remove(depot_path),
+            ChangeEffect::Upsert => {
+                let mod_time = mod_time
+                    .or_else(|| self.get(depot_path).map(|e| e.mod_time))
+                    .unwrap_or_else(unix_now);
+                self.upsert(FileEntry {
+                    depot_path: depot_path.to_string(),
+                    mod_time,
+                });
+                true
+            }
+            ChangeEffect::None => false,
+        }
+    }
+
+    /// Add a batch of files to the index, replacing entries for paths already present
     pub fn add_batch(&mut self, entries: Vec<FileEntry>) {
-        self.files.extend(entries);
+        Arc::make_mut(&mut self.files).reserve(entries.len());
+        for entry in entries {
+            self.upsert(entry);
+        }
     }
 
-    /// Clear all entries (before workspace reload)
+    /// Clear all entries (before workspace reload).
+    /// The workspace key is kept but the index is no longer considered complete.
     pub fn clear(&mut self) {
-        self.files.clear();
+        self.clear_files();
+        self.changelist = None;
+    }
+
+    fn clear_files(&mut self) {
+        // Replace rather than clear, so a snapshot held by a search isn't copied first
+        self.files = Arc::new(Vec::new());
+        self.slots.clear();
+        self.generation += 1;
     }
 
     /// Get current file count
@@ -42,10 +166,17 @@ impl FileIndex {
     }
 
     /// Get reference to files for searching
-    #[allow(dead_code)]
     pub fn files(&self) -> &[FileEntry] {
         &self.files
     }
+
+    /// Cheap point-in-time view of the entries that can be searched without holding the lock
+    pub fn snapshot(&self) -> IndexSnapshot {
+        IndexSnapshot {
+            files: Arc::clone(&self.files),
+            generation: self.generation,
+        }
+    }
 }
 
 impl Default for FileIndex {
@@ -54,9 +185,60 @@ impl Default for FileIndex {
     }
 }
 
-/// Thread-safe state wrapper for Tauri
+/// Which p4 operation reported a file change, which determines how its action is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeSource {
+    /// `p4 sync` progress: "adding", "updating", "deleting", "can't clobber"
+    Sync,
+    /// Files in a submitted changelist: the open action ("edit", "add", "delete", ...)
+    Submit,
+    /// Files that were reverted: the open action they had before the revert
+    Revert,
+}
+
+enum ChangeEffect {
+    Upsert,
+    Remove,
+    None,
+}
+
+impl ChangeSource {
+    fn effect(self, action: &str) -> ChangeEffect {
+        match self {
+            ChangeSource::Sync => match action {
+                "deleted" | "deleting" => ChangeEffect::Remove,
+                "added" | "adding" | "updating" | "refreshing" | "replacing" => ChangeEffect::Upsert,
+                // "can't clobber" and anything unrecognised leave the workspace file as it was
+                _ => ChangeEffect::None,
+            },
+            ChangeSource::Submit => match action {
+                "delete" | "move/delete" | "purge" | "archive" => ChangeEffect::Remove,
+                _ => ChangeEffect::Upsert,
+            },
+            // Reverting an add (or a branch/move target) removes the file from the
+            // workspace; reverting anything else restores the depot file already indexed
+            ChangeSource::Revert => match action {
+                "add" | "move/add" | "branch" | "import" => ChangeEffect::Remove,
+                _ => ChangeEffect::None,
+            },
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Thread-safe state wrapper, one per connection (see state::Connection)
 pub type FileIndexState = Arc<Mutex<FileIndex>>;
 
 pub fn create_file_index_state() -> FileIndexState {
     Arc::new(Mutex::new(FileIndex::new()))
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,205 @@
+use super::{FileEntry, FileIndex, FileIndexState};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Leading bytes of every index file
+const MAGIC: &[u8; 8] = b"DEPOTIDX";
+
+/// On-disk format version. Bump whenever the layout or the meaning of stored
+/// fields changes; files with any other version are discarded and rebuilt.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Identifies which workspace view an index was built from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexKey {
+    pub server: String,
+    pub client: String,
+    /// Depot path the index covers, e.g. "//stream/main/..."
+    pub depot_path: String,
+}
+
+impl IndexKey {
+    /// File name for this key inside the index directory.
+    /// Sanitizing is lossy, so a hash of the unsanitized key keeps keys that
+    /// sanitize alike apart; the full key is also stored in the file and checked on load.
+    pub fn file_name(&self) -> String {
+        let raw = format!("{}_{}_{}", self.server, self.client, self.depot_path);
+        let sanitized: String = raw
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let hash = fnv1a(&[&self.server, &self.client, &self.depot_path]);
+        format!("{}_{:016x}.idx", sanitized, hash)
+    }
+}
+
+/// 64-bit FNV-1a over each part followed by a 0 separator. Stable across builds,
+/// unlike std's DefaultHasher, so file names stay the same between releases.
+fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for &byte in part.as_bytes().iter().chain(&[0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Directory holding persisted indexes, under the app data dir
+pub fn index_dir<R: Runtime>(app: &AppHandle<R>) -> io::Result<PathBuf> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+    Ok(data_dir.join("file-index"))
+}
+
+/// Path of the index file for `key` inside `dir`
+pub fn index_path(dir: &Path, key: &IndexKey) -> PathBuf {
+    dir.join(key.file_name())
+}
+
+/// Layout (all integers little-endian):
+///   magic[8] version:u32 server:str client:str depot_path:str changelist:i64 count:u64
+///   count * (depot_path:str mod_time:u64)
+/// where str is a u32 byte length followed by UTF-8 bytes.
+pub fn save(path: &Path, index: &FileIndex) -> io::Result<()> {
+    let key = index
+        .key()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "index has no workspace key"))?;
+    let changelist = index
+        .changelist()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "index is incomplete"))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Write to a temp file and rename, so a crash mid-write never leaves a torn index
+    let temp_path = path.with_extension("idx.tmp");
+    {
+        let mut out = BufWriter::new(fs::File::create(&temp_path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_str(&mut out, &key.server)?;
+        write_str(&mut out, &key.client)?;
+        write_str(&mut out, &key.depot_path)?;
+        out.write_all(&changelist.to_le_bytes())?;
+        out.write_all(&(index.files().len() as u64).to_le_bytes())?;
+        for entry in index.files() {
+            write_str(&mut out, &entry.depot_path)?;
+            out.write_all(&entry.mod_time.to_le_bytes())?;
+        }
+        out.flush()?;
+    }
+    fs::rename(&temp_path, path)
+}
+
+/// Load the index for `key` from `path`.
+/// Returns None when there is no file, or it was written by another format version
+/// or for another workspace. Corrupt files are reported as errors.
+pub fn load(path: &Path, key: &IndexKey) -> io::Result<Option<FileIndex>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut reader = Reader { data: &data, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid_data("not a file index"));
+    }
+    if reader.u32()? != FORMAT_VERSION {
+        return Ok(None);
+    }
+
+    let stored_key = IndexKey {
+        server: reader.string()?,
+        client: reader.string()?,
+        depot_path: reader.string()?,
+    };
+    if &stored_key != key {
+        return Ok(None);
+    }
+
+    let changelist = reader.u64()? as i64;
+    let count = reader.u64()? as usize;
+
+    // Each entry is at least 12 bytes; don't trust a count the data can't hold
+    let mut files = Vec::with_capacity(count.min(data.len() / 12));
+    for _ in 0..count {
+        files.push(FileEntry {
+            depot_path: reader.string()?,
+            mod_time: reader.u64()?,
+        });
+    }
+
+    let mut index = FileIndex::new();
+    index.reset(stored_key);
+    index.add_batch(files);
+    index.set_changelist(changelist);
+    Ok(Some(index))
+}
+
+/// Load the index for `key` off the async runtime
+pub async fn load_index(path: PathBuf, key: IndexKey) -> io::Result<Option<FileIndex>> {
+    tokio::task::spawn_blocking(move || load(&path, &key))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Save the shared index off the async runtime.
+/// Holds the lock while writing so the snapshot is consistent.
+pub async fn save_index(state: &FileIndexState, path: PathBuf) -> io::Result<()> {
+    let index = state.clone().lock_owned().await;
+    tokio::task::spawn_blocking(move || save(&path, &index))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn write_str(out: &mut impl Write, s: &str) -> io::Result<()> {
+    out.write_all(&(s.len() as u32).to_le_bytes())?;
+    out.write_all(s.as_bytes())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid_data("file index is truncated"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid_data("invalid UTF-8 in file index"))
+    }
+}
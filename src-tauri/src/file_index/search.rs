@@ -1,9 +1,17 @@
-use super::FileIndex;
+use super::{unix_now, FileEntry, FileIndex};
 use nucleo_matcher::pattern::{AtomKind, CaseMatching, Normalization, Pattern};
 use nucleo_matcher::{Config, Matcher};
+use rayon::prelude::*;
 use serde::Serialize;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Entries scored per parallel work item; cancellation is checked between chunks
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SearchMode {
     Fuzzy,
     Exact,
@@ -17,6 +25,13 @@ pub struct SearchResult {
     pub mod_time: u64,
 }
 
+/// Point-in-time view of the index entries, taken under the lock and searched without it
+#[derive(Clone)]
+pub struct IndexSnapshot {
+    pub(super) files: Arc<Vec<FileEntry>>,
+    pub(super) generation: u64,
+}
+
 impl FileIndex {
     /// Search with combined fuzzy score and recency bias
     pub fn search(&self, query: &str, mode: SearchMode, max_results: usize) -> Vec<SearchResult> {
@@ -24,62 +39,259 @@ impl FileIndex {
             return Vec::new();
         }
 
-        let mut matcher = Matcher::new(Config::DEFAULT);
-        let mut haystack_buf = Vec::new();
+        scan(&self.files, None, &build_pattern(query, mode), max_results, &|| false)
+            .map(|scan| scan.top.results(&self.files))
+            .unwrap_or_default()
+    }
+}
 
-        // Create pattern based on mode
-        let pattern = match mode {
-            SearchMode::Fuzzy => {
-                Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart)
-            }
-            SearchMode::Exact => Pattern::new(
-                query,
-                CaseMatching::Ignore,
-                Normalization::Smart,
-                AtomKind::Substring,
-            ),
-        };
-
-        // Current time for recency calculation
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-
-        let mut results: Vec<SearchResult> = Vec::new();
-
-        for entry in &self.files {
-            // Convert path to Utf32Str for nucleo
-            haystack_buf.clear();
-            let haystack = nucleo_matcher::Utf32Str::new(&entry.depot_path, &mut haystack_buf);
-
-            if let Some(fuzzy_score) = pattern.score(haystack, &mut matcher) {
-                // Apply recency bias: files modified in last 7 days get 1.5x boost
-                let age_days = if entry.mod_time > 0 && now > entry.mod_time {
-                    (now - entry.mod_time) / 86400
-                } else {
-                    u64::MAX // Unknown mod time, no boost
-                };
+/// Coordinates searches issued by the frontend.
+/// Each search takes a ticket; starting a new one cancels any still scanning.
+/// The full hit list of the last completed search is kept, so a query that
+/// extends it (the user typing another character) only rescores those hits.
+#[derive(Default)]
+pub struct Searcher {
+    latest: AtomicU64,
+    cache: Mutex<Option<SearchCache>>,
+}
 
-                let recency_multiplier = if age_days < 7 { 1.5 } else { 1.0 };
-                let combined_score = (fuzzy_score as f64 * recency_multiplier) as u32;
+/// Every entry matching `query` in the index generation it was computed against
+struct SearchCache {
+    generation: u64,
+    mode: SearchMode,
+    query: String,
+    hits: Arc<Vec<u32>>,
+}
 
-                results.push(SearchResult {
-                    depot_path: entry.depot_path.clone(),
-                    score: combined_score,
-                    mod_time: entry.mod_time,
-                });
-            }
+impl Searcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new search, superseding any in flight. Returns its ticket.
+    pub fn begin(&self) -> u64 {
+        self.latest.fetch_add(1, AtomicOrdering::SeqCst) + 1
+    }
+
+    /// True while no newer search has been started
+    pub fn is_current(&self, ticket: u64) -> bool {
+        self.latest.load(AtomicOrdering::SeqCst) == ticket
+    }
+
+    /// Run the search for `ticket` against `snapshot`.
+    /// Returns None if a newer search started before this one finished.
+    pub fn search(
+        &self,
+        snapshot: &IndexSnapshot,
+        ticket: u64,
+        query: &str,
+        mode: SearchMode,
+        max_results: usize,
+    ) -> Option<Vec<SearchResult>> {
+        if query.is_empty() {
+            return Some(Vec::new());
         }
 
-        // Sort by score descending, then path ascending for stable ordering
-        results.sort_by(|a, b| {
-            b.score
-                .cmp(&a.score)
-                .then_with(|| a.depot_path.cmp(&b.depot_path))
+        let previous_hits = self.cached_hits(snapshot.generation, mode, query);
+        let cancelled = || !self.is_current(ticket);
+        let Scan { top, hits } = scan(
+            &snapshot.files,
+            previous_hits.as_deref().map(Vec::as_slice),
+            &build_pattern(query, mode),
+            max_results,
+            &cancelled,
+        )?;
+
+        let results = top.results(&snapshot.files);
+        *self.cache.lock().unwrap() = Some(SearchCache {
+            generation: snapshot.generation,
+            mode,
+            query: query.to_string(),
+            hits: Arc::new(hits),
         });
+        Some(results)
+    }
+
+    /// Hits of the previous search if every match for `query` must be among them
+    fn cached_hits(&self, generation: u64, mode: SearchMode, query: &str) -> Option<Arc<Vec<u32>>> {
+        let cache = self.cache.lock().unwrap();
+        let cache = cache.as_ref()?;
+        let narrows = cache.generation == generation
+            && cache.mode == mode
+            && query.starts_with(&cache.query)
+            && !has_pattern_syntax(&cache.query);
+        narrows.then(|| Arc::clone(&cache.hits))
+    }
+}
+
+/// Thread-safe state wrapper for Tauri
+pub type SearchState = Arc<Searcher>;
+
+pub fn create_search_state() -> SearchState {
+    Arc::new(Searcher::new())
+}
+
+/// Whether `query` uses pattern syntax (negation, anchors, escapes).
+/// Appending to such a query can widen the match set, so its hits can't be reused.
+fn has_pattern_syntax(query: &str) -> bool {
+    query.contains(['!', '^', '$', '\'', '\\'])
+}
+
+fn build_pattern(query: &str, mode: SearchMode) -> Pattern {
+    match mode {
+        SearchMode::Fuzzy => Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart),
+        SearchMode::Exact => Pattern::new(
+            query,
+            CaseMatching::Ignore,
+            Normalization::Smart,
+            AtomKind::Substring,
+        ),
+    }
+}
+
+/// A scored entry. Ordered so that better matches compare greater:
+/// higher score first, then depot path ascending for stable ordering.
+#[derive(PartialEq, Eq)]
+struct Candidate<'a> {
+    score: u32,
+    depot_path: &'a str,
+    slot: u32,
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .cmp(&other.score)
+            .then_with(|| other.depot_path.cmp(self.depot_path))
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Bounded min-heap keeping the best `limit` candidates seen
+struct TopK<'a> {
+    limit: usize,
+    heap: BinaryHeap<Reverse<Candidate<'a>>>,
+}
+
+impl<'a> TopK<'a> {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            heap: BinaryHeap::with_capacity(limit.min(CHUNK_SIZE)),
+        }
+    }
 
-        results.truncate(max_results);
-        results
+    fn push(&mut self, candidate: Candidate<'a>) {
+        if self.heap.len() < self.limit {
+            self.heap.push(Reverse(candidate));
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if candidate > worst.0 {
+                *worst = Reverse(candidate);
+            }
+        }
     }
+
+    fn merge(mut self, other: Self) -> Self {
+        for Reverse(candidate) in other.heap {
+            self.push(candidate);
+        }
+        self
+    }
+
+    /// Results for the kept candidates, best first
+    fn results(self, files: &[FileEntry]) -> Vec<SearchResult> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(candidate)| SearchResult {
+                depot_path: candidate.depot_path.to_string(),
+                score: candidate.score,
+                mod_time: files[candidate.slot as usize].mod_time,
+            })
+            .collect()
+    }
+}
+
+/// Outcome of scoring a set of entries
+struct Scan<'a> {
+    top: TopK<'a>,
+    /// Slots of every matching entry, for narrowing the next query
+    hits: Vec<u32>,
+}
+
+/// Score entries in parallel, keeping the best `limit`.
+/// Scans `slots` when given (hits of a previous query), otherwise every entry.
+/// Returns None if `cancelled` reports true before the scan completes.
+fn scan<'a>(
+    files: &'a [FileEntry],
+    slots: Option<&[u32]>,
+    pattern: &Pattern,
+    limit: usize,
+    cancelled: &(dyn Fn() -> bool + Sync),
+) -> Option<Scan<'a>> {
+    let total = slots.map_or(files.len(), <[u32]>::len);
+    let now = unix_now();
+
+    (0..total.div_ceil(CHUNK_SIZE))
+        .into_par_iter()
+        .map_init(
+            || (Matcher::new(Config::DEFAULT), Vec::new()),
+            |(matcher, haystack_buf), chunk| {
+                if cancelled() {
+                    return None;
+                }
+
+                let mut scan = Scan {
+                    top: TopK::new(limit),
+                    hits: Vec::new(),
+                };
+                let end = ((chunk + 1) * CHUNK_SIZE).min(total);
+                for i in chunk * CHUNK_SIZE..end {
+                    let slot = slots.map_or(i as u32, |slots| slots[i]);
+                    let entry = &files[slot as usize];
+
+                    // Convert path to Utf32Str for nucleo
+                    haystack_buf.clear();
+                    let haystack = nucleo_matcher::Utf32Str::new(&entry.depot_path, haystack_buf);
+
+                    if let Some(fuzzy_score) = pattern.score(haystack, matcher) {
+                        scan.hits.push(slot);
+                        scan.top.push(Candidate {
+                            score: combined_score(fuzzy_score, entry.mod_time, now),
+                            depot_path: &entry.depot_path,
+                            slot,
+                        });
+                    }
+                }
+                Some(scan)
+            },
+        )
+        .try_reduce(
+            || Scan {
+                top: TopK::new(limit),
+                hits: Vec::new(),
+            },
+            |mut left, right| {
+                left.hits.extend(right.hits);
+                left.top = left.top.merge(right.top);
+                Some(left)
+            },
+        )
+}
+
+/// Apply recency bias: files modified in last 7 days get 1.5x boost
+fn combined_score(fuzzy_score: u32, mod_time: u64, now: u64) -> u32 {
+    let age_days = if mod_time > 0 && now > mod_time {
+        (now - mod_time) / 86400
+    } else {
+        u64::MAX // Unknown mod time, no boost
+    };
+
+    let recency_multiplier = if age_days < 7 { 1.5 } else { 1.0 };
+    (fuzzy_score as f64 * recency_multiplier) as u32
 }
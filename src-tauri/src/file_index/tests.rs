@@ -0,0 +1,314 @@
+use super::persist::{self, IndexKey, FORMAT_VERSION};
+use super::{ChangeSource, FileEntry, FileIndex, SearchMode, Searcher};
+
+fn key() -> IndexKey {
+    IndexKey {
+        server: "ssl:perforce:1666".to_string(),
+        client: "alice-ws".to_string(),
+        depot_path: "//stream/main/...".to_string(),
+    }
+}
+
+fn entry(depot_path: &str, mod_time: u64) -> FileEntry {
+    FileEntry {
+        depot_path: depot_path.to_string(),
+        mod_time,
+    }
+}
+
+fn paths(index: &FileIndex) -> Vec<&str> {
+    let mut paths: Vec<&str> = index.files().iter().map(|e| e.depot_path.as_str()).collect();
+    paths.sort();
+    paths
+}
+
+fn built_index() -> FileIndex {
+    let mut index = FileIndex::new();
+    index.reset(key());
+    index.add_batch(vec![
+        entry("//stream/main/a.cpp", 100),
+        entry("//stream/main/src/b.h", 200),
+    ]);
+    index.set_changelist(1234);
+    index
+}
+
+#[test]
+fn test_index_key_file_name_is_filesystem_safe() {
+    let name = key().file_name();
+    assert!(name.ends_with(".idx"));
+    assert!(name
+        .trim_end_matches(".idx")
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+}
+
+#[test]
+fn test_save_and_load_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = persist::index_path(dir.path(), &key());
+
+    persist::save(&path, &built_index()).unwrap();
+    let loaded = persist::load(&path, &key()).unwrap().unwrap();
+
+    assert_eq!(loaded.key(), Some(&key()));
+    assert_eq!(loaded.changelist(), Some(1234));
+    assert_eq!(paths(&loaded), ["//stream/main/a.cpp", "//stream/main/src/b.h"]);
+    assert_eq!(loaded.files()[1].mod_time, 200);
+}
+
+#[test]
+fn test_save_requires_complete_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut index = built_index();
+    index.clear();
+    assert!(persist::save(&dir.path().join("x.idx"), &index).is_err());
+}
+
+#[test]
+fn test_load_missing_or_other_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = persist::index_path(dir.path(), &key());
+    assert!(persist::load(&path, &key()).unwrap().is_none());
+
+    persist::save(&path, &built_index()).unwrap();
+    let other = IndexKey {
+        client: "bob-ws".to_string(),
+        ..key()
+    };
+    assert!(persist::load(&path, &other).unwrap().is_none());
+}
+
+#[test]
+fn test_file_name_keeps_keys_that_sanitize_alike_apart() {
+    let dotted = IndexKey {
+        client: "alice.ws".to_string(),
+        ..key()
+    };
+    let underscored = IndexKey {
+        client: "alice_ws".to_string(),
+        ..key()
+    };
+    assert_ne!(dotted.file_name(), underscored.file_name());
+    assert_eq!(dotted.file_name(), dotted.clone().file_name());
+    assert!(dotted.file_name().starts_with("ssl_perforce_1666_alice_ws_"));
+    assert!(dotted.file_name().ends_with(".idx"));
+}
+
+#[test]
+fn test_load_rejects_other_format_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = persist::index_path(dir.path(), &key());
+    persist::save(&path, &built_index()).unwrap();
+
+    let mut data = std::fs::read(&path).unwrap();
+    data[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    std::fs::write(&path, data).unwrap();
+
+    assert!(persist::load(&path, &key()).unwrap().is_none());
+}
+
+#[test]
+fn test_load_truncated_file_is_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = persist::index_path(dir.path(), &key());
+    persist::save(&path, &built_index()).unwrap();
+
+    let data = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &data[..data.len() - 5]).unwrap();
+    assert!(persist::load(&path, &key()).is_err());
+
+    std::fs::write(&path, b"garbage").unwrap();
+    assert!(persist::load(&path, &key()).is_err());
+}
+
+#[test]
+fn test_apply_changes_replaces_and_removes() {
+    let mut index = built_index();
+    index.apply_changes(
+        vec![entry("//stream/main/a.cpp", 300), entry("//stream/main/c.txt", 400)],
+        &["//stream/main/src/b.h".to_string()],
+    );
+
+    assert_eq!(paths(&index), ["//stream/main/a.cpp", "//stream/main/c.txt"]);
+    let a = index
+        .files()
+        .iter()
+        .find(|e| e.depot_path == "//stream/main/a.cpp")
+        .unwrap();
+    assert_eq!(a.mod_time, 300);
+}
+
+#[test]
+fn test_clear_keeps_key_but_marks_incomplete() {
+    let mut index = built_index();
+    index.clear();
+    assert!(index.is_empty());
+    assert_eq!(index.key(), Some(&key()));
+    assert_eq!(index.changelist(), None);
+}
+
+#[test]
+fn test_add_batch_dedupes_by_path() {
+    let mut index = built_index();
+    index.add_batch(vec![
+        entry("//stream/main/a.cpp", 500),
+        entry("//stream/main/a.cpp", 600),
+    ]);
+
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.get("//stream/main/a.cpp").unwrap().mod_time, 600);
+}
+
+#[test]
+fn test_remove_keeps_slots_consistent() {
+    let mut index = built_index();
+    index.upsert(entry("//stream/main/c.txt", 300));
+
+    // Removing the first entry moves the last one into its slot
+    assert!(index.remove("//stream/main/a.cpp"));
+    assert!(!index.remove("//stream/main/a.cpp"));
+    assert_eq!(index.get("//stream/main/c.txt").unwrap().mod_time, 300);
+    assert_eq!(index.get("//stream/main/src/b.h").unwrap().mod_time, 200);
+
+    assert!(index.upsert(entry("//stream/main/a.cpp", 700)));
+    assert!(!index.upsert(entry("//stream/main/c.txt", 800)));
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.get("//stream/main/c.txt").unwrap().mod_time, 800);
+}
+
+#[test]
+fn test_apply_action_sync() {
+    let mut index = built_index();
+
+    assert!(index.apply_action(ChangeSource::Sync, "//stream/main/src/b.h", "deleting", None));
+    assert!(index.apply_action(ChangeSource::Sync, "//stream/main/a.cpp", "updating", None));
+    assert!(index.apply_action(ChangeSource::Sync, "//stream/main/new.txt", "adding", Some(900)));
+    assert!(!index.apply_action(ChangeSource::Sync, "//stream/main/x.txt", "can't clobber", None));
+
+    assert_eq!(paths(&index), ["//stream/main/a.cpp", "//stream/main/new.txt"]);
+    // Updates without a timestamp keep the indexed one
+    assert_eq!(index.get("//stream/main/a.cpp").unwrap().mod_time, 100);
+    assert_eq!(index.get("//stream/main/new.txt").unwrap().mod_time, 900);
+}
+
+#[test]
+fn test_apply_action_submit_and_revert() {
+    let mut index = built_index();
+
+    index.apply_action(ChangeSource::Submit, "//stream/main/a.cpp", "move/delete", None);
+    index.apply_action(ChangeSource::Submit, "//stream/main/moved.cpp", "move/add", None);
+    assert_eq!(paths(&index), ["//stream/main/moved.cpp", "//stream/main/src/b.h"]);
+    assert!(index.get("//stream/main/moved.cpp").unwrap().mod_time > 0);
+
+    // Reverting an add removes the file; reverting an edit leaves the entry alone
+    index.upsert(entry("//stream/main/added.txt", 1000));
+    assert!(index.apply_action(ChangeSource::Revert, "//stream/main/added.txt", "add", None));
+    assert!(!index.apply_action(ChangeSource::Revert, "//stream/main/src/b.h", "edit", None));
+    assert_eq!(paths(&index), ["//stream/main/moved.cpp", "//stream/main/src/b.h"]);
+}
+
+fn large_index(count: usize) -> FileIndex {
+    let mut index = FileIndex::new();
+    index.add_batch(
+        (0..count)
+            .map(|i| entry(&format!("//stream/main/dir{}/file{}.cpp", i % 97, i), 0))
+            .collect(),
+    );
+    index
+}
+
+fn result_paths(results: &[super::SearchResult]) -> Vec<&str> {
+    results.iter().map(|r| r.depot_path.as_str()).collect()
+}
+
+#[test]
+fn test_search_top_k_matches_full_sort() {
+    let index = large_index(20_000);
+
+    let top = index.search("file12", SearchMode::Fuzzy, 25);
+    let all = index.search("file12", SearchMode::Fuzzy, usize::MAX);
+
+    assert_eq!(top.len(), 25);
+    assert_eq!(result_paths(&top), result_paths(&all[..25]));
+    assert!(top
+        .windows(2)
+        .all(|w| w[0].score > w[1].score
+            || (w[0].score == w[1].score && w[0].depot_path < w[1].depot_path)));
+}
+
+#[test]
+fn test_search_exact_mode_and_empty_query() {
+    let index = built_index();
+    assert_eq!(
+        result_paths(&index.search("src/b", SearchMode::Exact, 10)),
+        ["//stream/main/src/b.h"]
+    );
+    assert!(index.search("srcb", SearchMode::Exact, 10).is_empty());
+    assert!(index.search("", SearchMode::Fuzzy, 10).is_empty());
+}
+
+#[test]
+fn test_searcher_narrows_previous_hits() {
+    let index = large_index(20_000);
+    let searcher = Searcher::new();
+    let snapshot = index.snapshot();
+
+    let ticket = searcher.begin();
+    searcher.search(&snapshot, ticket, "file1", SearchMode::Fuzzy, 50).unwrap();
+    let ticket = searcher.begin();
+    let narrowed = searcher
+        .search(&snapshot, ticket, "file12", SearchMode::Fuzzy, 50)
+        .unwrap();
+
+    let fresh = index.search("file12", SearchMode::Fuzzy, 50);
+    assert_eq!(result_paths(&narrowed), result_paths(&fresh));
+}
+
+#[test]
+fn test_searcher_ignores_cache_after_index_changes() {
+    let mut index = built_index();
+    let searcher = Searcher::new();
+
+    let ticket = searcher.begin();
+    let before = searcher
+        .search(&index.snapshot(), ticket, "a.c", SearchMode::Exact, 10)
+        .unwrap();
+    assert_eq!(result_paths(&before), ["//stream/main/a.cpp"]);
+
+    index.upsert(entry("//stream/main/a.cmake", 0));
+    let ticket = searcher.begin();
+    let after = searcher
+        .search(&index.snapshot(), ticket, "a.cm", SearchMode::Exact, 10)
+        .unwrap();
+    assert_eq!(result_paths(&after), ["//stream/main/a.cmake"]);
+}
+
+#[test]
+fn test_searcher_cancels_superseded_search() {
+    let index = large_index(1_000);
+    let searcher = Searcher::new();
+
+    let stale = searcher.begin();
+    searcher.begin();
+    assert!(searcher
+        .search(&index.snapshot(), stale, "file", SearchMode::Fuzzy, 10)
+        .is_none());
+}
+
+#[test]
+fn test_snapshot_is_unaffected_by_later_changes() {
+    let mut index = built_index();
+    let snapshot = index.snapshot();
+
+    index.remove("//stream/main/a.cpp");
+    index.upsert(entry("//stream/main/c.txt", 300));
+
+    let searcher = Searcher::new();
+    let ticket = searcher.begin();
+    let results = searcher
+        .search(&snapshot, ticket, "a.cpp", SearchMode::Exact, 10)
+        .unwrap();
+    assert_eq!(result_paths(&results), ["//stream/main/a.cpp"]);
+    assert_eq!(paths(&index), ["//stream/main/c.txt", "//stream/main/src/b.h"]);
+}
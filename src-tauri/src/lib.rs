@@ -1,10 +1,11 @@
 mod commands;
+mod content_index;
+mod diff;
 mod file_index;
 mod state;
 
-use file_index::create_file_index_state;
-use state::ProcessManager;
-use tauri::Manager;
+use state::{create_connections_state, create_p4_executor_state, ConnectionsState, ProcessManager};
+use tauri::{Emitter, Manager};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -13,21 +14,35 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_store::Builder::new().build())
-        .manage(create_file_index_state())
+        .manage(create_connections_state())
         .manage(ProcessManager::new())
+        .manage(create_p4_executor_state())
+        .manage(commands::create_opened_cache_state())
         .invoke_handler(tauri::generate_handler![
             commands::spawn_p4_command,
             commands::p4_command,
             commands::kill_process,
             commands::kill_all_processes,
+            commands::list_processes,
+            commands::kill_connection_processes,
+            commands::list_connection_profiles,
+            commands::save_connection_profile,
+            commands::delete_connection_profile,
             commands::p4_info,
             commands::p4_fstat,
             commands::p4_fstat_stream,
             commands::p4_opened,
             commands::p4_fstat_opened,
+            commands::p4_opened_all,
+            commands::p4_file_opened_by,
             commands::p4_changes,
             commands::p4_edit,
             commands::p4_revert,
+            commands::p4_add,
+            commands::p4_delete,
+            commands::p4_lock,
+            commands::p4_unlock,
+            commands::p4_move,
             commands::p4_submit,
             commands::p4_sync,
             commands::p4_list_workspaces,
@@ -50,26 +65,83 @@ pub fn run() {
             commands::p4_reconcile_preview,
             commands::p4_reconcile_apply,
             commands::p4_resolve_preview,
+            commands::p4_integrate_preview,
+            commands::p4_integrate,
             commands::p4_fstat_unresolved,
             commands::p4_resolve_accept,
             commands::launch_merge_tool,
             commands::p4_files,
             commands::p4_list_streams,
+            commands::p4_stream_integration_status,
+            commands::p4_get_stream_spec,
+            commands::p4_save_stream_spec,
+            commands::p4_create_stream,
             commands::p4_get_client_spec,
+            commands::p4_create_client,
+            commands::p4_save_client_spec,
+            commands::p4_delete_client,
             commands::p4_update_client_stream,
             commands::p4_dirs,
+            commands::p4_list_labels,
+            commands::p4_get_label_spec,
+            commands::p4_save_label_spec,
+            commands::p4_delete_label,
+            commands::p4_tag,
+            commands::p4_labelsync,
+            commands::p4_jobs,
+            commands::p4_get_job_spec,
+            commands::p4_save_job_spec,
+            commands::p4_get_user_spec,
+            commands::p4_save_user_spec,
+            commands::p4_fix,
+            commands::p4_list_branches,
+            commands::p4_get_branch_spec,
+            commands::p4_save_branch_spec,
+            commands::p4_diff2_branch,
+            commands::p4_login,
+            commands::p4_logout,
+            commands::p4_login_status,
+            commands::p4_trust_status,
+            commands::p4_trust,
+            commands::p4_diff,
+            commands::p4_changelist_diff,
             commands::p4_depots,
             commands::p4_annotate,
             commands::search_workspace_files,
             commands::add_files_to_index,
+            commands::remove_files_from_index,
+            commands::apply_file_index_changes,
             commands::clear_file_index,
             commands::get_file_index_count,
+            commands::save_file_index,
+            commands::p4_open_file_index,
+            commands::p4_grep,
+            commands::p4_build_content_index,
+            commands::search_content_index,
+            commands::clear_content_index,
         ])
         .setup(|app| {
+            // Load saved connection profiles; a broken file leaves only the default connection
+            let connections = app.state::<ConnectionsState>();
+            match app.path().app_data_dir() {
+                Ok(dir) => {
+                    if let Err(e) = connections.load(&dir.join("connections.json")) {
+                        eprintln!("Failed to load connection profiles: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to locate app data dir: {}", e),
+            }
+
             // Get process manager for cleanup
             let process_manager = app.state::<ProcessManager>();
             let pm = process_manager.inner().clone();
 
+            // Report every tracked process exit to the frontend
+            let handle = app.handle().clone();
+            pm.set_exit_listener(move |exit| {
+                let _ = handle.emit("process-exited", exit);
+            });
+
             // Listen for window close to cleanup processes
             if let Some(window) = app.get_webview_window("main") {
                 window.on_window_event(move |event| {
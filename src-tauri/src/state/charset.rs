@@ -0,0 +1,53 @@
+use encoding_rs::{Encoding, UTF_8};
+
+/// Charset used for a unicode-mode server when neither the profile nor p4's own
+/// settings name one
+pub const DEFAULT_UNICODE_CHARSET: &str = "utf8";
+
+/// Text encoding for a P4CHARSET / P4COMMANDCHARSET value.
+/// None for "none", "auto" and charsets without a known encoding; treat those as UTF-8.
+pub fn charset_encoding(charset: &str) -> Option<&'static Encoding> {
+    let charset = charset.trim().to_ascii_lowercase();
+    let encoding = match charset.as_str() {
+        "utf8" | "utf8-bom" | "utf8unchecked" | "utf8unchecked-bom" => UTF_8,
+        "utf16" | "utf16-nobom" | "utf16le" | "utf16le-bom" => encoding_rs::UTF_16LE,
+        "utf16be" | "utf16be-bom" => encoding_rs::UTF_16BE,
+        "shiftjis" => encoding_rs::SHIFT_JIS,
+        "eucjp" => encoding_rs::EUC_JP,
+        // WHATWG treats latin1 as its windows-1252 superset
+        "iso8859-1" | "winansi" => encoding_rs::WINDOWS_1252,
+        "iso8859-5" => encoding_rs::ISO_8859_5,
+        "iso8859-7" => encoding_rs::ISO_8859_7,
+        "iso8859-15" => encoding_rs::ISO_8859_15,
+        "macosroman" => encoding_rs::MACINTOSH,
+        "cp936" => encoding_rs::GBK,
+        "cp949" => encoding_rs::EUC_KR,
+        "cp950" => encoding_rs::BIG5,
+        "cp1251" => encoding_rs::WINDOWS_1251,
+        "cp1253" => encoding_rs::WINDOWS_1253,
+        "koi8-r" => encoding_rs::KOI8_R,
+        _ => return None,
+    };
+    Some(encoding)
+}
+
+/// Whether p4 refuses to write command output in this charset.
+/// UTF-16 and UTF-32 charsets need a separate P4COMMANDCHARSET.
+pub fn is_wide_charset(charset: &str) -> bool {
+    let charset = charset.trim().to_ascii_lowercase();
+    charset.starts_with("utf16") || charset.starts_with("utf32")
+}
+
+/// Decode p4 output or file content. A byte order mark wins over `encoding`;
+/// without either the text is UTF-8. Undecodable bytes become U+FFFD.
+pub fn decode_text(bytes: &[u8], encoding: Option<&'static Encoding>) -> String {
+    let (text, _, _) = encoding.unwrap_or(UTF_8).decode(bytes);
+    text.into_owned()
+}
+
+/// Encode text (e.g. a spec form) for p4's stdin. UTF-16 encodings write UTF-8,
+/// which is what p4 expects whenever the command charset is wide.
+pub fn encode_text(text: &str, encoding: Option<&'static Encoding>) -> Vec<u8> {
+    let (bytes, _, _) = encoding.unwrap_or(UTF_8).encode(text);
+    bytes.into_owned()
+}
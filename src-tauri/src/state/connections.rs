@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::content_index::{create_content_index_state, ContentIndexState};
+use crate::file_index::{create_file_index_state, create_search_state, FileIndexState, SearchState};
+
+use encoding_rs::Encoding;
+
+use super::charset::{charset_encoding, is_wide_charset, DEFAULT_UNICODE_CHARSET};
+use super::P4Command;
+
+/// Saved settings for one Perforce connection, referenced by ID from every command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionProfile {
+    pub id: String,
+    /// Display name, e.g. "Main" or "Edge (Berlin)"
+    pub name: String,
+    pub server: Option<String>,
+    pub user: Option<String>,
+    pub client: Option<String>,
+    /// P4CHARSET for unicode-mode servers, e.g. "utf8"
+    pub charset: Option<String>,
+    /// Let P4CONFIG files and P4ROOT apply even though server/user/client are set.
+    /// Off by default, so a local DVCS or config file can't redirect the profile.
+    pub use_p4config: bool,
+    /// P4TICKETS file, to keep this profile's login tickets apart from the default
+    pub tickets_file: Option<String>,
+}
+
+impl ConnectionProfile {
+    /// Add this profile's global options and environment to a p4 command
+    pub fn apply(&self, cmd: &mut P4Command) {
+        let server = non_empty(&self.server);
+        let user = non_empty(&self.user);
+        let client = non_empty(&self.client);
+
+        if !self.use_p4config && (server.is_some() || user.is_some() || client.is_some()) {
+            // Clear P4CONFIG and P4ROOT to prevent DVCS/local config from interfering
+            cmd.env("P4CONFIG", "");
+            cmd.env("P4ROOT", "");
+        }
+
+        if let Some(s) = server {
+            cmd.args(["-p", s]);
+            // Also set env var to ensure override even if P4PORT is set in environment
+            cmd.env("P4PORT", s);
+        }
+        if let Some(u) = user {
+            cmd.args(["-u", u]);
+            cmd.env("P4USER", u);
+        }
+        if let Some(c) = client {
+            cmd.args(["-c", c]);
+            cmd.env("P4CLIENT", c);
+        }
+        if let Some(charset) = non_empty(&self.charset) {
+            cmd.args(["-C", charset]);
+            cmd.env("P4CHARSET", charset);
+            if is_wide_charset(charset) {
+                // p4 won't write UTF-16/32 output, so have it talk UTF-8 to us
+                cmd.args(["-Q", DEFAULT_UNICODE_CHARSET]);
+                cmd.env("P4COMMANDCHARSET", DEFAULT_UNICODE_CHARSET);
+            }
+        }
+        if let Some(tickets) = non_empty(&self.tickets_file) {
+            cmd.env("P4TICKETS", tickets);
+        }
+    }
+
+    /// Encoding p4 translates unicode files to in the workspace; None for UTF-8 or unset
+    pub fn file_encoding(&self) -> Option<&'static Encoding> {
+        non_empty(&self.charset).and_then(charset_encoding)
+    }
+
+    /// This profile without its workspace, for commands on client specs themselves
+    pub fn without_client(&self) -> ConnectionProfile {
+        ConnectionProfile {
+            client: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether both profiles talk to the same server as the same user and workspace,
+    /// so indexes built for one are valid for the other
+    fn same_target(&self, other: &ConnectionProfile) -> bool {
+        self.server == other.server && self.user == other.user && self.client == other.client
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|s| !s.is_empty())
+}
+
+/// A profile plus the state that must not be shared between connections
+pub struct Connection {
+    pub profile: ConnectionProfile,
+    pub file_index: FileIndexState,
+    pub searcher: SearchState,
+    pub content_index: ContentIndexState,
+}
+
+impl Connection {
+    fn new(profile: ConnectionProfile) -> Self {
+        Self {
+            profile,
+            file_index: create_file_index_state(),
+            searcher: create_search_state(),
+            content_index: create_content_index_state(),
+        }
+    }
+
+    /// The same connection with an updated profile, sharing its indexes
+    fn with_profile(&self, profile: ConnectionProfile) -> Self {
+        Self {
+            profile,
+            file_index: self.file_index.clone(),
+            searcher: self.searcher.clone(),
+            content_index: self.content_index.clone(),
+        }
+    }
+}
+
+/// All connection profiles, any number of which can be in use at once.
+/// Commands without a profile ID use the default connection, which takes
+/// everything from the environment and P4CONFIG.
+pub struct Connections {
+    default: RwLock<Arc<Connection>>,
+    profiles: RwLock<HashMap<String, Arc<Connection>>>,
+    /// File the profiles are saved to; None until `load` is called
+    path: RwLock<Option<PathBuf>>,
+    /// Held while the list is written, so concurrent saves don't share the temp
+    /// file and the last one to finish writes the latest list
+    persist_lock: Mutex<()>,
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Self {
+            default: RwLock::new(Arc::new(Connection::new(ConnectionProfile::default()))),
+            profiles: RwLock::new(HashMap::new()),
+            path: RwLock::new(None),
+            persist_lock: Mutex::new(()),
+        }
+    }
+
+    /// The connection for a profile ID, or the default connection for None
+    pub fn get(&self, id: &Option<String>) -> Result<Arc<Connection>, String> {
+        let Some(id) = id.as_deref().filter(|id| !id.is_empty()) else {
+            return Ok(self.default.read().unwrap().clone());
+        };
+        self.profiles
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown connection profile '{}'", id))
+    }
+
+    /// Saved profiles, sorted by name
+    pub fn list(&self) -> Vec<ConnectionProfile> {
+        let mut profiles: Vec<ConnectionProfile> = self
+            .profiles
+            .read()
+            .unwrap()
+            .values()
+            .map(|c| c.profile.clone())
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        profiles
+    }
+
+    /// Add or replace a profile and persist the list.
+    /// Indexes are kept when server, user and workspace are unchanged.
+    pub fn save(&self, profile: ConnectionProfile) -> io::Result<()> {
+        if profile.id.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Connection profile has no ID"));
+        }
+        {
+            let mut profiles = self.profiles.write().unwrap();
+            let connection = match profiles.get(&profile.id) {
+                Some(existing) if existing.profile.same_target(&profile) => {
+                    existing.with_profile(profile.clone())
+                }
+                _ => Connection::new(profile.clone()),
+            };
+            profiles.insert(profile.id, Arc::new(connection));
+        }
+        self.persist()
+    }
+
+    /// Use `charset` for a connection whose profile doesn't name one, after finding
+    /// that its server is in unicode mode. Indexes are kept. Not written to disk
+    /// unless the profile is saved again.
+    pub fn set_detected_charset(&self, id: &Option<String>, charset: &str) -> Result<Arc<Connection>, String> {
+        let with_charset = |current: &Arc<Connection>| {
+            if non_empty(&current.profile.charset).is_some() {
+                return current.clone();
+            }
+            Arc::new(current.with_profile(ConnectionProfile {
+                charset: Some(charset.to_string()),
+                ..current.profile.clone()
+            }))
+        };
+        let Some(id) = id.as_deref().filter(|id| !id.is_empty()) else {
+            let mut default = self.default.write().unwrap();
+            *default = with_charset(&default);
+            return Ok(default.clone());
+        };
+        let mut profiles = self.profiles.write().unwrap();
+        let current = profiles
+            .get(id)
+            .ok_or_else(|| format!("Unknown connection profile '{}'", id))?;
+        let updated = with_charset(current);
+        profiles.insert(id.to_string(), updated.clone());
+        Ok(updated)
+    }
+
+    /// Remove a profile and persist the list. Returns false if it didn't exist.
+    pub fn remove(&self, id: &str) -> io::Result<bool> {
+        let removed = self.profiles.write().unwrap().remove(id).is_some();
+        if removed {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    /// Load saved profiles from `path` and save future changes there.
+    /// A missing file is an empty list.
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        *self.path.write().unwrap() = Some(path.to_path_buf());
+        let saved: Vec<ConnectionProfile> = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut profiles = self.profiles.write().unwrap();
+        for profile in saved {
+            profiles.insert(profile.id.clone(), Arc::new(Connection::new(profile)));
+        }
+        Ok(())
+    }
+
+    fn persist(&self) -> io::Result<()> {
+        let Some(path) = self.path.read().unwrap().clone() else {
+            return Ok(());
+        };
+        // Take the list under the lock too: one taken before another save finished could be stale
+        let _guard = self.persist_lock.lock().unwrap();
+        let json = serde_json::to_vec_pretty(&self.list()).map_err(io::Error::other)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write a temp file and rename, so a crash never leaves a truncated list
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)
+    }
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Thread-safe state wrapper for Tauri
+pub type ConnectionsState = Arc<Connections>;
+
+pub fn create_connections_state() -> ConnectionsState {
+    Arc::new(Connections::new())
+}
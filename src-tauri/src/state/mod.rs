@@ -1,2 +1,22 @@
+mod charset;
+mod connections;
+mod p4_executor;
 mod process_manager;
-pub use process_manager::ProcessManager;
+pub use charset::{
+    charset_encoding, decode_text, encode_text, is_wide_charset, DEFAULT_UNICODE_CHARSET,
+};
+pub use connections::{
+    create_connections_state, Connection, ConnectionProfile, Connections, ConnectionsState,
+};
+#[cfg(test)]
+pub use p4_executor::{RecordedCall, ScriptedExecutor};
+pub use p4_executor::{
+    create_p4_executor_state, P4Child, P4Command, P4Executor, P4ExecutorState, P4Output,
+    P4OutputStream, P4Process, ProcessExecutor,
+};
+pub use process_manager::{
+    ExitReason, ProcessExit, ProcessInfo, ProcessManager, RegisteredProcess,
+};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,375 @@
+use async_trait::async_trait;
+use encoding_rs::Encoding;
+use std::io;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio::process::Command;
+
+use super::charset::{charset_encoding, decode_text, encode_text};
+
+// Windows-specific import for hiding console windows
+#[cfg(target_os = "windows")]
+#[allow(unused_imports)] // Trait is used via creation_flags() method
+use std::os::windows::process::CommandExt;
+
+/// A p4 invocation: global options, command and arguments, plus environment overrides.
+/// Built up by handlers and handed to a P4Executor to run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct P4Command {
+    pub args: Vec<String>,
+    pub envs: Vec<(String, String)>,
+}
+
+impl P4Command {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<str>) -> &mut Self {
+        self.args.push(arg.as_ref().to_string());
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.args.extend(args.into_iter().map(|a| a.as_ref().to_string()));
+        self
+    }
+
+    pub fn env(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.envs.push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Charset this command sets for p4's output: P4COMMANDCHARSET, else P4CHARSET
+    pub fn output_charset(&self) -> Option<&str> {
+        let env = |key: &str| {
+            self.envs
+                .iter()
+                .rev()
+                .find(|(k, v)| k == key && !v.is_empty())
+                .map(|(_, v)| v.as_str())
+        };
+        env("P4COMMANDCHARSET").or_else(|| env("P4CHARSET"))
+    }
+
+    /// Encoding of p4's output for this command; None for UTF-8 or unset
+    pub fn output_encoding(&self) -> Option<&'static Encoding> {
+        self.output_charset().and_then(charset_encoding)
+    }
+}
+
+/// Captured result of a p4 process that ran to completion.
+#[derive(Debug, Clone, Default)]
+pub struct P4Output {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// None when the process was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Charset p4 wrote its output in; None for UTF-8
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl P4Output {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Decoded stdout
+    pub fn stdout_text(&self) -> String {
+        decode_text(&self.stdout, self.encoding)
+    }
+
+    /// Decoded stderr
+    pub fn stderr_text(&self) -> String {
+        decode_text(&self.stderr, self.encoding)
+    }
+}
+
+impl From<std::process::Output> for P4Output {
+    fn from(output: std::process::Output) -> Self {
+        Self {
+            stdout: output.stdout,
+            stderr: output.stderr,
+            exit_code: output.status.code(),
+            encoding: None,
+        }
+    }
+}
+
+/// Handle to a running p4 process, tracked by ProcessManager for cancellation.
+#[async_trait]
+pub trait P4Child: Send + Sync {
+    /// OS process ID, if the process is still running
+    fn id(&self) -> Option<u32>;
+    async fn kill(&mut self) -> io::Result<()>;
+    /// Wait for exit and return the exit code (None if killed by a signal)
+    async fn wait(&mut self) -> io::Result<Option<i32>>;
+}
+
+#[async_trait]
+impl P4Child for tokio::process::Child {
+    fn id(&self) -> Option<u32> {
+        tokio::process::Child::id(self)
+    }
+
+    async fn kill(&mut self) -> io::Result<()> {
+        tokio::process::Child::kill(self).await
+    }
+
+    async fn wait(&mut self) -> io::Result<Option<i32>> {
+        Ok(tokio::process::Child::wait(self).await?.code())
+    }
+}
+
+/// Output stream of a spawned p4 process
+pub type P4OutputStream = Box<dyn AsyncRead + Send + Unpin>;
+
+/// A spawned p4 process with piped stdout/stderr for streaming commands.
+pub struct P4Process {
+    pub stdout: Option<P4OutputStream>,
+    pub stderr: Option<P4OutputStream>,
+    pub child: Box<dyn P4Child>,
+    /// Charset of stdout and stderr; None for UTF-8
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// Runs p4 invocations. Injected as Tauri state so handlers can be exercised
+/// against a scripted fake instead of a real p4 binary.
+#[async_trait]
+pub trait P4Executor: Send + Sync {
+    /// Run to completion, capturing stdout and stderr.
+    async fn output(&self, cmd: &P4Command) -> io::Result<P4Output>;
+
+    /// Run to completion with `input` written to stdin (spec forms for `-i` commands),
+    /// encoded in the command's charset.
+    async fn output_with_input(&self, cmd: &P4Command, input: &str) -> io::Result<P4Output>;
+
+    /// Spawn with piped stdout/stderr for streaming commands.
+    fn spawn(&self, cmd: &P4Command) -> io::Result<P4Process>;
+}
+
+/// Thread-safe state wrapper for Tauri
+pub type P4ExecutorState = Arc<dyn P4Executor>;
+
+pub fn create_p4_executor_state() -> P4ExecutorState {
+    Arc::new(ProcessExecutor)
+}
+
+/// Executor that spawns the real p4 binary from PATH.
+pub struct ProcessExecutor;
+
+impl ProcessExecutor {
+    fn command(cmd: &P4Command) -> Command {
+        let mut command = Command::new("p4");
+        command.args(&cmd.args);
+        for (key, value) in &cmd.envs {
+            command.env(key, value);
+        }
+
+        // On Windows, hide the console window (CREATE_NO_WINDOW flag)
+        #[cfg(target_os = "windows")]
+        command.creation_flags(0x08000000);
+
+        command
+    }
+
+    /// Charset p4 will write output in: the command's own, else the inherited environment's
+    fn output_encoding(cmd: &P4Command) -> Option<&'static Encoding> {
+        if cmd.output_charset().is_some() {
+            return cmd.output_encoding();
+        }
+        ["P4COMMANDCHARSET", "P4CHARSET"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()))
+            .and_then(|charset| charset_encoding(&charset))
+    }
+}
+
+#[async_trait]
+impl P4Executor for ProcessExecutor {
+    async fn output(&self, cmd: &P4Command) -> io::Result<P4Output> {
+        let output: P4Output = Self::command(cmd).output().await?.into();
+        Ok(P4Output {
+            encoding: Self::output_encoding(cmd),
+            ..output
+        })
+    }
+
+    async fn output_with_input(&self, cmd: &P4Command, input: &str) -> io::Result<P4Output> {
+        let encoding = Self::output_encoding(cmd);
+        let mut command = Self::command(cmd);
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        let mut child = command.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&encode_text(input, encoding)).await?;
+            // Drop stdin to signal EOF
+        }
+
+        let output: P4Output = child.wait_with_output().await?.into();
+        Ok(P4Output { encoding, ..output })
+    }
+
+    fn spawn(&self, cmd: &P4Command) -> io::Result<P4Process> {
+        let mut command = Self::command(cmd);
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        let mut child = command.spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .map(|s| Box::new(s) as P4OutputStream);
+        let stderr = child
+            .stderr
+            .take()
+            .map(|s| Box::new(s) as P4OutputStream);
+
+        Ok(P4Process {
+            stdout,
+            stderr,
+            child: Box::new(child),
+            encoding: Self::output_encoding(cmd),
+        })
+    }
+}
+
+#[cfg(test)]
+pub use scripted::{RecordedCall, ScriptedExecutor};
+
+/// Scripted fake executor for handler tests: replays queued stdout/stderr/exit
+/// codes in order and records every invocation for assertions.
+#[cfg(test)]
+mod scripted {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// An invocation observed by ScriptedExecutor
+    #[derive(Debug, Clone)]
+    pub struct RecordedCall {
+        pub args: Vec<String>,
+        pub envs: Vec<(String, String)>,
+        pub input: Option<String>,
+        /// Raw contents of the -x argument file, read when the call was made
+        pub arg_file: Option<Vec<u8>>,
+    }
+
+    #[derive(Default)]
+    pub struct ScriptedExecutor {
+        responses: Mutex<VecDeque<P4Output>>,
+        calls: Mutex<Vec<RecordedCall>>,
+    }
+
+    impl ScriptedExecutor {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queue a successful response with the given stdout.
+        pub fn ok(self, stdout: &str) -> Self {
+            self.respond(stdout, "", 0)
+        }
+
+        /// Queue a failed response with the given stderr and exit code.
+        pub fn fail(self, stderr: &str, exit_code: i32) -> Self {
+            self.respond("", stderr, exit_code)
+        }
+
+        /// Queue a response with explicit stdout, stderr and exit code.
+        pub fn respond(self, stdout: &str, stderr: &str, exit_code: i32) -> Self {
+            self.respond_bytes(stdout.as_bytes(), stderr.as_bytes(), exit_code)
+        }
+
+        /// Queue a response with raw output, for charsets other than UTF-8.
+        pub fn respond_bytes(self, stdout: &[u8], stderr: &[u8], exit_code: i32) -> Self {
+            self.responses.lock().unwrap().push_back(P4Output {
+                stdout: stdout.to_vec(),
+                stderr: stderr.to_vec(),
+                exit_code: Some(exit_code),
+                encoding: None,
+            });
+            self
+        }
+
+        /// All invocations seen so far, in order.
+        pub fn calls(&self) -> Vec<RecordedCall> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn next(&self, cmd: &P4Command, input: Option<&str>) -> io::Result<P4Output> {
+            self.calls.lock().unwrap().push(RecordedCall {
+                args: cmd.args.clone(),
+                envs: cmd.envs.clone(),
+                input: input.map(str::to_string),
+                arg_file: cmd
+                    .args
+                    .iter()
+                    .position(|arg| arg == "-x")
+                    .and_then(|i| cmd.args.get(i + 1))
+                    .and_then(|path| std::fs::read(path).ok()),
+            });
+            let output = self.responses.lock().unwrap().pop_front().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("unexpected p4 invocation: {:?}", cmd.args),
+                )
+            })?;
+            // Like the real executor, output is in the command's charset
+            Ok(P4Output {
+                encoding: cmd.output_encoding(),
+                ..output
+            })
+        }
+    }
+
+    /// Child for a scripted process that has already finished
+    struct FinishedChild {
+        exit_code: Option<i32>,
+    }
+
+    #[async_trait]
+    impl P4Child for FinishedChild {
+        fn id(&self) -> Option<u32> {
+            None
+        }
+
+        async fn kill(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn wait(&mut self) -> io::Result<Option<i32>> {
+            Ok(self.exit_code)
+        }
+    }
+
+    #[async_trait]
+    impl P4Executor for ScriptedExecutor {
+        async fn output(&self, cmd: &P4Command) -> io::Result<P4Output> {
+            self.next(cmd, None)
+        }
+
+        async fn output_with_input(&self, cmd: &P4Command, input: &str) -> io::Result<P4Output> {
+            self.next(cmd, Some(input))
+        }
+
+        fn spawn(&self, cmd: &P4Command) -> io::Result<P4Process> {
+            let output = self.next(cmd, None)?;
+            Ok(P4Process {
+                stdout: Some(Box::new(io::Cursor::new(output.stdout))),
+                stderr: Some(Box::new(io::Cursor::new(output.stderr))),
+                child: Box::new(FinishedChild {
+                    exit_code: output.exit_code,
+                }),
+                encoding: output.encoding,
+            })
+        }
+    }
+}
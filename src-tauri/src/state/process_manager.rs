@@ -1,76 +1,254 @@
+use serde::Serialize;
 use std::collections::HashMap;
-use tokio::process::Child;
+use std::io;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{oneshot, Mutex};
 use uuid::Uuid;
 
+use super::P4Child;
+
 // Windows-specific import for hiding console windows
 #[cfg(target_os = "windows")]
 #[allow(unused_imports)] // Trait is used via creation_flags() method
 use std::os::windows::process::CommandExt;
 
+/// A tracked process, as reported by list_processes
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessInfo {
+    pub id: String,
+    /// p4 arguments the process was started with
+    pub args: Vec<String>,
+    /// Connection profile the process runs against; None for the default connection
+    pub connection: Option<String>,
+    /// OS process ID
+    pub pid: Option<u32>,
+    /// Unix timestamp in milliseconds
+    pub started_at: u64,
+    pub timeout_ms: Option<u64>,
+}
+
+/// Why a tracked process stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExitReason {
+    /// Ran to completion (with any exit code)
+    Exited,
+    /// Killed through kill / kill_all
+    Killed,
+    /// Killed after exceeding its timeout
+    TimedOut,
+    /// Waiting on the process failed
+    Failed,
+}
+
+/// Payload of the `process-exited` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessExit {
+    pub process_id: String,
+    /// None when killed by a signal or the status could not be read
+    pub exit_code: Option<i32>,
+    pub reason: ExitReason,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Reply channel for a kill request; receives the result of killing the process
+type KillRequest = oneshot::Sender<Result<(), String>>;
+
+type ExitListener = Arc<dyn Fn(&ProcessExit) + Send + Sync>;
+
+/// A process handed to the manager
+pub struct RegisteredProcess {
+    /// Tracking ID, used for kill and reported in `process-exited`
+    pub id: String,
+    /// Resolves once the process has been reaped.
+    /// Streaming commands wait on this to report their completion.
+    pub exited: oneshot::Receiver<ProcessExit>,
+}
+
+struct ProcessEntry {
+    info: ProcessInfo,
+    /// Taken by the first kill request
+    kill: Option<oneshot::Sender<KillRequest>>,
+}
+
 /// Tracks active p4.exe processes for cancellation and cleanup.
+/// Each registered process is supervised by a task that waits for it to exit,
+/// enforces its timeout, removes its entry and reports the exit to the listener.
 /// Uses tokio::sync::Mutex because we hold across await points.
 /// Wrapped in Arc for cloning into event handlers.
 #[derive(Clone)]
 pub struct ProcessManager {
-    processes: Arc<Mutex<HashMap<String, Child>>>,
+    processes: Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    on_exit: Arc<std::sync::Mutex<Option<ExitListener>>>,
 }
 
 impl ProcessManager {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            on_exit: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
-    /// Register a process and return its tracking ID.
-    pub async fn register(&self, child: Child) -> String {
+    /// Set the callback invoked whenever a tracked process exits.
+    /// The app emits `process-exited` from here.
+    pub fn set_exit_listener(&self, listener: impl Fn(&ProcessExit) + Send + Sync + 'static) {
+        *self.on_exit.lock().unwrap() = Some(Arc::new(listener));
+    }
+
+    /// Register a process for tracking and supervision, grouped under its connection.
+    /// The process is killed if it is still running after `timeout`.
+    pub async fn register(
+        &self,
+        child: Box<dyn P4Child>,
+        args: Vec<String>,
+        connection: Option<String>,
+        timeout: Option<Duration>,
+    ) -> RegisteredProcess {
         let id = Uuid::new_v4().to_string();
-        let mut processes = self.processes.lock().await;
-        processes.insert(id.clone(), child);
-        id
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let info = ProcessInfo {
+            id: id.clone(),
+            args,
+            connection,
+            pid: child.id(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            timeout_ms: timeout.map(|t| t.as_millis() as u64),
+        };
+
+        // Insert before the supervisor starts, so it always finds the entry to remove
+        self.processes.lock().await.insert(
+            id.clone(),
+            ProcessEntry {
+                info,
+                kill: Some(kill_tx),
+            },
+        );
+        tokio::spawn(self.clone().supervise(id.clone(), child, kill_rx, exit_tx, timeout));
+
+        RegisteredProcess { id, exited: exit_rx }
     }
 
-    /// Kill and remove a process by ID. Returns true if found and killed.
+    /// Currently running processes, oldest first
+    pub async fn list(&self) -> Vec<ProcessInfo> {
+        let processes = self.processes.lock().await;
+        let mut infos: Vec<ProcessInfo> = processes.values().map(|e| e.info.clone()).collect();
+        infos.sort_by_key(|info| info.started_at);
+        infos
+    }
+
+    /// Kill a process by ID and wait until it has been reaped.
+    /// Returns true if found and killed.
     pub async fn kill(&self, id: &str) -> Result<bool, String> {
-        let mut processes = self.processes.lock().await;
-        if let Some(mut child) = processes.remove(id) {
-            // On Windows, child.kill() may not kill child processes.
-            // Use taskkill for reliable tree killing.
-            #[cfg(target_os = "windows")]
-            {
-                if let Some(pid) = child.id() {
-                    let mut cmd = tokio::process::Command::new("taskkill");
-                    cmd.args(["/F", "/T", "/PID", &pid.to_string()]);
-                    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-                    let _ = cmd.output().await;
-                }
-            }
-            child.kill().await.map_err(|e| e.to_string())?;
-            let _ = child.wait().await; // Reap zombie
-            Ok(true)
-        } else {
-            Ok(false)
+        let kill = self
+            .processes
+            .lock()
+            .await
+            .get_mut(id)
+            .and_then(|entry| entry.kill.take());
+        match kill {
+            Some(kill) => request_kill(kill).await,
+            None => Ok(false),
         }
     }
 
     /// Kill all tracked processes. Called on app close.
     pub async fn kill_all(&self) {
-        let mut processes = self.processes.lock().await;
-        for (_, mut child) in processes.drain() {
-            #[cfg(target_os = "windows")]
-            {
-                if let Some(pid) = child.id() {
-                    let mut cmd = tokio::process::Command::new("taskkill");
-                    cmd.args(["/F", "/T", "/PID", &pid.to_string()]);
-                    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-                    let _ = cmd.output().await;
-                }
+        self.kill_matching(|_| true).await;
+    }
+
+    /// Kill the processes running against one connection, leaving other connections alone
+    pub async fn kill_connection(&self, connection: &Option<String>) {
+        self.kill_matching(|info| info.connection == *connection).await;
+    }
+
+    async fn kill_matching(&self, matches: impl Fn(&ProcessInfo) -> bool) {
+        let kills: Vec<_> = self
+            .processes
+            .lock()
+            .await
+            .values_mut()
+            .filter(|entry| matches(&entry.info))
+            .filter_map(|entry| entry.kill.take())
+            .collect();
+        for kill in kills {
+            let _ = request_kill(kill).await;
+        }
+    }
+
+    /// Wait for the process to exit, be killed or time out, then report it
+    async fn supervise(
+        self,
+        id: String,
+        mut child: Box<dyn P4Child>,
+        kill_rx: oneshot::Receiver<KillRequest>,
+        exit_tx: oneshot::Sender<ProcessExit>,
+        timeout: Option<Duration>,
+    ) {
+        let started = Instant::now();
+        let deadline = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        enum Outcome {
+            Exited(io::Result<Option<i32>>),
+            Kill(Option<KillRequest>),
+            TimedOut,
+        }
+
+        let outcome = tokio::select! {
+            status = child.wait() => Outcome::Exited(status),
+            // A dropped sender means the manager itself is gone; stop the process too
+            request = kill_rx => Outcome::Kill(request.ok()),
+            _ = deadline => Outcome::TimedOut,
+        };
+
+        let (exit_code, reason, error, reply) = match outcome {
+            Outcome::Exited(Ok(code)) => (code, ExitReason::Exited, None, None),
+            Outcome::Exited(Err(e)) => (None, ExitReason::Failed, Some(e.to_string()), None),
+            Outcome::Kill(reply) => {
+                let result = terminate(child.as_mut()).await;
+                (None, ExitReason::Killed, result.clone().err(), reply.map(|r| (r, result)))
+            }
+            Outcome::TimedOut => {
+                let result = terminate(child.as_mut()).await;
+                let error = match result {
+                    Ok(()) => format!("Timed out after {:?}", timeout.unwrap_or_default()),
+                    Err(e) => format!("Timed out, but kill failed: {}", e),
+                };
+                (None, ExitReason::TimedOut, Some(error), None)
             }
-            let _ = child.kill().await;
-            let _ = child.wait().await; // Reap zombie
+        };
+
+        // Remove before replying, so a finished kill() never sees the entry
+        self.processes.lock().await.remove(&id);
+        if let Some((reply, result)) = reply {
+            let _ = reply.send(result);
+        }
+
+        let exit = ProcessExit {
+            process_id: id,
+            exit_code,
+            reason,
+            duration_ms: started.elapsed().as_millis() as u64,
+            error,
+        };
+        let listener = self.on_exit.lock().unwrap().clone();
+        if let Some(listener) = listener {
+            listener(&exit);
         }
+        let _ = exit_tx.send(exit);
     }
 }
 
@@ -79,3 +257,34 @@ impl Default for ProcessManager {
         Self::new()
     }
 }
+
+/// Ask a supervisor to kill its process and wait for the result
+async fn request_kill(kill: oneshot::Sender<KillRequest>) -> Result<bool, String> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if kill.send(reply_tx).is_err() {
+        // Supervisor already finished: the process exited on its own
+        return Ok(false);
+    }
+    match reply_rx.await {
+        Ok(result) => result.map(|()| true),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Kill a child (and on Windows its process tree) and reap it
+async fn terminate(child: &mut dyn P4Child) -> Result<(), String> {
+    // On Windows, child.kill() may not kill child processes.
+    // Use taskkill for reliable tree killing.
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(pid) = child.id() {
+            let mut cmd = tokio::process::Command::new("taskkill");
+            cmd.args(["/F", "/T", "/PID", &pid.to_string()]);
+            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            let _ = cmd.output().await;
+        }
+    }
+    let result = child.kill().await.map_err(|e| e.to_string());
+    let _ = child.wait().await; // Reap zombie
+    result
+}
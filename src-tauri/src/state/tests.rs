@@ -0,0 +1,352 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    charset_encoding, decode_text, encode_text, ConnectionProfile, Connections, ExitReason,
+    P4Child, P4Command, ProcessExit, ProcessManager,
+};
+
+/// Child that runs until `finish` fires (with an exit code) or it is killed
+struct FakeChild {
+    finish: Option<oneshot::Receiver<i32>>,
+    killed: Arc<Mutex<bool>>,
+}
+
+#[async_trait]
+impl P4Child for FakeChild {
+    fn id(&self) -> Option<u32> {
+        Some(4242)
+    }
+
+    async fn kill(&mut self) -> io::Result<()> {
+        *self.killed.lock().unwrap() = true;
+        self.finish = None;
+        Ok(())
+    }
+
+    async fn wait(&mut self) -> io::Result<Option<i32>> {
+        match self.finish.as_mut() {
+            Some(finish) => Ok(finish.await.ok()),
+            None => Ok(None),
+        }
+    }
+}
+
+fn fake_child() -> (Box<dyn P4Child>, oneshot::Sender<i32>, Arc<Mutex<bool>>) {
+    let (finish_tx, finish_rx) = oneshot::channel();
+    let killed = Arc::new(Mutex::new(false));
+    let child = FakeChild {
+        finish: Some(finish_rx),
+        killed: killed.clone(),
+    };
+    (Box::new(child), finish_tx, killed)
+}
+
+/// Manager whose exit listener forwards every exit to the returned receiver
+fn manager() -> (ProcessManager, mpsc::UnboundedReceiver<ProcessExit>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let manager = ProcessManager::new();
+    manager.set_exit_listener(move |exit| {
+        let _ = tx.send(exit.clone());
+    });
+    (manager, rx)
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+#[test]
+fn test_exit_is_reported_and_entry_removed() {
+    block_on(async {
+        let (manager, mut exits) = manager();
+        let (child, finish, _) = fake_child();
+        let registered = manager.register(child, args(&["sync", "//..."]), None, None).await;
+        let id = registered.id.clone();
+
+        let listed = manager.list().await;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, id);
+        assert_eq!(listed[0].args, vec!["sync", "//..."]);
+        assert_eq!(listed[0].pid, Some(4242));
+
+        finish.send(1).unwrap();
+        let exit = exits.recv().await.unwrap();
+        assert_eq!(exit.process_id, id);
+        assert_eq!(exit.exit_code, Some(1));
+        assert_eq!(exit.reason, ExitReason::Exited);
+        assert!(exit.error.is_none());
+        assert!(manager.list().await.is_empty());
+
+        // The registration's own receiver sees the same exit
+        let exited = registered.exited.await.unwrap();
+        assert_eq!(exited.process_id, id);
+        assert_eq!(exited.exit_code, Some(1));
+
+        // Already reaped: nothing left to kill
+        assert_eq!(manager.kill(&id).await, Ok(false));
+    });
+}
+
+#[test]
+fn test_kill_terminates_and_reports_killed() {
+    block_on(async {
+        let (manager, mut exits) = manager();
+        let (child, _finish, killed) = fake_child();
+        let id = manager.register(child, args(&["fstat", "//..."]), None, None).await.id;
+
+        assert_eq!(manager.kill(&id).await, Ok(true));
+        assert!(*killed.lock().unwrap());
+        assert!(manager.list().await.is_empty());
+
+        let exit = exits.recv().await.unwrap();
+        assert_eq!(exit.process_id, id);
+        assert_eq!(exit.reason, ExitReason::Killed);
+        assert_eq!(exit.exit_code, None);
+    });
+}
+
+#[test]
+fn test_timeout_kills_process() {
+    block_on(async {
+        let (manager, mut exits) = manager();
+        let (child, _finish, killed) = fake_child();
+        let id = manager
+            .register(child, args(&["changes"]), None, Some(Duration::from_millis(20)))
+            .await
+            .id;
+        assert_eq!(manager.list().await[0].timeout_ms, Some(20));
+
+        let exit = exits.recv().await.unwrap();
+        assert_eq!(exit.process_id, id);
+        assert_eq!(exit.reason, ExitReason::TimedOut);
+        assert!(exit.error.unwrap().starts_with("Timed out"));
+        assert!(*killed.lock().unwrap());
+        assert!(manager.list().await.is_empty());
+    });
+}
+
+#[test]
+fn test_kill_all_stops_every_process() {
+    block_on(async {
+        let (manager, mut exits) = manager();
+        let (first, _finish_first, _) = fake_child();
+        let (second, _finish_second, _) = fake_child();
+        manager.register(first, args(&["sync"]), None, None).await;
+        manager.register(second, args(&["grep"]), None, None).await;
+
+        manager.kill_all().await;
+        assert!(manager.list().await.is_empty());
+        for _ in 0..2 {
+            assert_eq!(exits.recv().await.unwrap().reason, ExitReason::Killed);
+        }
+    });
+}
+
+#[test]
+fn test_kill_connection_only_stops_its_processes() {
+    block_on(async {
+        let (manager, mut exits) = manager();
+        let (main, _finish_main, main_killed) = fake_child();
+        let (edge, _finish_edge, edge_killed) = fake_child();
+        let edge_id = Some("edge".to_string());
+        manager.register(main, args(&["sync"]), None, None).await;
+        manager.register(edge, args(&["grep"]), edge_id.clone(), None).await;
+
+        manager.kill_connection(&edge_id).await;
+        assert_eq!(exits.recv().await.unwrap().reason, ExitReason::Killed);
+        assert!(*edge_killed.lock().unwrap());
+        assert!(!*main_killed.lock().unwrap());
+
+        let remaining = manager.list().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].connection, None);
+        manager.kill_all().await;
+    });
+}
+
+fn profile(id: &str, server: &str) -> ConnectionProfile {
+    ConnectionProfile {
+        id: id.to_string(),
+        name: id.to_string(),
+        server: Some(server.to_string()),
+        user: Some("alice".to_string()),
+        client: Some("alice-ws".to_string()),
+        ..Default::default()
+    }
+}
+
+fn applied(profile: &ConnectionProfile) -> P4Command {
+    let mut cmd = P4Command::new();
+    profile.apply(&mut cmd);
+    cmd
+}
+
+fn env<'a>(cmd: &'a P4Command, key: &str) -> Option<&'a str> {
+    cmd.envs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn test_profile_apply_sets_flags_and_environment() {
+    let mut edge = profile("edge", "ssl:edge:1666");
+    edge.charset = Some("utf8".to_string());
+    edge.tickets_file = Some("/home/alice/.p4tickets-edge".to_string());
+    let cmd = applied(&edge);
+    assert_eq!(
+        cmd.args,
+        args(&["-p", "ssl:edge:1666", "-u", "alice", "-c", "alice-ws", "-C", "utf8"])
+    );
+    assert_eq!(env(&cmd, "P4CONFIG"), Some(""));
+    assert_eq!(env(&cmd, "P4CHARSET"), Some("utf8"));
+    assert_eq!(env(&cmd, "P4TICKETS"), Some("/home/alice/.p4tickets-edge"));
+
+    let without_client = applied(&edge.without_client());
+    assert!(!without_client.args.contains(&"-c".to_string()));
+}
+
+#[test]
+fn test_profile_p4config_handling() {
+    let mut main = profile("main", "main:1666");
+    main.use_p4config = true;
+    assert_eq!(env(&applied(&main), "P4CONFIG"), None);
+
+    // The default connection leaves the environment alone
+    let cmd = applied(&ConnectionProfile::default());
+    assert!(cmd.args.is_empty());
+    assert!(cmd.envs.is_empty());
+}
+
+#[test]
+fn test_connections_resolve_profiles() {
+    let connections = Connections::new();
+    connections.save(profile("main", "main:1666")).unwrap();
+    connections.save(profile("edge", "edge:1666")).unwrap();
+
+    let default = connections.get(&None).unwrap();
+    assert_eq!(default.profile, ConnectionProfile::default());
+    let edge = connections.get(&Some("edge".to_string())).unwrap();
+    assert_eq!(edge.profile.server.as_deref(), Some("edge:1666"));
+    assert!(connections.get(&Some("missing".to_string())).is_err());
+
+    let names: Vec<String> = connections.list().into_iter().map(|p| p.name).collect();
+    assert_eq!(names, vec!["edge", "main"]);
+    assert!(connections.remove("edge").unwrap());
+    assert!(!connections.remove("edge").unwrap());
+    assert!(connections.save(profile("", "x:1666")).is_err());
+}
+
+#[test]
+fn test_connections_keep_indexes_for_same_target() {
+    let connections = Connections::new();
+    connections.save(profile("main", "main:1666")).unwrap();
+    let before = connections.get(&Some("main".to_string())).unwrap();
+    let other = connections.get(&None).unwrap();
+    assert!(!Arc::ptr_eq(&before.file_index, &other.file_index));
+
+    let mut renamed = profile("main", "main:1666");
+    renamed.name = "Main server".to_string();
+    connections.save(renamed).unwrap();
+    let after = connections.get(&Some("main".to_string())).unwrap();
+    assert_eq!(after.profile.name, "Main server");
+    assert!(Arc::ptr_eq(&before.file_index, &after.file_index));
+
+    // A different server can't reuse the old indexes
+    connections.save(profile("main", "replica:1666")).unwrap();
+    let moved = connections.get(&Some("main".to_string())).unwrap();
+    assert!(!Arc::ptr_eq(&before.file_index, &moved.file_index));
+}
+
+#[test]
+fn test_connections_concurrent_saves_persist_every_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("connections.json");
+    let connections = Arc::new(Connections::new());
+    connections.load(&path).unwrap();
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let connections = connections.clone();
+            std::thread::spawn(move || connections.save(profile(&format!("p{}", i), "main:1666")))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap().unwrap();
+    }
+
+    let reloaded = Connections::new();
+    reloaded.load(&path).unwrap();
+    assert_eq!(reloaded.list().len(), 8);
+}
+
+#[test]
+fn test_profile_wide_charset_uses_utf8_for_output() {
+    let mut main = profile("main", "main:1666");
+    main.charset = Some("utf16le-bom".to_string());
+    let cmd = applied(&main);
+    assert!(cmd.args.ends_with(&args(&["-C", "utf16le-bom", "-Q", "utf8"])));
+    assert_eq!(cmd.output_charset(), Some("utf8"));
+    assert_eq!(cmd.output_encoding(), Some(encoding_rs::UTF_8));
+    // Workspace files are still translated to UTF-16
+    assert_eq!(main.file_encoding(), Some(encoding_rs::UTF_16LE));
+
+    main.charset = Some("shiftjis".to_string());
+    assert_eq!(applied(&main).output_encoding(), Some(encoding_rs::SHIFT_JIS));
+    assert_eq!(applied(&ConnectionProfile::default()).output_encoding(), None);
+}
+
+#[test]
+fn test_decode_p4_charsets() {
+    assert_eq!(charset_encoding("none"), None);
+    assert_eq!(charset_encoding("EUCJP"), Some(encoding_rs::EUC_JP));
+
+    let shiftjis = charset_encoding("shiftjis");
+    assert_eq!(decode_text(b"\x93\xfa\x96\x7b\x8c\xea", shiftjis), "日本語");
+    assert_eq!(decode_text(b"\xc6\xfc\xcb\xdc\xb8\xec", charset_encoding("eucjp")), "日本語");
+    assert_eq!(encode_text("日本語", shiftjis), b"\x93\xfa\x96\x7b\x8c\xea");
+
+    // A byte order mark overrides the charset; bad bytes don't fail the decode
+    assert_eq!(decode_text(b"\xff\xfe\xe9\x00\n\x00", shiftjis), "\u{e9}\n");
+    assert_eq!(decode_text(b"ok \xff", None), "ok \u{fffd}");
+}
+
+#[test]
+fn test_connections_detected_charset() {
+    let connections = Connections::new();
+    connections.save(profile("main", "main:1666")).unwrap();
+    let before = connections.get(&Some("main".to_string())).unwrap();
+
+    let detected = connections.set_detected_charset(&Some("main".to_string()), "utf8").unwrap();
+    assert_eq!(detected.profile.charset.as_deref(), Some("utf8"));
+    assert!(Arc::ptr_eq(&before.file_index, &detected.file_index));
+
+    // A configured charset is never replaced
+    let again = connections.set_detected_charset(&Some("main".to_string()), "shiftjis").unwrap();
+    assert!(Arc::ptr_eq(&detected, &again));
+
+    connections.set_detected_charset(&None, "eucjp").unwrap();
+    assert_eq!(connections.get(&None).unwrap().profile.charset.as_deref(), Some("eucjp"));
+    assert!(connections.set_detected_charset(&Some("gone".to_string()), "utf8").is_err());
+}
+
+#[test]
+fn test_connections_persist_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("connections.json");
+
+    let connections = Connections::new();
+    connections.load(&path).unwrap();
+    assert!(connections.list().is_empty());
+    connections.save(profile("main", "main:1666")).unwrap();
+
+    let reloaded = Connections::new();
+    reloaded.load(&path).unwrap();
+    assert_eq!(reloaded.list(), vec![profile("main", "main:1666")]);
+}
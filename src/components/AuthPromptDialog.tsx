@@ -0,0 +1,182 @@
+import { useEffect, useRef, useState } from 'react';
+import {
+  invokeListConnectionProfiles,
+  invokeP4Login,
+  invokeP4LoginStatus,
+  invokeP4Trust,
+  invokeP4TrustStatus,
+  setAuthHandler,
+  type AuthTarget,
+  type P4TrustStatus,
+} from '@/lib/tauri';
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from '@/components/ui/dialog';
+import { Input } from '@/components/ui/input';
+import { Button } from '@/components/ui/button';
+
+/** Server and user shown in the prompt */
+interface TargetLabel {
+  server: string | null;
+  user: string | null;
+}
+
+type Prompt =
+  | { mode: 'login'; target: AuthTarget; label: TargetLabel }
+  | { mode: 'trust'; target: AuthTarget; label: TargetLabel; trust: P4TrustStatus };
+
+async function describeTarget(target: AuthTarget): Promise<TargetLabel> {
+  if (!target.connection) return { server: null, user: null };
+  const profiles = await invokeListConnectionProfiles().catch(() => []);
+  const profile = profiles.find(p => p.id === target.connection);
+  return { server: profile?.server ?? null, user: profile?.user ?? null };
+}
+
+/**
+ * Prompts for a password or SSL trust when a command fails with loginRequired or
+ * trustRequired. Registered as the invoke auth handler while mounted; the failed
+ * command is retried once the prompt succeeds.
+ */
+export function AuthPromptDialog() {
+  const [prompt, setPrompt] = useState<Prompt | null>(null);
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [isSubmitting, setIsSubmitting] = useState(false);
+  const resolveRef = useRef<((retry: boolean) => void) | null>(null);
+
+  useEffect(() => {
+    setAuthHandler(async (failure, target) => {
+      const label = await describeTarget(target);
+      let next: Prompt = { mode: 'login', target, label };
+      if (failure.kind === 'trustRequired') {
+        const trust = await invokeP4TrustStatus(target).catch(() => null);
+        if (!trust?.fingerprint) return false;
+        next = { mode: 'trust', target, label, trust };
+      }
+      setPassword('');
+      setError(null);
+      setPrompt(next);
+      return new Promise<boolean>((resolve) => {
+        resolveRef.current = resolve;
+      });
+    });
+    return () => setAuthHandler(null);
+  }, []);
+
+  const finish = (retry: boolean) => {
+    resolveRef.current?.(retry);
+    resolveRef.current = null;
+    setPrompt(null);
+    setPassword('');
+  };
+
+  const handleLogin = async (target: AuthTarget) => {
+    setIsSubmitting(true);
+    setError(null);
+    try {
+      await invokeP4Login(password, false, target);
+      finish(true);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setIsSubmitting(false);
+    }
+  };
+
+  const handleTrust = async (target: AuthTarget, label: TargetLabel, trust: P4TrustStatus) => {
+    setIsSubmitting(true);
+    setError(null);
+    try {
+      await invokeP4Trust(trust.fingerprint!, trust.changed, target);
+      // A newly trusted server usually needs a login as well
+      const status = await invokeP4LoginStatus(target);
+      if (status.loggedIn) {
+        finish(true);
+      } else {
+        setPrompt({ mode: 'login', target, label });
+      }
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setIsSubmitting(false);
+    }
+  };
+
+  const server = prompt?.label.server ?? 'the Perforce server';
+
+  return (
+    <Dialog open={prompt !== null} onOpenChange={(open) => !open && finish(false)}>
+      <DialogContent className="sm:max-w-[475px]">
+        {prompt?.mode === 'login' && (
+          <form
+            onSubmit={(e) => {
+              e.preventDefault();
+              handleLogin(prompt.target);
+            }}
+            className="space-y-4"
+          >
+            <DialogHeader>
+              <DialogTitle>Log in to Perforce</DialogTitle>
+              <DialogDescription>
+                Your session on {server} has expired or you are not logged in
+                {prompt.label.user ? ` as ${prompt.label.user}` : ''}.
+              </DialogDescription>
+            </DialogHeader>
+            <Input
+              type="password"
+              placeholder="Password"
+              autoFocus
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+            />
+            {error && <p className="text-sm text-destructive">{error}</p>}
+            <DialogFooter>
+              <Button type="button" variant="outline" onClick={() => finish(false)}>
+                Cancel
+              </Button>
+              <Button type="submit" disabled={isSubmitting || password.length === 0}>
+                {isSubmitting ? 'Logging in...' : 'Log In'}
+              </Button>
+            </DialogFooter>
+          </form>
+        )}
+
+        {prompt?.mode === 'trust' && (
+          <div className="space-y-4">
+            <DialogHeader>
+              <DialogTitle>
+                {prompt.trust.changed ? 'Server identity has changed' : 'Trust this server?'}
+              </DialogTitle>
+              <DialogDescription>
+                {prompt.trust.changed
+                  ? `The SSL key presented by ${server} does not match the one trusted before. Unless your administrator announced a key change, someone may be intercepting the connection.`
+                  : `The authenticity of ${server} can't be established. Check the fingerprint with your administrator before trusting it.`}
+              </DialogDescription>
+            </DialogHeader>
+            <p className="font-mono text-xs break-all rounded bg-muted p-2">
+              {prompt.trust.fingerprint}
+            </p>
+            {error && <p className="text-sm text-destructive">{error}</p>}
+            <DialogFooter>
+              <Button type="button" variant="outline" onClick={() => finish(false)}>
+                Cancel
+              </Button>
+              <Button
+                variant={prompt.trust.changed ? 'destructive' : 'default'}
+                disabled={isSubmitting}
+                onClick={() => handleTrust(prompt.target, prompt.label, prompt.trust)}
+              >
+                {prompt.trust.changed ? 'Replace Fingerprint' : 'Trust'}
+              </Button>
+            </DialogFooter>
+          </div>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}
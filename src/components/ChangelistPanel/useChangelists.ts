@@ -186,7 +186,11 @@ export function useChangelists(): {
               if (verbose) {
                 addOutputLine(`... loaded ${progress.successCount} CLs${progress.errorCount > 0 ? `, ${progress.errorCount} errors` : ''}`, false);
               }
-              completeOperation(progress.errorCount === 0 || progress.cancelled);
+              if (progress.cancelled || progress.success) {
+                completeOperation(progress.errorCount === 0 || progress.cancelled);
+              } else {
+                completeOperation(false, progress.error ?? 'p4 describe failed');
+              }
             }
           }
         );
@@ -4,7 +4,13 @@ import { zodResolver } from '@hookform/resolvers/zod';
 import { z } from 'zod';
 import { useQueryClient } from '@tanstack/react-query';
 import { loadSettings, saveSettings } from '@/lib/settings';
-import { invokeListWorkspaces, invokeTestConnection } from '@/lib/tauri';
+import {
+  invokeDeleteConnectionProfile,
+  invokeListConnectionProfiles,
+  invokeListWorkspaces,
+  invokeSaveConnectionProfile,
+  invokeTestConnection,
+} from '@/lib/tauri';
 import { useConnectionStore } from '@/stores/connectionStore';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
 import { useDetailPaneStore } from '@/stores/detailPaneStore';
@@ -36,16 +42,58 @@ import {
   SelectValue,
 } from '@/components/ui/select';
 import toast from 'react-hot-toast';
-import type { P4Workspace } from '@/lib/tauri';
+import type { ConnectionProfile, P4Workspace } from '@/lib/tauri';
 
 const connectionSchema = z.object({
+  name: z.string(),
   p4port: z.string().min(1, 'Server address is required'),
   p4user: z.string().min(1, 'Username is required'),
   p4client: z.string().min(1, 'Workspace is required'),
+  charset: z.string(),
+  ticketsFile: z.string(),
+  useP4config: z.boolean(),
 });
 
 type ConnectionFields = z.infer<typeof connectionSchema>;
 
+/** Select value for a profile that hasn't been saved yet */
+const NEW_PROFILE = '__new__';
+
+const emptyFields: ConnectionFields = {
+  name: '',
+  p4port: '',
+  p4user: '',
+  p4client: '',
+  charset: '',
+  ticketsFile: '',
+  useP4config: false,
+};
+
+function fieldsFromProfile(profile: ConnectionProfile): ConnectionFields {
+  return {
+    name: profile.name,
+    p4port: profile.server ?? '',
+    p4user: profile.user ?? '',
+    p4client: profile.client ?? '',
+    charset: profile.charset ?? '',
+    ticketsFile: profile.ticketsFile ?? '',
+    useP4config: profile.useP4config,
+  };
+}
+
+function profileFromFields(id: string, data: ConnectionFields): ConnectionProfile {
+  return {
+    id,
+    name: data.name.trim() || data.p4port,
+    server: data.p4port,
+    user: data.p4user,
+    client: data.p4client || null,
+    charset: data.charset.trim() || null,
+    useP4config: data.useP4config,
+    ticketsFile: data.ticketsFile.trim() || null,
+  };
+}
+
 interface ConnectionDialogProps {
   open: boolean;
   onOpenChange: (open: boolean) => void;
@@ -55,19 +103,20 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
   const [workspaces, setWorkspaces] = useState<P4Workspace[]>([]);
   const [isBrowsing, setIsBrowsing] = useState(false);
   const [isSubmitting, setIsSubmitting] = useState(false);
+  const [profiles, setProfiles] = useState<ConnectionProfile[]>([]);
+  // Profile being edited; a fresh ID until it is first saved
+  const [profileId, setProfileId] = useState<string>(() => crypto.randomUUID());
   const { status, setConnected, setDisconnected } = useConnectionStore();
   const queryClient = useQueryClient();
 
   const form = useForm<ConnectionFields>({
     resolver: zodResolver(connectionSchema),
-    defaultValues: {
-      p4port: '',
-      p4user: '',
-      p4client: '',
-    },
+    defaultValues: emptyFields,
     mode: 'onSubmit',
   });
 
+  const isSaved = profiles.some(p => p.id === profileId);
+
   const watchedPort = form.watch('p4port');
   const watchedUser = form.watch('p4user');
 
@@ -75,9 +124,15 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
     if (open) {
       const load = async () => {
         try {
-          const settings = await loadSettings();
-          if (settings.p4port || settings.p4user || settings.p4client) {
+          const [settings, saved] = await Promise.all([loadSettings(), invokeListConnectionProfiles()]);
+          setProfiles(saved);
+          const active = saved.find(p => p.id === settings.activeProfileId);
+          if (active) {
+            setProfileId(active.id);
+            form.reset(fieldsFromProfile(active));
+          } else if (settings.p4port || settings.p4user || settings.p4client) {
             form.reset({
+              ...emptyFields,
               p4port: settings.p4port,
               p4user: settings.p4user,
               p4client: settings.p4client,
@@ -91,6 +146,37 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
     }
   }, [open, form]);
 
+  const handleSelectProfile = (id: string) => {
+    const profile = profiles.find(p => p.id === id);
+    setProfileId(profile ? profile.id : crypto.randomUUID());
+    form.reset(profile ? fieldsFromProfile(profile) : emptyFields);
+    setWorkspaces([]);
+  };
+
+  const handleDeleteProfile = async () => {
+    const active = useConnectionStore.getState().profileId;
+    if (profileId === active) {
+      toast.error('Disconnect before deleting the active connection');
+      return;
+    }
+    try {
+      setProfiles(await invokeDeleteConnectionProfile(profileId));
+      handleSelectProfile(NEW_PROFILE);
+    } catch (error) {
+      toast.error(error instanceof Error ? error.message : String(error));
+    }
+  };
+
+  /**
+   * Save the form as a profile, so commands run while testing it (and any login
+   * prompt they raise) can reference it by ID
+   */
+  const saveDraft = async (data: ConnectionFields) => {
+    const profile = profileFromFields(profileId, data);
+    setProfiles(await invokeSaveConnectionProfile(profile));
+    return profile;
+  };
+
   const handleBrowseWorkspaces = async () => {
     if (!watchedPort || !watchedUser) {
       toast.error('Enter server and user first');
@@ -99,7 +185,8 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
 
     setIsBrowsing(true);
     try {
-      const result = await invokeListWorkspaces(watchedPort, watchedUser);
+      const profile = await saveDraft(form.getValues());
+      const result = await invokeListWorkspaces(profile);
       setWorkspaces(result);
       if (result.length === 0) {
         toast('No workspaces found for this user');
@@ -125,14 +212,15 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
     useDetailPaneStore.getState().clear();
     useSearchFilterStore.getState().clearFilter();
 
-    // Clear connection fields in saved settings
+    // Clear connection fields in saved settings; the profile itself stays saved
     try {
       const settings = await loadSettings();
-      await saveSettings({ ...settings, p4port: '', p4user: '', p4client: '' });
+      await saveSettings({ ...settings, p4port: '', p4user: '', p4client: '', activeProfileId: '' });
     } catch {
       // ignore
     }
-    form.reset({ p4port: '', p4user: '', p4client: '' });
+    setProfileId(crypto.randomUUID());
+    form.reset(emptyFields);
     setWorkspaces([]);
     toast.success('Disconnected');
   };
@@ -140,12 +228,26 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
   const onSubmit = async (data: ConnectionFields) => {
     setIsSubmitting(true);
     try {
-      const info = await invokeTestConnection(data.p4port, data.p4user, data.p4client);
+      const profile = await saveDraft(data);
+      const info = await invokeTestConnection(profile);
+
+      // Unicode server: keep the charset p4 settled on so the profile works as-is next time
+      if (!profile.charset && info.charset) {
+        form.setValue('charset', info.charset);
+        setProfiles(await invokeSaveConnectionProfile({ ...profile, charset: info.charset }));
+      }
 
       // Merge connection fields into existing settings
       const existing = await loadSettings();
-      await saveSettings({ ...existing, ...data });
+      await saveSettings({
+        ...existing,
+        p4port: data.p4port,
+        p4user: data.p4user,
+        p4client: data.p4client,
+        activeProfileId: profile.id,
+      });
 
+      const switching = useConnectionStore.getState().profileId !== profile.id;
       setConnected({
         workspace: info.client_name,
         stream: info.client_stream || undefined,
@@ -154,8 +256,15 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
         p4port: data.p4port,
         p4user: data.p4user,
         p4client: data.p4client,
+        profileId: profile.id,
       });
 
+      // The file tree and detail pane belong to the previous connection
+      if (switching) {
+        useFileTreeStore.setState({ files: new Map(), selectedFile: null });
+        useDetailPaneStore.getState().clear();
+      }
+
       // Force all queries to refetch now that we're connected
       await queryClient.invalidateQueries();
 
@@ -178,8 +287,50 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
           </DialogDescription>
         </DialogHeader>
 
+        {profiles.length > 0 && (
+          <div className="flex gap-2">
+            <Select value={isSaved ? profileId : NEW_PROFILE} onValueChange={handleSelectProfile}>
+              <SelectTrigger className="flex-1">
+                <SelectValue placeholder="Saved connections" />
+              </SelectTrigger>
+              <SelectContent>
+                <SelectItem value={NEW_PROFILE}>New connection</SelectItem>
+                {profiles.map((p) => (
+                  <SelectItem key={p.id} value={p.id}>
+                    <div className="flex flex-col">
+                      <span className="font-medium">{p.name}</span>
+                      <span className="text-xs text-muted-foreground">
+                        {p.user}@{p.server}
+                      </span>
+                    </div>
+                  </SelectItem>
+                ))}
+              </SelectContent>
+            </Select>
+            {isSaved && (
+              <Button type="button" variant="outline" onClick={handleDeleteProfile} disabled={isSubmitting}>
+                Delete
+              </Button>
+            )}
+          </div>
+        )}
+
         <Form {...form}>
           <form onSubmit={form.handleSubmit(onSubmit)} className="space-y-4">
+            <FormField
+              control={form.control}
+              name="name"
+              render={({ field }) => (
+                <FormItem>
+                  <FormLabel>Name</FormLabel>
+                  <FormControl>
+                    <Input placeholder="Defaults to the server address" {...field} />
+                  </FormControl>
+                  <FormMessage />
+                </FormItem>
+              )}
+            />
+
             <FormField
               control={form.control}
               name="p4port"
@@ -263,6 +414,59 @@ export function ConnectionDialog({ open, onOpenChange }: ConnectionDialogProps)
               />
             )}
 
+            <div className="grid grid-cols-2 gap-4">
+              <FormField
+                control={form.control}
+                name="charset"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>Charset</FormLabel>
+                    <FormControl>
+                      <Input placeholder="Detect (e.g. utf8, shiftjis)" {...field} />
+                    </FormControl>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+
+              <FormField
+                control={form.control}
+                name="ticketsFile"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>Ticket file</FormLabel>
+                    <FormControl>
+                      <Input placeholder="Default P4TICKETS" {...field} />
+                    </FormControl>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+            </div>
+
+            <FormField
+              control={form.control}
+              name="useP4config"
+              render={({ field }) => (
+                <FormItem className="flex items-center gap-3">
+                  <FormControl>
+                    <input
+                      type="checkbox"
+                      checked={field.value}
+                      onChange={field.onChange}
+                      className="h-4 w-4 rounded border-border accent-primary"
+                    />
+                  </FormControl>
+                  <div>
+                    <FormLabel className="text-sm">Use P4CONFIG files</FormLabel>
+                    <p className="text-xs text-muted-foreground">
+                      Let config files in the workspace override these settings
+                    </p>
+                  </div>
+                </FormItem>
+              )}
+            />
+
             <DialogFooter>
               {status === 'connected' && (
                 <Button
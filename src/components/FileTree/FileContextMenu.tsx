@@ -1,9 +1,10 @@
 import { useEffect, useRef } from 'react';
 import { P4File, FileStatus } from '@/types/p4';
 import { FileContextMenuItems } from '@/components/shared/FileContextMenuItems';
-import { Download, Plus } from 'lucide-react';
+import { Download, Lock, LockOpen, Plus, Trash2 } from 'lucide-react';
 import { cn } from '@/lib/utils';
-import { invokeP4Sync, invokeP4ReconcileApply } from '@/lib/tauri';
+import { invokeP4Sync } from '@/lib/tauri';
+import { useFileOperations } from '@/hooks/useFileOperations';
 import { useQueryClient } from '@tanstack/react-query';
 import toast from 'react-hot-toast';
 
@@ -26,12 +27,15 @@ interface FileContextMenuProps {
  * - File History (always available)
  * - Diff against Have (when file is checked out)
  * - Copy local path
+ * - Get Revision / Mark for Delete (synced files), Add to Depot (local files)
+ * - Lock / Unlock (opened files), Force Unlock (files locked by someone else)
  *
  * Closes on click outside or Escape key
  */
 export function FileContextMenu({ file, x, y, onClose, onShowHistory, onDiffAgainstHave, onResolve }: FileContextMenuProps) {
   const menuRef = useRef<HTMLDivElement>(null);
   const queryClient = useQueryClient();
+  const { add, markForDelete, lock, unlock } = useFileOperations();
 
   // Handle click outside to close
   useEffect(() => {
@@ -58,7 +62,12 @@ export function FileContextMenu({ file, x, y, onClose, onShowHistory, onDiffAgai
 
   // Determine if workspace-specific operations are available
   const canSync = file.status === FileStatus.Synced || file.status === FileStatus.OutOfDate;
-  const canAdd = file.status === FileStatus.Modified; // Modified files can be added via reconcile
+  const canAdd = file.status === FileStatus.Modified; // Local files not yet in the depot
+  const canDelete = canSync;
+  const canLock = !!file.action && !file.ourLock && !file.lockedBy;
+  const canUnlock = !!file.ourLock;
+  const canForceUnlock = !!file.lockedBy;
+  const canChangeLock = canLock || canUnlock || canForceUnlock;
 
   async function handleGetRevision() {
     try {
@@ -83,22 +92,37 @@ export function FileContextMenu({ file, x, y, onClose, onShowHistory, onDiffAgai
 
   async function handleAddToDepot() {
     try {
-      toast.loading('Adding file to depot...', { id: 'add-file' });
+      await add([file.localPath]);
+      onClose();
+    } catch {
+      // Error already handled by useFileOperations with toast
+    }
+  }
 
-      // Use reconcile to add the file
-      await invokeP4ReconcileApply(
-        [file.localPath],
-        undefined // Default changelist
-      );
+  async function handleMarkForDelete() {
+    try {
+      await markForDelete([file.depotPath]);
+      onClose();
+    } catch {
+      // Error already handled by useFileOperations with toast
+    }
+  }
 
-      // Refresh file tree and opened files
-      await queryClient.invalidateQueries({ queryKey: ['fileTree'] });
-      await queryClient.invalidateQueries({ queryKey: ['p4', 'opened'] });
+  async function handleLock() {
+    try {
+      await lock([file.depotPath]);
+      onClose();
+    } catch {
+      // Error already handled by useFileOperations with toast
+    }
+  }
 
-      toast.success('File added to depot', { id: 'add-file' });
+  async function handleUnlock(force: boolean) {
+    try {
+      await unlock([file.depotPath], force);
       onClose();
-    } catch (error) {
-      toast.error(`Failed to add file: ${error}`, { id: 'add-file' });
+    } catch {
+      // Error already handled by useFileOperations with toast
     }
   }
 
@@ -118,7 +142,7 @@ export function FileContextMenu({ file, x, y, onClose, onShowHistory, onDiffAgai
       />
 
       {/* Workspace-specific operations */}
-      {(canSync || canAdd) && <div className="h-px bg-border my-1" />}
+      {(canSync || canAdd || canDelete || canChangeLock) && <div className="h-px bg-border my-1" />}
 
       {canSync && (
         <button
@@ -151,6 +175,72 @@ export function FileContextMenu({ file, x, y, onClose, onShowHistory, onDiffAgai
           </span>
         </button>
       )}
+
+      {canDelete && (
+        <button
+          onClick={handleMarkForDelete}
+          className={cn(
+            'w-full px-4 py-2 text-left text-sm text-foreground',
+            'hover:bg-accent',
+            'flex items-center justify-between gap-6'
+          )}
+        >
+          <span className="flex items-center gap-2">
+            <Trash2 className="w-4 h-4" />
+            Mark for Delete
+          </span>
+        </button>
+      )}
+
+      {canLock && (
+        <button
+          onClick={handleLock}
+          className={cn(
+            'w-full px-4 py-2 text-left text-sm text-foreground',
+            'hover:bg-accent',
+            'flex items-center justify-between gap-6'
+          )}
+        >
+          <span className="flex items-center gap-2">
+            <Lock className="w-4 h-4" />
+            Lock
+          </span>
+        </button>
+      )}
+
+      {canUnlock && (
+        <button
+          onClick={() => handleUnlock(false)}
+          className={cn(
+            'w-full px-4 py-2 text-left text-sm text-foreground',
+            'hover:bg-accent',
+            'flex items-center justify-between gap-6'
+          )}
+        >
+          <span className="flex items-center gap-2">
+            <LockOpen className="w-4 h-4" />
+            Unlock
+          </span>
+        </button>
+      )}
+
+      {canForceUnlock && (
+        <button
+          onClick={() => handleUnlock(true)}
+          title={`Locked by ${file.lockedBy}`}
+          className={cn(
+            'w-full px-4 py-2 text-left text-sm text-foreground',
+            'hover:bg-accent',
+            'flex items-center justify-between gap-6'
+          )}
+        >
+          <span className="flex items-center gap-2">
+            <LockOpen className="w-4 h-4" />
+            Force Unlock
+          </span>
+          <span className="text-xs text-muted-foreground truncate max-w-32">{file.lockedBy}</span>
+        </button>
+      )}
     </div>
   );
 }
@@ -2,6 +2,7 @@ import { NodeRendererProps } from 'react-arborist';
 import { FolderOpen, Folder, File, AlertTriangle, ArrowDown } from 'lucide-react';
 import { P4File, FileStatus } from '@/types/p4';
 import { FileStatusIcon } from './FileStatusIcon';
+import { OpenedByTooltip } from './OpenedByTooltip';
 import { useUnresolvedFiles } from '@/hooks/useResolve';
 import { cn } from '@/lib/utils';
 import { useDetailPaneStore } from '@/stores/detailPaneStore';
@@ -64,7 +65,7 @@ export function FileNode({ node, style, dragHandle }: NodeRendererProps<FileNode
     }
   }
 
-  return (
+  const row = (
     <div
       ref={dragHandle}
       style={{ ...style, paddingLeft: (style.paddingLeft as number || 0) + 12 }}
@@ -135,4 +136,10 @@ export function FileNode({ node, style, dragHandle }: NodeRendererProps<FileNode
       )}
     </div>
   );
+
+  // Files show which workspaces have them open on hover
+  if (isFolder || !file || dimmed) {
+    return row;
+  }
+  return <OpenedByTooltip depotPath={file.depotPath}>{row}</OpenedByTooltip>;
 }
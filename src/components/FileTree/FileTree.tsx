@@ -10,6 +10,7 @@ import { useDetailPaneStore } from '@/stores/detailPaneStore';
 import { useSearchFilterStore } from '@/stores/searchFilterStore';
 import { P4File } from '@/types/p4';
 import { AlertCircle } from 'lucide-react';
+import { TooltipProvider } from '@/components/ui/tooltip';
 import { useDndManager } from '@/contexts/DndContext';
 import { cn } from '@/lib/utils';
 import { useCommand } from '@/hooks/useCommand';
@@ -273,21 +274,23 @@ export function FileTree() {
       data-testid="file-tree"
     >
       <div className="flex-1 overflow-hidden">
-        <Tree
-          data={enhancedTree(filteredTree)}
-          idAccessor="id"
-          width="100%"
-          indent={16}
-          rowHeight={32}
-          height={containerHeight}
-          overscanCount={10}
-          disableDrag
-          disableDrop
-          disableEdit
-          dndManager={dndManager}
-        >
-          {FileNode}
-        </Tree>
+        <TooltipProvider>
+          <Tree
+            data={enhancedTree(filteredTree)}
+            idAccessor="id"
+            width="100%"
+            indent={16}
+            rowHeight={32}
+            height={containerHeight}
+            overscanCount={10}
+            disableDrag
+            disableDrop
+            disableEdit
+            dndManager={dndManager}
+          >
+            {FileNode}
+          </Tree>
+        </TooltipProvider>
       </div>
 
       {/* Context menu */}
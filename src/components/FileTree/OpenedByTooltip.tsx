@@ -0,0 +1,62 @@
+import { useState } from 'react';
+import {
+  Tooltip,
+  TooltipContent,
+  TooltipTrigger,
+} from '@/components/ui/tooltip';
+import { useFileOpenedBy } from '@/hooks/useFileOpenedBy';
+import { Loader2, Lock } from 'lucide-react';
+
+interface OpenedByTooltipProps {
+  depotPath: string;
+  children: React.ReactNode;
+}
+
+/**
+ * Tooltip wrapper for file tree rows.
+ * Shows every workspace that has the file open, loaded when the tooltip opens.
+ *
+ * Delays 700ms so moving through the tree doesn't trigger lookups.
+ */
+export function OpenedByTooltip({ depotPath, children }: OpenedByTooltipProps) {
+  const [isOpen, setIsOpen] = useState(false);
+
+  const { data: opens, isLoading, isError } = useFileOpenedBy(depotPath, { enabled: isOpen });
+
+  return (
+    <Tooltip delayDuration={700} onOpenChange={setIsOpen}>
+      <TooltipTrigger asChild>
+        {children}
+      </TooltipTrigger>
+      <TooltipContent side="right" className="max-w-md">
+        {isLoading ? (
+          <div className="flex items-center gap-2">
+            <Loader2 className="h-4 w-4 animate-spin" />
+            <span>Loading...</span>
+          </div>
+        ) : isError ? (
+          <div className="text-xs text-muted-foreground">Could not load opened files</div>
+        ) : !opens || opens.length === 0 ? (
+          <div className="text-xs text-muted-foreground">Not opened in any workspace</div>
+        ) : (
+          <div className="space-y-1">
+            <div className="font-semibold">Opened by</div>
+            {opens.map((open) => (
+              <div key={`${open.user}@${open.client}`} className="flex items-center gap-2 text-xs">
+                {open.locked && <Lock className="h-3 w-3 text-red-500 flex-shrink-0" />}
+                <span>
+                  {open.user}@{open.client}
+                </span>
+                <span className="text-muted-foreground">
+                  {open.action}
+                  {open.changelist === 'default' ? ' (default)' : ` in CL ${open.changelist}`}
+                  {open.exclusive && ', exclusive'}
+                </span>
+              </div>
+            ))}
+          </div>
+        )}
+      </TooltipContent>
+    </Tooltip>
+  );
+}
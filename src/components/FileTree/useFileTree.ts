@@ -11,6 +11,8 @@ import {
   FstatStreamBatch,
   addFilesToIndex,
   clearFileIndex,
+  invokeP4OpenFileIndex,
+  saveFileIndex,
 } from '@/lib/tauri';
 import { useOperationStore } from '@/store/operation';
 import { getVerboseLogging, getDeltaRefreshInterval, getFullRefreshInterval } from '@/lib/settings';
@@ -65,6 +67,9 @@ function mapP4FileInfo(info: P4FileInfo): P4File {
     changelist: info.changelist,
     fileType: info.file_type,
     isDirectory: false,
+    openedBy: info.other_open.map((o) => `${o.user}@${o.client}`),
+    lockedBy: info.other_lock ? `${info.other_lock.user}@${info.other_lock.client}` : undefined,
+    ourLock: info.our_lock,
   };
 }
 
@@ -169,10 +174,23 @@ export function useFileTree() {
     accumulatedFilesRef.current = [];
     estimatedTotalRef.current = 0;
 
-    // Clear FileIndex before starting new stream
-    clearFileIndex().catch(err => {
-      console.warn('Failed to clear file index:', err);
-    });
+    // Open the persisted FileIndex; it only needs repopulating from this stream
+    // when there is no usable index on disk
+    let rebuildIndex = true;
+    let indexChangelist: number | null = null;
+    const pendingIndexAdds: Promise<unknown>[] = [];
+    try {
+      const indexStatus = await invokeP4OpenFileIndex(depotPath);
+      rebuildIndex = indexStatus.rebuildRequired;
+      indexChangelist = indexStatus.changelist;
+    } catch (err) {
+      console.warn('Failed to open file index:', err);
+    }
+    if (rebuildIndex) {
+      clearFileIndex().catch(err => {
+        console.warn('Failed to clear file index:', err);
+      });
+    }
 
     const verbose = await getVerboseLogging();
     if (verbose) addOutputLine(`p4 fstat ${depotPath} (streaming)`, false);
@@ -185,13 +203,17 @@ export function useFileTree() {
           accumulatedFilesRef.current.push(...mappedBatch);
 
           // Populate FileIndex incrementally (fire-and-forget for performance)
-          const indexEntries = batch.files.map(file => ({
-            depotPath: file.depot_path,
-            modTime: file.head_mod_time ?? 0,  // Use headModTime for recency bias
-          }));
-          addFilesToIndex(indexEntries).catch(err => {
-            console.warn('Failed to add files to index:', err);
-          });
+          if (rebuildIndex) {
+            const indexEntries = batch.files.map(file => ({
+              depotPath: file.depot_path,
+              modTime: file.head_mod_time ?? 0,  // Use headModTime for recency bias
+            }));
+            pendingIndexAdds.push(
+              addFilesToIndex(indexEntries).catch(err => {
+                console.warn('Failed to add files to index:', err);
+              })
+            );
+          }
 
           // Update estimate: first batch sets baseline, subsequent batches refine
           if (estimatedTotalRef.current === 0) {
@@ -230,11 +252,21 @@ export function useFileTree() {
               accumulatedFilesRef.current.map(f => [f.depotPath, f])
             );
             lastFullRefreshRef.current = Date.now();
+            // Persist the rebuilt index so the next launch can skip this pass
+            if (rebuildIndex && indexChangelist !== null) {
+              const changelist = indexChangelist;
+              Promise.all(pendingIndexAdds)
+                .then(() => saveFileIndex(changelist))
+                .catch(err => {
+                  console.warn('Failed to save file index:', err);
+                });
+            }
             completeOperation(true);
             resolve(accumulatedFilesRef.current);
           } else {
-            completeOperation(false, batch.error ?? 'Unknown error');
-            reject(new Error(batch.error ?? 'Streaming failed'));
+            const error = batch.cancelled ? 'Cancelled by user' : (batch.error ?? 'Streaming failed');
+            completeOperation(false, error);
+            reject(new Error(error));
           }
 
           setIsStreaming(false);
@@ -1,13 +1,23 @@
 import { useState, useEffect } from 'react';
 import { Loader2, Copy, Check } from 'lucide-react';
+import toast from 'react-hot-toast';
 import {
   Dialog,
   DialogContent,
+  DialogFooter,
   DialogHeader,
   DialogTitle,
 } from '@/components/ui/dialog';
 import { Button } from '@/components/ui/button';
-import { invokeP4GetClientSpec, P4ClientSpec } from '@/lib/tauri';
+import { Input } from '@/components/ui/input';
+import {
+  invokeP4DeleteClient,
+  invokeP4GetClientSpec,
+  invokeP4SaveClientSpec,
+  P4ClientSpec,
+  P4CommandError,
+  SpecFieldError,
+} from '@/lib/tauri';
 import { useConnectionStore } from '@/stores/connectionStore';
 import { cn } from '@/lib/utils';
 
@@ -17,9 +27,13 @@ interface ClientSpecDialogProps {
   workspace: string;
 }
 
+const textareaClass =
+  'w-full px-3 py-2 bg-background border border-border rounded-md text-sm font-mono text-foreground focus:outline-none focus:ring-2 focus:ring-ring';
+
 /**
- * ClientSpecDialog displays the full client spec for a workspace in a read-only format.
- * Allows users to view workspace configuration including root, stream, view mappings, and options.
+ * ClientSpecDialog displays the full client spec for a workspace.
+ * Edit switches to a form covering every field, including the view; validation errors
+ * are shown next to the field they belong to. Delete removes the workspace.
  */
 export function ClientSpecDialog({
   open,
@@ -30,6 +44,10 @@ export function ClientSpecDialog({
   const [spec, setSpec] = useState<P4ClientSpec | null>(null);
   const [isLoading, setIsLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [draft, setDraft] = useState<P4ClientSpec | null>(null);
+  const [view, setView] = useState('');
+  const [isSaving, setIsSaving] = useState(false);
+  const [fieldErrors, setFieldErrors] = useState<SpecFieldError[]>([]);
 
   // Fetch spec when dialog opens
   useEffect(() => {
@@ -37,6 +55,8 @@ export function ClientSpecDialog({
       const fetchSpec = async () => {
         setIsLoading(true);
         setError(null);
+        setDraft(null);
+        setFieldErrors([]);
         try {
           const result = await invokeP4GetClientSpec(workspace);
           setSpec(result);
@@ -52,6 +72,65 @@ export function ClientSpecDialog({
     }
   }, [open, workspace, p4port, p4user]);
 
+  const startEditing = () => {
+    if (!spec) return;
+    setDraft(spec);
+    setView(spec.view.join('\n'));
+    setFieldErrors([]);
+    setError(null);
+  };
+
+  const showError = (err: unknown) => {
+    if (err instanceof P4CommandError && err.fieldErrors.length > 0) {
+      setFieldErrors(err.fieldErrors);
+      const unattributed = err.fieldErrors.filter((e) => !e.field).map((e) => e.message);
+      setError(unattributed.length > 0 ? unattributed.join('\n') : null);
+    } else {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  const handleSave = async () => {
+    if (!draft) return;
+    setIsSaving(true);
+    setError(null);
+    setFieldErrors([]);
+    try {
+      const updated = {
+        ...draft,
+        view: view.split('\n').map((l) => l.trim()).filter(Boolean),
+      };
+      const message = await invokeP4SaveClientSpec(updated);
+      toast.success(message || `Saved ${workspace}`);
+      setSpec(updated);
+      setDraft(null);
+    } catch (err) {
+      showError(err);
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  const handleDelete = async () => {
+    if (!window.confirm(`Delete workspace ${workspace}? Files on disk are left in place.`)) return;
+    setIsSaving(true);
+    setError(null);
+    try {
+      const message = await invokeP4DeleteClient(workspace);
+      toast.success(message || `Deleted ${workspace}`);
+      onOpenChange(false);
+    } catch (err) {
+      showError(err);
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  const errorFor = (field: string) =>
+    fieldErrors.filter((e) => e.field === field).map((e) => e.message).join('\n') || undefined;
+
+  const update = (changes: Partial<P4ClientSpec>) => setDraft((d) => (d ? { ...d, ...changes } : d));
+
   return (
     <Dialog open={open} onOpenChange={onOpenChange}>
       <DialogContent className="max-w-2xl max-h-[80vh] overflow-y-auto">
@@ -64,10 +143,65 @@ export function ClientSpecDialog({
             <Loader2 className="w-8 h-8 animate-spin text-blue-400 mb-3" />
             <p className="text-sm text-muted-foreground">Loading client spec...</p>
           </div>
-        ) : error ? (
+        ) : error && !spec ? (
           <div className="py-6">
             <p className="text-sm text-red-400">Failed to load client spec: {error}</p>
           </div>
+        ) : draft ? (
+          <div className="space-y-3">
+            <div className="grid grid-cols-2 gap-3">
+              <EditField label="Owner" error={errorFor('Owner')}>
+                <Input value={draft.owner} onChange={(e) => update({ owner: e.target.value })} />
+              </EditField>
+              <EditField label="Host" error={errorFor('Host')}>
+                <Input value={draft.host} onChange={(e) => update({ host: e.target.value })} />
+              </EditField>
+            </div>
+            <EditField label="Root" error={errorFor('Root')}>
+              <Input value={draft.root} onChange={(e) => update({ root: e.target.value })} />
+            </EditField>
+            <EditField label="Description" error={errorFor('Description')}>
+              <textarea
+                value={draft.description}
+                onChange={(e) => update({ description: e.target.value })}
+                className={`${textareaClass} h-20 font-sans`}
+              />
+            </EditField>
+            <EditField label="Options" error={errorFor('Options')}>
+              <Input value={draft.options} onChange={(e) => update({ options: e.target.value })} />
+            </EditField>
+            <div className="grid grid-cols-2 gap-3">
+              <EditField label="Submit Options" error={errorFor('SubmitOptions')}>
+                <select
+                  value={draft.submit_options}
+                  onChange={(e) => update({ submit_options: e.target.value })}
+                  className="w-full h-9 px-3 bg-background border border-border rounded-md text-sm text-foreground"
+                >
+                  {['submitunchanged', 'submitunchanged+reopen', 'revertunchanged', 'revertunchanged+reopen', 'leaveunchanged', 'leaveunchanged+reopen'].map((o) => (
+                    <option key={o} value={o}>{o}</option>
+                  ))}
+                </select>
+              </EditField>
+              <EditField label="Line Endings" error={errorFor('LineEnd')}>
+                <select
+                  value={draft.line_end}
+                  onChange={(e) => update({ line_end: e.target.value })}
+                  className="w-full h-9 px-3 bg-background border border-border rounded-md text-sm text-foreground"
+                >
+                  {['local', 'unix', 'mac', 'win', 'share'].map((o) => (
+                    <option key={o} value={o}>{o}</option>
+                  ))}
+                </select>
+              </EditField>
+            </div>
+            {draft.stream ? (
+              <SpecField label="Stream" value={draft.stream} />
+            ) : (
+              <EditField label="View" error={errorFor('View')}>
+                <textarea value={view} onChange={(e) => setView(e.target.value)} className={`${textareaClass} h-32`} />
+              </EditField>
+            )}
+          </div>
         ) : spec ? (
           <div className="space-y-3">
             <SpecField label="Client" value={spec.client} />
@@ -79,6 +213,7 @@ export function ClientSpecDialog({
             <SpecField label="Options" value={spec.options} />
             <SpecField label="Host" value={spec.host} />
             <SpecField label="Submit Options" value={spec.submit_options} />
+            <SpecField label="Line Endings" value={spec.line_end} />
 
             {/* View mappings */}
             <div>
@@ -91,6 +226,33 @@ export function ClientSpecDialog({
             </div>
           </div>
         ) : null}
+
+        {error && spec && <p className="text-sm text-red-400 whitespace-pre-wrap">{error}</p>}
+
+        {spec && (
+          <DialogFooter>
+            {draft ? (
+              <>
+                <Button variant="outline" onClick={() => setDraft(null)} disabled={isSaving}>
+                  Cancel
+                </Button>
+                <Button onClick={handleSave} disabled={isSaving}>
+                  {isSaving && <Loader2 className="w-4 h-4 animate-spin" />}
+                  Save
+                </Button>
+              </>
+            ) : (
+              <>
+                <Button variant="outline" onClick={handleDelete} disabled={isSaving} className="text-red-400">
+                  Delete
+                </Button>
+                <Button onClick={startEditing} disabled={isSaving}>
+                  Edit
+                </Button>
+              </>
+            )}
+          </DialogFooter>
+        )}
       </DialogContent>
     </Dialog>
   );
@@ -145,3 +307,19 @@ function SpecField({ label, value, copyable = false, multiline = false }: SpecFi
     </div>
   );
 }
+
+interface EditFieldProps {
+  label: string;
+  error?: string;
+  children: React.ReactNode;
+}
+
+function EditField({ label, error, children }: EditFieldProps) {
+  return (
+    <div>
+      <label className="block text-sm font-medium text-foreground mb-1.5">{label}</label>
+      {children}
+      {error && <p className="mt-1 text-xs text-red-400 whitespace-pre-wrap">{error}</p>}
+    </div>
+  );
+}
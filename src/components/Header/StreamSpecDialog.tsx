@@ -0,0 +1,205 @@
+import { useState, useEffect } from 'react';
+import { Loader2 } from 'lucide-react';
+import toast from 'react-hot-toast';
+import {
+  Dialog,
+  DialogContent,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from '@/components/ui/dialog';
+import { Button } from '@/components/ui/button';
+import { Input } from '@/components/ui/input';
+import {
+  invokeP4GetStreamSpec,
+  invokeP4SaveStreamSpec,
+  P4CommandError,
+  P4StreamSpec,
+  SpecFieldError,
+} from '@/lib/tauri';
+import { useConnectionStore } from '@/stores/connectionStore';
+
+interface StreamSpecDialogProps {
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+  stream: string;
+}
+
+const textareaClass =
+  'w-full px-3 py-2 bg-background border border-border rounded-md text-sm font-mono text-foreground focus:outline-none focus:ring-2 focus:ring-ring';
+
+/** One entry per line, dropping blank lines */
+const toLines = (text: string) => text.split('\n').map((l) => l.trim()).filter(Boolean);
+
+/**
+ * StreamSpecDialog shows a stream's spec and lets the owner edit it:
+ * description, options, parent view and the Paths / Remapped / Ignored views.
+ * Validation errors from the backend are shown next to the field they belong to.
+ */
+export function StreamSpecDialog({ open, onOpenChange, stream }: StreamSpecDialogProps) {
+  const { p4port, p4user } = useConnectionStore();
+  const [spec, setSpec] = useState<P4StreamSpec | null>(null);
+  const [paths, setPaths] = useState('');
+  const [remapped, setRemapped] = useState('');
+  const [ignored, setIgnored] = useState('');
+  const [isLoading, setIsLoading] = useState(false);
+  const [isSaving, setIsSaving] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const [fieldErrors, setFieldErrors] = useState<SpecFieldError[]>([]);
+
+  // Fetch spec when dialog opens
+  useEffect(() => {
+    if (open && stream && p4port && p4user) {
+      const fetchSpec = async () => {
+        setIsLoading(true);
+        setError(null);
+        setFieldErrors([]);
+        try {
+          const result = await invokeP4GetStreamSpec(stream);
+          setSpec(result);
+          setPaths(result.paths.join('\n'));
+          setRemapped(result.remapped.join('\n'));
+          setIgnored(result.ignored.join('\n'));
+        } catch (err) {
+          setError(err instanceof Error ? err.message : String(err));
+        } finally {
+          setIsLoading(false);
+        }
+      };
+
+      fetchSpec();
+    }
+  }, [open, stream, p4port, p4user]);
+
+  const handleSave = async () => {
+    if (!spec) return;
+    setIsSaving(true);
+    setError(null);
+    setFieldErrors([]);
+    try {
+      const message = await invokeP4SaveStreamSpec({
+        ...spec,
+        paths: toLines(paths),
+        remapped: toLines(remapped),
+        ignored: toLines(ignored),
+      });
+      toast.success(message || `Saved ${stream}`);
+      onOpenChange(false);
+    } catch (err) {
+      if (err instanceof P4CommandError && err.fieldErrors.length > 0) {
+        setFieldErrors(err.fieldErrors);
+        // Errors p4 couldn't attribute to a field go in the general slot
+        const unattributed = err.fieldErrors.filter((e) => !e.field).map((e) => e.message);
+        setError(unattributed.length > 0 ? unattributed.join('\n') : null);
+      } else {
+        setError(err instanceof Error ? err.message : String(err));
+      }
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  const errorFor = (field: string) =>
+    fieldErrors.filter((e) => e.field === field).map((e) => e.message).join('\n') || undefined;
+
+  const update = (changes: Partial<P4StreamSpec>) => setSpec((s) => (s ? { ...s, ...changes } : s));
+
+  return (
+    <Dialog open={open} onOpenChange={onOpenChange}>
+      <DialogContent className="max-w-2xl max-h-[80vh] overflow-y-auto">
+        <DialogHeader>
+          <DialogTitle>Stream Spec: {stream}</DialogTitle>
+        </DialogHeader>
+
+        {isLoading ? (
+          <div className="flex flex-col items-center justify-center py-12">
+            <Loader2 className="w-8 h-8 animate-spin text-blue-400 mb-3" />
+            <p className="text-sm text-muted-foreground">Loading stream spec...</p>
+          </div>
+        ) : spec ? (
+          <div className="space-y-3">
+            <div className="grid grid-cols-2 gap-3">
+              <FormField label="Type">
+                <p className="text-sm text-foreground py-2">{spec.streamType}</p>
+              </FormField>
+              <FormField label="Parent">
+                <p className="text-sm text-foreground py-2 break-all">{spec.parent ?? 'none'}</p>
+              </FormField>
+              <FormField label="Name" error={errorFor('Name')}>
+                <Input value={spec.name} onChange={(e) => update({ name: e.target.value })} />
+              </FormField>
+              <FormField label="Owner" error={errorFor('Owner')}>
+                <Input value={spec.owner} onChange={(e) => update({ owner: e.target.value })} />
+              </FormField>
+            </div>
+            <FormField label="Description" error={errorFor('Description')}>
+              <textarea
+                value={spec.description}
+                onChange={(e) => update({ description: e.target.value })}
+                className={`${textareaClass} h-20 font-sans`}
+              />
+            </FormField>
+            <FormField label="Options" error={errorFor('Options')}>
+              <Input value={spec.options} onChange={(e) => update({ options: e.target.value })} />
+            </FormField>
+            {spec.parentView !== null && (
+              <FormField label="Parent View" error={errorFor('ParentView')}>
+                <select
+                  value={spec.parentView}
+                  onChange={(e) => update({ parentView: e.target.value })}
+                  className="w-full h-9 px-3 bg-background border border-border rounded-md text-sm text-foreground"
+                >
+                  <option value="inherit">inherit</option>
+                  <option value="noinherit">noinherit</option>
+                </select>
+              </FormField>
+            )}
+            <FormField label="Paths" error={errorFor('Paths')}>
+              <textarea value={paths} onChange={(e) => setPaths(e.target.value)} className={`${textareaClass} h-28`} />
+            </FormField>
+            <FormField label="Remapped" error={errorFor('Remapped')}>
+              <textarea value={remapped} onChange={(e) => setRemapped(e.target.value)} className={`${textareaClass} h-16`} />
+            </FormField>
+            <FormField label="Ignored" error={errorFor('Ignored')}>
+              <textarea value={ignored} onChange={(e) => setIgnored(e.target.value)} className={`${textareaClass} h-16`} />
+            </FormField>
+          </div>
+        ) : null}
+
+        {error && (
+          <p className="text-sm text-red-400 whitespace-pre-wrap">
+            {spec ? error : `Failed to load stream spec: ${error}`}
+          </p>
+        )}
+
+        {spec && (
+          <DialogFooter>
+            <Button variant="outline" onClick={() => onOpenChange(false)} disabled={isSaving}>
+              Cancel
+            </Button>
+            <Button onClick={handleSave} disabled={isSaving}>
+              {isSaving && <Loader2 className="w-4 h-4 animate-spin" />}
+              Save
+            </Button>
+          </DialogFooter>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}
+
+interface FormFieldProps {
+  label: string;
+  error?: string;
+  children: React.ReactNode;
+}
+
+function FormField({ label, error, children }: FormFieldProps) {
+  return (
+    <div>
+      <label className="block text-sm font-medium text-foreground mb-1.5">{label}</label>
+      {children}
+      {error && <p className="mt-1 text-xs text-red-400 whitespace-pre-wrap">{error}</p>}
+    </div>
+  );
+}
@@ -1,6 +1,6 @@
 import { useState, useEffect } from 'react';
 import { useQueryClient } from '@tanstack/react-query';
-import { Loader2 } from 'lucide-react';
+import { ArrowDownToLine, ArrowUpFromLine, FileText, Loader2 } from 'lucide-react';
 import toast from 'react-hot-toast';
 import { useConnectionStore } from '@/stores/connectionStore';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
@@ -24,12 +24,16 @@ import {
   SelectTrigger,
   SelectValue,
 } from '@/components/ui/select';
+import { Button } from '@/components/ui/button';
 import { ShelveConfirmDialog } from '@/components/dialogs/ShelveConfirmDialog';
+import { StreamSpecDialog } from './StreamSpecDialog';
+import { useIntegrate, useStreamIntegrationStatus } from '@/hooks/useIntegrate';
 
 /**
  * Stream dropdown switcher for the header.
  * Displays current stream and allows switching to another stream.
  * Before switching, checks for open files and prompts to shelve them.
+ * Offers merge down into the current stream and copy up from its child streams when p4 istat says they're due.
  */
 export function StreamSwitcher() {
   const { status, p4port, p4user, p4client, workspace, stream, setConnected } = useConnectionStore();
@@ -42,11 +46,16 @@ export function StreamSwitcher() {
   const [openFiles, setOpenFiles] = useState<P4FileInfo[]>([]);
   const [pendingStream, setPendingStream] = useState<string | null>(null);
   const [isShelving, setIsShelving] = useState(false);
+  const [streamsVersion, setStreamsVersion] = useState(0);
+  const [streamSpecOpen, setStreamSpecOpen] = useState(false);
 
-  // Fetch streams when connection params are available
+  const { mergeDown, copyUp } = useIntegrate();
+  const { data: integration } = useStreamIntegrationStatus(stream ?? undefined);
+
+  // Fetch streams (with merge-down / copy-up status) when connection params are available
   useEffect(() => {
     if (status === 'connected' && p4port && p4user && p4client) {
-      invokeP4ListStreams()
+      invokeP4ListStreams(true)
         .then(setStreams)
         .catch(err => {
           console.error('Failed to list streams:', err);
@@ -55,7 +64,23 @@ export function StreamSwitcher() {
     } else {
       setStreams([]);
     }
-  }, [status, p4port, p4user, p4client]);
+  }, [status, p4port, p4user, p4client, streamsVersion]);
+
+  // Children of the current stream with changes waiting to be copied up into it
+  const copyUpStreams = streams.filter(
+    (s) => s.parent === stream && s.integration?.needsCopyUp
+  );
+
+  // Run merge down / copy up, then refresh the istat flags shown in the list
+  const runIntegration = async (run: () => Promise<unknown>) => {
+    try {
+      await run();
+    } catch (error) {
+      toast.error(`Integration failed: ${error instanceof Error ? error.message : String(error)}`);
+    } finally {
+      setStreamsVersion((v) => v + 1);
+    }
+  };
 
   // Get short name from stream path (e.g., "main" from "//depot/main")
   const getStreamShortName = (streamPath: string): string => {
@@ -212,40 +237,88 @@ export function StreamSwitcher() {
 
   return (
     <>
-      <div className="flex flex-col">
-        <span className="text-[10px] uppercase tracking-wider text-muted-foreground">
-          Stream
-        </span>
-        <Select
-          value={stream || ''}
-          onValueChange={handleSwitch}
-          disabled={isSwitching}
-        >
-          <SelectTrigger className="h-auto min-w-[180px] w-auto border-0 px-0 py-0 shadow-none focus:ring-0 hover:bg-transparent">
-            {isSwitching ? (
-              <div className="flex items-center gap-2">
-                <Loader2 className="h-3 w-3 animate-spin" />
-                <span className="text-sm font-medium">Switching...</span>
-              </div>
-            ) : (
-              <SelectValue>
-                <span className="text-sm font-medium text-foreground">
-                  {getStreamShortName(stream)}
-                </span>
-              </SelectValue>
-            )}
-          </SelectTrigger>
-          <SelectContent>
-            {streams.map((s) => (
-              <SelectItem key={s.stream} value={s.stream}>
-                <div className="flex flex-col">
-                  <span>{getStreamShortName(s.stream)}</span>
-                  <span className="text-xs text-muted-foreground">{s.stream}</span>
+      <div className="flex items-center gap-2">
+        <div className="flex flex-col">
+          <span className="text-[10px] uppercase tracking-wider text-muted-foreground">
+            Stream
+          </span>
+          <Select
+            value={stream || ''}
+            onValueChange={handleSwitch}
+            disabled={isSwitching}
+          >
+            <SelectTrigger className="h-auto min-w-[180px] w-auto border-0 px-0 py-0 shadow-none focus:ring-0 hover:bg-transparent">
+              {isSwitching ? (
+                <div className="flex items-center gap-2">
+                  <Loader2 className="h-3 w-3 animate-spin" />
+                  <span className="text-sm font-medium">Switching...</span>
                 </div>
-              </SelectItem>
-            ))}
-          </SelectContent>
-        </Select>
+              ) : (
+                <SelectValue>
+                  <span className="text-sm font-medium text-foreground">
+                    {getStreamShortName(stream)}
+                  </span>
+                </SelectValue>
+              )}
+            </SelectTrigger>
+            <SelectContent>
+              {streams.map((s) => (
+                <SelectItem key={s.stream} value={s.stream}>
+                  <div className="flex flex-col">
+                    <span>{getStreamShortName(s.stream)}</span>
+                    <span className="text-xs text-muted-foreground">{s.stream}</span>
+                    {s.integration && (s.integration.needsMergeDown || s.integration.needsCopyUp) && (
+                      <span className="text-xs text-muted-foreground">
+                        {[
+                          s.integration.needsMergeDown && 'needs merge down',
+                          s.integration.needsCopyUp && 'ready to copy up',
+                        ].filter(Boolean).join(' · ')}
+                      </span>
+                    )}
+                    {s.integration?.error && (
+                      <span className="text-xs text-muted-foreground" title={s.integration.error}>
+                        integration status unavailable
+                      </span>
+                    )}
+                  </div>
+                </SelectItem>
+              ))}
+            </SelectContent>
+          </Select>
+        </div>
+
+        <Button
+          variant="ghost"
+          size="sm"
+          onClick={() => setStreamSpecOpen(true)}
+          className="h-6 w-6 p-0 text-muted-foreground hover:text-foreground"
+          title="View Stream Spec"
+        >
+          <FileText className="w-4 h-4" />
+        </Button>
+        {integration?.needsMergeDown && integration.parent && (
+          <Button
+            variant="ghost"
+            size="sm"
+            onClick={() => runIntegration(() => mergeDown(stream))}
+            className="h-6 w-6 p-0 text-muted-foreground hover:text-foreground"
+            title={`Merge down ${integration.pendingMergeDown.length} change(s) from ${getStreamShortName(integration.parent)}`}
+          >
+            <ArrowDownToLine className="w-4 h-4" />
+          </Button>
+        )}
+        {copyUpStreams.map((child) => (
+          <Button
+            key={child.stream}
+            variant="ghost"
+            size="sm"
+            onClick={() => runIntegration(() => copyUp(child.stream))}
+            className="h-6 w-6 p-0 text-muted-foreground hover:text-foreground"
+            title={`Copy up ${getStreamShortName(child.stream)} into ${getStreamShortName(stream)}`}
+          >
+            <ArrowUpFromLine className="w-4 h-4" />
+          </Button>
+        ))}
       </div>
 
       <ShelveConfirmDialog
@@ -256,6 +329,12 @@ export function StreamSwitcher() {
         onCancel={handleCancel}
         isShelving={isShelving}
       />
+
+      <StreamSpecDialog
+        open={streamSpecOpen}
+        onOpenChange={setStreamSpecOpen}
+        stream={stream}
+      />
     </>
   );
 }
@@ -6,7 +6,13 @@ import { useConnectionStore } from '@/stores/connectionStore';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
 import { useDetailPaneStore } from '@/stores/detailPaneStore';
 import { useOperationStore } from '@/store/operation';
-import { invokeListWorkspaces, invokeP4Info, P4Workspace } from '@/lib/tauri';
+import {
+  getActiveProfile,
+  invokeListWorkspaces,
+  invokeP4Info,
+  updateActiveProfile,
+  P4Workspace,
+} from '@/lib/tauri';
 import {
   Select,
   SelectContent,
@@ -22,7 +28,7 @@ import { ClientSpecDialog } from './ClientSpecDialog';
  * Displays current workspace and allows switching to another workspace.
  */
 export function WorkspaceSwitcher() {
-  const { status, p4port, p4user, p4client, workspace, setConnected } = useConnectionStore();
+  const { status, profileId, p4port, p4user, p4client, workspace, setConnected } = useConnectionStore();
   const clear = useDetailPaneStore(s => s.clear);
   const queryClient = useQueryClient();
 
@@ -32,8 +38,9 @@ export function WorkspaceSwitcher() {
 
   // Fetch workspaces when connection params are available
   useEffect(() => {
-    if (p4port && p4user) {
-      invokeListWorkspaces(p4port, p4user)
+    if (profileId && p4port && p4user) {
+      getActiveProfile()
+        .then(invokeListWorkspaces)
         .then(setWorkspaces)
         .catch(err => {
           console.error('Failed to list workspaces:', err);
@@ -42,7 +49,7 @@ export function WorkspaceSwitcher() {
     } else {
       setWorkspaces([]);
     }
-  }, [p4port, p4user]);
+  }, [profileId, p4port, p4user]);
 
   // Handle workspace switch
   const handleSwitch = async (newClient: string) => {
@@ -52,7 +59,8 @@ export function WorkspaceSwitcher() {
     const { startOperation, completeOperation } = useOperationStore.getState();
     startOperation('workspace-switch', `switch -c ${newClient}`);
     try {
-      // Update p4client BEFORE calling info so it targets new workspace
+      // Point the profile at the new workspace BEFORE calling info so it targets it
+      await updateActiveProfile({ client: newClient });
       useConnectionStore.setState({ p4client: newClient });
 
       // Validate new workspace and get updated info
@@ -7,6 +7,7 @@ import { ChangelistPanel } from '@/components/ChangelistPanel/ChangelistPanel';
 import { DetailPane } from '@/components/DetailPane/DetailPane';
 import { ConnectionStatus } from '@/components/ConnectionStatus';
 import { ConnectionDialog } from '@/components/ConnectionDialog';
+import { AuthPromptDialog } from '@/components/AuthPromptDialog';
 import { SettingsDialog } from '@/components/SettingsDialog';
 import { SearchBar } from '@/components/SearchBar';
 import { CommandPalette } from '@/components/CommandPalette';
@@ -386,6 +387,9 @@ export function MainLayout() {
       {/* Connection dialog */}
       <ConnectionDialog open={connectionDialogOpen} onOpenChange={setConnectionDialogOpen} />
 
+      {/* Login / SSL trust prompt for commands that fail authentication */}
+      <AuthPromptDialog />
+
       {/* Settings dialog */}
       <SettingsDialog open={settingsOpen} onOpenChange={setSettingsOpen} />
 
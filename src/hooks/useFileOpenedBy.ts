@@ -0,0 +1,27 @@
+import { useQuery } from '@tanstack/react-query';
+import { invokeP4FileOpenedBy, P4OpenedFile } from '@/lib/tauri';
+
+/**
+ * Fetch which workspaces have a file open (p4 opened -a) on demand.
+ *
+ * The backend caches results for a few seconds, so sweeping the pointer
+ * across the tree doesn't query the server for every file.
+ *
+ * @param depotPath - Depot path of the file
+ * @param options - Query options (e.g., enabled for lazy loading)
+ * @returns Query result with one entry per workspace that has the file open
+ */
+export function useFileOpenedBy(
+  depotPath: string,
+  options?: { enabled?: boolean }
+) {
+  return useQuery({
+    queryKey: ['p4', 'opened-by', depotPath],
+    queryFn: async (): Promise<P4OpenedFile[]> => {
+      return invokeP4FileOpenedBy(depotPath);
+    },
+    staleTime: 15000, // Matches the backend cache; opens change often
+    refetchOnWindowFocus: false,
+    enabled: options?.enabled ?? false, // Disabled by default for lazy loading
+  });
+}
@@ -1,7 +1,22 @@
 import { useCallback } from 'react';
 import { useQueryClient } from '@tanstack/react-query';
 import { useOperationStore } from '@/store/operation';
-import { invokeP4Edit, invokeP4Revert, invokeP4Submit, invokeP4Fstat, P4FileInfo } from '@/lib/tauri';
+import {
+  invokeP4Edit,
+  invokeP4Revert,
+  invokeP4Add,
+  invokeP4Delete,
+  invokeP4Lock,
+  invokeP4Unlock,
+  invokeP4Move,
+  invokeP4Submit,
+  invokeP4Fstat,
+  applyFileIndexChanges,
+  FileIndexChange,
+  P4FileInfo,
+  P4FileOpResult,
+  P4MoveRequest,
+} from '@/lib/tauri';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
 import { P4File, FileStatus, FileAction } from '@/types/p4';
 import toast from 'react-hot-toast';
@@ -17,6 +32,15 @@ interface RunOperationOptions<T> {
   invalidateKeys: string[][];
 }
 
+/**
+ * Summarize per-file results of add/delete/move, e.g. "Added 3 file(s), 1 failed"
+ */
+function fileOpSummary(verb: string, results: P4FileOpResult[]): string {
+  const failed = results.filter((r) => !r.success).length;
+  const done = results.length - failed;
+  return failed > 0 ? `${verb} ${done} file(s), ${failed} failed` : `${verb} ${done} file(s)`;
+}
+
 /**
  * Maps P4FileInfo from backend to P4File type for frontend
  * (duplicated from useFileTree.ts to avoid circular dependency)
@@ -57,9 +81,26 @@ function mapP4FileInfo(info: P4FileInfo): P4File {
     changelist: info.changelist,
     fileType: info.file_type,
     isDirectory: false,
+    openedBy: info.other_open.map((o) => `${o.user}@${o.client}`),
+    lockedBy: info.other_lock ? `${info.other_lock.user}@${info.other_lock.client}` : undefined,
+    ourLock: info.our_lock,
   };
 }
 
+/**
+ * Collect the open action of each file matching `predicate` from the file tree store,
+ * for applying to the search index once the operation succeeds.
+ */
+function openedFileChanges(predicate: (file: P4File) => boolean): FileIndexChange[] {
+  const changes: FileIndexChange[] = [];
+  for (const file of useFileTreeStore.getState().files.values()) {
+    if (file.action && predicate(file)) {
+      changes.push({ depotPath: file.depotPath, action: file.action });
+    }
+  }
+  return changes;
+}
+
 /**
  * Hook for P4 file operations with state management
  *
@@ -211,6 +252,10 @@ export function useFileOperations() {
    * @returns Array of reverted depot paths
    */
   const revert = useCallback(async (paths: string[]) => {
+    // Capture open actions before the revert clears them
+    const requested = new Set(paths);
+    const openedChanges = openedFileChanges((file) => requested.has(file.depotPath));
+
     const result = await runOperation({
       operationId: `revert-${Date.now()}`,
       operationName: `Reverting ${paths.length} file(s)`,
@@ -231,6 +276,13 @@ export function useFileOperations() {
       ],
     });
 
+    // Reverted adds no longer exist in the workspace; drop them from search
+    const reverted = new Set(result);
+    applyFileIndexChanges(
+      'revert',
+      openedChanges.filter((change) => reverted.has(change.depotPath))
+    ).catch((error) => console.warn('Failed to update file index after revert:', error));
+
     // Update only the affected files instead of invalidating entire tree
     await updateAffectedFiles(paths);
 
@@ -245,7 +297,10 @@ export function useFileOperations() {
    * @returns Submitted changelist number
    */
   const submit = useCallback(async (changelist: number, description?: string) => {
-    return runOperation({
+    // Capture the changelist's files before the submit refreshes the tree
+    const submittedChanges = openedFileChanges((file) => file.changelist === changelist);
+
+    const submittedCl = await runOperation({
       operationId: `submit-${Date.now()}`,
       operationName: `Submitting changelist ${changelist}`,
       command: `p4 submit -c ${changelist}`,
@@ -261,11 +316,154 @@ export function useFileOperations() {
         ['p4', 'changes'],
       ],
     });
+
+    applyFileIndexChanges('submit', submittedChanges)
+      .catch((error) => console.warn('Failed to update file index after submit:', error));
+
+    return submittedCl;
   }, [runOperation, addOutputLine]);
 
+  /**
+   * Log per-file results of add/delete/move to the output panel
+   */
+  const logFileOpResults = useCallback((results: P4FileOpResult[]) => {
+    for (const result of results) {
+      if (!result.success) {
+        addOutputLine(`${result.path} - ${result.message ?? 'failed'}`, true);
+      } else if (result.movedFrom) {
+        addOutputLine(`${result.movedFrom} - moved to ${result.path}`, false);
+      } else {
+        addOutputLine(`${result.path}#${result.revision ?? 0} - opened for ${result.action ?? 'add'}`, false);
+      }
+    }
+  }, [addOutputLine]);
+
+  /**
+   * Open new workspace files for add
+   *
+   * @param paths - Local paths of the files to add
+   * @param changelist - Optional changelist number (undefined = default)
+   * @param fileType - Optional file type override (e.g. "binary+l")
+   */
+  const add = useCallback(async (paths: string[], changelist?: number, fileType?: string) => {
+    const results = await runOperation({
+      operationId: `add-${Date.now()}`,
+      operationName: `Adding ${paths.length} file(s)`,
+      command: `p4 add ${fileType ? `-t ${fileType} ` : ''}${paths.join(' ')}`,
+      fn: () => invokeP4Add(paths, changelist, fileType),
+      onSuccess: logFileOpResults,
+      successMessage: (results) => fileOpSummary('Added', results),
+      errorMessage: 'Add failed',
+      invalidateKeys: [
+        ['fileTree'],
+        ['p4', 'opened'],
+        ['p4', 'changes'],
+      ],
+    });
+    return results;
+  }, [runOperation, logFileOpResults]);
+
+  /**
+   * Open files for delete
+   *
+   * @param paths - Depot paths to delete
+   * @param changelist - Optional changelist number (undefined = default)
+   */
+  const markForDelete = useCallback(async (paths: string[], changelist?: number) => {
+    const results = await runOperation({
+      operationId: `delete-${Date.now()}`,
+      operationName: `Deleting ${paths.length} file(s)`,
+      command: `p4 delete ${paths.join(' ')}`,
+      fn: () => invokeP4Delete(paths, changelist),
+      onSuccess: logFileOpResults,
+      successMessage: (results) => fileOpSummary('Marked for delete', results),
+      errorMessage: 'Delete failed',
+      invalidateKeys: [
+        ['p4', 'opened'],
+        ['p4', 'changes'],
+      ],
+    });
+
+    await updateAffectedFiles(paths);
+    return results;
+  }, [runOperation, logFileOpResults, updateAffectedFiles]);
+
+  /**
+   * Lock opened files so they can't be submitted from another workspace first
+   *
+   * @param paths - Depot paths to lock
+   */
+  const lock = useCallback(async (paths: string[]) => {
+    const results = await runOperation({
+      operationId: `lock-${Date.now()}`,
+      operationName: `Locking ${paths.length} file(s)`,
+      command: `p4 lock ${paths.join(' ')}`,
+      fn: () => invokeP4Lock(paths),
+      onSuccess: logFileOpResults,
+      successMessage: (results) => fileOpSummary('Locked', results),
+      errorMessage: 'Lock failed',
+      invalidateKeys: [['p4', 'opened']],
+    });
+
+    await updateAffectedFiles(paths);
+    return results;
+  }, [runOperation, logFileOpResults, updateAffectedFiles]);
+
+  /**
+   * Release file locks
+   *
+   * @param paths - Depot paths to unlock
+   * @param force - Release another user's lock (admin only)
+   */
+  const unlock = useCallback(async (paths: string[], force = false) => {
+    const results = await runOperation({
+      operationId: `unlock-${Date.now()}`,
+      operationName: `Unlocking ${paths.length} file(s)`,
+      command: `p4 unlock${force ? ' -f' : ''} ${paths.join(' ')}`,
+      fn: () => invokeP4Unlock(paths, force),
+      onSuccess: logFileOpResults,
+      successMessage: (results) => fileOpSummary('Unlocked', results),
+      errorMessage: 'Unlock failed',
+      invalidateKeys: [['p4', 'opened']],
+    });
+
+    await updateAffectedFiles(paths);
+    return results;
+  }, [runOperation, logFileOpResults, updateAffectedFiles]);
+
+  /**
+   * Move/rename files or directories
+   *
+   * @param moves - Source and target pairs (directories end in "/...")
+   * @param changelist - Optional changelist number (undefined = default)
+   */
+  const move = useCallback(async (moves: P4MoveRequest[], changelist?: number) => {
+    const results = await runOperation({
+      operationId: `move-${Date.now()}`,
+      operationName: `Moving ${moves.length} item(s)`,
+      command: moves.map((m) => `p4 move ${m.from} ${m.to}`).join('\n'),
+      fn: () => invokeP4Move(moves, changelist),
+      onSuccess: logFileOpResults,
+      successMessage: (results) => fileOpSummary('Moved', results),
+      errorMessage: 'Move failed',
+      invalidateKeys: [
+        // Targets are new paths, so the tree needs a full refresh
+        ['fileTree'],
+        ['p4', 'opened'],
+        ['p4', 'changes'],
+      ],
+    });
+    return results;
+  }, [runOperation, logFileOpResults]);
+
   return {
     checkout,
     revert,
     submit,
+    add,
+    markForDelete,
+    lock,
+    unlock,
+    move,
   };
 }
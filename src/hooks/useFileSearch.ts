@@ -1,5 +1,5 @@
 import { useQuery } from '@tanstack/react-query';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '@/lib/tauri';
 import { useSearchFilterStore } from '@/stores/searchFilterStore';
 import { useDebounce } from './useDebounce';
 
@@ -35,6 +35,8 @@ export function useFileSearch(query: string) {
       return results;
     },
     enabled: debouncedQuery.length > 0,
+    // A newer query cancels this one in the backend; retrying would cancel the newer one
+    retry: false,
     staleTime: 5000, // Cache results for 5s
     gcTime: 30000,   // Keep in cache for 30s
   });
@@ -0,0 +1,185 @@
+import { useCallback } from 'react';
+import { useQuery, useQueryClient } from '@tanstack/react-query';
+import toast from 'react-hot-toast';
+import {
+  invokeP4Integrate,
+  invokeP4IntegratePreview,
+  invokeP4StreamIntegrationStatus,
+  IntegrateFile,
+  IntegrateOptions,
+  IntegrateStreamBatch,
+  StreamCompletion,
+} from '@/lib/tauri';
+import { useOperationStore } from '@/store/operation';
+import { useConnectionStore } from '@/stores/connectionStore';
+
+/**
+ * Command line shown in the output panel for an integration
+ */
+function describeIntegrate(options: IntegrateOptions, preview: boolean): string {
+  const parts = ['p4', options.kind];
+  if (preview) parts.push('-n');
+  if (options.changelist) parts.push('-c', String(options.changelist));
+  if (options.stream) parts.push('-S', options.stream);
+  if (options.parentStream) parts.push('-P', options.parentStream);
+  if (options.branch) parts.push('-b', options.branch);
+  if (options.reverse) parts.push('-r');
+  const range = options.revisionRange ?? '';
+  if (options.fromPath) parts.push(`${options.fromPath}${range}`);
+  if (options.toPath) parts.push(options.fromPath ? options.toPath : `${options.toPath}${range}`);
+  return parts.join(' ');
+}
+
+/**
+ * Output panel line for an opened file, in p4's own format
+ */
+function formatIntegrateFile(file: IntegrateFile): string {
+  const source = file.fromFile ? ` from ${file.fromFile}${file.endFromRev ?? ''}` : '';
+  return `${file.depotPath}#${file.workRev ?? 1} - ${file.action}${source}`;
+}
+
+/**
+ * Hook for merge / copy / integrate between streams, branch specs or file pairs.
+ *
+ * Provides:
+ * - Preview (-n) returning the per-file actions without opening anything
+ * - Streaming run with progress, output logging and cancellation via the operation store
+ * - One-click merge down / copy up along a stream's parent relationship
+ * - Refresh of unresolved files so the resolve UI picks up the new conflicts
+ */
+export function useIntegrate() {
+  const queryClient = useQueryClient();
+  const {
+    startOperation,
+    setProcessId,
+    updateMessage,
+    completeOperation,
+    addOutputLine,
+    currentOperation,
+  } = useOperationStore();
+
+  const preview = useCallback(async (options: IntegrateOptions) => {
+    addOutputLine(describeIntegrate(options, true), false);
+    try {
+      const result = await invokeP4IntegratePreview(options);
+      for (const file of result.files) {
+        addOutputLine(formatIntegrateFile(file), false);
+      }
+      for (const message of result.messages) {
+        addOutputLine(message, true);
+      }
+      if (result.files.length === 0 && result.messages.length === 0) {
+        addOutputLine('All revision(s) already integrated.', false);
+      }
+      return result;
+    } catch (error) {
+      addOutputLine(`Error: ${error}`, true);
+      throw error;
+    }
+  }, [addOutputLine]);
+
+  const integrate = useCallback(async (options: IntegrateOptions) => {
+    if (currentOperation && currentOperation.status === 'running') {
+      throw new Error('Another operation is already in progress');
+    }
+
+    const command = describeIntegrate(options, false);
+    startOperation(`${options.kind}-${Date.now()}`, command);
+    addOutputLine(command, false);
+
+    try {
+      // Resolved by the single 'complete' batch sent once the process exits
+      let onComplete: (completion: StreamCompletion & { totalFiles: number }) => void = () => {};
+      const completed = new Promise<StreamCompletion & { totalFiles: number }>((resolve) => {
+        onComplete = resolve;
+      });
+
+      const processId = await invokeP4Integrate(options, (batch: IntegrateStreamBatch) => {
+        if (batch.type === 'complete') {
+          onComplete(batch);
+          return;
+        }
+        for (const file of batch.files) {
+          addOutputLine(formatIntegrateFile(file), false);
+        }
+        updateMessage(`${options.kind}: ${batch.totalReceived} files opened`);
+      });
+      setProcessId(processId);
+
+      const completion = await completed;
+      if (!completion.cancelled) {
+        completeOperation(completion.success, completion.error ?? undefined);
+        if (completion.success) {
+          toast.success(
+            completion.totalFiles > 0
+              ? `Opened ${completion.totalFiles} file(s) for ${options.kind}`
+              : 'All revision(s) already integrated'
+          );
+        } else {
+          toast.error(`${options.kind} failed: ${completion.error}`);
+        }
+      }
+
+      // Integrated files are opened and usually need resolve
+      await Promise.all([
+        queryClient.invalidateQueries({ queryKey: ['p4', 'unresolved'] }),
+        queryClient.invalidateQueries({ queryKey: ['p4', 'streamIntegration'] }),
+        queryClient.invalidateQueries({ queryKey: ['p4', 'opened'] }),
+        queryClient.invalidateQueries({ queryKey: ['p4', 'changes'] }),
+        queryClient.invalidateQueries({ queryKey: ['fileTree'] }),
+      ]);
+      return completion;
+    } catch (error) {
+      addOutputLine(`Error: ${error}`, true);
+      completeOperation(false, String(error));
+      throw error;
+    }
+  }, [
+    currentOperation,
+    startOperation,
+    setProcessId,
+    updateMessage,
+    completeOperation,
+    addOutputLine,
+    queryClient,
+  ]);
+
+  /**
+   * Merge the parent's changes down into `stream` (p4 merge -S).
+   * The workspace must be on `stream`.
+   */
+  const mergeDown = useCallback(
+    (stream: string, changelist?: number) => integrate({ kind: 'merge', stream, changelist }),
+    [integrate]
+  );
+
+  /**
+   * Copy `stream` up to its parent (p4 copy -S).
+   * The workspace must be on the parent stream.
+   */
+  const copyUp = useCallback(
+    (stream: string, changelist?: number) => integrate({ kind: 'copy', stream, changelist }),
+    [integrate]
+  );
+
+  return {
+    preview,
+    integrate,
+    mergeDown,
+    copyUp,
+  };
+}
+
+/**
+ * Query whether a stream needs a merge down or is ready to copy up,
+ * including the changelists waiting in each direction.
+ */
+export function useStreamIntegrationStatus(stream: string | undefined) {
+  const { profileId, status } = useConnectionStore();
+
+  return useQuery({
+    queryKey: ['p4', 'streamIntegration', stream, profileId],
+    queryFn: () => invokeP4StreamIntegrationStatus(stream!),
+    enabled: status === 'connected' && !!stream,
+  });
+}
@@ -1,6 +1,6 @@
 import { useQuery } from '@tanstack/react-query';
 import { useCallback } from 'react';
-import { invokeP4Command, invokeSpawnP4, invokeKillProcess, OutputLine } from '@/lib/tauri';
+import { invokeP4Command, invokeSpawnP4, invokeKillProcess, OutputEvent } from '@/lib/tauri';
 import { useOperationStore } from '@/store/operation';
 
 /**
@@ -58,20 +58,23 @@ export function useP4Command() {
     try {
       if (options?.streaming) {
         // Long-running command with streaming output
-        const processId = await invokeSpawnP4(fullArgs, (output: OutputLine) => {
-          addOutputLine(output.line, output.is_stderr);
+        const processId = await invokeSpawnP4(fullArgs, (event: OutputEvent) => {
+          if (event.type === 'complete') {
+            // Cancellation is reported by cancel() itself
+            if (!event.cancelled) {
+              completeOperation(event.success, event.error ?? undefined);
+            }
+            return;
+          }
+          addOutputLine(event.line, event.is_stderr);
           // Update message with last line (simple progress indicator)
-          if (!output.is_stderr) {
-            updateMessage(output.line);
+          if (!event.is_stderr) {
+            updateMessage(event.line);
           }
         });
 
+        // The operation stays running until the 'complete' event arrives
         setProcessId(processId);
-
-        // Note: For streaming commands, completion is detected by process exit
-        // which we'd handle via a completion channel. For now, mark as complete
-        // after spawn returns (caller handles waiting if needed).
-        completeOperation(true);
         return processId;
       } else {
         // Short command, wait for result
@@ -2,6 +2,8 @@ import { useEffect } from 'react';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
 import { useChangelistStore } from '@/stores/changelistStore';
+import { useOperationStore } from '@/store/operation';
+import { ProcessExitedEvent } from '@/lib/tauri';
 import { FileStatus, FileAction } from '@/types/p4';
 
 /**
@@ -40,6 +42,7 @@ export interface UseP4EventsConfig {
   changelists?: boolean;
   syncProgress?: boolean;
   operationComplete?: boolean;
+  processExited?: boolean;
 }
 
 /**
@@ -64,6 +67,7 @@ export function useP4Events(config?: UseP4EventsConfig) {
   const subscribeToChangelists = config?.changelists ?? true;
   const subscribeToSyncProgress = config?.syncProgress ?? true;
   const subscribeToOperationComplete = config?.operationComplete ?? true;
+  const subscribeToProcessExited = config?.processExited ?? true;
 
   useEffect(() => {
     const unlisteners: UnlistenFn[] = [];
@@ -125,6 +129,25 @@ export function useP4Events(config?: UseP4EventsConfig) {
       });
     }
 
+    // Process exited event: completes the current operation if it was waiting on that process
+    if (subscribeToProcessExited) {
+      listen<ProcessExitedEvent>('process-exited', (event) => {
+        const { processId, exitCode, reason, error } = event.payload;
+        const { currentOperation, completeOperation } = useOperationStore.getState();
+
+        if (currentOperation?.processId !== processId || currentOperation.status !== 'running') {
+          return;
+        }
+        if (reason === 'exited' && exitCode === 0) {
+          completeOperation(true);
+        } else {
+          completeOperation(false, error ?? `p4 exited with code ${exitCode ?? 'unknown'}`);
+        }
+      }).then((unlisten) => {
+        unlisteners.push(unlisten);
+      });
+    }
+
     // Cleanup: remove all listeners on unmount
     return () => {
       unlisteners.forEach((unlisten) => unlisten());
@@ -134,6 +157,7 @@ export function useP4Events(config?: UseP4EventsConfig) {
     subscribeToChangelists,
     subscribeToSyncProgress,
     subscribeToOperationComplete,
+    subscribeToProcessExited,
     updateFile,
     updateChangelist,
   ]);
@@ -2,7 +2,7 @@ import { useCallback } from 'react';
 import { useQuery, useQueryClient } from '@tanstack/react-query';
 import { useOperationStore } from '@/store/operation';
 import { useConnectionStore } from '@/stores/connectionStore';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '@/lib/tauri';
 import { P4UnresolvedFile } from '@/types/p4';
 import toast from 'react-hot-toast';
 
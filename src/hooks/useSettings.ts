@@ -1,10 +1,40 @@
 import { useState, useEffect } from 'react';
-import { loadSettings, getVerboseLogging } from '@/lib/settings';
+import { loadSettings, saveSettings, getVerboseLogging } from '@/lib/settings';
 import { useConnectionStore } from '@/stores/connectionStore';
 import { useOperationStore } from '@/store/operation';
-import { invokeP4Info } from '@/lib/tauri';
+import {
+  invokeListConnectionProfiles,
+  invokeP4Info,
+  invokeSaveConnectionProfile,
+  type ConnectionProfile,
+} from '@/lib/tauri';
 import type { P4Settings } from '@/types/settings';
 
+/**
+ * The saved profile the settings point at. Settings from before connection
+ * profiles existed are turned into a profile on first launch.
+ */
+async function resolveActiveProfile(s: P4Settings): Promise<ConnectionProfile> {
+  const profiles = await invokeListConnectionProfiles();
+  const active = profiles.find(p => p.id === s.activeProfileId);
+  if (active) {
+    return active;
+  }
+  const profile: ConnectionProfile = {
+    id: crypto.randomUUID(),
+    name: s.p4port,
+    server: s.p4port,
+    user: s.p4user,
+    client: s.p4client,
+    charset: null,
+    useP4config: false,
+    ticketsFile: null,
+  };
+  await invokeSaveConnectionProfile(profile);
+  await saveSettings({ ...s, activeProfileId: profile.id });
+  return profile;
+}
+
 export function useSettings() {
   const [settings, setSettings] = useState<P4Settings | null>(null);
   const [isLoading, setIsLoading] = useState(true);
@@ -20,6 +50,8 @@ export function useSettings() {
     // Test connection with saved settings
     setConnecting();
     try {
+      const profile = await resolveActiveProfile(s);
+      useConnectionStore.setState({ profileId: profile.id });
       const { addOutputLine } = useOperationStore.getState();
       const verbose = await getVerboseLogging();
       if (verbose) addOutputLine('p4 info', false);
@@ -30,9 +62,10 @@ export function useSettings() {
         stream: info.client_stream || undefined,
         server: info.server_address,
         user: info.user_name,
-        p4port: s.p4port,
-        p4user: s.p4user,
-        p4client: s.p4client,
+        p4port: profile.server ?? s.p4port,
+        p4user: profile.user ?? s.p4user,
+        p4client: profile.client ?? s.p4client,
+        profileId: profile.id,
       });
     } catch (error) {
       setError(error instanceof Error ? error.message : String(error));
@@ -1,11 +1,22 @@
 import { useCallback, useRef, useState } from 'react';
 import { useQuery, useQueryClient } from '@tanstack/react-query';
-import { invokeP4Sync, invokeKillProcess, invokeP4Info, SyncProgress } from '@/lib/tauri';
+import {
+  invokeP4Sync,
+  invokeKillProcess,
+  invokeP4Info,
+  applyFileIndexChanges,
+  FileIndexChange,
+  StreamCompletion,
+  SyncStreamEvent,
+} from '@/lib/tauri';
 import { useOperationStore } from '@/store/operation';
 import { getVerboseLogging } from '@/lib/settings';
 import { useFileTreeStore } from '@/stores/fileTreeStore';
 import { useConnectionStore } from '@/stores/connectionStore';
 
+/** Delay before buffered sync progress is applied to the search index */
+const INDEX_FLUSH_DELAY_MS = 250;
+
 export interface SyncConflict {
   depotPath: string;
   action: string;
@@ -65,6 +76,20 @@ export function useSync() {
   const [syncedFiles, setSyncedFiles] = useState(0);
   const totalFilesRef = useRef(0);
   const syncedFilesRef = useRef(0);
+  const pendingIndexChangesRef = useRef<FileIndexChange[]>([]);
+  const indexFlushTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null);
+
+  /**
+   * Apply buffered sync progress to the search index in one call.
+   */
+  const flushIndexChanges = useCallback(() => {
+    indexFlushTimerRef.current = null;
+    const changes = pendingIndexChangesRef.current;
+    if (changes.length === 0) return;
+    pendingIndexChangesRef.current = [];
+    applyFileIndexChanges('sync', changes)
+      .catch((error) => console.warn('Failed to update file index after sync:', error));
+  }, []);
 
   /**
    * Execute sync operation with progress tracking.
@@ -94,11 +119,23 @@ export function useSync() {
         syncArgs.unshift('-f');
       }
 
+      // Resolved by the single 'complete' event sent once the sync process exits
+      let onComplete: (completion: StreamCompletion) => void = () => {};
+      const completed = new Promise<StreamCompletion>((resolve) => {
+        onComplete = resolve;
+      });
+
       // Pass depot path for syncing (avoids -d flag issues with DVCS)
       const processId = await invokeP4Sync(
         syncArgs,
         depotPath,
-        (progress: SyncProgress) => {
+        (event: SyncStreamEvent) => {
+          if (event.type === 'complete') {
+            onComplete(event);
+            return;
+          }
+          const progress = event;
+
           // Handle conflict detection
           if (progress.is_conflict) {
             setConflict({
@@ -140,6 +177,15 @@ export function useSync() {
             false
           );
 
+          // Keep the search index in step (batched; one invoke per flush)
+          pendingIndexChangesRef.current.push({
+            depotPath: progress.depot_path,
+            action: progress.action,
+          });
+          if (indexFlushTimerRef.current === null) {
+            indexFlushTimerRef.current = setTimeout(flushIndexChanges, INDEX_FLUSH_DELAY_MS);
+          }
+
           // Update file tree store with new revision
           updateFile(progress.depot_path, {
             revision: progress.revision,
@@ -150,9 +196,16 @@ export function useSync() {
 
       setProcessId(processId);
 
-      // Sync completes when backend process exits
-      // For now, mark as complete (backend will send completion via events in future)
-      completeOperation(true);
+      const completion = await completed;
+      if (indexFlushTimerRef.current !== null) {
+        clearTimeout(indexFlushTimerRef.current);
+      }
+      flushIndexChanges();
+
+      // Cancellation is reported by cancel() itself
+      if (!completion.cancelled) {
+        completeOperation(completion.success, completion.error ?? undefined);
+      }
 
       // Invalidate queries to refresh all file and changelist states
       await Promise.all([
@@ -173,6 +226,7 @@ export function useSync() {
     completeOperation,
     addOutputLine,
     updateFile,
+    flushIndexChanges,
     depotPath,
     queryClient,
   ]);
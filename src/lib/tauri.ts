@@ -1,16 +1,158 @@
-import { invoke, Channel } from '@tauri-apps/api/core';
+import { invoke as tauriInvoke, Channel, type InvokeArgs } from '@tauri-apps/api/core';
 import { useConnectionStore } from '../stores/connectionStore';
 
 /**
- * Get current connection args from the store.
+ * Error category reported by the backend for failed p4 commands.
+ * Matches Rust P4ErrorKind (serialized as camelCase).
+ */
+export type P4ErrorKind =
+  | 'connectionRefused'
+  | 'loginRequired'
+  | 'trustRequired'
+  | 'charsetMismatch'
+  | 'noSuchFile'
+  | 'fileLocked'
+  | 'needsResolve'
+  | 'outOfDate'
+  | 'permissionDenied'
+  | 'invalidSpec'
+  | 'unknown';
+
+/**
+ * A problem with one field of a spec form (matches Rust SpecFieldError).
+ */
+export interface SpecFieldError {
+  /** Form field name as p4 spells it (Paths, Options, ...) */
+  field: string | null;
+  message: string;
+}
+
+/**
+ * Typed error payload returned by p4 commands (matches Rust P4Error).
+ */
+export interface P4Error {
+  kind: P4ErrorKind;
+  message: string;
+  stderr: string;
+  exitCode: number | null;
+  /** Present for invalidSpec errors */
+  fieldErrors?: SpecFieldError[];
+}
+
+function isP4Error(value: unknown): value is P4Error {
+  return (
+    typeof value === 'object' &&
+    value !== null &&
+    typeof (value as P4Error).kind === 'string' &&
+    typeof (value as P4Error).message === 'string'
+  );
+}
+
+/**
+ * Error thrown by invoke wrappers when a p4 command fails.
+ * Stringifies to the plain message so existing `String(error)` call sites keep working.
+ */
+export class P4CommandError extends Error {
+  readonly kind: P4ErrorKind;
+  readonly stderr: string;
+  readonly exitCode: number | null;
+  readonly fieldErrors: SpecFieldError[];
+
+  constructor(error: P4Error) {
+    super(error.message);
+    this.name = 'P4CommandError';
+    this.kind = error.kind;
+    this.stderr = error.stderr;
+    this.exitCode = error.exitCode;
+    this.fieldErrors = error.fieldErrors ?? [];
+  }
+
+  toString(): string {
+    return this.message;
+  }
+}
+
+/**
+ * Connection profile a failed command ran against, for logging in or trusting the right server.
+ * Undefined means the default connection from the environment.
+ */
+export interface AuthTarget {
+  connection?: string;
+}
+
+/**
+ * Resolves a loginRequired or trustRequired failure, typically by prompting the user.
+ * Returns true when the command should be retried.
+ */
+export type AuthHandler = (error: P4CommandError, target: AuthTarget) => Promise<boolean>;
+
+let authHandler: AuthHandler | null = null;
+let pendingAuth: Promise<boolean> | null = null;
+
+/** Commands that must not trigger the auth prompt themselves */
+const AUTH_COMMANDS = new Set(['p4_login', 'p4_logout', 'p4_login_status', 'p4_trust', 'p4_trust_status']);
+
+/**
+ * Register the handler used when a command fails for lack of a ticket or SSL trust.
+ */
+export function setAuthHandler(handler: AuthHandler | null) {
+  authHandler = handler;
+}
+
+/**
+ * Ask the auth handler once; commands failing while a prompt is open wait for the same answer.
+ */
+function resolveAuth(error: P4CommandError, target: AuthTarget): Promise<boolean> {
+  if (!authHandler) return Promise.resolve(false);
+  if (!pendingAuth) {
+    pendingAuth = authHandler(error, target).finally(() => {
+      pendingAuth = null;
+    });
+  }
+  return pendingAuth;
+}
+
+async function invokeOnce<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await tauriInvoke<T>(cmd, args);
+  } catch (error) {
+    throw isP4Error(error) ? new P4CommandError(error) : error;
+  }
+}
+
+/**
+ * Invoke a backend command, converting structured P4Error payloads into P4CommandError.
+ * Login and trust failures go to the auth handler, and the command is retried once if it resolves them.
+ */
+export async function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await invokeOnce<T>(cmd, args);
+  } catch (error) {
+    const needsAuth =
+      error instanceof P4CommandError &&
+      (error.kind === 'loginRequired' || error.kind === 'trustRequired') &&
+      !AUTH_COMMANDS.has(cmd);
+    if (!needsAuth) throw error;
+
+    // Commands taking a whole profile (connection tests) authenticate against its saved copy
+    const { connection, profile } = (args ?? {}) as { connection?: unknown; profile?: { id?: unknown } };
+    const id = connection ?? profile?.id;
+    const target: AuthTarget = {
+      connection: typeof id === 'string' && id !== '' ? id : undefined,
+    };
+    if (!(await resolveAuth(error, target))) throw error;
+    return invokeOnce<T>(cmd, args);
+  }
+}
+
+/**
+ * Get the active connection profile from the store.
  * Auto-injected into all P4 commands.
  */
 function getConnectionArgs() {
-  const { p4port, p4user, p4client } = useConnectionStore.getState();
+  const { profileId } = useConnectionStore.getState();
   return {
-    server: p4port ?? undefined,
-    user: p4user ?? undefined,
-    client: p4client ?? undefined,
+    connection: profileId ?? undefined,
   };
 }
 
@@ -20,6 +162,25 @@ export interface OutputLine {
   is_stderr: boolean;
 }
 
+/**
+ * How a streamed command ended. Every streaming command sends exactly one,
+ * as the last message on its channel, after the process has exited.
+ */
+export interface StreamCompletion {
+  success: boolean;
+  cancelled: boolean; // Stopped via invokeKillProcess
+  exitCode: number | null;
+  error: string | null;
+  errorKind: P4ErrorKind | null;
+}
+
+/**
+ * Message from spawn_p4_command: output lines, then one 'complete'
+ */
+export type OutputEvent =
+  | ({ type: 'output' } & OutputLine)
+  | ({ type: 'complete' } & StreamCompletion);
+
 export interface ProcessResult {
   exit_code: number | null;
   success: boolean;
@@ -37,6 +198,39 @@ export interface P4FileInfo {
   file_type: string;
   head_action?: string;
   head_mod_time?: number;  // Unix timestamp of head revision (for recency bias)
+  other_open: P4OtherOpen[];
+  /** Another workspace's lock (p4 lock, or an open of an exclusive +l file) */
+  other_lock: P4OtherOpen | null;
+  our_lock: boolean;
+}
+
+/**
+ * Another workspace's open of a file (matches Rust P4OtherOpen).
+ */
+export interface P4OtherOpen {
+  user: string;
+  client: string;
+  action: string | null;
+  /** Changelist number, or "default" */
+  changelist: string | null;
+}
+
+/**
+ * A file opened in any workspace, from p4 opened -a (matches Rust P4OpenedFile).
+ */
+export interface P4OpenedFile {
+  depotPath: string;
+  user: string;
+  client: string;
+  /** Changelist number, or "default" */
+  changelist: string;
+  action: string;
+  fileType: string | null;
+  revision: number | null;
+  /** Locked with p4 lock */
+  locked: boolean;
+  /** The file type is exclusive-open (+l), so no other workspace can open it */
+  exclusive: boolean;
 }
 
 export interface P4ChangelistInfo {
@@ -56,14 +250,21 @@ export interface SyncProgress {
   is_conflict: boolean;
 }
 
+/**
+ * Message from p4_sync: per-file progress, then one 'complete'
+ */
+export type SyncStreamEvent =
+  | ({ type: 'progress' } & SyncProgress)
+  | ({ type: 'complete' } & StreamCompletion);
+
 /**
  * Streaming batch from p4_fstat_stream
  * type: "data" contains file batches
- * type: "complete" signals end of stream
+ * type: "complete" is sent once at the end of the stream
  */
 export type FstatStreamBatch =
   | { type: 'data'; files: P4FileInfo[]; totalReceived: number }
-  | { type: 'complete'; totalFiles: number; success: boolean; error?: string };
+  | ({ type: 'complete'; totalFiles: number } & StreamCompletion);
 
 export interface P4ClientInfo {
   client_name: string;
@@ -71,6 +272,10 @@ export interface P4ClientInfo {
   client_stream: string | null;
   user_name: string;
   server_address: string;
+  /** Server runs in unicode mode and requires a charset */
+  server_unicode: boolean;
+  /** Charset used on this connection, configured or detected */
+  charset: string | null;
 }
 
 export interface P4Workspace {
@@ -85,27 +290,59 @@ export interface P4Workspace {
  * Use for quick commands like 'p4 info', 'p4 client'.
  */
 export async function invokeP4Command(args: string[]): Promise<string> {
-  return invoke<string>('p4_command', { args });
+  return invoke<string>('p4_command', { args, ...getConnectionArgs() });
 }
 
 /**
  * Spawn a long-running p4 command with streaming output.
  * Use for commands like 'p4 sync' that produce lots of output.
- * Returns process ID for cancellation.
+ * The process is killed if it is still running after timeoutMs.
+ * Returns process ID for cancellation; its exit is reported by the 'process-exited' event.
  */
 export async function invokeSpawnP4(
   args: string[],
-  onOutput: (line: OutputLine) => void
+  onOutput: (event: OutputEvent) => void,
+  timeoutMs?: number
 ): Promise<string> {
-  const channel = new Channel<OutputLine>();
+  const channel = new Channel<OutputEvent>();
   channel.onmessage = onOutput;
 
   return invoke<string>('spawn_p4_command', {
     args,
+    timeoutMs: timeoutMs ?? null,
+    ...getConnectionArgs(),
     onOutput: channel,
   });
 }
 
+export interface ProcessInfo {
+  id: string;
+  args: string[];
+  /** Connection profile the process runs against; null for the default connection */
+  connection: string | null;
+  pid: number | null;
+  startedAt: number; // Unix timestamp in milliseconds
+  timeoutMs: number | null;
+}
+
+export type ProcessExitReason = 'exited' | 'killed' | 'timedOut' | 'failed';
+
+/** Payload of the 'process-exited' event */
+export interface ProcessExitedEvent {
+  processId: string;
+  exitCode: number | null;
+  reason: ProcessExitReason;
+  durationMs: number;
+  error: string | null;
+}
+
+/**
+ * List tracked p4 processes that are still running, oldest first.
+ */
+export async function invokeListProcesses(): Promise<ProcessInfo[]> {
+  return invoke<ProcessInfo[]>('list_processes');
+}
+
 /**
  * Kill a tracked process by ID.
  * Returns true if process was found and killed.
@@ -122,6 +359,74 @@ export async function invokeKillAllProcesses(): Promise<void> {
   return invoke<void>('kill_all_processes');
 }
 
+/**
+ * Kill the processes of one connection profile; other connections keep running.
+ */
+export async function invokeKillConnectionProcesses(connection?: string): Promise<void> {
+  return invoke<void>('kill_connection_processes', { connection });
+}
+
+/**
+ * Saved settings for one Perforce connection. Commands reference it by id.
+ */
+export interface ConnectionProfile {
+  id: string;
+  name: string;
+  server: string | null;
+  user: string | null;
+  client: string | null;
+  /** P4CHARSET for unicode-mode servers, e.g. "utf8" */
+  charset: string | null;
+  /** Let P4CONFIG files apply even though server/user/client are set */
+  useP4config: boolean;
+  /** P4TICKETS file for this profile's login tickets */
+  ticketsFile: string | null;
+}
+
+/**
+ * Saved connection profiles, sorted by name.
+ */
+export async function invokeListConnectionProfiles(): Promise<ConnectionProfile[]> {
+  return invoke<ConnectionProfile[]>('list_connection_profiles');
+}
+
+/**
+ * Add or update a connection profile. Returns the updated list.
+ */
+export async function invokeSaveConnectionProfile(profile: ConnectionProfile): Promise<ConnectionProfile[]> {
+  return invoke<ConnectionProfile[]>('save_connection_profile', { profile });
+}
+
+/**
+ * Delete a connection profile, killing its running processes. Returns the updated list.
+ */
+export async function invokeDeleteConnectionProfile(id: string): Promise<ConnectionProfile[]> {
+  return invoke<ConnectionProfile[]>('delete_connection_profile', { id });
+}
+
+/**
+ * The saved profile commands currently run against.
+ */
+export async function getActiveProfile(): Promise<ConnectionProfile> {
+  const { profileId } = useConnectionStore.getState();
+  const profile = (await invokeListConnectionProfiles()).find(p => p.id === profileId);
+  if (!profile) {
+    throw new Error('No active connection profile');
+  }
+  return profile;
+}
+
+/**
+ * Save changes to the active profile, e.g. after switching workspace.
+ */
+export async function updateActiveProfile(
+  changes: Partial<Omit<ConnectionProfile, 'id'>>
+): Promise<ConnectionProfile> {
+  const updated = { ...(await getActiveProfile()), ...changes };
+  await invokeSaveConnectionProfile(updated);
+  return updated;
+}
+
 /**
  * Get P4 client/workspace info (client root, user, server).
  * Use to determine workspace root path on startup.
@@ -180,6 +485,26 @@ export async function invokeP4FstatOpened(): Promise<P4FileInfo[]> {
   return invoke<P4FileInfo[]>('p4_fstat_opened', getConnectionArgs());
 }
 
+/**
+ * Get files opened in every workspace (p4 opened -a).
+ * path (a depot path or pattern) takes precedence over stream; with neither, lists the whole server.
+ */
+export async function invokeP4OpenedAll(
+  path?: string,
+  stream?: string,
+  maxFiles?: number
+): Promise<P4OpenedFile[]> {
+  return invoke<P4OpenedFile[]>('p4_opened_all', { path, stream, maxFiles, ...getConnectionArgs() });
+}
+
+/**
+ * Get the workspaces that have a file open. Served from a short-lived backend cache,
+ * so it is cheap to call on hover.
+ */
+export async function invokeP4FileOpenedBy(depotPath: string): Promise<P4OpenedFile[]> {
+  return invoke<P4OpenedFile[]>('p4_file_opened_by', { depotPath, ...getConnectionArgs() });
+}
+
 /**
  * Get changelists (pending, submitted, or all).
  * Use for displaying changelist panel.
@@ -204,12 +529,87 @@ export async function invokeP4Revert(paths: string[]): Promise<string[]> {
   return invoke<string[]>('p4_revert', { paths, ...getConnectionArgs() });
 }
 
+/**
+ * Outcome of add, delete or move for a single file
+ */
+export interface P4FileOpResult {
+  path: string; // Depot path, or the path p4 reported when it couldn't map the file
+  depotPath: string | null;
+  localPath: string | null;
+  action: string | null; // add, delete, move/add
+  fileType: string | null;
+  revision: number | null;
+  movedFrom: string | null; // Source depot path of a move/add
+  success: boolean;
+  message: string | null; // p4's error or warning when the file wasn't opened
+}
+
+/**
+ * A single rename; both sides may be directories ending in "/..."
+ */
+export interface P4MoveRequest {
+  from: string;
+  to: string;
+}
+
+/**
+ * Open new workspace files for add.
+ * Names containing @ # % * are added literally.
+ * @param fileType - Overrides the detected file type (e.g. "binary+l")
+ */
+export async function invokeP4Add(
+  paths: string[],
+  changelist?: number,
+  fileType?: string
+): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_add', { paths, changelist, fileType, ...getConnectionArgs() });
+}
+
+/**
+ * Lock opened files so no other workspace can submit them first.
+ */
+export async function invokeP4Lock(paths: string[], changelist?: number): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_lock', { paths, changelist, ...getConnectionArgs() });
+}
+
+/**
+ * Release locks. force (-f) releases another user's lock (admin only; pass depot paths).
+ */
+export async function invokeP4Unlock(paths: string[], force = false): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_unlock', { paths, force, ...getConnectionArgs() });
+}
+
+/**
+ * Open files for delete (p4 removes the workspace files).
+ */
+export async function invokeP4Delete(paths: string[], changelist?: number): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_delete', { paths, changelist, ...getConnectionArgs() });
+}
+
+/**
+ * Move/rename files or directories. Sources are opened for edit first if needed.
+ */
+export async function invokeP4Move(moves: P4MoveRequest[], changelist?: number): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_move', { moves, changelist, ...getConnectionArgs() });
+}
+
 /**
  * Submit changelist to depot.
  * Use when user wants to commit their changes.
+ * jobStatus sets the status every job fixed by the changelist gets on submit;
+ * the default changelist (0) can't fix jobs, so it is rejected there.
  */
-export async function invokeP4Submit(changelist: number, description?: string): Promise<number> {
-  return invoke<number>('p4_submit', { changelist, description, ...getConnectionArgs() });
+export async function invokeP4Submit(
+  changelist: number,
+  description?: string,
+  jobStatus?: string
+): Promise<number> {
+  return invoke<number>('p4_submit', {
+    changelist,
+    description,
+    jobStatus: jobStatus ?? null,
+    ...getConnectionArgs(),
+  });
 }
 
 /**
@@ -217,32 +617,99 @@ export async function invokeP4Submit(changelist: number, description?: string):
  * Use for syncing workspace to latest revision.
  * @param paths - Specific paths to sync, or empty for all workspace files
  * @param depotPath - Depot path to sync (e.g., "//stream/main/...") when paths is empty
- * @param onProgress - Callback for streaming progress updates
+ * @param onProgress - Callback for streaming progress updates and the final completion
+ * @param revisionSpec - Revision to sync every path to: "@label", "@1234" or "#head"
  */
 export async function invokeP4Sync(
   paths: string[],
   depotPath: string | undefined,
-  onProgress: (progress: SyncProgress) => void
+  onProgress: (event: SyncStreamEvent) => void,
+  revisionSpec?: string
 ): Promise<string> {
-  const channel = new Channel<SyncProgress>();
+  const channel = new Channel<SyncStreamEvent>();
   channel.onmessage = onProgress;
-  return invoke<string>('p4_sync', { paths, depotPath, ...getConnectionArgs(), onProgress: channel });
+  return invoke<string>('p4_sync', {
+    paths,
+    depotPath,
+    revisionSpec: revisionSpec ?? null,
+    ...getConnectionArgs(),
+    onProgress: channel,
+  });
 }
 
 /**
- * List available workspaces for a given server and user.
+ * List available workspaces for a profile's server and user; the profile need not be saved.
  * Use for browsing workspaces during settings configuration.
  */
-export async function invokeListWorkspaces(server: string, user: string): Promise<P4Workspace[]> {
-  return invoke<P4Workspace[]>('p4_list_workspaces', { server, user });
+export async function invokeListWorkspaces(profile: ConnectionProfile): Promise<P4Workspace[]> {
+  return invoke<P4Workspace[]>('p4_list_workspaces', { profile });
+}
+
+/**
+ * Test a connection profile before it is saved.
+ */
+export async function invokeTestConnection(profile: ConnectionProfile): Promise<P4ClientInfo> {
+  return invoke<P4ClientInfo>('p4_test_connection', { profile });
+}
+
+export interface P4LoginStatus {
+  loggedIn: boolean;
+  user: string | null;
+  /** Seconds until the ticket expires; null when logged out or tickets aren't used */
+  expiresIn: number | null;
+}
+
+export interface P4TrustStatus {
+  trusted: boolean;
+  /** Fingerprint the server presented, when it isn't trusted */
+  fingerprint: string | null;
+  /** The server's key differs from the one trusted before */
+  changed: boolean;
+}
+
+/**
+ * Connection args for `target`, or the active profile when none is given
+ */
+function authConnectionArgs(target?: AuthTarget) {
+  return target ? { connection: target.connection } : getConnectionArgs();
+}
+
+/**
+ * Log in; the password is passed to p4 on stdin.
+ * allHosts requests a ticket valid on every machine.
+ */
+export async function invokeP4Login(
+  password: string,
+  allHosts = false,
+  target?: AuthTarget
+): Promise<P4LoginStatus> {
+  return invoke<P4LoginStatus>('p4_login', { password, allHosts, ...authConnectionArgs(target) });
+}
+
+export async function invokeP4Logout(allHosts = false): Promise<void> {
+  return invoke<void>('p4_logout', { allHosts, ...getConnectionArgs() });
+}
+
+export async function invokeP4LoginStatus(target?: AuthTarget): Promise<P4LoginStatus> {
+  return invoke<P4LoginStatus>('p4_login_status', authConnectionArgs(target));
+}
+
+/**
+ * Check whether the server's SSL fingerprint is trusted, without changing anything.
+ */
+export async function invokeP4TrustStatus(target?: AuthTarget): Promise<P4TrustStatus> {
+  return invoke<P4TrustStatus>('p4_trust_status', authConnectionArgs(target));
 }
 
 /**
- * Test connection to P4 server with given credentials.
- * Use to validate settings before saving.
+ * Trust the fingerprint the user confirmed. replace overwrites a changed fingerprint.
  */
-export async function invokeTestConnection(server: string, user: string, client: string): Promise<P4ClientInfo> {
-  return invoke<P4ClientInfo>('p4_test_connection', { server, user, client });
+export async function invokeP4Trust(
+  fingerprint: string,
+  replace = false,
+  target?: AuthTarget
+): Promise<void> {
+  return invoke<void>('p4_trust', { fingerprint, replace, ...authConnectionArgs(target) });
 }
 
 /**
@@ -311,12 +778,19 @@ export async function invokeP4PrintToFile(
 /**
  * Print a specific revision of a file and return its content as a string.
  * Automatically checks file size and rejects binary files or files >10MB.
+ * revisionSpec ("@label", "@1234") is used instead of revision when given.
  */
 export async function invokeP4PrintContent(
   depotPath: string,
-  revision: number
+  revision: number,
+  revisionSpec?: string
 ): Promise<string> {
-  return invoke<string>('p4_print_content', { depotPath, revision, ...getConnectionArgs() });
+  return invoke<string>('p4_print_content', {
+    depotPath,
+    revision,
+    revisionSpec: revisionSpec ?? null,
+    ...getConnectionArgs(),
+  });
 }
 
 /**
@@ -359,7 +833,7 @@ export interface ShelvedBatchResult {
 export type ShelvedBatchProgress =
   | { type: 'progress'; loaded: number; total: number; message: string }
   | { type: 'result'; result: ShelvedBatchResult }
-  | { type: 'complete'; successCount: number; errorCount: number; cancelled: boolean };
+  | ({ type: 'complete'; successCount: number; errorCount: number } & StreamCompletion);
 
 /**
  * File info from p4 describe output
@@ -382,6 +856,17 @@ export interface P4ChangelistDescription {
   description: string;
   status: string;
   files: P4DescribeFile[];
+  /** Jobs the changelist fixes (or will fix, when pending) */
+  jobs: P4FixedJob[];
+}
+
+/**
+ * Job linked to a changelist by p4 fix
+ */
+export interface P4FixedJob {
+  job: string;
+  /** For a pending changelist, the status the job gets on submit */
+  status: string;
 }
 
 /**
@@ -508,6 +993,77 @@ export async function invokeP4ResolvePreview(): Promise<string[]> {
   return invoke<string[]>('p4_resolve_preview', getConnectionArgs());
 }
 
+export type IntegrateKind = 'merge' | 'copy' | 'integrate';
+
+/**
+ * Source and target of p4 merge / copy / integrate.
+ * Set exactly one of `stream`, `branch`, or both `fromPath` and `toPath`.
+ */
+export interface IntegrateOptions {
+  kind: IntegrateKind;
+  /** Integrate between this stream and its parent (-S) */
+  stream?: string;
+  /** Override the stream's parent (-P) */
+  parentStream?: string;
+  /** Branch spec mapping source to target (-b) */
+  branch?: string;
+  /** Run the stream or branch mapping from target to source (-r) */
+  reverse?: boolean;
+  fromPath?: string;
+  toPath?: string;
+  /** Pending changelist to open target files in (-c) */
+  changelist?: number;
+  /** Source revisions, e.g. "@1200" or "@1200,@1300" */
+  revisionRange?: string;
+}
+
+/**
+ * Target file opened (or, in a preview, to be opened) by merge / copy / integrate
+ */
+export interface IntegrateFile {
+  depotPath: string;
+  localPath: string | null;
+  /** integrate, branch, delete, import, ... */
+  action: string;
+  fromFile: string | null;
+  startFromRev: string | null;
+  endFromRev: string | null;
+  workRev: number | null;
+}
+
+export interface IntegratePreview {
+  files: IntegrateFile[];
+  /** Per-file messages p4 reported instead of an action */
+  messages: string[];
+}
+
+export type IntegrateStreamBatch =
+  | { type: 'data'; files: IntegrateFile[]; totalReceived: number }
+  | ({ type: 'complete'; totalFiles: number } & StreamCompletion);
+
+/**
+ * Preview merge / copy / integrate (-n) without opening any files.
+ * Returns an empty preview when everything is already integrated.
+ */
+export async function invokeP4IntegratePreview(
+  options: IntegrateOptions
+): Promise<IntegratePreview> {
+  return invoke<IntegratePreview>('p4_integrate_preview', { options, ...getConnectionArgs() });
+}
+
+/**
+ * Run merge / copy / integrate, streaming opened files in batches.
+ * Returns the process ID for cancellation; exactly one 'complete' batch ends the stream.
+ */
+export async function invokeP4Integrate(
+  options: IntegrateOptions,
+  onBatch: (batch: IntegrateStreamBatch) => void
+): Promise<string> {
+  const channel = new Channel<IntegrateStreamBatch>();
+  channel.onmessage = onBatch;
+  return invoke<string>('p4_integrate', { options, ...getConnectionArgs(), onBatch: channel });
+}
+
 /**
  * File result from p4 files command
  */
@@ -520,14 +1076,20 @@ export interface P4FileResult {
 }
 
 /**
- * Search depot for files matching a pattern.
+ * Search depot for files matching a pattern, optionally at a revision such as "@label".
  * Example: p4 files //depot/.../*.cpp
  */
 export async function invokeP4Files(
   pattern: string,
-  maxResults: number = 50
+  maxResults: number = 50,
+  revisionSpec?: string
 ): Promise<P4FileResult[]> {
-  return invoke<P4FileResult[]>('p4_files', { pattern, maxResults, ...getConnectionArgs() });
+  return invoke<P4FileResult[]>('p4_files', {
+    pattern,
+    maxResults,
+    revisionSpec: revisionSpec ?? null,
+    ...getConnectionArgs(),
+  });
 }
 
 /**
@@ -539,6 +1101,29 @@ export interface P4Stream {
   parent: string | null;
   stream_type: string;  // mainline, development, release
   description: string;
+  /** Merge-down / copy-up status, only when listed with `withIntegration` */
+  integration: StreamIntegrationStatus | null;
+}
+
+/**
+ * Integration status of a stream against its parent (p4 istat / p4 interchanges)
+ */
+export interface StreamIntegrationStatus {
+  stream: string;
+  parent: string | null;
+  changeFlowsFromParent: boolean;
+  changeFlowsToParent: boolean;
+  /** Parent has changes not yet merged down */
+  needsMergeDown: boolean;
+  mergeDownHow: string | null;
+  /** Stream has changes not yet copied up */
+  needsCopyUp: boolean;
+  copyUpHow: string | null;
+  /** Filled in only by invokeP4StreamIntegrationStatus */
+  pendingMergeDown: P4ChangelistInfo[];
+  pendingCopyUp: P4ChangelistInfo[];
+  /** Why the status couldn't be read; the other fields are unset when this is */
+  error: string | null;
 }
 
 /**
@@ -554,68 +1139,597 @@ export interface P4ClientSpec {
   options: string;
   host: string;
   submit_options: string;
+  line_end: string;
 }
 
 /**
  * List available streams for the depot.
+ * With withIntegration, each stream with a parent carries its p4 istat status (no changelists).
  */
-export async function invokeP4ListStreams(): Promise<P4Stream[]> {
-  return invoke<P4Stream[]>('p4_list_streams', getConnectionArgs());
+export async function invokeP4ListStreams(withIntegration: boolean = false): Promise<P4Stream[]> {
+  return invoke<P4Stream[]>('p4_list_streams', { withIntegration, ...getConnectionArgs() });
 }
 
 /**
- * Get client spec for a specific workspace.
+ * Whether a stream needs a merge down or is ready to copy up,
+ * with the changelists waiting in each direction.
  */
-export async function invokeP4GetClientSpec(
-  workspace: string
-): Promise<P4ClientSpec> {
-  const { server, user } = getConnectionArgs();
-  return invoke<P4ClientSpec>('p4_get_client_spec', { workspace, server, user });
+export async function invokeP4StreamIntegrationStatus(
+  stream: string
+): Promise<StreamIntegrationStatus> {
+  return invoke<StreamIntegrationStatus>('p4_stream_integration_status', { stream, ...getConnectionArgs() });
 }
 
 /**
- * Update client spec's Stream field (for stream switching).
- * Returns success message from P4.
+ * Editable stream spec (p4 stream -o)
  */
-export async function invokeP4UpdateClientStream(
-  workspace: string,
-  newStream: string
-): Promise<string> {
-  const { server, user } = getConnectionArgs();
-  return invoke<string>('p4_update_client_stream', { workspace, newStream, server, user });
+export interface P4StreamSpec {
+  stream: string;
+  name: string;
+  owner: string;
+  /** null for mainlines */
+  parent: string | null;
+  /** mainline, development, release, virtual or task */
+  streamType: string;
+  description: string;
+  /** e.g. "allsubmit unlocked toparent fromparent mergedown" */
+  options: string;
+  /** inherit or noinherit */
+  parentView: string | null;
+  /** e.g. "share ...", "import lib/... //depot/lib/..." */
+  paths: string[];
+  remapped: string[];
+  ignored: string[];
+  /** Read-only dates from the server */
+  update?: string | null;
+  access?: string | null;
 }
 
+export type CreatableStreamType = 'development' | 'task' | 'virtual' | 'release';
+
 /**
- * Depot information from p4 depots
+ * Get the editable spec of a stream.
  */
-export interface P4Depot {
-  name: string;
-  depot_type: string;
+export async function invokeP4GetStreamSpec(stream: string): Promise<P4StreamSpec> {
+  return invoke<P4StreamSpec>('p4_get_stream_spec', { stream, ...getConnectionArgs() });
 }
 
 /**
- * List all depot roots with name and type.
+ * Save an edited stream spec. Rejections throw a P4CommandError
+ * with kind 'invalidSpec' and per-field fieldErrors.
  */
-export async function invokeP4Depots(): Promise<P4Depot[]> {
-  const { server, user } = getConnectionArgs();
-  return invoke<P4Depot[]>('p4_depots', { server, user });
+export async function invokeP4SaveStreamSpec(spec: P4StreamSpec): Promise<string> {
+  return invoke<string>('p4_save_stream_spec', { spec, ...getConnectionArgs() });
 }
 
 /**
- * List immediate subdirectories of a depot path.
- * @param depotPath - Depot path to query (e.g., "//depot/*")
- * @param includeDeleted - Whether to include deleted directories (-D flag)
- * @returns Array of depot directory paths
+ * Create a stream under a parent, e.g. a task stream for a single fix.
  */
-export async function invokeP4Dirs(
-  depotPath: string,
-  includeDeleted: boolean = false
-): Promise<string[]> {
-  return invoke<string[]>('p4_dirs', { depotPath, includeDeleted, ...getConnectionArgs() });
+export async function invokeP4CreateStream(
+  stream: string,
+  streamType: CreatableStreamType,
+  parent: string,
+  name?: string,
+  description?: string
+): Promise<string> {
+  return invoke<string>('p4_create_stream', {
+    stream,
+    streamType,
+    parent,
+    name,
+    description,
+    ...getConnectionArgs(),
+  });
 }
 
 /**
- * Annotation line from p4 annotate (blame).
+ * Get client spec for a specific workspace.
+ */
+export async function invokeP4GetClientSpec(
+  workspace: string
+): Promise<P4ClientSpec> {
+  return invoke<P4ClientSpec>('p4_get_client_spec', { workspace, ...getConnectionArgs() });
+}
+
+/**
+ * Create a workspace. With template, it starts as a copy of that workspace (p4 client -t);
+ * an empty view and no stream keep the template's view.
+ * Validation failures come back as an invalidSpec error with fieldErrors.
+ */
+export async function invokeP4CreateClient(
+  spec: P4ClientSpec,
+  template?: string
+): Promise<string> {
+  return invoke<string>('p4_create_client', { spec, template: template ?? null, ...getConnectionArgs() });
+}
+
+/**
+ * Save every editable field of an existing workspace, including its view.
+ */
+export async function invokeP4SaveClientSpec(spec: P4ClientSpec): Promise<string> {
+  return invoke<string>('p4_save_client_spec', { spec, ...getConnectionArgs() });
+}
+
+/**
+ * Delete a workspace. force (-f) deletes it even with opened files (admin only).
+ */
+export async function invokeP4DeleteClient(workspace: string, force = false): Promise<string> {
+  return invoke<string>('p4_delete_client', { workspace, force, ...getConnectionArgs() });
+}
+
+/**
+ * Update client spec's Stream field (for stream switching).
+ * Returns success message from P4.
+ */
+export async function invokeP4UpdateClientStream(
+  workspace: string,
+  newStream: string
+): Promise<string> {
+  return invoke<string>('p4_update_client_stream', { workspace, newStream, ...getConnectionArgs() });
+}
+
+/**
+ * Label from p4 labels
+ */
+export interface P4Label {
+  label: string;
+  owner: string;
+  description: string;
+  /** e.g. "unlocked noautoreload" */
+  options: string;
+  /** Revision the label's view is pinned to ("@1234"), if any */
+  revision: string | null;
+  /** Unix seconds */
+  update: number;
+  access: number;
+}
+
+/**
+ * Editable label spec from p4 label -o
+ */
+export interface P4LabelSpec {
+  label: string;
+  owner: string;
+  description: string;
+  options: string;
+  revision: string | null;
+  view: string[];
+  /** Read-only dates reported by the server */
+  update?: string | null;
+  access?: string | null;
+}
+
+/**
+ * List labels, optionally by owner or name pattern (case-insensitive, e.g. "release-*").
+ */
+export async function invokeP4ListLabels(
+  filter: { nameFilter?: string; owner?: string; maxResults?: number } = {}
+): Promise<P4Label[]> {
+  return invoke<P4Label[]>('p4_list_labels', {
+    nameFilter: filter.nameFilter ?? null,
+    owner: filter.owner ?? null,
+    maxResults: filter.maxResults ?? null,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Get a label's spec; for a new label name, p4's defaults.
+ */
+export async function invokeP4GetLabelSpec(label: string): Promise<P4LabelSpec> {
+  return invoke<P4LabelSpec>('p4_get_label_spec', { label, ...getConnectionArgs() });
+}
+
+/**
+ * Create or update a label.
+ * Validation failures come back as an invalidSpec error with fieldErrors.
+ */
+export async function invokeP4SaveLabelSpec(spec: P4LabelSpec): Promise<string> {
+  return invoke<string>('p4_save_label_spec', { spec, ...getConnectionArgs() });
+}
+
+/**
+ * Delete a label. force (-f) is needed for locked labels or other owners' labels.
+ */
+export async function invokeP4DeleteLabel(label: string, force = false): Promise<string> {
+  return invoke<string>('p4_delete_label', { label, force, ...getConnectionArgs() });
+}
+
+/**
+ * Add files to a label (p4 tag), or remove them with remove.
+ * With changelist, tags the revisions submitted in that change under paths (or the whole depot).
+ */
+export async function invokeP4Tag(
+  label: string,
+  paths: string[],
+  options: { changelist?: number; remove?: boolean; preview?: boolean } = {}
+): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_tag', {
+    label,
+    paths,
+    changelist: options.changelist ?? null,
+    delete: options.remove ?? false,
+    preview: options.preview ?? false,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Make a label match the workspace's have revisions (p4 labelsync).
+ * add only adds files; remove only removes paths from the label.
+ */
+export async function invokeP4Labelsync(
+  label: string,
+  paths: string[] = [],
+  options: { add?: boolean; remove?: boolean; preview?: boolean } = {}
+): Promise<P4FileOpResult[]> {
+  return invoke<P4FileOpResult[]>('p4_labelsync', {
+    label,
+    paths,
+    add: options.add ?? false,
+    delete: options.remove ?? false,
+    preview: options.preview ?? false,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Job from p4 jobs. fields holds everything beyond the standard five.
+ */
+export interface P4Job {
+  job: string;
+  status: string;
+  user: string;
+  date: string;
+  description: string;
+  fields: Record<string, string>;
+}
+
+/**
+ * One field of a job, typed by the server's jobspec
+ */
+export interface P4JobField {
+  name: string;
+  value: string;
+  /** word, date, select, line, text or bulk */
+  dataType: string;
+  /** optional, default, required, once or always; once and always are set by the server */
+  fieldType: string;
+  /** Choices for select fields */
+  values: string[];
+}
+
+/**
+ * Editable job from p4 job -o
+ */
+export interface P4JobSpec {
+  /** "new" until the server assigns a name */
+  job: string;
+  fields: P4JobField[];
+}
+
+/**
+ * Search jobs with a jobview query, e.g. "status=open user=alice crash".
+ */
+export async function invokeP4Jobs(query?: string, maxResults?: number): Promise<P4Job[]> {
+  return invoke<P4Job[]>('p4_jobs', {
+    query: query ?? null,
+    maxResults: maxResults ?? null,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Get a job with its fields; "new" returns a blank job to fill in.
+ */
+export async function invokeP4GetJobSpec(job: string): Promise<P4JobSpec> {
+  return invoke<P4JobSpec>('p4_get_job_spec', { job, ...getConnectionArgs() });
+}
+
+/**
+ * Create or update a job. Validation failures come back as an invalidSpec error with fieldErrors.
+ */
+export async function invokeP4SaveJobSpec(spec: P4JobSpec): Promise<string> {
+  return invoke<string>('p4_save_job_spec', { spec, ...getConnectionArgs() });
+}
+
+/**
+ * Editable user spec from p4 user -o (matches Rust P4UserSpec)
+ */
+export interface P4UserSpec {
+  user: string;
+  email: string;
+  fullName: string;
+  /** Jobs the user is notified about, e.g. "status=open" */
+  jobView: string | null;
+  /** Depot paths whose submitted changes the user is notified about */
+  reviews: string[];
+  /** Read-only dates reported by the server */
+  update: string | null;
+  access: string | null;
+}
+
+/**
+ * Get a user's spec. An empty user is the connection's own user.
+ */
+export async function invokeP4GetUserSpec(user = ''): Promise<P4UserSpec> {
+  return invoke<P4UserSpec>('p4_get_user_spec', { user, ...getConnectionArgs() });
+}
+
+/**
+ * Update the connection's own user spec. Validation failures come back as an invalidSpec error with fieldErrors.
+ */
+export async function invokeP4SaveUserSpec(spec: P4UserSpec): Promise<string> {
+  return invoke<string>('p4_save_user_spec', { spec, ...getConnectionArgs() });
+}
+
+/**
+ * Link jobs to a pending changelist, or unlink them with remove (p4 fix -d).
+ * status is the status the jobs get when the changelist is submitted.
+ */
+export async function invokeP4Fix(
+  changelist: number,
+  jobs: string[],
+  options: { status?: string; remove?: boolean } = {}
+): Promise<string> {
+  return invoke<string>('p4_fix', {
+    changelist,
+    jobs,
+    status: options.status ?? null,
+    delete: options.remove ?? false,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Branch spec from p4 branches
+ */
+export interface P4Branch {
+  branch: string;
+  owner: string;
+  description: string;
+  /** locked or unlocked */
+  options: string;
+  /** Unix seconds */
+  update: number;
+  access: number;
+}
+
+/**
+ * What a view line does: include, exclude ("-" line) or overlay ("+" line)
+ */
+export type MappingKind = 'include' | 'exclude' | 'overlay';
+
+/**
+ * One line of a spec's view: source (left) mapped to target (right)
+ */
+export interface P4ViewMapping {
+  kind: MappingKind;
+  source: string;
+  target: string;
+}
+
+/**
+ * Editable branch spec from p4 branch -o
+ */
+export interface P4BranchSpec {
+  branch: string;
+  owner: string;
+  description: string;
+  options: string;
+  view: P4ViewMapping[];
+  /** Read-only dates reported by the server */
+  update?: string | null;
+  access?: string | null;
+}
+
+/**
+ * How the two sides of a p4 diff2 pair compare
+ */
+export type Diff2Status = 'content' | 'types' | 'identical' | 'leftOnly' | 'rightOnly';
+
+/**
+ * One file pair from p4 diff2; the side a file is missing from is null
+ */
+export interface P4Diff2File {
+  status: Diff2Status;
+  leftPath: string | null;
+  leftRevision: number | null;
+  leftType: string | null;
+  rightPath: string | null;
+  rightRevision: number | null;
+  rightType: string | null;
+}
+
+/**
+ * List branch specs, optionally by owner or name pattern (case-insensitive, e.g. "rel-*").
+ */
+export async function invokeP4ListBranches(
+  filter: { nameFilter?: string; owner?: string; maxResults?: number } = {}
+): Promise<P4Branch[]> {
+  return invoke<P4Branch[]>('p4_list_branches', {
+    nameFilter: filter.nameFilter ?? null,
+    owner: filter.owner ?? null,
+    maxResults: filter.maxResults ?? null,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Get a branch spec; for a new branch name, p4's defaults.
+ */
+export async function invokeP4GetBranchSpec(branch: string): Promise<P4BranchSpec> {
+  return invoke<P4BranchSpec>('p4_get_branch_spec', { branch, ...getConnectionArgs() });
+}
+
+/**
+ * Create or update a branch spec.
+ * Validation failures come back as an invalidSpec error with fieldErrors.
+ */
+export async function invokeP4SaveBranchSpec(spec: P4BranchSpec): Promise<string> {
+  return invoke<string>('p4_save_branch_spec', { spec, ...getConnectionArgs() });
+}
+
+/**
+ * Compare both sides of a branch view file by file (p4 diff2 -b).
+ * path limits the comparison to target files under it; identical files are omitted by default.
+ */
+export async function invokeP4Diff2Branch(
+  branch: string,
+  path?: string,
+  includeIdentical = false
+): Promise<P4Diff2File[]> {
+  return invoke<P4Diff2File[]>('p4_diff2_branch', {
+    branch,
+    path: path ?? null,
+    includeIdentical,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * One side of an in-app diff
+ */
+export type DiffSource =
+  | { kind: 'depot'; depotPath: string; revision: number; revisionSpec?: string }
+  | { kind: 'shelved'; depotPath: string; changelist: number }
+  | { kind: 'local'; path: string };
+
+export type WhitespaceMode = 'exact' | 'ignoreChanges' | 'ignoreAll';
+
+export interface DiffOptions {
+  /** Unchanged lines around each change (default 3) */
+  context?: number;
+  whitespace?: WhitespaceMode;
+  ignoreLineEndings?: boolean;
+  /** Mark changed words within paired removed/added lines */
+  wordDiff?: boolean;
+}
+
+export type DiffLineKind = 'context' | 'added' | 'removed';
+
+export interface DiffSegment {
+  text: string;
+  changed: boolean;
+}
+
+export interface DiffLine {
+  kind: DiffLineKind;
+  oldLine: number | null;
+  newLine: number | null;
+  text: string;
+  segments?: DiffSegment[];
+}
+
+export interface DiffHunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  lines: DiffLine[];
+}
+
+export interface TextDiff {
+  /** Either side is binary; hunks is empty */
+  binary: boolean;
+  hunks: DiffHunk[];
+  added: number;
+  removed: number;
+}
+
+/**
+ * Diff two versions of a file in-process: depot revisions, shelved files or local files.
+ */
+export async function invokeP4Diff(
+  left: DiffSource,
+  right: DiffSource,
+  options?: DiffOptions
+): Promise<TextDiff> {
+  return invoke<TextDiff>('p4_diff', {
+    left,
+    right,
+    options: options ?? null,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Which diff of a changelist to produce: submitted and shelved use p4 describe -du,
+ * pending diffs the opened files against their have revisions
+ */
+export type ChangeDiffSource = 'submitted' | 'shelved' | 'pending';
+
+/**
+ * Unified diff of one file in a changelist
+ */
+export interface P4FileDiff {
+  depotPath: string;
+  revision: number | null;
+  localPath: string | null;
+  action: string | null;
+  fileType: string | null;
+  binary: boolean;
+  hunks: DiffHunk[];
+  added: number;
+  removed: number;
+  /** Diff text exceeded the content limit; later hunks were dropped */
+  truncated: boolean;
+}
+
+export type ChangeDiffBatch =
+  | { type: 'data'; files: P4FileDiff[]; totalReceived: number }
+  | ({ type: 'complete'; totalFiles: number } & StreamCompletion);
+
+/**
+ * Per-file unified diffs for a changelist, streamed via Channel.
+ * Use changelist 0 with 'pending' for the default changelist.
+ * Returns process ID for cancellation.
+ */
+export async function invokeP4ChangelistDiff(
+  changelist: number,
+  source: ChangeDiffSource,
+  onBatch: (batch: ChangeDiffBatch) => void
+): Promise<string> {
+  const channel = new Channel<ChangeDiffBatch>();
+  channel.onmessage = onBatch;
+  return invoke<string>('p4_changelist_diff', {
+    changelist,
+    source,
+    ...getConnectionArgs(),
+    onBatch: channel,
+  });
+}
+
+/**
+ * Depot information from p4 depots
+ */
+export interface P4Depot {
+  name: string;
+  depot_type: string;
+}
+
+/**
+ * List all depot roots with name and type.
+ */
+export async function invokeP4Depots(): Promise<P4Depot[]> {
+  return invoke<P4Depot[]>('p4_depots', getConnectionArgs());
+}
+
+/**
+ * List immediate subdirectories of a depot path.
+ * @param depotPath - Depot path to query (e.g., "//depot/*")
+ * @param includeDeleted - Whether to include deleted directories (-D flag)
+ * @returns Array of depot directory paths
+ */
+export async function invokeP4Dirs(
+  depotPath: string,
+  includeDeleted: boolean = false
+): Promise<string[]> {
+  return invoke<string[]>('p4_dirs', { depotPath, includeDeleted, ...getConnectionArgs() });
+}
+
+/**
+ * Annotation line from p4 annotate (blame).
  */
 export interface P4AnnotationLine {
   lineNumber: number;
@@ -664,14 +1778,51 @@ export interface SearchResult {
  * Returns the new total count of indexed files.
  */
 export async function addFilesToIndex(files: FileIndexEntry[]): Promise<number> {
-  return invoke<number>('add_files_to_index', { files });
+  return invoke<number>('add_files_to_index', { files, ...getConnectionArgs() });
+}
+
+/**
+ * Remove files from the FileIndex by depot path.
+ * Returns the new total count of indexed files.
+ */
+export async function removeFilesFromIndex(depotPaths: string[]): Promise<number> {
+  return invoke<number>('remove_files_from_index', { depotPaths, ...getConnectionArgs() });
+}
+
+/**
+ * Operation that reported a file change; decides how `action` is interpreted.
+ * sync: p4 sync progress action ("adding", "updating", "deleting", ...)
+ * submit / revert: the file's open action ("edit", "add", "delete", ...)
+ */
+export type FileIndexChangeSource = 'sync' | 'submit' | 'revert';
+
+/**
+ * A single file change to apply to the FileIndex.
+ * modTime: Unix timestamp if known; otherwise the indexed value (or now) is kept
+ */
+export interface FileIndexChange {
+  depotPath: string;
+  action: string;
+  modTime?: number;
+}
+
+/**
+ * Apply file changes from a sync, submit or revert to the FileIndex,
+ * keeping search current without a full rebuild.
+ * Returns the new total count of indexed files.
+ */
+export async function applyFileIndexChanges(
+  source: FileIndexChangeSource,
+  changes: FileIndexChange[]
+): Promise<number> {
+  return invoke<number>('apply_file_index_changes', { source, changes, ...getConnectionArgs() });
 }
 
 /**
  * Clear the file index (called when workspace changes or new stream starts).
  */
 export async function clearFileIndex(): Promise<void> {
-  return invoke<void>('clear_file_index');
+  return invoke<void>('clear_file_index', getConnectionArgs());
 }
 
 /**
@@ -685,12 +1836,138 @@ export async function searchWorkspaceFiles(
   mode: 'fuzzy' | 'exact' = 'fuzzy',
   maxResults: number = 50
 ): Promise<SearchResult[]> {
-  return invoke<SearchResult[]>('search_workspace_files', { query, mode, maxResults });
+  return invoke<SearchResult[]>('search_workspace_files', { query, mode, maxResults, ...getConnectionArgs() });
 }
 
 /**
  * Get current count of indexed files.
  */
 export async function getFileIndexCount(): Promise<number> {
-  return invoke<number>('get_file_index_count');
+  return invoke<number>('get_file_index_count', getConnectionArgs());
+}
+
+/**
+ * State of the persisted file index after opening it for a workspace.
+ * changelist: head submitted changelist the index is current to (or will be, after a rebuild)
+ */
+export interface FileIndexStatus {
+  rebuildRequired: boolean;
+  changelist: number;
+  fileCount: number;
+  changedFiles: number;
+}
+
+/**
+ * Load the persisted file index for the workspace and reconcile it with files
+ * changed since it was saved. When rebuildRequired is true, repopulate it from a
+ * full fstat stream and then call saveFileIndex(status.changelist).
+ */
+export async function invokeP4OpenFileIndex(depotPath: string): Promise<FileIndexStatus> {
+  return invoke<FileIndexStatus>('p4_open_file_index', { depotPath, ...getConnectionArgs() });
+}
+
+/**
+ * Persist the file index after a full rebuild.
+ * Returns the number of files saved.
+ */
+export async function saveFileIndex(changelist: number): Promise<number> {
+  return invoke<number>('save_file_index', { changelist, ...getConnectionArgs() });
+}
+
+// ============================================================================
+// Content Search Commands
+// ============================================================================
+
+/**
+ * Options for server-side content search (p4 grep).
+ * regex: treat pattern as a regular expression instead of a fixed string
+ * context: lines of context before and after each match
+ * revisionRange: appended to every path, e.g. "@2024/01/01,@now" or "#head"
+ * allRevisions: search every revision in the range, not just the latest
+ */
+export interface GrepOptions {
+  pattern: string;
+  regex?: boolean;
+  caseSensitive?: boolean;
+  context?: number;
+  revisionRange?: string;
+  allRevisions?: boolean;
+}
+
+/**
+ * A matching line (or a context line around one) from content search.
+ * Returned by both p4 grep and the local content index.
+ */
+export interface GrepMatch {
+  depotPath: string;
+  revision: number;
+  lineNumber: number;
+  line: string;
+  isContext: boolean;
+}
+
+export type GrepStreamBatch =
+  | { type: 'data'; matches: GrepMatch[]; totalReceived: number }
+  | ({ type: 'complete'; totalMatches: number } & StreamCompletion);
+
+/**
+ * Search file contents on the server with p4 grep, streaming matches via Channel.
+ * Searches paths, or depotPath when paths is empty.
+ * Returns process ID for cancellation.
+ */
+export async function invokeP4Grep(
+  options: GrepOptions,
+  paths: string[],
+  depotPath: string | undefined,
+  onBatch: (batch: GrepStreamBatch) => void
+): Promise<string> {
+  const channel = new Channel<GrepStreamBatch>();
+  channel.onmessage = onBatch;
+  return invoke<string>('p4_grep', {
+    options,
+    paths,
+    depotPath,
+    ...getConnectionArgs(),
+    onBatch: channel,
+  });
+}
+
+export interface ContentIndexStatus {
+  fileCount: number;
+  skippedCount: number;
+  trigramCount: number;
+}
+
+/**
+ * Build the local content index from synced text files under depotPath.
+ * Replaces any previous index.
+ */
+export async function invokeP4BuildContentIndex(depotPath: string): Promise<ContentIndexStatus> {
+  return invoke<ContentIndexStatus>('p4_build_content_index', { depotPath, ...getConnectionArgs() });
+}
+
+/**
+ * Search workspace file contents using the local content index.
+ * Results reflect files as they are on disk; build the index first.
+ */
+export async function searchContentIndex(
+  pattern: string,
+  options: { regex?: boolean; caseSensitive?: boolean; context?: number } = {},
+  maxResults: number = 500
+): Promise<GrepMatch[]> {
+  return invoke<GrepMatch[]>('search_content_index', {
+    pattern,
+    regex: options.regex ?? false,
+    caseSensitive: options.caseSensitive ?? false,
+    context: options.context ?? 0,
+    maxResults,
+    ...getConnectionArgs(),
+  });
+}
+
+/**
+ * Drop the local content index.
+ */
+export async function clearContentIndex(): Promise<void> {
+  return invoke<void>('clear_content_index', getConnectionArgs());
 }
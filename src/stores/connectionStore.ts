@@ -8,7 +8,9 @@ interface ConnectionState {
   server: string | null;
   user: string | null;
   errorMessage: string | null;
-  // Connection args (from user settings — used for p4 command -p/-u/-c flags)
+  // Active connection profile, passed to every p4 command
+  profileId: string | null;
+  // The profile's server, user and workspace (from user settings)
   p4port: string | null;
   p4user: string | null;
   p4client: string | null;
@@ -22,29 +24,33 @@ interface ConnectionState {
     p4port: string;
     p4user: string;
     p4client: string;
+    /** Defaults to the current profile */
+    profileId?: string;
   }) => void;
   setDisconnected: () => void;
   setError: (message: string) => void;
 }
 
-export const useConnectionStore = create<ConnectionState>((set) => ({
+export const useConnectionStore = create<ConnectionState>((set, get) => ({
   status: 'disconnected',
   workspace: null,
   stream: null,
   server: null,
   user: null,
   errorMessage: null,
+  profileId: null,
   p4port: null,
   p4user: null,
   p4client: null,
 
   setConnecting: () => set({ status: 'connecting', errorMessage: null }),
-  setConnected: ({ workspace, stream, server, user, p4port, p4user, p4client }) => set({
+  setConnected: ({ workspace, stream, server, user, p4port, p4user, p4client, profileId }) => set({
     status: 'connected',
     workspace,
     stream: stream || null,
     server,
     user,
+    profileId: profileId ?? get().profileId,
     p4port,
     p4user,
     p4client,
@@ -56,6 +62,7 @@ export const useConnectionStore = create<ConnectionState>((set) => ({
     stream: null,
     server: null,
     user: null,
+    profileId: null,
     p4port: null,
     p4user: null,
     p4client: null,
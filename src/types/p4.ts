@@ -56,6 +56,12 @@ export interface P4File {
   fileType: string;
   /** True if this represents a directory */
   isDirectory: boolean;
+  /** "user@workspace" of each other workspace that has the file open */
+  openedBy?: string[];
+  /** "user@workspace" holding a lock on the file, when it isn't this workspace */
+  lockedBy?: string;
+  /** This workspace holds the file's lock */
+  ourLock?: boolean;
 }
 
 /**
@@ -4,6 +4,7 @@ export const settingsSchema = z.object({
   p4port: z.string().min(1, 'Server address is required'),
   p4user: z.string().min(1, 'Username is required'),
   p4client: z.string().min(1, 'Workspace is required'),
+  activeProfileId: z.string(),
   diffToolPath: z.string(),
   diffToolArgs: z.string(),
   editorPath: z.string(),
@@ -20,6 +21,7 @@ export const defaultSettings: P4Settings = {
   p4port: '',
   p4user: '',
   p4client: '',
+  activeProfileId: '',
   diffToolPath: '',
   diffToolArgs: '',
   editorPath: '',